@@ -5,15 +5,19 @@
 #![feature(associated_type_defaults)]
 #![feature(generic_arg_infer)]
 
+use blinky_shared::calendar::CalendarKind;
 use blinky_shared::commands::Commands;
+use blinky_shared::contract::packets::HandshakePacket;
 use blinky_shared::events::Events;
 use blinky_shared::message_bus::MessageBus;
 use blinky_shared::modules::calendar_module::CalendarModule;
-use blinky_shared::modules::fonts_set::FontSet466;
-use blinky_shared::modules::icon_set_466::IconsSet466;
+use blinky_shared::modules::gesture_module::GestureModule;
+use blinky_shared::modules::persister_module::PersisterModule;
 use blinky_shared::modules::reference_time::ReferenceTime;
 use blinky_shared::modules::renderer::Renderer;
-use blinky_shared::persistence::PersistenceUnitKind;
+use blinky_shared::modules::solar_module::SolarModule;
+use blinky_shared::modules::weather_module::WeatherModule;
+use blinky_shared::supervisor::{ModuleSpec, Supervisor};
 use esp_idf_hal::peripherals::Peripherals;
 use esp_idf_svc::log::{set_target_level, EspLogger};
 use log::*;
@@ -23,14 +27,15 @@ use ::peripherals::pins::mapping::PinsMapping;
 
 #[cfg(feature = "tdisplay143")]
 use ::peripherals::pins::tdisplay143::TDisplay143;
+#[cfg(feature = "tdisplay143")]
+use blinky_shared::modules::{fonts_set::FontSet466, icon_set_466::IconsSet466};
 
 #[cfg(feature = "twatch_2021")]
-use peripherals::pins::twatch_2021::TWatch2021Pins;
+use ::peripherals::pins::twatch_2021::TWatch2021Pins;
+#[cfg(feature = "twatch_2021")]
+use blinky_shared::modules::{fonts_set::FontSet240, icon_set_240::IconsSet240};
 
-use peripherals::rtc::Rtc;
 use std::cell::RefCell;
-use std::future::Future;
-use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -44,7 +49,6 @@ use peripherals::hal::{HalConfig, PinConfig, HAL};
 
 use modules::accel_module::AccelerometerModule;
 use modules::ble_module::BleModule;
-use modules::persister_module::PersisterModule;
 use modules::power_module::PowerModule;
 use modules::rtc_module::RtcModule;
 use modules::time_sync::TimeSync;
@@ -53,6 +57,19 @@ use modules::user_input::UserInput;
 
 use crate::modules::logging_module::LoggingModule;
 use crate::peripherals::display::ClockDisplay;
+use crate::peripherals::nvs_storage::NvsStorage;
+#[cfg(feature = "trace")]
+use crate::peripherals::rtc_trace::RtcTrace;
+#[cfg(feature = "trace")]
+use blinky_shared::modules::recorder::Recorder;
+
+#[cfg(feature = "twatch_2021")]
+const SCREEN_SIDE: u16 = 240;
+
+#[cfg(feature = "tdisplay143")]
+const SCREEN_SIDE: u16 = 466;
+
+const NVS_NAMESPACE: &str = "blinky_persistence";
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // It is necessary to call this function once. Otherwise some patches to the runtime
@@ -95,7 +112,7 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
     let peripherals = Peripherals::take().unwrap();
 
     #[cfg(feature = "twatch_2021")]
-    let mut pins_mapping = TWatch2021Pins::new(peripherals.pins);
+    let mut pins_mapping = Arc::new(Mutex::new(TWatch2021Pins::new(peripherals.pins)));
 
     #[cfg(feature = "tdisplay143")]
     let mut pins_mapping = Arc::new(Mutex::new(TDisplay143::new(peripherals.pins)));
@@ -113,11 +130,6 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
 
     let hal: HAL = HAL::new(hal_conf.clone(), i2c, pins_mapping.clone());
 
-    let logging_task = start_logging(&message_bus);
-
-    let mut mb = message_bus.clone();
-    let wait_for_first_render_task = mb.wait_for(Events::FirstRender);
-
     let i2c_proxy = hal.get_i2c_proxy_async().clone();
 
     let spi = peripherals.spi2;
@@ -125,125 +137,162 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
     let fasttrack_result =
         RtcDisplayFastTrack::run_and_decompose(spi, i2c_proxy, pins_mapping.clone());
 
-    let rtc_task = start_rtc(&message_bus, fasttrack_result.rtc);
+    // panics abort on the device, so no module gets a restart policy here
+    let mut supervisor = Supervisor::new(message_bus);
+
+    supervisor.register(ModuleSpec::new("logging", LoggingModule::start));
+
+    #[cfg(feature = "trace")]
+    {
+        let trace = RtcTrace::restore();
+        trace.dump();
 
-    let mb = message_bus.clone();
+        let mut trace = Some(trace);
+        supervisor.register(ModuleSpec::new("recorder", move |mb| {
+            Recorder::start(mb, trace.take().unwrap())
+        }));
+    }
+
+    let mut rtc = Some(fasttrack_result.rtc);
+    supervisor.register(ModuleSpec::new("rtc", move |mb| {
+        RtcModule::start(rtc.take().unwrap(), mb)
+    }));
+
+    let mut display = Some(fasttrack_result.display);
+    let mut rtc_data = Some(fasttrack_result.rtc_data);
 
     #[cfg(feature = "twatch_2021")]
-    let renderer_task = Renderer::<_, FontSet240, IconsSet240>::start(
-        mb,
-        fasttrack_result.display,
-        fasttrack_result.rtc_data,
-    );
+    let renderer_module = ModuleSpec::new("renderer", move |mb| {
+        Renderer::<_, FontSet240, IconsSet240>::start(
+            mb,
+            display.take().unwrap(),
+            rtc_data.take().unwrap(),
+        )
+    });
 
     #[cfg(feature = "tdisplay143")]
-    let renderer_task = Renderer::<_, FontSet466, IconsSet466>::start(
-        mb,
-        fasttrack_result.display,
-        fasttrack_result.rtc_data,
-    );
-
-    //let renderer_task = start_renderer_(&message_bus, fasttrack_result);
+    let renderer_module = ModuleSpec::new("renderer", move |mb| {
+        Renderer::<_, FontSet466, IconsSet466>::start(
+            mb,
+            display.take().unwrap(),
+            rtc_data.take().unwrap(),
+        )
+    });
 
-    let tasks_batch: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
-        Box::pin(logging_task),
-        Box::pin(wait_for_first_render_task),
-        Box::pin(rtc_task),
-        Box::pin(renderer_task),
-    ];
+    supervisor.register(renderer_module.ready_on(Events::FirstRender));
 
     let pins_mapping_cpy = pins_mapping.clone();
     let pins_mapping_cpy2 = pins_mapping.clone();
 
-    let (_, _, mut remaining_tasks) = futures::future::select_all(tasks_batch).await;
+    supervisor.register(ModuleSpec::new("time_sync", TimeSync::start).depends_on("renderer"));
 
-    let time_sync_task = start_time_sync(&message_bus);
+    supervisor.register(
+        ModuleSpec::new("persister", |mb| async move {
+            PersisterModule::start(mb, NvsStorage::create(NVS_NAMESPACE)).await;
+        })
+        .depends_on("renderer"),
+    );
 
-    let mb = message_bus.clone();
-    let persister_task = PersisterModule::start(mb);
+    //supervisor.register(ModuleSpec::new("ble", BleModule::start).depends_on("renderer"));
 
-    //let mb = message_bus.clone();
-    //let ble_task = BleModule::start(mb);
+    supervisor.register(
+        ModuleSpec::new("user_input", move |mb| {
+            UserInput::start(mb, pins_mapping_cpy.clone())
+        })
+        .depends_on("renderer"),
+    );
 
-    let mb = message_bus.clone();
-    let user_input_task = UserInput::start(mb, pins_mapping_cpy);
+    let mut touch_config = Some(hal.get_touch_config());
+    let mut touch_proxy = Some(hal.get_i2c_proxy_async());
+
+    supervisor.register(
+        ModuleSpec::new("touch", move |mb| {
+            TouchModule::start(
+                touch_config.take().unwrap(),
+                touch_proxy.take().unwrap(),
+                mb,
+            )
+        })
+        .depends_on("renderer"),
+    );
 
-    let mb = message_bus.clone();
-    let touch_config = hal.get_touch_config();
-    let touch_proxy = hal.get_i2c_proxy_async();
-    //let touch_task = TouchModule::start(touch_config, touch_proxy, mb);
+    supervisor.register(
+        ModuleSpec::new("gestures", |mb| {
+            GestureModule::start(mb, SCREEN_SIDE as usize)
+        })
+        .depends_on("touch"),
+    );
 
     let accel_proxy = hal.get_i2c_proxy_async();
     let accel_proxy_ex = hal.get_i2c_proxy_async();
 
-    let mb = message_bus.clone();
     //let accel_task = AccelerometerModule::start(accel_proxy, accel_proxy_ex, mb);
 
-    let mb = message_bus.clone();
-    let reference_time_task = ReferenceTime::start(mb);
-
-    let mb = message_bus.clone();
-    let calendar_task = CalendarModule::start(mb);
+    supervisor.register(
+        ModuleSpec::new("reference_time", |mb| {
+            let handshake = HandshakePacket::watch(
+                SCREEN_SIDE,
+                vec![
+                    CalendarKind::Phone,
+                    CalendarKind::Trains,
+                    CalendarKind::Weather,
+                ],
+            );
 
-    let mb = message_bus.clone();
+            ReferenceTime::start(mb, handshake)
+        })
+        .depends_on("renderer"),
+    );
 
-    let startup_sequence = async move {
-        mb.send_cmd(Commands::Restore(PersistenceUnitKind::RtcSyncInfo));
-        mb.send_cmd(Commands::SyncCalendar);
+    supervisor.register(
+        ModuleSpec::new("calendar", CalendarModule::start)
+            .depends_on("renderer")
+            .depends_on("persister"),
+    );
 
-        info!("startup sequence done.");
-    };
+    supervisor.register(
+        ModuleSpec::new("weather", WeatherModule::start)
+            .depends_on("renderer")
+            .depends_on("persister"),
+    );
 
-    let mb = message_bus.clone();
+    supervisor.register(
+        ModuleSpec::new("solar", SolarModule::start)
+            .depends_on("renderer")
+            .depends_on("persister"),
+    );
 
-    #[cfg(feature = "twatch_2021")]
-    let power_task = PowerModule::start(peripherals.adc1, &mut pins_mapping, pin_conf, mb);
+    supervisor.register(
+        ModuleSpec::new("startup_sequence", |mb| async move {
+            mb.send_cmd(Commands::SyncCalendar);
 
-    #[cfg(feature = "tdisplay143")]
-    let power_task = PowerModule::start(
-        peripherals.adc1,
-        pins_mapping_cpy2,
-        fasttrack_result.backlight,
-        pin_conf,
-        mb,
+            info!("startup sequence done.");
+        })
+        .depends_on("calendar"),
     );
 
-    let mut rest: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
-        Box::pin(power_task),
-        Box::pin(time_sync_task),
-        Box::pin(persister_task),
-        //Box::pin(accel_task),
-        //Box::pin(ble_task),
-        Box::pin(user_input_task),
-        //Box::pin(touch_task),
-        Box::pin(reference_time_task),
-        Box::pin(calendar_task),
-        Box::pin(startup_sequence),
-    ];
+    let mut adc = Some(peripherals.adc1);
+    let mut pin_conf = Some(pin_conf);
 
-    remaining_tasks.append(&mut rest);
+    let mut backlight = Some(fasttrack_result.backlight);
 
-    futures::future::join_all(remaining_tasks).await;
+    let power_module = ModuleSpec::new("power", move |mb| {
+        PowerModule::start(
+            adc.take().unwrap(),
+            pins_mapping_cpy2.clone(),
+            backlight.take().unwrap(),
+            pin_conf.take().unwrap(),
+            mb,
+        )
+    });
 
-    info!("done.");
+    supervisor.register(power_module.depends_on("renderer"));
 
-    Ok(())
-}
+    supervisor.run().await.map_err(|err| err.0)?;
 
-#[inline]
-fn start_logging(mb: &MessageBus) -> impl Future<Output = ()> {
-    let mb = mb.clone();
-    LoggingModule::start(mb)
-}
-
-fn start_time_sync(mb: &MessageBus) -> impl Future<Output = ()> {
-    let mb = mb.clone();
-    TimeSync::start(mb)
-}
+    info!("done.");
 
-fn start_rtc(mb: &MessageBus, rtc: Rtc<'static>) -> impl Future<Output = ()> {
-    let mb = mb.clone();
-    RtcModule::start(rtc, mb)
+    Ok(())
 }
 
 // fn start_renderer(
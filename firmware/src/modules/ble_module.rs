@@ -29,6 +29,7 @@ pub enum BleCommands {
     StartAdvertising,
     Shutdown,
     ReplyPersisted(Arc<Vec<CalendarEventKey>>),
+    Notify(Arc<Vec<u8>>),
 }
 
 impl BusHandler<Context> for BleModule {
@@ -57,6 +58,9 @@ impl BusHandler<Context> for BleModule {
             Commands::ShutdownBle | Commands::StartDeepSleep => {
                 context.tx.send(BleCommands::Shutdown).unwrap();
             }
+            Commands::SendData(data) => {
+                context.tx.send(BleCommands::Notify(data)).unwrap();
+            }
             _ => {}
         }
     }
@@ -135,6 +139,13 @@ impl BleModule {
                     error!("reply_persisted skipped!");
                 }
             }
+            BleCommands::Notify(data) => {
+                if context.is_ble_initialized {
+                    Self::notify(context, &data);
+                } else {
+                    error!("notify skipped!");
+                }
+            }
         }
     }
 
@@ -233,6 +244,17 @@ impl BleModule {
         info!("BLE shut down.");
     }
 
+    fn notify(context: &BleContext, buf: &[u8]) {
+        if let Some(characteristic) = context.rw_characteristic.as_ref() {
+            let mut guard = characteristic.lock();
+
+            guard.set_value(buf);
+            guard.notify();
+        } else {
+            info!("failed to get characteristic to write to");
+        }
+    }
+
     fn reply_persisted(context: &BleContext, events: Arc<Vec<CalendarEventKey>>) {
         info!("replying persisted {} events...", events.len());
 
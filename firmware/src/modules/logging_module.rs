@@ -1,5 +1,5 @@
 use blinky_shared::{commands::Commands, events::Events, message_bus::BusSender};
-use log::{error, info};
+use log::{error, info, warn};
 
 use blinky_shared::message_bus::{BusHandler, ContextStub, MessageBus};
 
@@ -20,6 +20,9 @@ impl BusHandler<ContextStub> for LoggingModule {
                 //info!("IncomingData {:02X?}", &data);
                 info!("IncomingData len {}", data.len());
             }
+            Events::Reply(correlation_id, event) => {
+                info!("Reply {:?} {}", correlation_id, event.as_ref());
+            }
             Events::EventTimelyData(_) => {}
             Events::TimelyDataBatch(_) => {}
             _ => {
@@ -28,8 +31,14 @@ impl BusHandler<ContextStub> for LoggingModule {
         }
     }
 
-    async fn command_handler(_bus: &BusSender, _context: &mut ContextStub, command: Commands) {
+    async fn command_handler(bus: &BusSender, _context: &mut ContextStub, command: Commands) {
         info!("{:?}", command);
+
+        if matches!(command, Commands::StartDeepSleep) {
+            for metric in bus.lag_metrics().iter().filter(|x| x.lagged > 0) {
+                warn!("{} lagged by {}", metric.subscriber, metric.lagged);
+            }
+        }
     }
 }
 
@@ -2,7 +2,6 @@ pub mod accel_module;
 pub mod ble_module;
 pub mod logging_module;
 pub mod module;
-pub mod persister_module;
 pub mod power_module;
 pub mod reference_data;
 pub mod rtc_display_fasttrack;
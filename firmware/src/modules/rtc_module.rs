@@ -1,10 +1,12 @@
-use std::collections::BTreeSet;
-
-use blinky_shared::reminders::Reminder;
-use log::{error, info};
+use blinky_shared::persistence::{PersistenceUnit, PersistenceUnitKind};
+use blinky_shared::reference_data::TimezoneRules;
+use blinky_shared::reminders::ReminderSet;
+use blinky_shared::rtc_drift::{DriftEstimate, SyncSample};
+use log::{error, info, warn};
 use time::{PrimitiveDateTime, UtcOffset};
 use tokio::select;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::oneshot;
 use tokio::time::MissedTickBehavior;
 
 use crate::peripherals::rtc::Rtc;
@@ -44,17 +46,22 @@ impl RtcModule {
         info!("starting...");
         let (tx_rtc, rx_rtc) = channel::<Commands>(10);
 
+        let (tx_restored, rx_restored) = oneshot::channel::<ReminderSet>();
+
         let bus_clone = bus.clone();
 
         let rtc_task = tokio::task::spawn_blocking(move || {
-            Self::rtc_loop(bus_clone, rx_rtc, rtc);
+            Self::rtc_loop(bus_clone, rx_rtc, rx_restored, rtc);
         });
 
         let timer = tokio::spawn(Self::ticker_loop(tx_rtc.clone()));
 
         let context = Context { tx_rtc };
 
-        MessageBus::handle::<Context, Self>(bus, context).await;
+        let _ = tokio::join!(
+            Self::restore_reminders(bus.clone(), tx_restored),
+            MessageBus::handle::<Context, Self>(bus, context)
+        );
 
         rtc_task.await.unwrap();
 
@@ -63,10 +70,44 @@ impl RtcModule {
         info!("done.");
     }
 
-    fn rtc_loop(bus: MessageBus, mut rx: Receiver<Commands>, rtc_param: Rtc) {
-        let mut reminders: BTreeSet<Reminder> = BTreeSet::new();
+    async fn restore_reminders(bus: MessageBus, tx: oneshot::Sender<ReminderSet>) {
+        let reply = bus
+            .request(Commands::Restore(PersistenceUnitKind::Reminders))
+            .await;
+
+        let unit = match reply {
+            Ok(Events::Restored(unit)) => unit,
+            Ok(event) => {
+                warn!("unexpected reply {:?}", event.as_ref());
+                return;
+            }
+            Err(timeout) => {
+                error!("restore timed out {:?}", timeout);
+                return;
+            }
+        };
+
+        match unit.deserialize::<ReminderSet>().await {
+            Ok(reminders) => {
+                if tx.send(reminders).is_err() {
+                    warn!("rtc loop is gone");
+                }
+            }
+            Err(error) => error!("{:?}", error),
+        }
+    }
+
+    fn rtc_loop(
+        bus: MessageBus,
+        mut rx: Receiver<Commands>,
+        mut rx_restored: oneshot::Receiver<ReminderSet>,
+        rtc_param: Rtc,
+    ) {
+        let mut reminders = ReminderSet::new();
 
         let mut timezone: UtcOffset = Self::get_timezone();
+        let mut timezone_rules: Option<TimezoneRules> = None;
+        let mut drift: Option<DriftEstimate> = None;
 
         let mut rtc = rtc_param;
 
@@ -82,30 +123,82 @@ impl RtcModule {
 
             info!("rtc loop {:?}", command_opt);
 
+            if let Ok(restored) = rx_restored.try_recv() {
+                reminders.restore(restored);
+                persist_reminders(&bus, &reminders);
+                set_next_alarm(&mut rtc, &reminders, drift.as_ref(), timezone);
+            }
+
             match command_opt {
                 Some(command) => match command {
                     Commands::GetTimeNow => {
-                        if let Ok(now_utc) = rtc.get_now_utc() {
+                        if let Ok(mut now_utc) = rtc.get_now_utc() {
+                            if let Some(rules) = &timezone_rules {
+                                Self::follow_transition(
+                                    &mut rtc,
+                                    &mut timezone,
+                                    &mut now_utc,
+                                    rules,
+                                );
+                            }
+
                             let now = now_utc.assume_offset(timezone);
+                            let now = drift.as_ref().map_or(now, |x| x.correct(now));
 
                             if !is_paused {
                                 bus.send_event(Events::TimeNow(now));
                             }
 
-                            invoke_reminders(&mut reminders, now, &bus);
+                            let due = reminders.due(now);
+
+                            if !due.is_empty() {
+                                persist_reminders(&bus, &reminders);
+                            }
+
+                            for reminder in due {
+                                bus.send_event(Events::Reminder(reminder));
+                            }
                         } else {
                             error!("failed to get rtc time");
                         }
                     }
-                    Commands::SetReminders(mut reminders_param) => {
-                        for reminder in reminders_param.drain(..) {
-                            info!("set reminder {:?}", reminder);
-                            reminders.insert(reminder);
+                    Commands::SetReminders(reminders_param) => {
+                        info!("set {} reminders", reminders_param.len());
+
+                        reminders.set(reminders_param);
+                        persist_reminders(&bus, &reminders);
+                    }
+                    Commands::SnoozeReminder(reminder, duration) => match rtc.get_now_utc() {
+                        Ok(now_utc) => {
+                            let now = now_utc.assume_offset(timezone);
+                            let until = drift.as_ref().map_or(now, |x| x.correct(now)) + duration;
+                            info!("snooze reminder {:?} until {}", reminder, until);
+
+                            reminders.snooze(reminder, until);
+                            persist_reminders(&bus, &reminders);
                         }
+                        Err(error) => error!("failed to get rtc time, {:?}", error),
+                    },
+                    Commands::DismissReminder(reminder) => {
+                        info!("dismiss reminder {:?}", reminder);
+
+                        reminders.dismiss(&reminder);
+                        persist_reminders(&bus, &reminders);
                     }
                     Commands::SetTime(time) => {
                         let offset_utc = time.offset();
 
+                        match rtc.get_now_utc() {
+                            Ok(now_utc) => bus.send_event(Events::RtcAdjusted(SyncSample {
+                                rtc: now_utc.assume_offset(timezone).unix_timestamp(),
+                                reference: time.unix_timestamp(),
+                            })),
+                            Err(error) => error!("failed to get rtc time, {:?}", error),
+                        }
+
+                        // until time sync refits, the rate holds from the new reference
+                        drift = drift.map(|x| x.anchored_at(time.unix_timestamp()));
+
                         unsafe {
                             UTC_OFFSET = Some(time.offset());
                         }
@@ -115,6 +208,10 @@ impl RtcModule {
                         let now = PrimitiveDateTime::new(time.date(), time.time());
                         rtc.set_now_utc(now).unwrap()
                     }
+                    Commands::SetRtcDrift(estimate) => {
+                        info!("rtc drift {:?}", estimate);
+                        drift = Some(estimate);
+                    }
                     Commands::SetTimezone(tz) => {
                         timezone = UtcOffset::from_whole_seconds(tz).unwrap();
 
@@ -122,6 +219,13 @@ impl RtcModule {
                             UTC_OFFSET = Some(timezone);
                         }
                     }
+                    Commands::SetTimezoneRules(rules) => {
+                        if let Ok(mut now_utc) = rtc.get_now_utc() {
+                            Self::follow_transition(&mut rtc, &mut timezone, &mut now_utc, &rules);
+                        }
+
+                        timezone_rules = Some(rules);
+                    }
                     Commands::PauseRendering => {
                         is_paused = true;
                     }
@@ -129,13 +233,13 @@ impl RtcModule {
                         is_paused = false;
                     }
                     Commands::StartDeepSleep => {
-                        set_next_alarm(&mut rtc, &reminders);
+                        set_next_alarm(&mut rtc, &reminders, drift.as_ref(), timezone);
                         is_paused = false;
                         break;
                     }
                     Commands::HandleAlarm => {
                         if rtc.get_alarm_status() {
-                            set_next_alarm(&mut rtc, &reminders);
+                            set_next_alarm(&mut rtc, &reminders, drift.as_ref(), timezone);
                         }
                     }
                     _ => {}
@@ -166,6 +270,35 @@ impl RtcModule {
         }
     }
 
+    // the rtc keeps local wall time, when a transition passed it is moved to the new offset,
+    // the offset survives deep sleep in rtc memory, the rules are restored by time sync
+    fn follow_transition(
+        rtc: &mut Rtc,
+        timezone: &mut UtcOffset,
+        wall: &mut PrimitiveDateTime,
+        rules: &TimezoneRules,
+    ) {
+        let Some(shifted) = rules.shift_wall_clock(*wall, *timezone) else {
+            return;
+        };
+
+        info!("timezone offset {} -> {}", timezone, shifted.offset());
+
+        let shifted_wall = PrimitiveDateTime::new(shifted.date(), shifted.time());
+
+        if let Err(error) = rtc.set_now_utc(shifted_wall) {
+            error!("failed to shift rtc time, {:?}", error);
+            return;
+        }
+
+        *wall = shifted_wall;
+        *timezone = shifted.offset();
+
+        unsafe {
+            UTC_OFFSET = Some(*timezone);
+        }
+    }
+
     fn get_timezone() -> UtcOffset {
         let utc_offset = unsafe {
             if UTC_OFFSET.is_none() {
@@ -178,42 +311,26 @@ impl RtcModule {
     }
 }
 
-fn set_next_alarm(rtc: &mut Rtc, reminders: &BTreeSet<Reminder>) {
-    if reminders.is_empty() {
-        return;
-    }
-
-    let first = reminders.first();
-
-    if let Some(next_reminder) = first {
+// reminders are in corrected time, the alarm fires on the raw rtc wall clock
+fn set_next_alarm(
+    rtc: &mut Rtc,
+    reminders: &ReminderSet,
+    drift: Option<&DriftEstimate>,
+    timezone: UtcOffset,
+) {
+    if let Some(next_reminder) = reminders.next() {
         let remind_at = next_reminder.remind_at;
+        let remind_at = drift
+            .map_or(remind_at, |x| x.to_rtc(remind_at))
+            .to_offset(timezone);
 
         rtc.set_alarm(remind_at);
         info!("set next rtc alarm for {}", remind_at);
     }
 }
 
-fn invoke_reminders(
-    reminders: &mut BTreeSet<Reminder>,
-    now: time::OffsetDateTime,
-    bus: &MessageBus,
-) {
-    loop {
-        let first_opt = reminders.first();
+fn persist_reminders(bus: &MessageBus, reminders: &ReminderSet) {
+    let unit = PersistenceUnit::new(PersistenceUnitKind::Reminders, reminders);
 
-        if first_opt.is_none() {
-            break;
-        }
-
-        let remind_at = first_opt.unwrap().remind_at;
-        if now < remind_at {
-            break;
-        }
-
-        let first = reminders.pop_first();
-
-        if now >= remind_at {
-            bus.send_event(Events::Reminder(first.unwrap()));
-        }
-    }
+    bus.send_cmd(Commands::Persist(unit));
 }
@@ -1,6 +1,8 @@
 use blinky_shared::persistence::{PersistenceUnit, PersistenceUnitKind};
+use blinky_shared::reference_data::TimezoneRules;
+use blinky_shared::rtc_drift::{DriftEstimate, RtcSyncInfo};
 use log::{error, info};
-use time::{Duration, OffsetDateTime, UtcOffset};
+use time::{Duration, OffsetDateTime};
 
 use blinky_shared::commands::Commands;
 use blinky_shared::events::Events;
@@ -14,24 +16,6 @@ struct Context {
     sync_info: Option<RtcSyncInfo>,
 }
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, Hash)]
-pub struct RtcSyncInfo {
-    pub last_sync: i64,
-    pub offset: i32,
-    pub in_sync: bool,
-}
-
-impl Into<OffsetDateTime> for &RtcSyncInfo {
-    fn into(self) -> OffsetDateTime {
-        let last_sync = OffsetDateTime::from_unix_timestamp(self.last_sync + self.offset as i64)
-            .unwrap()
-            .replace_offset(UtcOffset::from_whole_seconds(self.offset).unwrap());
-        last_sync
-    }
-}
-
-const SYNC_INTERVAL_MINUTES: i8 = 10;
-
 impl BusHandler<Context> for TimeSync {
     async fn event_handler(bus: &BusSender, context: &mut Context, event: Events) {
         match event {
@@ -42,54 +26,43 @@ impl BusHandler<Context> for TimeSync {
 
                 context.now = Some(time);
 
-                if Self::is_sync_required(&context.now, &context.sync_info) {
-                    bus.send_cmd(Commands::GetReferenceTime);
-                }
+                Self::restore_sync_info(bus, context).await;
+                Self::restore_timezone(bus).await;
             }
             Events::ReferenceTime(now) => {
                 bus.send_cmd(Commands::SetTime(now));
 
-                let rtc_sync_info = RtcSyncInfo {
+                let history = context
+                    .sync_info
+                    .take()
+                    .map(|x| x.history)
+                    .unwrap_or_default();
+
+                context.sync_info = Some(RtcSyncInfo {
                     in_sync: true,
                     last_sync: now.unix_timestamp(),
                     offset: now.offset().whole_seconds(),
-                };
-
-                let unit = PersistenceUnit::new(PersistenceUnitKind::RtcSyncInfo, &rtc_sync_info);
-                bus.send_cmd(Commands::Persist(unit));
+                    history,
+                });
             }
-            Events::Restored(unit) => {
-                if !matches!(unit.kind, PersistenceUnitKind::RtcSyncInfo) {
-                    return;
-                }
-
-                if let Err(error) = unit.data {
-                    error!("{}", error);
-                    bus.send_cmd(Commands::GetReferenceTime);
+            // the rtc module replies to set time with the reading it has overwritten
+            Events::RtcAdjusted(sample) => {
+                let Some(sync_info) = context.sync_info.as_mut() else {
                     return;
-                }
-
-                let res = unit.deserialize().await;
+                };
 
-                match res {
-                    Ok(sync_info_restored) => {
-                        info!("{:?}", sync_info_restored);
+                sync_info.history.push(sample);
 
-                        context.sync_info = Some(sync_info_restored);
+                let unit = PersistenceUnit::new(PersistenceUnitKind::RtcSyncInfo, sync_info);
+                bus.send_cmd(Commands::Persist(unit));
 
-                        let utc_offset = context.sync_info.as_ref().unwrap().offset;
-                        bus.send_cmd(Commands::SetTimezone(utc_offset));
+                Self::set_drift(bus, sync_info);
+            }
+            Events::ReferenceTimezone(timezone) => {
+                let unit = PersistenceUnit::new(PersistenceUnitKind::TimezoneRules, &timezone);
+                bus.send_cmd(Commands::Persist(unit));
 
-                        if Self::is_sync_required(&context.now, &context.sync_info) {
-                            bus.send_cmd(Commands::GetReferenceTime);
-                        }
-                    }
-                    Err(error) => {
-                        error!("{:?}", error);
-                        bus.send_cmd(Commands::GetReferenceTime);
-                        return;
-                    }
-                }
+                bus.send_cmd(Commands::SetTimezoneRules(timezone));
             }
             _ => {}
         }
@@ -112,6 +85,88 @@ impl TimeSync {
         info!("done.");
     }
 
+    async fn restore_sync_info(bus: &BusSender, context: &mut Context) {
+        let reply = bus
+            .request(Commands::Restore(PersistenceUnitKind::RtcSyncInfo))
+            .await;
+
+        let unit = match reply {
+            Ok(Events::Restored(unit)) => unit,
+            Ok(event) => {
+                error!("unexpected reply {:?}", event.as_ref());
+                bus.send_cmd(Commands::GetReferenceTime);
+                return;
+            }
+            Err(timeout) => {
+                error!("restore timed out {:?}", timeout);
+                bus.send_cmd(Commands::GetReferenceTime);
+                return;
+            }
+        };
+
+        if let Err(error) = unit.data {
+            error!("{}", error);
+            bus.send_cmd(Commands::GetReferenceTime);
+            return;
+        }
+
+        let res = unit.deserialize().await;
+
+        match res {
+            Ok(sync_info_restored) => {
+                info!("{:?}", sync_info_restored);
+
+                context.sync_info = Some(sync_info_restored);
+
+                let utc_offset = context.sync_info.as_ref().unwrap().offset;
+                bus.send_cmd(Commands::SetTimezone(utc_offset));
+
+                Self::set_drift(bus, context.sync_info.as_ref().unwrap());
+
+                if Self::is_sync_required(&context.now, &context.sync_info) {
+                    bus.send_cmd(Commands::GetReferenceTime);
+                }
+            }
+            Err(error) => {
+                error!("{:?}", error);
+                bus.send_cmd(Commands::GetReferenceTime);
+            }
+        }
+    }
+
+    async fn restore_timezone(bus: &BusSender) {
+        let reply = bus
+            .request(Commands::Restore(PersistenceUnitKind::TimezoneRules))
+            .await;
+
+        let unit = match reply {
+            Ok(Events::Restored(unit)) => unit,
+            Ok(event) => {
+                error!("unexpected reply {:?}", event.as_ref());
+                return;
+            }
+            Err(timeout) => {
+                error!("restore timed out {:?}", timeout);
+                return;
+            }
+        };
+
+        match unit.deserialize::<TimezoneRules>().await {
+            Ok(timezone) => bus.send_cmd(Commands::SetTimezoneRules(timezone)),
+            Err(error) => info!("no timezone rules: {:?}", error),
+        }
+    }
+
+    fn set_drift(bus: &BusSender, sync_info: &RtcSyncInfo) {
+        match sync_info.history.estimate() {
+            Some(estimate) => {
+                info!("rtc drift {:.1} ppm", estimate.ppm);
+                bus.send_cmd(Commands::SetRtcDrift(estimate));
+            }
+            None => info!("not enough syncs for a drift estimate"),
+        }
+    }
+
     fn is_sync_required(
         now_opt: &Option<OffsetDateTime>,
         sync_info_opt: &Option<RtcSyncInfo>,
@@ -124,14 +179,15 @@ impl TimeSync {
         let now = now_opt.unwrap();
         let last_sync: OffsetDateTime = sync_info.into();
 
-        let in_sync = sync_info.in_sync && Self::is_in_sync(&now, &last_sync);
+        let interval = DriftEstimate::sync_interval(sync_info.history.estimate().as_ref());
+        let in_sync = sync_info.in_sync && Self::is_in_sync(&now, &last_sync, interval);
 
         return !in_sync;
     }
 
-    fn is_in_sync(now: &OffsetDateTime, last_sync: &OffsetDateTime) -> bool {
+    fn is_in_sync(now: &OffsetDateTime, last_sync: &OffsetDateTime, interval: Duration) -> bool {
         let diff = *now - *last_sync;
-        let is_in_sync = diff <= Duration::minutes(SYNC_INTERVAL_MINUTES as i64);
+        let is_in_sync = diff <= interval;
 
         info!("{:?} {:?}", diff, is_in_sync);
 
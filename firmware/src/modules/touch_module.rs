@@ -1,42 +1,29 @@
+use std::time::Duration;
+
 use crate::peripherals::touchpad::{Touchpad, TouchpadConfig};
 use esp_idf_hal::i2c::I2cDriver;
 use log::info;
 use peripherals::i2c_proxy_async::I2cProxyAsync;
+use tokio::select;
+use tokio::time::sleep;
 
 use blinky_shared::commands::Commands;
+use blinky_shared::domain::TouchPosition;
 use blinky_shared::events::Events;
 use blinky_shared::message_bus::{BusHandler, BusSender, ContextStub, MessageBus};
 
-#[derive(Clone, Debug)]
-pub struct TouchPosition {
-    pub x: i32,
-    pub y: i32,
-}
+// often enough for a swipe to leave a trace of several positions
+const POLL_INTERVAL: Duration = Duration::from_millis(25);
 
-struct Context<'a> {
-    touchpad: Touchpad<'a>,
-}
+// the interrupt line pulses, a single missed sample must not split a swipe
+const RELEASE_AFTER_EMPTY_POLLS: u8 = 4;
 
 pub struct TouchModule {}
 
 impl BusHandler<ContextStub> for TouchModule {
-    async fn event_handler(bus: &BusSender, context: &mut ContextStub, event: Events) {
-        match event {
-            /*                        Events::TouchOrMove => {
-                                        if let Some((x,y)) = touchpad.try_get_pos() {
-                                            let pos = TouchPosition {
-                                                x,
-                                                y
-                                            };
-                                            events.send(Events::TouchPos(pos)).unwrap();
-                                        }
-                                    }
-            */
-            _ => {}
-        }
-    }
+    async fn event_handler(_bus: &BusSender, _context: &mut ContextStub, _event: Events) {}
 
-    async fn command_handler(bus: &BusSender, context: &mut ContextStub, command: Commands) {}
+    async fn command_handler(_bus: &BusSender, _context: &mut ContextStub, _command: Commands) {}
 }
 
 impl TouchModule {
@@ -47,10 +34,41 @@ impl TouchModule {
     ) {
         info!("starting...");
 
-        let touchpad = Touchpad::create(proxy, config);
+        let mut touchpad = Touchpad::create(proxy, config);
 
-        MessageBus::handle::<ContextStub, Self>(bus, ContextStub {}).await;
+        select! {
+            _ = MessageBus::handle::<ContextStub, Self>(bus.clone(), ContextStub {}) => {}
+            _ = Self::poll(&mut touchpad, &bus) => {}
+        }
 
         info!("done.")
     }
+
+    // positions while the finger is down, a release once it stays lifted
+    async fn poll(touchpad: &mut Touchpad<'_>, bus: &MessageBus) {
+        let mut touching = false;
+        let mut empty_polls: u8 = 0;
+
+        loop {
+            match touchpad.try_get_pos() {
+                Some((x, y)) => {
+                    touching = true;
+                    empty_polls = 0;
+                    bus.send_event(Events::TouchPos(TouchPosition { x, y }));
+                }
+                None if touching => {
+                    empty_polls += 1;
+
+                    if empty_polls >= RELEASE_AFTER_EMPTY_POLLS {
+                        touching = false;
+                        empty_polls = 0;
+                        bus.send_event(Events::TouchReleased);
+                    }
+                }
+                None => {}
+            }
+
+            sleep(POLL_INTERVAL).await;
+        }
+    }
 }
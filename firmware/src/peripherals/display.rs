@@ -1,8 +1,10 @@
 use crate::peripherals::spi_interface_no_dc::SpiInterfaceNoDC;
+use blinky_shared::dirty_region::{
+    layer_index, merge_layers, window_pixels, DirtyLayers, DirtyTracker, PixelCounter,
+};
 use blinky_shared::display_interface::{ClockDisplayInterface, LayerType, RenderMode};
 use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
 use embedded_graphics::prelude::*;
-use embedded_graphics::primitives::Rectangle;
 use embedded_graphics_framebuf::FrameBuf;
 use enumflags2::BitFlags;
 use esp_idf_hal::delay::Ets;
@@ -53,6 +55,7 @@ where
     display: DisplaySPI<'a, RST>,
     buffer_base: Box<[Rgb565]>,
     buffer_layers: Vec<Box<[Rgb565]>>,
+    dirty_layers: DirtyLayers,
     static_rendered: bool,
     is_first_render: bool,
     en: Option<PinDriver<'a, EN, Output>>,
@@ -125,6 +128,7 @@ where
             display,
             buffer_layers,
             buffer_base: Self::prepare_frame_buf(),
+            dirty_layers: DirtyLayers::new(FRAME_BUFFER_SIDE),
             static_rendered: false,
             is_first_render: true,
         }
@@ -207,6 +211,7 @@ where
             display,
             buffer_layers,
             buffer_base: Self::prepare_frame_buf(),
+            dirty_layers: DirtyLayers::new(FRAME_BUFFER_SIDE),
             static_rendered: false,
             is_first_render: true,
             en: Some(en_driver),
@@ -225,7 +230,7 @@ where
     type Error = Infallible;
     type ColorModel = Rgb565;
     type FrameBuffer<'b> =
-        FrameBuf<Self::ColorModel, &'b mut [Self::ColorModel; FRAME_BUFFER_SIZE]>;
+        DirtyTracker<FrameBuf<Self::ColorModel, &'b mut [Self::ColorModel; FRAME_BUFFER_SIZE]>>;
 
     const FRAME_BUFFER_SIZE: usize = FRAME_BUFFER_SIZE;
 
@@ -235,13 +240,7 @@ where
         mode: RenderMode,
         func: impl FnOnce(Self::FrameBuffer<'c>) -> Self::FrameBuffer<'c>,
     ) {
-        let layer_value = layer as i32;
-
-        let log2 = f32::log2(layer_value as f32);
-
-        let layer_index: usize = f32::round(log2) as usize;
-
-        let data = self.buffer_layers[layer_index].as_mut();
+        let data = self.buffer_layers[layer_index(layer)].as_mut();
 
         let buf: &'c mut [Self::ColorModel; FRAME_BUFFER_SIZE] = data.try_into().unwrap();
 
@@ -255,87 +254,67 @@ where
 
         let timing_reset = now.elapsed();
 
-        func(frame);
+        let (_, drawn) = func(DirtyTracker::new(frame)).into_parts();
 
         let timing_frame = now.elapsed() - timing_reset;
 
         info!(
-            "render timing: layer {:?} reset {} frame {}",
-            layer, timing_reset, timing_frame
+            "render timing: layer {:?} reset {} frame {} dirty {:?}",
+            layer,
+            timing_reset,
+            timing_frame,
+            drawn.rects()
         );
+
+        self.dirty_layers.rendered(layer, mode, drawn);
     }
 
     fn commit(&mut self, layers_mask: BitFlags<LayerType>) {
         let now = Instant::now();
 
-        let layers_count = self.buffer_layers.len();
-
-        let base_layer = self.buffer_base.as_mut();
-
-        base_layer.fill(Rgb565::BLACK);
+        let dirty = self.dirty_layers.take(layers_mask);
 
-        let timing_fill_black = now.elapsed();
-
-        for layer_index in 0..layers_count {
-            if !layers_mask.contains(BitFlags::from_bits_truncate(1 << layer_index)) {
-                continue;
-            }
-
-            let layer = self.buffer_layers[layer_index].as_ref();
-
-            for pixel_index in 0..layer.len() {
-                if layer[pixel_index] == RgbColor::BLACK {
-                    continue;
-                }
-
-                base_layer[pixel_index] = layer[pixel_index];
-            }
+        for rect in dirty.rects() {
+            merge_layers(
+                &mut self.buffer_base,
+                &self.buffer_layers,
+                layers_mask,
+                FRAME_BUFFER_SIDE,
+                rect,
+            );
         }
 
-        let timing_merge = now.elapsed() - timing_fill_black;
-
-        let rect = Rectangle::new(
-            Point::zero(),
-            Size::new(
-                Self::FRAME_BUFFER_SIDE as u32,
-                Self::FRAME_BUFFER_SIDE as u32,
-            ),
-        );
+        let timing_merge = now.elapsed();
 
         let data = self.buffer_base.as_ref();
 
-        self.is_first_render = false;
-
-        if self.is_first_render {
-            let iter = data.iter().enumerate().filter_map(|item| {
-                let color = *item.1;
-                if color == Self::ColorModel::BLACK {
-                    return None;
-                }
-
-                let y = item.0 / Self::FRAME_BUFFER_SIDE;
-                let x = item.0 - y * Self::FRAME_BUFFER_SIDE;
-                let point = Point::new(x as i32, y as i32);
-
-                Some(Pixel(point, color))
-            });
+        // one window per dirty rect, the rest of the panel keeps what it shows
+        for rect in dirty.rects() {
+            let iter = window_pixels(data, FRAME_BUFFER_SIDE, rect);
 
-            self.display.draw_iter(iter).unwrap();
+            self.display.fill_contiguous(rect, iter).unwrap();
+        }
 
-            self.is_first_render = false;
-        } else {
-            let iter = data.iter().map(|x| *x);
+        self.is_first_render = false;
 
-            self.display.fill_contiguous(&rect, iter).unwrap();
-        }
+        let timing_render = now.elapsed() - timing_merge;
 
-        let timing_render = now.elapsed() - timing_fill_black - timing_merge;
+        let counter = self.dirty_layers.counter();
 
         info!(
-            "render commit timing: clear_black {} merge {}  render {}",
-            timing_fill_black, timing_merge, timing_render
+            "render commit timing: merge {} render {}, pushed {} pixels in {} windows, {} in {} frames",
+            timing_merge,
+            timing_render,
+            counter.last_frame,
+            dirty.rects().len(),
+            counter.total,
+            counter.frames
         );
     }
+
+    fn pixel_counter(&self) -> PixelCounter {
+        self.dirty_layers.counter()
+    }
 }
 
 impl<'a, DC, RST, EN> ClockDisplay<'a, DC, RST, EN>
@@ -10,6 +10,7 @@ pub mod nvs_storage;
 pub mod output;
 pub mod rtc;
 pub mod rtc_memory;
+pub mod rtc_trace;
 pub mod spi_interface_no_dc;
 pub mod touchpad;
 pub mod wifi;
@@ -1,10 +1,17 @@
+use std::ffi::{CStr, CString};
+
 use blinky_shared::error::Error;
+use blinky_shared::storage::KeyValueStorage;
 use esp_idf_svc::nvs;
 use esp_idf_svc::nvs::{EspNvs, NvsDefault};
-use serde::{Deserialize, Serialize};
+use esp_idf_sys::{
+    nvs_entry_find, nvs_entry_info, nvs_entry_info_t, nvs_entry_next, nvs_iterator_t,
+    nvs_release_iterator, nvs_type_t_NVS_TYPE_ANY, ESP_OK,
+};
 
 pub struct NvsStorage {
     nvs: EspNvs<NvsDefault>,
+    namespace: CString,
 }
 
 const NVS_KEY_MAX_LENGTH: usize = 15;
@@ -16,30 +23,9 @@ impl NvsStorage {
         let partition = nvs::EspDefaultNvsPartition::take().unwrap();
         let nvs = EspNvs::new(partition, trimmed_key, true).unwrap();
 
-        Self { nvs }
-    }
-
-    pub fn read<T>(&self, name: &str) -> Result<T, Error>
-    where
-        for<'a> T: Deserialize<'a>,
-    {
-        let trimmed_key = Self::trim_nvs_key(name);
-
-        let len_opt = self
-            .nvs
-            .blob_len(trimmed_key)
-            .map_err(|_| Error::from("nvs len error"))
-            .unwrap();
+        let namespace = CString::new(trimmed_key).unwrap();
 
-        if let Some(len) = len_opt {
-            let mut buffer = vec![0; len];
-            self.nvs.get_raw(trimmed_key, &mut buffer[..]).unwrap();
-
-            return rmp_serde::from_slice(&buffer)
-                .map_err(|err| Error::from(format!("deserialization error: {:?}", err)));
-        }
-
-        return Err(Error::from("unable to read nvs"));
+        Self { nvs, namespace }
     }
 
     fn trim_nvs_key(namespace: &str) -> &str {
@@ -52,19 +38,22 @@ impl NvsStorage {
 
         &namespace[..cutoff]
     }
+}
 
-    pub fn read_bytes(&self, name: &str) -> Result<Vec<u8>, Error> {
-        let trimmed_key = Self::trim_nvs_key(name);
+impl KeyValueStorage for NvsStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>, Error> {
+        let trimmed_key = Self::trim_nvs_key(key);
 
         let len_opt = self
             .nvs
             .blob_len(trimmed_key)
-            .map_err(|err| Error::from(format!("nvs len error {:?}", err)))
-            .unwrap();
+            .map_err(|err| Error::from(format!("nvs len error {:?}", err)))?;
 
         if let Some(len) = len_opt {
             let mut buffer = vec![0; len];
-            self.nvs.get_raw(trimmed_key, &mut buffer[..]).unwrap();
+            self.nvs
+                .get_raw(trimmed_key, &mut buffer[..])
+                .map_err(|err| Error::from(format!("nvs read error {:?}", err)))?;
 
             return Ok(buffer);
         }
@@ -72,33 +61,52 @@ impl NvsStorage {
         return Err(Error::from("unable to read nvs"));
     }
 
-    pub fn write<T>(&mut self, name: &str, data: &T) -> Result<(), Error>
-    where
-        T: Serialize,
-    {
-        let trimmed_key = Self::trim_nvs_key(name);
-
-        let buf = rmp_serde::to_vec(data).unwrap();
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        let trimmed_key = Self::trim_nvs_key(key);
 
         self.nvs
-            .set_raw(trimmed_key, &buf)
-            .map_err(|err| err.to_string())
-            .unwrap();
+            .set_raw(trimmed_key, data)
+            .map_err(|err| Error::from(format!("nvs write error {:?}", err)))?;
 
         Ok(())
     }
 
-    pub fn write_bytes<T>(&mut self, name: &str, data: &Vec<u8>) -> Result<(), Error>
-    where
-        T: Serialize,
-    {
-        let trimmed_key = Self::trim_nvs_key(name);
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        let trimmed_key = Self::trim_nvs_key(key);
 
         self.nvs
-            .set_raw(trimmed_key, data)
-            .map_err(|err| err.to_string())
-            .unwrap();
+            .remove(trimmed_key)
+            .map_err(|err| Error::from(format!("nvs delete error {:?}", err)))?;
 
         Ok(())
     }
+
+    // keys come back trimmed to the nvs key length
+    fn list(&self) -> Result<Vec<String>, Error> {
+        let mut keys = vec![];
+        let mut iterator: nvs_iterator_t = std::ptr::null_mut();
+
+        unsafe {
+            let mut result = nvs_entry_find(
+                c"nvs".as_ptr(),
+                self.namespace.as_ptr(),
+                nvs_type_t_NVS_TYPE_ANY,
+                &mut iterator,
+            );
+
+            while result == ESP_OK {
+                let mut info: nvs_entry_info_t = std::mem::zeroed();
+                nvs_entry_info(iterator, &mut info);
+
+                let key = CStr::from_ptr(info.key.as_ptr());
+                keys.push(key.to_string_lossy().into_owned());
+
+                result = nvs_entry_next(&mut iterator);
+            }
+
+            nvs_release_iterator(iterator);
+        }
+
+        Ok(keys)
+    }
 }
@@ -5,3 +5,12 @@ pub static mut UTC_OFFSET: Option<UtcOffset> = None;
 
 #[link_section = ".rtc.data"]
 pub static mut RTC_INITIALIZED: bool = false;
+
+// the recorded trace kept across deep sleep, rtc slow memory is 8k on both boards
+pub const TRACE_CAPACITY: usize = 4096;
+
+#[link_section = ".rtc.data"]
+pub static mut TRACE_LEN: usize = 0;
+
+#[link_section = ".rtc.data"]
+pub static mut TRACE: [u8; TRACE_CAPACITY] = [0; TRACE_CAPACITY];
@@ -0,0 +1,48 @@
+use blinky_shared::modules::recorder::{to_hex_dump, TraceRing, TraceSink};
+use log::{error, info};
+
+use crate::peripherals::rtc_memory::{TRACE, TRACE_CAPACITY, TRACE_LEN};
+
+// the newest records survive deep sleep in rtc memory and are dumped to the console on wakeup,
+// the sim replays a saved console log
+pub struct RtcTrace {
+    ring: TraceRing,
+}
+
+impl RtcTrace {
+    pub fn restore() -> Self {
+        let kept = unsafe { &TRACE[..TRACE_LEN.min(TRACE_CAPACITY)] };
+
+        let ring = TraceRing::from_trace(kept, TRACE_CAPACITY).unwrap_or_else(|err| {
+            error!("dropping kept trace, {:?}", err);
+            TraceRing::new(TRACE_CAPACITY)
+        });
+
+        Self { ring }
+    }
+
+    pub fn dump(&self) {
+        info!("trace of {} records", self.ring.len());
+
+        for line in to_hex_dump(&self.ring.to_trace()) {
+            info!("{}", line);
+        }
+    }
+}
+
+impl TraceSink for RtcTrace {
+    fn write_record(&mut self, record: &[u8]) -> std::io::Result<()> {
+        self.ring.write_record(record)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let trace = self.ring.to_trace();
+
+        unsafe {
+            TRACE[..trace.len()].copy_from_slice(&trace);
+            TRACE_LEN = trace.len();
+        }
+
+        Ok(())
+    }
+}
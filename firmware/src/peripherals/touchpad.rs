@@ -3,7 +3,6 @@ use embedded_hal_compat::{Reverse, ReverseCompat};
 use esp_idf_hal::delay::Ets;
 use esp_idf_hal::gpio::{AnyIOPin, Input, Output, PinDriver};
 use esp_idf_hal::i2c::I2cDriver;
-use log::debug;
 use peripherals::i2c_proxy_async::I2cProxyAsync;
 
 pub type TouchpadDevice<'d> = CST816S<
@@ -38,17 +37,10 @@ impl<'d> Touchpad<'d> {
         Self { device: touchpad }
     }
 
+    // polled every few ms while awake, so no logging here
     pub fn try_get_pos(&mut self) -> Option<(i32, i32)> {
-        debug!("reading touch event...");
-        let touch_event_opt = self.device.read_one_touch_event(true);
-        debug!("touch event {:?}", touch_event_opt);
-
-        match touch_event_opt {
-            Some(touch_event) => {
-                let TouchEvent { x, y, .. } = touch_event;
-                Some((x, y))
-            }
-            None => None,
-        }
+        self.device
+            .read_one_touch_event(true)
+            .map(|TouchEvent { x, y, .. }| (x, y))
     }
 }
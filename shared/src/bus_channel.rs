@@ -0,0 +1,224 @@
+use std::{
+    collections::VecDeque,
+    mem::discriminant,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, Weak,
+    },
+};
+
+use tokio::sync::Notify;
+
+// critical messages grow a full queue up to this, past it a subscriber that stopped reading
+// loses the oldest ones like any other message, reported as lag
+pub const MAX_BACKLOG: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    DropOldest,
+    LatestWins,
+    NeverDrop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecvError {
+    Lagged(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LagMetric {
+    pub subscriber: &'static str,
+    pub lagged: u32,
+}
+
+struct Subscriber<T> {
+    name: Mutex<&'static str>,
+    queue: Mutex<VecDeque<T>>,
+    notify: Notify,
+    lagged: AtomicU32,
+}
+
+struct Shared<T> {
+    capacity: usize,
+    policy: fn(&T) -> OverflowPolicy,
+    subscribers: Mutex<Vec<Weak<Subscriber<T>>>>,
+}
+
+pub struct ChannelSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct ChannelReceiver<T> {
+    shared: Arc<Shared<T>>,
+    subscriber: Arc<Subscriber<T>>,
+    reported_lag: u32,
+}
+
+pub fn channel<T: Clone>(
+    capacity: usize,
+    policy: fn(&T) -> OverflowPolicy,
+) -> (ChannelSender<T>, ChannelReceiver<T>) {
+    let sender = ChannelSender {
+        shared: Arc::new(Shared {
+            capacity: capacity.max(1),
+            policy,
+            subscribers: Mutex::new(vec![]),
+        }),
+    };
+
+    let receiver = sender.subscribe();
+
+    (sender, receiver)
+}
+
+impl<T: Clone> ChannelSender<T> {
+    pub fn send(&self, message: T) -> Result<usize, SendError> {
+        let subscribers: Vec<Arc<Subscriber<T>>> = {
+            let mut subscribers = self.shared.subscribers.lock().unwrap();
+            subscribers.retain(|x| x.strong_count() > 0);
+            subscribers.iter().filter_map(|x| x.upgrade()).collect()
+        };
+
+        if subscribers.is_empty() {
+            return Err(SendError);
+        }
+
+        for subscriber in subscribers.iter() {
+            self.push(subscriber, message.clone());
+        }
+
+        Ok(subscribers.len())
+    }
+
+    pub fn subscribe(&self) -> ChannelReceiver<T> {
+        let subscriber = Arc::new(Subscriber {
+            name: Mutex::new("unnamed"),
+            queue: Mutex::new(VecDeque::with_capacity(self.shared.capacity)),
+            notify: Notify::new(),
+            lagged: AtomicU32::new(0),
+        });
+
+        self.shared
+            .subscribers
+            .lock()
+            .unwrap()
+            .push(Arc::downgrade(&subscriber));
+
+        ChannelReceiver {
+            shared: self.shared.clone(),
+            subscriber,
+            reported_lag: 0,
+        }
+    }
+
+    pub fn lag_metrics(&self) -> Vec<LagMetric> {
+        self.shared
+            .subscribers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|x| x.upgrade())
+            .map(|x| LagMetric {
+                subscriber: *x.name.lock().unwrap(),
+                lagged: x.lagged.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    fn push(&self, subscriber: &Subscriber<T>, message: T) {
+        let policy = self.shared.policy;
+
+        {
+            let mut queue = subscriber.queue.lock().unwrap();
+
+            if queue.len() >= self.shared.capacity {
+                let coalesced = match policy(&message) {
+                    OverflowPolicy::LatestWins => queue
+                        .iter()
+                        .position(|x| discriminant(x) == discriminant(&message)),
+                    _ => None,
+                };
+
+                let victim = coalesced.or_else(|| {
+                    queue
+                        .iter()
+                        .position(|x| policy(x) != OverflowPolicy::NeverDrop)
+                });
+
+                match victim {
+                    Some(index) => {
+                        queue.remove(index);
+                        subscriber.lagged.fetch_add(1, Ordering::Relaxed);
+                    }
+                    // the queue is full of critical messages, only critical ones may grow it and
+                    // they are not evicted before the backlog limit, senders are sync so the queue
+                    // grows instead of waiting
+                    None if policy(&message) != OverflowPolicy::NeverDrop => {
+                        subscriber.lagged.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    None if queue.len() >= self.shared.capacity.max(MAX_BACKLOG) => {
+                        queue.pop_front();
+                        subscriber.lagged.fetch_add(1, Ordering::Relaxed);
+                    }
+                    None => {}
+                }
+            }
+
+            queue.push_back(message);
+        }
+
+        subscriber.notify.notify_one();
+    }
+}
+
+impl<T> Clone for ChannelSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Clone> ChannelReceiver<T> {
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        loop {
+            let lagged = self.subscriber.lagged.load(Ordering::Relaxed);
+
+            if lagged != self.reported_lag {
+                let count = lagged.wrapping_sub(self.reported_lag);
+                self.reported_lag = lagged;
+                return Err(RecvError::Lagged(count));
+            }
+
+            if let Some(message) = self.subscriber.queue.lock().unwrap().pop_front() {
+                return Ok(message);
+            }
+
+            self.subscriber.notify.notified().await;
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.subscriber.queue.lock().unwrap().pop_front()
+    }
+
+    pub fn set_name(&self, name: &'static str) {
+        *self.subscriber.name.lock().unwrap() = name;
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriber.queue.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+}
@@ -1,10 +1,14 @@
+pub mod recurrence;
+pub mod state;
+
 use std::hash::{Hash, Hasher};
 
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
-use time::{Duration, OffsetDateTime, UtcOffset};
+use time::{Duration, OffsetDateTime, Time, UtcOffset};
 
-use crate::reference_data::ReferenceTimeUtc;
+use crate::calendar::recurrence::RecurrenceRule;
+use crate::reference_data::{ReferenceTimeUtc, TimezoneRules};
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
 #[repr(u8)]
@@ -47,7 +51,7 @@ pub enum CalendarEventRemainderStatus {
     Passed = 2,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CalendarEvent {
     pub kind: CalendarKind,
     pub id: i32,
@@ -58,6 +62,12 @@ pub struct CalendarEvent {
     pub color: u32,
     pub description: String,
     pub lane: u8,
+
+    #[serde(default)]
+    pub recurrence: Option<RecurrenceRule>,
+
+    #[serde(default)]
+    pub reminder_offsets: Option<Vec<i16>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -69,7 +79,7 @@ pub struct TimelyDataRecord {
     pub data_marker: TimelyDataMarker,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EventTimelyData {
     pub linked_event_id: i32,
     pub timely_data: Vec<TimelyDataRecord>,
@@ -97,6 +107,13 @@ pub struct CalendarEventDto {
     pub color: u32,
     pub description: String,
     pub lane: u8,
+
+    #[serde(default)]
+    pub recurrence: Option<RecurrenceRule>,
+
+    // minutes before the start, the kind default applies when missing
+    #[serde(default)]
+    pub reminder_offsets: Option<Vec<i16>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Hash)]
@@ -114,6 +131,8 @@ impl From<CalendarEvent> for CalendarEventDto {
             color: value.color,
             description: value.description,
             lane: value.lane,
+            recurrence: value.recurrence,
+            reminder_offsets: value.reminder_offsets,
         }
     }
 }
@@ -130,6 +149,8 @@ impl From<&CalendarEvent> for CalendarEventDto {
             color: value.color,
             description: value.description.clone(),
             lane: value.lane,
+            recurrence: value.recurrence.clone(),
+            reminder_offsets: value.reminder_offsets.clone(),
         }
     }
 }
@@ -189,6 +210,8 @@ impl CalendarEvent {
             color: dto.color,
             description: dto.description.clone(),
             lane: dto.lane,
+            recurrence: dto.recurrence.clone(),
+            reminder_offsets: dto.reminder_offsets.clone(),
         }
     }
 
@@ -199,6 +222,63 @@ impl CalendarEvent {
     pub fn duration(&self) -> Duration {
         self.end - self.start
     }
+
+    // occurrences overlapping [from, to], a single event is its own only occurrence
+    pub fn occurrences_between(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        timezone: Option<&TimezoneRules>,
+    ) -> Vec<Self> {
+        let duration = self.duration();
+
+        let Some(rule) = &self.recurrence else {
+            return match self.end >= from && self.start <= to {
+                true => vec![self.clone()],
+                false => vec![],
+            };
+        };
+
+        rule.occurrences_from(self.start, from - duration)
+            .in_timezone(timezone)
+            .skip_while(|x| *x + duration < from)
+            .take_while(|x| *x <= to)
+            .map(|start| CalendarEvent {
+                start,
+                end: localize(start + duration, timezone),
+                recurrence: None,
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    pub fn is_over(&self, now: OffsetDateTime) -> bool {
+        let Some(rule) = &self.recurrence else {
+            return self.end < now;
+        };
+
+        let duration = self.duration();
+
+        rule.occurrences_from(self.start, now - duration)
+            .all(|x| x + duration < now)
+    }
+
+    // instants stay the same, only the offsets they are shown in change
+    pub fn in_timezone(self, timezone: &TimezoneRules) -> Self {
+        CalendarEvent {
+            start: timezone.to_local(self.start),
+            end: timezone.to_local(self.end),
+            ..self
+        }
+    }
+
+    pub fn is_all_day(&self) -> bool {
+        self.duration() >= Duration::days(1)
+    }
+
+    pub fn is_recurring(&self) -> bool {
+        self.recurrence.is_some()
+    }
 }
 
 impl From<CalendarEventDto> for CalendarEvent {
@@ -213,6 +293,8 @@ impl From<CalendarEventDto> for CalendarEvent {
             color: dto.color,
             description: dto.description,
             lane: dto.lane,
+            recurrence: dto.recurrence,
+            reminder_offsets: dto.reminder_offsets,
         }
     }
 }
@@ -235,6 +317,21 @@ impl PartialEq<Self> for CalendarEventOrderedByStartAsc {
 
 impl Eq for CalendarEventOrderedByStartAsc {}
 
+impl TimelyDataRecord {
+    // records only carry the local hour, a window that is already over today is tomorrow's forecast
+    pub fn window(&self, now: OffsetDateTime) -> (OffsetDateTime, OffsetDateTime) {
+        let start = now.replace_time(Time::MIDNIGHT) + Duration::hours(self.start_at_hour as i64);
+
+        let start = if start + self.duration <= now {
+            start + Duration::days(1)
+        } else {
+            start
+        };
+
+        (start, start + self.duration)
+    }
+}
+
 impl Hash for TimelyDataRecord {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.linked_event_id.hash(state);
@@ -254,3 +351,10 @@ impl PartialEq<Self> for TimelyDataRecord {
             && self.start_at_hour == other.start_at_hour
     }
 }
+
+fn localize(time: OffsetDateTime, timezone: Option<&TimezoneRules>) -> OffsetDateTime {
+    match timezone {
+        Some(timezone) => timezone.to_local(time),
+        None => time,
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use time::{Date, Duration, Month, OffsetDateTime, Weekday};
+
+use crate::reference_data::{ReferenceTimeUtc, TimezoneRules};
+
+// a rule that can't produce anything, e.g. the 31st of every second February, stops after that
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
+#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq, Clone, Copy, Hash)]
+#[repr(u8)]
+pub enum Frequency {
+    Daily = 1,
+    Weekly = 2,
+    Monthly = 3,
+    Yearly = 4,
+}
+
+// a subset of RFC 5545 RRULE, BYDAY has no ordinals and is ignored for yearly rules
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct RecurrenceRule {
+    pub frequency: Frequency,
+    pub interval: u16,
+
+    // bit 0 is monday
+    #[serde(default)]
+    pub by_day: u8,
+
+    #[serde(default)]
+    pub count: Option<u16>,
+
+    #[serde(default)]
+    pub until: Option<ReferenceTimeUtc>,
+
+    #[serde(default)]
+    pub exdates: Vec<ReferenceTimeUtc>,
+}
+
+pub struct Occurrences<'a> {
+    rule: &'a RecurrenceRule,
+    start: OffsetDateTime,
+    period: i64,
+    pending: VecDeque<OffsetDateTime>,
+    generated: u32,
+    empty_periods: u32,
+    done: bool,
+    timezone: Option<&'a TimezoneRules>,
+}
+
+impl RecurrenceRule {
+    pub fn new(frequency: Frequency, interval: u16) -> Self {
+        Self {
+            frequency,
+            interval,
+            by_day: 0,
+            count: None,
+            until: None,
+            exdates: vec![],
+        }
+    }
+
+    pub fn weekday_bit(weekday: Weekday) -> u8 {
+        1 << weekday.number_days_from_monday()
+    }
+
+    // occurrence starts in order, none before the series start
+    pub fn occurrences(&self, start: OffsetDateTime) -> Occurrences<'_> {
+        Occurrences::new(self, start, 0)
+    }
+
+    // skips whole periods before `from` when there is no COUNT to keep track of
+    pub fn occurrences_from(&self, start: OffsetDateTime, from: OffsetDateTime) -> Occurrences<'_> {
+        if self.count.is_some() || from <= start {
+            return self.occurrences(start);
+        }
+
+        let period = self.periods_between(start.date(), from.date()) - 1;
+
+        Occurrences::new(self, start, period.max(0))
+    }
+
+    fn interval(&self) -> i64 {
+        self.interval.max(1) as i64
+    }
+
+    fn periods_between(&self, start: Date, date: Date) -> i64 {
+        let periods = match self.frequency {
+            Frequency::Daily => (date - start).whole_days(),
+            Frequency::Weekly => (date - start).whole_weeks(),
+            Frequency::Monthly => {
+                (date.year() - start.year()) as i64 * 12 + date.month() as i64
+                    - start.month() as i64
+            }
+            Frequency::Yearly => (date.year() - start.year()) as i64,
+        };
+
+        periods / self.interval()
+    }
+
+    fn period_dates(&self, start: Date, period: i64) -> Option<Vec<Date>> {
+        let step = self.interval().checked_mul(period)?;
+
+        let dates = match self.frequency {
+            Frequency::Daily => {
+                let date = start.checked_add(Duration::days(step))?;
+
+                match self.by_day == 0 || self.has_weekday(date.weekday()) {
+                    true => vec![date],
+                    false => vec![],
+                }
+            }
+            Frequency::Weekly => {
+                let monday =
+                    start - Duration::days(start.weekday().number_days_from_monday() as i64);
+                let monday = monday.checked_add(Duration::weeks(step))?;
+
+                let by_day = match self.by_day {
+                    0 => Self::weekday_bit(start.weekday()),
+                    by_day => by_day,
+                };
+
+                (0..7)
+                    .filter(|x| by_day & (1 << x) != 0)
+                    .map(|x| monday + Duration::days(x))
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = start.year() as i64 * 12 + start.month() as i64 - 1 + step;
+                let year = i32::try_from(months.div_euclid(12)).ok()?;
+                let month = Month::try_from(months.rem_euclid(12) as u8 + 1).unwrap();
+
+                match self.by_day {
+                    0 => Date::from_calendar_date(year, month, start.day())
+                        .into_iter()
+                        .collect(),
+                    _ => (1..=time::util::days_in_year_month(year, month))
+                        .filter_map(|x| Date::from_calendar_date(year, month, x).ok())
+                        .filter(|x| self.has_weekday(x.weekday()))
+                        .collect(),
+                }
+            }
+            Frequency::Yearly => {
+                let year = i32::try_from(start.year() as i64 + step).ok()?;
+
+                Date::from_calendar_date(year, start.month(), start.day())
+                    .into_iter()
+                    .collect()
+            }
+        };
+
+        Some(dates)
+    }
+
+    fn has_weekday(&self, weekday: Weekday) -> bool {
+        self.by_day & Self::weekday_bit(weekday) != 0
+    }
+
+    fn is_excluded(&self, time: OffsetDateTime) -> bool {
+        let timestamp = time.unix_timestamp();
+
+        self.exdates
+            .iter()
+            .any(|x| x.unix_epoch_seconds == timestamp)
+    }
+
+    fn is_after_until(&self, time: OffsetDateTime) -> bool {
+        match &self.until {
+            Some(until) => time.unix_timestamp() > until.unix_epoch_seconds,
+            None => false,
+        }
+    }
+}
+
+impl<'a> Occurrences<'a> {
+    fn new(rule: &'a RecurrenceRule, start: OffsetDateTime, period: i64) -> Self {
+        Self {
+            rule,
+            start,
+            period,
+            pending: VecDeque::new(),
+            generated: 0,
+            empty_periods: 0,
+            done: false,
+            timezone: None,
+        }
+    }
+
+    // keeps the wall clock time of the series start across dst transitions
+    pub fn in_timezone(mut self, timezone: Option<&'a TimezoneRules>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    fn next_period(&mut self) {
+        let dates = self.rule.period_dates(self.start.date(), self.period);
+        self.period += 1;
+
+        let Some(dates) = dates else {
+            self.done = true;
+            return;
+        };
+
+        if dates.is_empty() {
+            self.empty_periods += 1;
+            self.done = self.empty_periods > MAX_EMPTY_PERIODS;
+            return;
+        }
+
+        self.empty_periods = 0;
+
+        let time = self.start.time();
+        let offset = self.start.offset();
+
+        let timezone = self.timezone;
+
+        self.pending
+            .extend(dates.into_iter().map(|x| match timezone {
+                Some(timezone) => timezone.resolve_local(x.with_time(time)),
+                None => x.with_time(time).assume_offset(offset),
+            }));
+    }
+}
+
+impl<'a> Iterator for Occurrences<'a> {
+    type Item = OffsetDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let Some(candidate) = self.pending.pop_front() else {
+                self.next_period();
+                continue;
+            };
+
+            // the first period may start before the series, e.g. the monday of its week
+            if candidate < self.start {
+                continue;
+            }
+
+            let exhausted = self.rule.count.is_some_and(|x| self.generated >= x as u32);
+
+            if exhausted || self.rule.is_after_until(candidate) {
+                self.done = true;
+                break;
+            }
+
+            // excluded dates still count towards COUNT
+            self.generated += 1;
+
+            if !self.rule.is_excluded(candidate) {
+                return Some(candidate);
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::{CalendarEventDto, TimelyDataRecord};
+use crate::error::Error;
+use crate::reference_data::ReferenceTimeUtc;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CalendarStateDto {
+    pub last_sync: ReferenceTimeUtc,
+    pub events: Vec<CalendarEventDto>,
+    pub timely_data: Vec<TimelyDataRecord>,
+}
+
+// layout before the persistence envelope, timely data was only there from version 3 on
+#[derive(Debug, Deserialize)]
+struct CalendarStateDtoV1 {
+    version: i32,
+    last_sync: ReferenceTimeUtc,
+    events: Vec<CalendarEventDto>,
+    #[serde(default)]
+    timely_data: Vec<TimelyDataRecord>,
+}
+
+impl CalendarStateDto {
+    pub fn new(
+        events: Vec<CalendarEventDto>,
+        timely_data: Vec<TimelyDataRecord>,
+        last_sync: ReferenceTimeUtc,
+    ) -> Self {
+        Self {
+            events,
+            timely_data,
+            last_sync,
+        }
+    }
+}
+
+pub fn migrate_calendar_state_v1(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let legacy: CalendarStateDtoV1 =
+        rmp_serde::from_slice(bytes).map_err(|err| Error::from(err.to_string()))?;
+
+    let timely_data = match legacy.version {
+        version if version > 2 => legacy.timely_data,
+        _ => vec![],
+    };
+
+    let state = CalendarStateDto::new(legacy.events, timely_data, legacy.last_sync);
+
+    Ok(rmp_serde::to_vec(&state).unwrap())
+}
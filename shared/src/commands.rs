@@ -1,26 +1,42 @@
 use crate::{
+    message_bus::CorrelationId,
+    modules::{layout::Face, navigation::Navigation},
     persistence::{PersistenceUnit, PersistenceUnitKind},
-    reminders::Reminder,
+    reference_data::TimezoneRules,
+    reminders::{Reminder, ReminderSettings},
+    rtc_drift::DriftEstimate,
 };
-use time::OffsetDateTime;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Commands {
     RequestReferenceData,
     SyncCalendar,
     GetTimeNow,
     GetReferenceTime,
     SetTime(OffsetDateTime),
+    SetRtcDrift(DriftEstimate),
     StartDeepSleep,
     PauseRendering,
     ResumeRendering,
     GetTemperature,
     Persist(PersistenceUnit),
     Restore(PersistenceUnitKind),
+    GetStorageUsage,
     SetTimezone(i32),
+    SetTimezoneRules(TimezoneRules),
     AbortSleep,
     ShutdownBle,
     SetReminders(Vec<Reminder>),
+    SetReminderSettings(ReminderSettings),
+    SnoozeReminder(Reminder, Duration),
+    DismissReminder(Reminder),
     DebugAccel,
     HandleAlarm,
+    SetFace(Face),
+    Navigate(Navigation),
+    Request(CorrelationId, Box<Commands>),
+    SendData(Arc<Vec<u8>>),
 }
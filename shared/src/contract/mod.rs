@@ -1 +1,4 @@
 pub mod packets;
+pub mod secure_channel;
+pub mod transport;
+pub mod versioning;
@@ -5,9 +5,14 @@ use serde_with::Bytes;
 
 use crate::calendar::TimelyDataMarker;
 use crate::calendar::{CalendarEventDto, CalendarKind};
+use crate::contract::versioning::{
+    LEGACY_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
 use crate::reference_data::GpsCoordinates;
 use crate::reference_data::ReferenceTimeOffset;
 use crate::reference_data::ReferenceTimeUtc;
+use crate::reference_data::TimezoneRules;
+use crate::weather::WeatherForecast;
 
 #[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq, Clone)]
 #[repr(u16)]
@@ -19,6 +24,13 @@ pub enum ReferenceDataPacketType {
     CalendarEventsSyncResponse = 5,
     DropCalendarEvent = 6,
     TimelyData = 7,
+    Handshake = 8,
+    PairingRequest = 9,
+    PairingResponse = 10,
+    PairingConfirm = 11,
+    Secure = 12,
+    Timezone = 13,
+    Weather = 14,
 }
 
 #[serde_as]
@@ -34,13 +46,20 @@ pub struct ReferenceDataPacket {
 
 impl ReferenceDataPacket {
     pub fn wrap<T>(packet_type: ReferenceDataPacketType, obj: T) -> Self
+    where
+        T: Serialize,
+    {
+        Self::wrap_with_version(LEGACY_PROTOCOL_VERSION, packet_type, obj)
+    }
+
+    pub fn wrap_with_version<T>(version: i32, packet_type: ReferenceDataPacketType, obj: T) -> Self
     where
         T: Serialize,
     {
         let buf = rmp_serde::to_vec(&obj).unwrap();
 
         let reference_data_packet = ReferenceDataPacket {
-            version: 2,
+            version,
             packet_type,
             packet_payload_size: buf.len() as i32,
             packet_payload: buf,
@@ -60,11 +79,21 @@ pub struct ReferenceTimePacket {
     pub time: ReferenceTimeOffset,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct ReferenceLocationPacket {
     pub coordinates: GpsCoordinates,
 }
 
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ReferenceTimezonePacket {
+    pub timezone: TimezoneRules,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ReferenceWeatherPacket {
+    pub forecast: WeatherForecast,
+}
+
 #[derive(Debug, Deserialize, PartialEq, Clone)]
 pub struct ReferenceCalendarEventPacket {
     pub calendar_event: CalendarEventDto,
@@ -97,3 +126,84 @@ pub struct DropCalendarEventPacket {
     pub kind: CalendarKind,
     pub event_id: i32,
 }
+
+// the companion opens a pairing before the pin is known to it
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PairingRequestPacket {
+    #[serde_as(as = "Bytes")]
+    pub session_id: [u8; 32],
+}
+
+// the cpace share of the watch, a point on the generator only the pin and the session id give
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PairingKeyPacket {
+    #[serde_as(as = "Bytes")]
+    pub public_key: [u8; 32],
+}
+
+// the share of the sender along with its key confirmation, the companion sends it once the pin is typed in
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct PairingConfirmPacket {
+    #[serde_as(as = "Bytes")]
+    pub public_key: [u8; 32],
+
+    #[serde_as(as = "Bytes")]
+    pub confirmation: [u8; 32],
+}
+
+// a sealed ReferenceDataPacket
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct SecurePacket {
+    pub counter: u64,
+
+    #[serde_as(as = "Bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct HandshakePacket {
+    pub protocol_version: i32,
+
+    #[serde(default)]
+    pub min_protocol_version: i32,
+
+    #[serde(default)]
+    pub supported_packet_types: Vec<ReferenceDataPacketType>,
+
+    #[serde(default)]
+    pub screen_side: u16,
+
+    #[serde(default)]
+    pub calendar_kinds: Vec<CalendarKind>,
+}
+
+impl HandshakePacket {
+    pub fn watch(screen_side: u16, calendar_kinds: Vec<CalendarKind>) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+            supported_packet_types: vec![
+                ReferenceDataPacketType::Time,
+                ReferenceDataPacketType::Location,
+                ReferenceDataPacketType::CalendarEvent,
+                ReferenceDataPacketType::CalendarEventsMeta,
+                ReferenceDataPacketType::CalendarEventsSyncResponse,
+                ReferenceDataPacketType::DropCalendarEvent,
+                ReferenceDataPacketType::TimelyData,
+                ReferenceDataPacketType::Handshake,
+                ReferenceDataPacketType::PairingRequest,
+                ReferenceDataPacketType::PairingResponse,
+                ReferenceDataPacketType::PairingConfirm,
+                ReferenceDataPacketType::Secure,
+                ReferenceDataPacketType::Timezone,
+                ReferenceDataPacketType::Weather,
+            ],
+            screen_side,
+            calendar_kinds,
+        }
+    }
+}
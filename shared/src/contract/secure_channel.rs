@@ -0,0 +1,424 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use serde_with::serde_as;
+use serde_with::Bytes;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::contract::packets::{
+    PairingConfirmPacket, PairingKeyPacket, PairingRequestPacket, ReferenceDataPacket,
+    ReferenceDataPacketType, SecurePacket,
+};
+use crate::contract::versioning::{ProtocolNegotiation, SECURE_PROTOCOL_VERSION};
+use crate::error::Error;
+
+pub const KEY_SIZE: usize = 32;
+
+pub const NONCE_SIZE: usize = 12;
+
+pub const PIN_MODULO: u32 = 1_000_000;
+
+const PAIRING_INFO: &[u8] = b"blinky pairing v2";
+
+const GENERATOR_INFO: &[u8] = b"blinky cpace generator";
+
+// counters older than that many packets behind the newest one are rejected
+const REPLAY_WINDOW: u64 = 64;
+
+#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum Role {
+    Watch = 1,
+    Companion = 2,
+}
+
+// one side of a cpace exchange on ristretto255, the generator is hashed from the pin and the
+// session id, so a share made for a wrong pin gives a different key and a peer gets one guess
+pub struct PairingSession {
+    role: Role,
+    scalar: Scalar,
+    session_id: [u8; KEY_SIZE],
+    public_key: [u8; KEY_SIZE],
+}
+
+struct PairingKeys {
+    channel_key: [u8; KEY_SIZE],
+    watch_confirmation_key: [u8; KEY_SIZE],
+    companion_confirmation_key: [u8; KEY_SIZE],
+    transcript: Vec<u8>,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct SecureChannel {
+    role: Role,
+
+    #[serde_as(as = "Bytes")]
+    key: [u8; KEY_SIZE],
+
+    tx_counter: u64,
+    rx_counter: u64,
+
+    // bit n is set when rx_counter - n was already received
+    rx_window: u64,
+}
+
+struct PendingPairing {
+    session: PairingSession,
+    pin: u32,
+}
+
+// the watch side state machine, owns the channel once pairing succeeded
+pub struct SecureLink {
+    channel: Option<SecureChannel>,
+    pending: Option<PendingPairing>,
+}
+
+impl Role {
+    fn peer(self) -> Role {
+        match self {
+            Role::Watch => Role::Companion,
+            Role::Companion => Role::Watch,
+        }
+    }
+}
+
+impl PairingSession {
+    pub fn new(role: Role, secret: [u8; KEY_SIZE], session_id: [u8; KEY_SIZE], pin: u32) -> Self {
+        let scalar = Scalar::from_bytes_mod_order(secret);
+        let public_key = (Self::generator(&session_id, pin) * scalar)
+            .compress()
+            .to_bytes();
+
+        Self {
+            role,
+            scalar,
+            session_id,
+            public_key,
+        }
+    }
+
+    pub fn public_key(&self) -> [u8; KEY_SIZE] {
+        self.public_key
+    }
+
+    pub fn confirmation(&self, peer_public_key: &[u8; KEY_SIZE]) -> Result<[u8; KEY_SIZE], Error> {
+        let keys = self.derive(peer_public_key)?;
+
+        Ok(keys.confirmation(self.role))
+    }
+
+    pub fn complete(
+        &self,
+        peer_public_key: &[u8; KEY_SIZE],
+        peer_confirmation: &[u8],
+    ) -> Result<SecureChannel, Error> {
+        let keys = self.derive(peer_public_key)?;
+
+        keys.confirmation_mac(self.role.peer())
+            .verify_slice(peer_confirmation)
+            .map_err(|_| Error::from("pairing confirmation mismatch"))?;
+
+        Ok(SecureChannel::new(self.role, keys.channel_key))
+    }
+
+    fn generator(session_id: &[u8; KEY_SIZE], pin: u32) -> RistrettoPoint {
+        let hash = Sha512::new()
+            .chain_update(GENERATOR_INFO)
+            .chain_update(format!("{:06}", pin % PIN_MODULO))
+            .chain_update(session_id)
+            .finalize();
+
+        RistrettoPoint::from_uniform_bytes(&hash.into())
+    }
+
+    fn derive(&self, peer_public_key: &[u8; KEY_SIZE]) -> Result<PairingKeys, Error> {
+        let peer = CompressedRistretto(*peer_public_key)
+            .decompress()
+            .ok_or_else(|| Error::from("invalid pairing key"))?;
+
+        let shared_secret = peer * self.scalar;
+
+        if peer.is_identity() || shared_secret.is_identity() {
+            return Err(Error::from("degenerate pairing key"));
+        }
+
+        let mut transcript = Vec::with_capacity(KEY_SIZE * 3);
+        transcript.extend_from_slice(&self.session_id);
+
+        match self.role {
+            Role::Watch => {
+                transcript.extend_from_slice(peer_public_key);
+                transcript.extend_from_slice(&self.public_key);
+            }
+            Role::Companion => {
+                transcript.extend_from_slice(&self.public_key);
+                transcript.extend_from_slice(peer_public_key);
+            }
+        }
+
+        let hkdf = Hkdf::<Sha256>::new(Some(&self.session_id), shared_secret.compress().as_bytes());
+
+        let mut info = PAIRING_INFO.to_vec();
+        info.extend_from_slice(&transcript);
+
+        let mut okm = [0u8; KEY_SIZE * 3];
+        hkdf.expand(&info, &mut okm)
+            .map_err(|err| Error::from(err.to_string()))?;
+
+        Ok(PairingKeys {
+            channel_key: okm[..KEY_SIZE].try_into().unwrap(),
+            watch_confirmation_key: okm[KEY_SIZE..KEY_SIZE * 2].try_into().unwrap(),
+            companion_confirmation_key: okm[KEY_SIZE * 2..].try_into().unwrap(),
+            transcript,
+        })
+    }
+}
+
+impl PairingKeys {
+    fn confirmation(&self, role: Role) -> [u8; KEY_SIZE] {
+        self.confirmation_mac(role).finalize().into_bytes().into()
+    }
+
+    fn confirmation_mac(&self, role: Role) -> Hmac<Sha256> {
+        let key = match role {
+            Role::Watch => &self.watch_confirmation_key,
+            Role::Companion => &self.companion_confirmation_key,
+        };
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap();
+        mac.update(&self.transcript);
+
+        mac
+    }
+}
+
+impl SecureChannel {
+    pub fn new(role: Role, key: [u8; KEY_SIZE]) -> Self {
+        Self {
+            role,
+            key,
+            tx_counter: 0,
+            rx_counter: 0,
+            rx_window: 0,
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn rx_counter(&self) -> u64 {
+        self.rx_counter
+    }
+
+    pub fn seal(&mut self, packet: ReferenceDataPacket) -> ReferenceDataPacket {
+        self.tx_counter += 1;
+
+        let nonce = Self::nonce(self.role, self.tx_counter);
+        let ciphertext = seal(&self.key, &nonce, &[], &packet.serialize());
+
+        ReferenceDataPacket::wrap_with_version(
+            SECURE_PROTOCOL_VERSION,
+            ReferenceDataPacketType::Secure,
+            SecurePacket {
+                counter: self.tx_counter,
+                ciphertext,
+            },
+        )
+    }
+
+    pub fn open(&mut self, packet: &SecurePacket) -> Result<ReferenceDataPacket, Error> {
+        self.check_replay(packet.counter)?;
+
+        let nonce = Self::nonce(self.role.peer(), packet.counter);
+        let plaintext = open(&self.key, &nonce, &[], &packet.ciphertext)?;
+
+        let inner: ReferenceDataPacket =
+            rmp_serde::from_slice(&plaintext).map_err(|err| Error::from(err.to_string()))?;
+
+        if inner.packet_type == ReferenceDataPacketType::Secure {
+            return Err(Error::from("nested secure packet"));
+        }
+
+        self.accept_counter(packet.counter);
+
+        Ok(inner)
+    }
+
+    fn check_replay(&self, counter: u64) -> Result<(), Error> {
+        if counter == 0 {
+            return Err(Error::from("zero packet counter"));
+        }
+
+        if counter > self.rx_counter {
+            return Ok(());
+        }
+
+        let behind = self.rx_counter - counter;
+
+        if behind >= REPLAY_WINDOW {
+            return Err(Error::from(format!(
+                "packet counter {} is too old",
+                counter
+            )));
+        }
+
+        if self.rx_window & (1 << behind) != 0 {
+            return Err(Error::from(format!("replayed packet counter {}", counter)));
+        }
+
+        Ok(())
+    }
+
+    fn accept_counter(&mut self, counter: u64) {
+        if counter > self.rx_counter {
+            let shift = counter - self.rx_counter;
+
+            self.rx_window = match shift < REPLAY_WINDOW {
+                true => self.rx_window << shift,
+                false => 0,
+            };
+            self.rx_window |= 1;
+            self.rx_counter = counter;
+        } else {
+            self.rx_window |= 1 << (self.rx_counter - counter);
+        }
+    }
+
+    // the sender role keeps both directions apart under the same key
+    fn nonce(sender: Role, counter: u64) -> [u8; NONCE_SIZE] {
+        let mut nonce = [0u8; NONCE_SIZE];
+
+        nonce[..4].copy_from_slice(&(sender as u32).to_be_bytes());
+        nonce[4..].copy_from_slice(&counter.to_be_bytes());
+
+        nonce
+    }
+}
+
+impl SecureLink {
+    pub fn new(channel: Option<SecureChannel>) -> Self {
+        Self {
+            channel,
+            pending: None,
+        }
+    }
+
+    pub fn is_paired(&self) -> bool {
+        self.channel.is_some()
+    }
+
+    pub fn channel(&self) -> Option<&SecureChannel> {
+        self.channel.as_ref()
+    }
+
+    pub fn start_pairing(
+        &mut self,
+        request: &PairingRequestPacket,
+        secret: [u8; KEY_SIZE],
+        pin: u32,
+    ) -> PairingKeyPacket {
+        let pin = pin % PIN_MODULO;
+        let session = PairingSession::new(Role::Watch, secret, request.session_id, pin);
+        let public_key = session.public_key();
+
+        self.pending = Some(PendingPairing { session, pin });
+
+        PairingKeyPacket { public_key }
+    }
+
+    // a pin is good for a single attempt, the existing channel survives a failed one,
+    // the watch confirms only after the companion proved it knows the pin
+    pub fn confirm_pairing(
+        &mut self,
+        confirm: &PairingConfirmPacket,
+    ) -> Result<PairingConfirmPacket, Error> {
+        let Some(pending) = self.pending.take() else {
+            return Err(Error::from("no pairing in progress"));
+        };
+
+        let session = pending.session;
+
+        let channel = session.complete(&confirm.public_key, &confirm.confirmation)?;
+        let confirmation = session.confirmation(&confirm.public_key)?;
+
+        self.channel = Some(channel);
+
+        Ok(PairingConfirmPacket {
+            public_key: session.public_key(),
+            confirmation,
+        })
+    }
+
+    pub fn pending_pin(&self) -> Option<u32> {
+        self.pending.as_ref().map(|x| x.pin)
+    }
+
+    // plain packets are accepted only until the first pairing, pairing itself is always allowed
+    pub fn open(
+        &mut self,
+        negotiation: &ProtocolNegotiation,
+        packet: ReferenceDataPacket,
+    ) -> Result<ReferenceDataPacket, Error> {
+        match packet.packet_type {
+            ReferenceDataPacketType::Secure => {
+                let Some(channel) = self.channel.as_mut() else {
+                    return Err(Error::from("secure packet before pairing"));
+                };
+
+                let secure = negotiation.decode::<SecurePacket>(&packet)?;
+
+                channel.open(&secure)
+            }
+            ReferenceDataPacketType::Handshake
+            | ReferenceDataPacketType::PairingRequest
+            | ReferenceDataPacketType::PairingConfirm => Ok(packet),
+            _ if self.is_paired() => Err(Error::from(format!(
+                "unauthenticated {:?} packet rejected",
+                packet.packet_type
+            ))),
+            _ => Ok(packet),
+        }
+    }
+}
+
+pub fn seal(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Vec<u8> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .unwrap()
+}
+
+pub fn open(
+    key: &[u8; KEY_SIZE],
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, Error> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| Error::from("packet authentication failed"))
+}
@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use serde_with::serde_as;
+use serde_with::Bytes;
+
+use crate::crc::crc32;
+use crate::error::Error;
+
+pub const FRAME_MAGIC: u8 = 0xFB;
+
+// magic, msgpack header of the frame and the trailing crc
+pub const FRAME_OVERHEAD: usize = 24;
+
+const COMPLETED_HISTORY: usize = 64;
+
+// a reassembly buffer is allocated per message before its fragments arrive, these bound it
+pub const MAX_INCOMPLETE_MESSAGES: usize = 8;
+
+pub const MAX_FRAGMENTS: u16 = 256;
+
+#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum FrameKind {
+    Data = 1,
+    Ack = 2,
+    Nack = 3,
+}
+
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TransportFrame {
+    pub kind: FrameKind,
+    pub message_id: u16,
+    pub seq: u16,
+    pub fragments_count: u16,
+
+    #[serde_as(as = "Bytes")]
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Default)]
+pub struct ReceiveResult {
+    pub packets: Vec<Vec<u8>>,
+    pub replies: Vec<Vec<u8>>,
+}
+
+pub struct TransportSender {
+    mtu: usize,
+    next_message_id: u16,
+    unacked: BTreeMap<u16, Vec<Vec<u8>>>,
+}
+
+pub struct TransportReceiver {
+    incomplete: BTreeMap<u16, PartialMessage>,
+    completed: VecDeque<u16>,
+    corrupted_frames: u32,
+    started_messages: u64,
+}
+
+struct PartialMessage {
+    fragments: Vec<Option<Vec<u8>>>,
+    started: u64,
+}
+
+impl TransportFrame {
+    pub fn encode(&self) -> Vec<u8> {
+        let body = rmp_serde::to_vec(self).unwrap();
+
+        let mut buf = Vec::with_capacity(body.len() + 5);
+        buf.push(FRAME_MAGIC);
+        buf.extend_from_slice(&body);
+        buf.extend_from_slice(&crc32(&body).to_be_bytes());
+
+        buf
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, Error> {
+        if !Self::is_frame(buf) || buf.len() < 5 {
+            return Err(Error::from("not a transport frame"));
+        }
+
+        let (body, crc) = buf[1..].split_at(buf.len() - 5);
+
+        let expected_crc = u32::from_be_bytes(crc.try_into().unwrap());
+
+        if crc32(body) != expected_crc {
+            return Err(Error::from("frame crc mismatch"));
+        }
+
+        rmp_serde::from_slice(body).map_err(|err| Error::from(err.to_string()))
+    }
+
+    pub fn is_frame(buf: &[u8]) -> bool {
+        buf.first() == Some(&FRAME_MAGIC)
+    }
+
+    fn control(kind: FrameKind, message_id: u16, missing: &[u16]) -> Self {
+        Self {
+            kind,
+            message_id,
+            seq: 0,
+            fragments_count: 0,
+            payload: rmp_serde::to_vec(missing).unwrap(),
+        }
+    }
+}
+
+impl TransportSender {
+    pub fn new(mtu: usize) -> Self {
+        Self {
+            mtu: mtu.max(FRAME_OVERHEAD + 1),
+            next_message_id: 0,
+            unacked: BTreeMap::new(),
+        }
+    }
+
+    pub fn send(&mut self, packet: &[u8]) -> Vec<Vec<u8>> {
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        let chunk_size = self.mtu - FRAME_OVERHEAD;
+        let chunks: Vec<&[u8]> = match packet.is_empty() {
+            true => vec![&[]],
+            false => packet.chunks(chunk_size).collect(),
+        };
+
+        let fragments_count = chunks.len() as u16;
+
+        let frames: Vec<Vec<u8>> = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(seq, chunk)| {
+                TransportFrame {
+                    kind: FrameKind::Data,
+                    message_id,
+                    seq: seq as u16,
+                    fragments_count,
+                    payload: chunk.to_vec(),
+                }
+                .encode()
+            })
+            .collect();
+
+        self.unacked.insert(message_id, frames.clone());
+
+        frames
+    }
+
+    pub fn handle_reply(&mut self, buf: &[u8]) -> Vec<Vec<u8>> {
+        let frame = match TransportFrame::decode(buf) {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!("{}", err);
+                return vec![];
+            }
+        };
+
+        match frame.kind {
+            FrameKind::Ack => {
+                self.unacked.remove(&frame.message_id);
+                vec![]
+            }
+            FrameKind::Nack => {
+                let missing: Vec<u16> = rmp_serde::from_slice(&frame.payload).unwrap_or_default();
+
+                let Some(frames) = self.unacked.get(&frame.message_id) else {
+                    return vec![];
+                };
+
+                match missing.is_empty() {
+                    true => frames.clone(),
+                    false => missing
+                        .iter()
+                        .filter_map(|x| frames.get(*x as usize).cloned())
+                        .collect(),
+                }
+            }
+            FrameKind::Data => {
+                error!("unexpected data frame in reply");
+                vec![]
+            }
+        }
+    }
+
+    pub fn retransmit_unacked(&self) -> Vec<Vec<u8>> {
+        self.unacked.values().flatten().cloned().collect()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.unacked.is_empty()
+    }
+}
+
+impl TransportReceiver {
+    pub fn new() -> Self {
+        Self {
+            incomplete: BTreeMap::new(),
+            completed: VecDeque::with_capacity(COMPLETED_HISTORY),
+            corrupted_frames: 0,
+            started_messages: 0,
+        }
+    }
+
+    pub fn receive(&mut self, buf: &[u8]) -> ReceiveResult {
+        let mut result = ReceiveResult::default();
+
+        // a plain ReferenceDataPacket from a companion app that doesn't frame its writes
+        if !TransportFrame::is_frame(buf) {
+            result.packets.push(buf.to_vec());
+            return result;
+        }
+
+        let frame = match TransportFrame::decode(buf) {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!("{}", err);
+                self.corrupted_frames += 1;
+                return result;
+            }
+        };
+
+        if frame.kind != FrameKind::Data || frame.seq >= frame.fragments_count {
+            warn!("unexpected frame {:?} {}", frame.kind, frame.seq);
+            return result;
+        }
+
+        if frame.fragments_count > MAX_FRAGMENTS {
+            warn!(
+                "message {} of {} fragments is too long",
+                frame.message_id, frame.fragments_count
+            );
+            return result;
+        }
+
+        if self.completed.contains(&frame.message_id) {
+            result.replies.push(Self::ack(frame.message_id));
+            return result;
+        }
+
+        if !self.incomplete.contains_key(&frame.message_id) {
+            self.evict_stale();
+
+            self.incomplete.insert(
+                frame.message_id,
+                PartialMessage {
+                    fragments: vec![None; frame.fragments_count as usize],
+                    started: self.started_messages,
+                },
+            );
+            self.started_messages += 1;
+        }
+
+        let message = self.incomplete.get_mut(&frame.message_id).unwrap();
+
+        if message.fragments.len() != frame.fragments_count as usize {
+            warn!("fragments count changed for {}", frame.message_id);
+            return result;
+        }
+
+        let is_last = frame.seq + 1 == frame.fragments_count;
+
+        message.fragments[frame.seq as usize] = Some(frame.payload);
+
+        if message.fragments.iter().all(|x| x.is_some()) {
+            let message = self.incomplete.remove(&frame.message_id).unwrap();
+
+            result
+                .packets
+                .push(message.fragments.into_iter().flatten().flatten().collect());
+            result.replies.push(Self::ack(frame.message_id));
+
+            if self.completed.len() == COMPLETED_HISTORY {
+                self.completed.pop_front();
+            }
+            self.completed.push_back(frame.message_id);
+        } else if is_last {
+            result.replies.push(Self::nack(frame.message_id, message));
+        }
+
+        result
+    }
+
+    pub fn nack_missing(&self) -> Vec<Vec<u8>> {
+        self.incomplete
+            .iter()
+            .map(|(message_id, message)| Self::nack(*message_id, message))
+            .collect()
+    }
+
+    pub fn corrupted_frames(&self) -> u32 {
+        self.corrupted_frames
+    }
+
+    pub fn incomplete_messages(&self) -> usize {
+        self.incomplete.len()
+    }
+
+    // the message started longest ago gives way, its sender retransmits it if it is still alive
+    fn evict_stale(&mut self) {
+        if self.incomplete.len() < MAX_INCOMPLETE_MESSAGES {
+            return;
+        }
+
+        let oldest = self
+            .incomplete
+            .iter()
+            .min_by_key(|(_, message)| message.started)
+            .map(|(message_id, _)| *message_id);
+
+        if let Some(message_id) = oldest {
+            warn!("dropping incomplete message {}", message_id);
+            self.incomplete.remove(&message_id);
+        }
+    }
+
+    fn ack(message_id: u16) -> Vec<u8> {
+        TransportFrame::control(FrameKind::Ack, message_id, &[]).encode()
+    }
+
+    fn nack(message_id: u16, message: &PartialMessage) -> Vec<u8> {
+        let missing: Vec<u16> = message
+            .fragments
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.is_none())
+            .map(|(seq, _)| seq as u16)
+            .collect();
+
+        TransportFrame::control(FrameKind::Nack, message_id, &missing).encode()
+    }
+}
+
+impl Default for TransportReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
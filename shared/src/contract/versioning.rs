@@ -0,0 +1,249 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::calendar::{CalendarEventDto, CalendarEventIcon, CalendarKind};
+use crate::contract::packets::{
+    CalendarEventsMetaPacket, DropCalendarEventPacket, HandshakePacket, PairingConfirmPacket,
+    PairingKeyPacket, PairingRequestPacket, ReferenceCalendarEventPacket, ReferenceDataPacket,
+    ReferenceLocationPacket, ReferenceTimePacket, ReferenceTimelyDataPacket,
+    ReferenceTimezonePacket, ReferenceWeatherPacket, SecurePacket,
+};
+use crate::error::Error;
+use crate::reference_data::ReferenceTimeUtc;
+
+// the version every packet had before the handshake existed
+pub const LEGACY_PROTOCOL_VERSION: i32 = 2;
+
+// v3: handshake and framed transport, payload layouts are the same as in v2
+// v4: pairing and sealed packets
+// v5: timezone transitions
+// v6: weather forecasts
+// v7: pin authenticated pairing, pairing packets of earlier versions are refused
+// v8: recurrence and reminder offsets of calendar events
+pub const PROTOCOL_VERSION: i32 = 8;
+
+pub const SECURE_PROTOCOL_VERSION: i32 = 4;
+
+pub const TIMEZONE_PROTOCOL_VERSION: i32 = 5;
+
+pub const WEATHER_PROTOCOL_VERSION: i32 = 6;
+
+pub const PAIRING_PROTOCOL_VERSION: i32 = 7;
+
+pub const RECURRENCE_PROTOCOL_VERSION: i32 = 8;
+
+pub const MIN_PROTOCOL_VERSION: i32 = LEGACY_PROTOCOL_VERSION;
+
+// a payload decodes as is from the version it was introduced in, one whose layout changes
+// overrides decode to read the older layouts
+pub trait VersionedPayload: Sized + DeserializeOwned {
+    const NAME: &'static str;
+
+    const SINCE_VERSION: i32 = LEGACY_PROTOCOL_VERSION;
+
+    fn decode(version: i32, payload: &[u8]) -> Result<Self, Error> {
+        match version {
+            version if (Self::SINCE_VERSION..=PROTOCOL_VERSION).contains(&version) => {
+                from_msgpack(payload)
+            }
+            _ => unsupported(Self::NAME, version),
+        }
+    }
+}
+
+pub struct ProtocolNegotiation {
+    negotiated_version: Option<i32>,
+}
+
+impl ProtocolNegotiation {
+    pub fn new() -> Self {
+        Self {
+            negotiated_version: None,
+        }
+    }
+
+    pub fn accept(&mut self, companion: &HandshakePacket) -> Result<i32, Error> {
+        if companion.protocol_version < MIN_PROTOCOL_VERSION
+            || companion.min_protocol_version > PROTOCOL_VERSION
+        {
+            self.negotiated_version = None;
+
+            return Err(Error::from(format!(
+                "companion protocol v{} (min v{}) is not compatible with v{}..v{}",
+                companion.protocol_version,
+                companion.min_protocol_version,
+                MIN_PROTOCOL_VERSION,
+                PROTOCOL_VERSION
+            )));
+        }
+
+        let version = companion.protocol_version.min(PROTOCOL_VERSION);
+
+        self.negotiated_version = Some(version);
+
+        Ok(version)
+    }
+
+    pub fn reset(&mut self) {
+        self.negotiated_version = None;
+    }
+
+    pub fn negotiated_version(&self) -> Option<i32> {
+        self.negotiated_version
+    }
+
+    pub fn decode<T>(&self, packet: &ReferenceDataPacket) -> Result<T, Error>
+    where
+        T: VersionedPayload,
+    {
+        let max_version = self.negotiated_version.unwrap_or(PROTOCOL_VERSION);
+
+        if packet.version < MIN_PROTOCOL_VERSION || packet.version > max_version {
+            return Err(Error::from(format!(
+                "{:?} packet v{} is outside of v{}..v{}",
+                packet.packet_type, packet.version, MIN_PROTOCOL_VERSION, max_version
+            )));
+        }
+
+        T::decode(packet.version, &packet.packet_payload)
+    }
+}
+
+impl Default for ProtocolNegotiation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VersionedPayload for HandshakePacket {
+    const NAME: &'static str = "handshake";
+}
+
+impl VersionedPayload for ReferenceTimePacket {
+    const NAME: &'static str = "time";
+}
+
+impl VersionedPayload for ReferenceLocationPacket {
+    const NAME: &'static str = "location";
+}
+
+impl VersionedPayload for CalendarEventsMetaPacket {
+    const NAME: &'static str = "calendar events meta";
+}
+
+impl VersionedPayload for ReferenceCalendarEventPacket {
+    const NAME: &'static str = "calendar event";
+
+    fn decode(version: i32, payload: &[u8]) -> Result<Self, Error> {
+        match version {
+            version if (RECURRENCE_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) => {
+                from_msgpack(payload)
+            }
+            version if (Self::SINCE_VERSION..RECURRENCE_PROTOCOL_VERSION).contains(&version) => {
+                let packet: CalendarEventPacketV2 = from_msgpack(payload)?;
+
+                Ok(packet.into())
+            }
+            _ => unsupported(Self::NAME, version),
+        }
+    }
+}
+
+impl VersionedPayload for DropCalendarEventPacket {
+    const NAME: &'static str = "drop calendar event";
+}
+
+impl VersionedPayload for ReferenceTimelyDataPacket {
+    const NAME: &'static str = "timely data";
+}
+
+impl VersionedPayload for PairingRequestPacket {
+    const NAME: &'static str = "pairing request";
+
+    const SINCE_VERSION: i32 = PAIRING_PROTOCOL_VERSION;
+}
+
+impl VersionedPayload for PairingKeyPacket {
+    const NAME: &'static str = "pairing key";
+
+    const SINCE_VERSION: i32 = PAIRING_PROTOCOL_VERSION;
+}
+
+impl VersionedPayload for PairingConfirmPacket {
+    const NAME: &'static str = "pairing confirm";
+
+    const SINCE_VERSION: i32 = PAIRING_PROTOCOL_VERSION;
+}
+
+impl VersionedPayload for SecurePacket {
+    const NAME: &'static str = "secure";
+
+    const SINCE_VERSION: i32 = SECURE_PROTOCOL_VERSION;
+}
+
+impl VersionedPayload for ReferenceTimezonePacket {
+    const NAME: &'static str = "timezone";
+
+    const SINCE_VERSION: i32 = TIMEZONE_PROTOCOL_VERSION;
+}
+
+impl VersionedPayload for ReferenceWeatherPacket {
+    const NAME: &'static str = "weather";
+
+    const SINCE_VERSION: i32 = WEATHER_PROTOCOL_VERSION;
+}
+
+// the calendar event layout of v2..v7, before recurrence and reminder offsets
+#[derive(Deserialize)]
+struct CalendarEventPacketV2 {
+    calendar_event: CalendarEventDtoV2,
+}
+
+#[derive(Deserialize)]
+struct CalendarEventDtoV2 {
+    kind: CalendarKind,
+    id: i32,
+    title: String,
+    start: ReferenceTimeUtc,
+    end: ReferenceTimeUtc,
+    icon: CalendarEventIcon,
+    color: u32,
+    description: String,
+    lane: u8,
+}
+
+impl From<CalendarEventPacketV2> for ReferenceCalendarEventPacket {
+    fn from(value: CalendarEventPacketV2) -> Self {
+        let event = value.calendar_event;
+
+        ReferenceCalendarEventPacket {
+            calendar_event: CalendarEventDto {
+                kind: event.kind,
+                id: event.id,
+                title: event.title,
+                start: event.start,
+                end: event.end,
+                icon: event.icon,
+                color: event.color,
+                description: event.description,
+                lane: event.lane,
+                recurrence: None,
+                reminder_offsets: None,
+            },
+        }
+    }
+}
+
+fn from_msgpack<T>(payload: &[u8]) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    rmp_serde::from_slice(payload).map_err(|err| Error::from(err.to_string()))
+}
+
+fn unsupported<T>(payload_name: &str, version: i32) -> Result<T, Error> {
+    Err(Error::from(format!(
+        "unsupported {} payload v{}",
+        payload_name, version
+    )))
+}
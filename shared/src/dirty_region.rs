@@ -0,0 +1,339 @@
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, Point, Size},
+    pixelcolor::RgbColor,
+    primitives::Rectangle,
+    Pixel,
+};
+use enumflags2::BitFlags;
+
+use crate::display_interface::{LayerType, RenderMode};
+
+// more windows cost more address setup than they save on the bus
+pub const MAX_DIRTY_RECTS: usize = 8;
+
+// pixels worth of bus time one more window costs, cheaper unions are taken
+pub const WINDOW_COST_PIXELS: u32 = 256;
+
+const LAYERS_COUNT: usize = 3;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirtyRegion {
+    rects: Vec<Rectangle>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PixelCounter {
+    pub frames: u32,
+    pub last_frame: u32,
+    pub total: u64,
+}
+
+// what a render touched, wraps the frame buffer of a layer
+pub struct DirtyTracker<T> {
+    target: T,
+    region: DirtyRegion,
+}
+
+// what each layer holds and what changed on screen since the last commit
+pub struct DirtyLayers {
+    side: usize,
+    layers: [DirtyRegion; LAYERS_COUNT],
+    pending: DirtyRegion,
+    merged_mask: Option<BitFlags<LayerType>>,
+    counter: PixelCounter,
+}
+
+impl DirtyRegion {
+    pub fn new() -> Self {
+        Self { rects: vec![] }
+    }
+
+    pub fn full(side: usize) -> Self {
+        let mut region = Self::new();
+        region.add(Rectangle::new(
+            Point::zero(),
+            Size::new(side as u32, side as u32),
+        ));
+        region
+    }
+
+    pub fn rects(&self) -> &[Rectangle] {
+        &self.rects
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    // overlapping rects are counted twice, that is what gets flushed
+    pub fn area(&self) -> u32 {
+        self.rects.iter().map(area).sum()
+    }
+
+    pub fn add(&mut self, rect: Rectangle) {
+        if rect.is_zero_sized() {
+            return;
+        }
+
+        let mut rect = rect;
+
+        // a union can make another rect worth absorbing, so until nothing changes
+        while let Some(index) = self
+            .rects
+            .iter()
+            .position(|x| merge_waste(x, &rect) <= WINDOW_COST_PIXELS)
+        {
+            rect = union(&self.rects.swap_remove(index), &rect);
+        }
+
+        self.rects.push(rect);
+
+        while self.rects.len() > MAX_DIRTY_RECTS {
+            self.merge_cheapest_pair();
+        }
+    }
+
+    pub fn extend(&mut self, other: &DirtyRegion) {
+        for rect in other.rects.iter() {
+            self.add(*rect);
+        }
+    }
+
+    pub fn clip(&mut self, bounds: &Rectangle) {
+        for rect in self.rects.iter_mut() {
+            *rect = rect.intersection(bounds);
+        }
+
+        self.rects.retain(|x| !x.is_zero_sized());
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+
+    fn merge_cheapest_pair(&mut self) {
+        let mut cheapest = (0, 1, u32::MAX);
+
+        for first in 0..self.rects.len() {
+            for second in first + 1..self.rects.len() {
+                let waste = merge_waste(&self.rects[first], &self.rects[second]);
+
+                if waste < cheapest.2 {
+                    cheapest = (first, second, waste);
+                }
+            }
+        }
+
+        let (first, second, _) = cheapest;
+
+        let merged = union(&self.rects[first], &self.rects[second]);
+        self.rects.swap_remove(second);
+        self.rects[first] = merged;
+    }
+}
+
+impl PixelCounter {
+    pub fn record(&mut self, pixels: u32) {
+        self.frames += 1;
+        self.last_frame = pixels;
+        self.total += pixels as u64;
+    }
+}
+
+impl<T> DirtyTracker<T> {
+    pub fn new(target: T) -> Self {
+        Self {
+            target,
+            region: DirtyRegion::new(),
+        }
+    }
+
+    pub fn into_parts(self) -> (T, DirtyRegion) {
+        (self.target, self.region)
+    }
+}
+
+impl<T: Dimensions> Dimensions for DirtyTracker<T> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<T: DrawTarget> DrawTarget for DirtyTracker<T> {
+    type Color = T::Color;
+    type Error = T::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounds = self.target.bounding_box();
+
+        let mut top_left = Point::new(i32::MAX, i32::MAX);
+        let mut bottom_right = Point::new(i32::MIN, i32::MIN);
+
+        let result = self
+            .target
+            .draw_iter(pixels.into_iter().inspect(|Pixel(point, _)| {
+                if bounds.contains(*point) {
+                    top_left = top_left.component_min(*point);
+                    bottom_right = bottom_right.component_max(*point);
+                }
+            }));
+
+        if top_left.x <= bottom_right.x {
+            self.region
+                .add(Rectangle::with_corners(top_left, bottom_right));
+        }
+
+        result
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.region
+            .add(area.intersection(&self.target.bounding_box()));
+        self.target.fill_contiguous(area, colors)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.region
+            .add(area.intersection(&self.target.bounding_box()));
+        self.target.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.region.add(self.target.bounding_box());
+        self.target.clear(color)
+    }
+}
+
+impl DirtyLayers {
+    pub fn new(side: usize) -> Self {
+        Self {
+            side,
+            layers: Default::default(),
+            pending: DirtyRegion::new(),
+            merged_mask: None,
+            counter: PixelCounter::default(),
+        }
+    }
+
+    // an invalidated layer also loses whatever it showed before
+    pub fn rendered(&mut self, layer: LayerType, mode: RenderMode, drawn: DirtyRegion) {
+        let content = &mut self.layers[layer_index(layer)];
+
+        if matches!(mode, RenderMode::Invalidate) {
+            self.pending.extend(content);
+            content.clear();
+        }
+
+        content.extend(&drawn);
+        self.pending.extend(&drawn);
+    }
+
+    // the whole screen on the first commit or when other layers get merged
+    pub fn take(&mut self, layers_mask: BitFlags<LayerType>) -> DirtyRegion {
+        let mut dirty = if self.merged_mask == Some(layers_mask) {
+            std::mem::take(&mut self.pending)
+        } else {
+            self.pending.clear();
+            DirtyRegion::full(self.side)
+        };
+
+        self.merged_mask = Some(layers_mask);
+
+        dirty.clip(&Rectangle::new(
+            Point::zero(),
+            Size::new(self.side as u32, self.side as u32),
+        ));
+
+        self.counter.record(dirty.area());
+
+        dirty
+    }
+
+    pub fn counter(&self) -> PixelCounter {
+        self.counter
+    }
+}
+
+pub fn layer_index(layer: LayerType) -> usize {
+    (layer as u8).trailing_zeros() as usize
+}
+
+// later layers cover earlier ones, black is transparent
+pub fn merge_layers<TColor: RgbColor>(
+    base: &mut [TColor],
+    layers: &[Box<[TColor]>],
+    layers_mask: BitFlags<LayerType>,
+    side: usize,
+    rect: &Rectangle,
+) {
+    for y in rect.rows() {
+        let row = y as usize * side;
+        let columns = row + rect.top_left.x as usize
+            ..row + (rect.top_left.x as usize) + rect.size.width as usize;
+
+        base[columns.clone()].fill(TColor::BLACK);
+
+        for (index, layer) in layers.iter().enumerate() {
+            if !layers_mask.contains(BitFlags::from_bits_truncate(1 << index)) {
+                continue;
+            }
+
+            for (pixel, layer_pixel) in base[columns.clone()]
+                .iter_mut()
+                .zip(layer[columns.clone()].iter())
+            {
+                if *layer_pixel != TColor::BLACK {
+                    *pixel = *layer_pixel;
+                }
+            }
+        }
+    }
+}
+
+// row by row, the order a display window is filled in
+pub fn window_pixels<'a, TColor: Copy>(
+    base: &'a [TColor],
+    side: usize,
+    rect: &Rectangle,
+) -> impl Iterator<Item = TColor> + 'a {
+    let (left, width) = (rect.top_left.x as usize, rect.size.width as usize);
+
+    rect.rows().flat_map(move |y| {
+        let row = y as usize * side + left;
+        base[row..row + width].iter().copied()
+    })
+}
+
+fn area(rect: &Rectangle) -> u32 {
+    rect.size.width * rect.size.height
+}
+
+fn union(first: &Rectangle, second: &Rectangle) -> Rectangle {
+    let first_bottom_right = first.top_left + first.size;
+    let second_bottom_right = second.top_left + second.size;
+
+    let top_left = first.top_left.component_min(second.top_left);
+    let bottom_right = first_bottom_right.component_max(second_bottom_right);
+
+    Rectangle::new(
+        top_left,
+        Size::new(
+            (bottom_right - top_left).x as u32,
+            (bottom_right - top_left).y as u32,
+        ),
+    )
+}
+
+// pixels flushed for nothing when both go out in one window
+fn merge_waste(first: &Rectangle, second: &Rectangle) -> u32 {
+    let overlap = area(&first.intersection(second));
+
+    (area(&union(first, second)) + overlap).saturating_sub(area(first) + area(second))
+}
@@ -3,9 +3,12 @@ use embedded_graphics::{
     pixelcolor::{raw::RawU16, PixelColor, Rgb555, RgbColor},
 };
 use enumflags2::{bitflags, BitFlags};
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
-#[derive(Clone, Copy, Debug)]
+use crate::dirty_region::PixelCounter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(u8)]
 #[bitflags]
 pub enum LayerType {
@@ -43,5 +46,8 @@ pub trait ClockDisplayInterface {
         func: impl FnOnce(Self::FrameBuffer<'b>) -> Self::FrameBuffer<'b>,
     );
 
+    // flushes only what the renders since the last commit touched
     fn commit(&mut self, layers_mask: BitFlags<LayerType>);
+
+    fn pixel_counter(&self) -> PixelCounter;
 }
@@ -6,7 +6,7 @@ use crate::{
     reference_data::{GpsCoordinates, ReferenceTimeOffset},
 };
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct ReferenceData {
     pub version: i32,
     pub reference_time: ReferenceTimeOffset,
@@ -14,7 +14,7 @@ pub struct ReferenceData {
     pub events: Vec<CalendarEventDto>,
 }
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub enum WakeupCause {
     Undef,
     All,
@@ -25,7 +25,7 @@ pub enum WakeupCause {
     Ulp,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TouchPosition {
     pub x: i32,
     pub y: i32,
@@ -2,23 +2,39 @@ use std::sync::Arc;
 
 use crate::calendar::{CalendarEvent, CalendarEventKey, EventTimelyData, TimelyDataRecord};
 use crate::domain::{ReferenceData, TouchPosition, WakeupCause};
+use crate::message_bus::CorrelationId;
+use crate::modules::layout::Face;
+use crate::modules::navigation::Navigation;
 use crate::persistence::PersistenceUnit;
+use crate::reference_data::{GpsCoordinates, TimezoneRules};
 use crate::reminders::Reminder;
+use crate::rtc_drift::SyncSample;
+use crate::solar::SolarDay;
+use crate::storage::StorageUsage;
+use crate::weather::{CurrentWeather, WeatherForecast};
+use serde::{Deserialize, Serialize};
 use strum_macros::AsRefStr;
 use time::OffsetDateTime;
 
-#[derive(Clone, Debug, AsRefStr)]
+#[derive(Clone, Debug, AsRefStr, Serialize, Deserialize)]
 pub enum Events {
     TimeNow(OffsetDateTime),
     BleClientConnected,
     BleClientDisconnected,
     ReferenceData(ReferenceData),
     ReferenceTime(OffsetDateTime),
+    RtcAdjusted(SyncSample),
+    ReferenceTimezone(TimezoneRules),
+    ReferenceWeather(WeatherForecast),
+    WeatherNow(CurrentWeather),
+    ReferenceLocation(GpsCoordinates),
+    SolarDays(Vec<SolarDay>),
     Wakeup(WakeupCause),
     SharedInterrupt, // touch, accelerometer, rtc alarm
     Key1Press,
     Key2Press,
     TouchPos(TouchPosition),
+    TouchReleased,
     IncomingData(Arc<Vec<u8>>),
     Temperature(i32),
     BatteryLevel(u16),
@@ -33,6 +49,7 @@ pub enum Events {
     TimelyDataBatch(Arc<Vec<TimelyDataRecord>>),
     DropCalendarEventsBatch(Arc<Vec<CalendarEventKey>>),
     Restored(PersistenceUnit),
+    StorageUsage(Vec<StorageUsage>),
     PersistedCalendarEvents(Arc<Vec<CalendarEventKey>>),
     FirstRender,
     Reminder(Reminder),
@@ -40,4 +57,9 @@ pub enum Events {
     AccelerometerInterrupt(u8),
     RtcAlarmInterrupt(bool),
     EventTimelyData(EventTimelyData),
+    Reply(CorrelationId, Box<Events>),
+    PairingPin(u32),
+    Paired(bool),
+    FaceSelected(Face),
+    Navigate(Navigation),
 }
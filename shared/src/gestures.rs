@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::TouchPosition;
+
+pub const LONG_PRESS: Duration = Duration::from_millis(600);
+
+// relative to the display side, 1000 is all of it
+const TAP_SLOP: i32 = 40;
+const SWIPE_DISTANCE: i32 = 150;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gesture {
+    Tap(TouchPosition),
+    LongPress(TouchPosition),
+    Swipe(SwipeDirection),
+}
+
+struct Contact {
+    start: TouchPosition,
+    started_at: Duration,
+    last: TouchPosition,
+    long_press_sent: bool,
+}
+
+// positions of one finger while it touches, times are from any monotonic origin
+pub struct GestureRecognizer {
+    side: i32,
+    contact: Option<Contact>,
+}
+
+impl GestureRecognizer {
+    pub fn new(side: usize) -> Self {
+        Self {
+            side: side as i32,
+            contact: None,
+        }
+    }
+
+    // the long press fires while still holding, nothing else comes out of that contact
+    pub fn touch(&mut self, position: TouchPosition, at: Duration) -> Option<Gesture> {
+        let contact = self.contact.get_or_insert(Contact {
+            start: position,
+            started_at: at,
+            last: position,
+            long_press_sent: false,
+        });
+
+        contact.last = position;
+
+        let held = at.saturating_sub(contact.started_at);
+
+        if contact.long_press_sent || held < LONG_PRESS || !self.within_slop(position) {
+            return None;
+        }
+
+        let contact = self.contact.as_mut().unwrap();
+        contact.long_press_sent = true;
+
+        Some(Gesture::LongPress(contact.start))
+    }
+
+    pub fn release(&mut self, at: Duration) -> Option<Gesture> {
+        let contact = self.contact.as_ref()?;
+
+        let (start, last, started_at) = (contact.start, contact.last, contact.started_at);
+        let long_press_sent = contact.long_press_sent;
+
+        let gesture = if long_press_sent {
+            None
+        } else if let Some(direction) = self.swipe_direction(start, last) {
+            Some(Gesture::Swipe(direction))
+        } else if !self.within_slop(last) {
+            // too far for a tap, too short for a swipe
+            None
+        } else if at.saturating_sub(started_at) >= LONG_PRESS {
+            Some(Gesture::LongPress(start))
+        } else {
+            Some(Gesture::Tap(start))
+        };
+
+        self.contact = None;
+
+        gesture
+    }
+
+    fn within_slop(&self, position: TouchPosition) -> bool {
+        let Some(contact) = &self.contact else {
+            return false;
+        };
+
+        let (dx, dy) = Self::delta(contact.start, position);
+
+        dx.abs().max(dy.abs()) * 1000 <= TAP_SLOP * self.side
+    }
+
+    // the longer axis decides, y grows downwards
+    fn swipe_direction(&self, start: TouchPosition, end: TouchPosition) -> Option<SwipeDirection> {
+        let (dx, dy) = Self::delta(start, end);
+
+        if dx.abs().max(dy.abs()) * 1000 < SWIPE_DISTANCE * self.side {
+            return None;
+        }
+
+        let direction = match (dx.abs() >= dy.abs(), dx > 0, dy > 0) {
+            (true, true, _) => SwipeDirection::Right,
+            (true, false, _) => SwipeDirection::Left,
+            (false, _, true) => SwipeDirection::Down,
+            (false, _, false) => SwipeDirection::Up,
+        };
+
+        Some(direction)
+    }
+
+    fn delta(from: TouchPosition, to: TouchPosition) -> (i32, i32) {
+        (to.x - from.x, to.y - from.y)
+    }
+}
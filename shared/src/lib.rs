@@ -1,16 +1,25 @@
+pub mod bus_channel;
 pub mod calendar;
 pub mod commands;
 pub mod contract;
+pub mod crc;
+pub mod dirty_region;
 pub mod display_interface;
 pub mod domain;
 pub mod error;
 pub mod events;
 pub mod fasttrack;
+pub mod gestures;
 pub mod message_bus;
 pub mod modules;
 pub mod persistence;
 pub mod reference_data;
 pub mod reminders;
+pub mod rtc_drift;
+pub mod solar;
+pub mod storage;
+pub mod supervisor;
+pub mod weather;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
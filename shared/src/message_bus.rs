@@ -1,22 +1,62 @@
-use std::{any::type_name, future::Future, mem};
+use std::{
+    any::type_name,
+    future::Future,
+    mem,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use log::{debug, error, info};
-use tokio::{
-    select,
-    sync::broadcast::{channel, Receiver, Sender},
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{select, sync::watch};
+
+use crate::{
+    bus_channel::{
+        channel, ChannelReceiver as Receiver, ChannelSender as Sender, LagMetric, OverflowPolicy,
+        RecvError,
+    },
+    commands::Commands,
+    events::Events,
 };
 
-use crate::{commands::Commands, events::Events};
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub const DEFAULT_COMMANDS_CAPACITY: usize = 20;
+
+pub const DEFAULT_EVENTS_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CorrelationId(pub u32);
+
+#[derive(Debug, Clone)]
+pub struct Timeout(pub CorrelationId);
 
 pub struct BusSender {
     commands_sender: Sender<Commands>,
     events_sender: Sender<Events>,
+    correlation_ids: Arc<AtomicU32>,
+    request_timeout: Duration,
+    current_request: Option<CorrelationId>,
 }
 
+// a new bus subscribes right away so its handler sees what is sent before handle starts,
+// clones carry no receivers, a new bus that is never handled must be dropped for a clone
 pub struct MessageBus {
     sender: BusSender,
     commands_recv: Option<Receiver<Commands>>,
     events_recv: Option<Receiver<Events>>,
+    shutdown: Option<watch::Receiver<bool>>,
+}
+
+#[derive(Clone, Copy)]
+pub struct MessageBusConfig {
+    pub commands_capacity: usize,
+    pub events_capacity: usize,
+    pub commands_policy: fn(&Commands) -> OverflowPolicy,
+    pub events_policy: fn(&Events) -> OverflowPolicy,
 }
 
 pub struct ContextStub {}
@@ -37,14 +77,31 @@ pub trait BusHandler<TContext> {
 impl MessageBus {
     pub fn clone(self: &MessageBus) -> Self {
         Self {
-            sender: BusSender {
-                commands_sender: self.sender.commands_sender.clone(),
-                events_sender: self.sender.events_sender.clone(),
-            },
+            sender: self.sender.clone(),
             commands_recv: None,
             events_recv: None,
+            shutdown: None,
+        }
+    }
+
+    pub(crate) fn supervised(&self, shutdown: watch::Receiver<bool>) -> Self {
+        Self {
+            shutdown: Some(shutdown),
+            ..self.clone()
         }
     }
+
+    pub(crate) fn subscribe_commands(&self, name: &'static str) -> Receiver<Commands> {
+        let receiver = self.sender.commands_sender.subscribe();
+        receiver.set_name(name);
+        receiver
+    }
+
+    pub(crate) fn subscribe_events(&self, name: &'static str) -> Receiver<Events> {
+        let receiver = self.sender.events_sender.subscribe();
+        receiver.set_name(name);
+        receiver
+    }
 }
 
 impl BusSender {
@@ -55,6 +112,87 @@ impl BusSender {
     pub fn send_event(&self, event: Events) {
         self.events_sender.send(event).unwrap();
     }
+
+    pub async fn request(&self, command: Commands) -> Result<Events, Timeout> {
+        self.request_with_timeout(command, self.request_timeout)
+            .await
+    }
+
+    pub async fn request_with_timeout(
+        &self,
+        command: Commands,
+        timeout: Duration,
+    ) -> Result<Events, Timeout> {
+        let correlation_id = CorrelationId(self.correlation_ids.fetch_add(1, Ordering::Relaxed));
+
+        let mut events_receiver = self.events_sender.subscribe();
+        events_receiver.set_name("request");
+
+        debug!("request {:?} {:?}", correlation_id, command);
+
+        if let Err(err) = self
+            .commands_sender
+            .send(Commands::Request(correlation_id, Box::new(command)))
+        {
+            error!("{:?}", err);
+        }
+
+        let wait_for_reply = async {
+            loop {
+                match events_receiver.recv().await {
+                    Ok(Events::Reply(reply_id, event)) if reply_id == correlation_id => {
+                        return *event;
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(count)) => {
+                        error!("waiting for {:?} lagged by {}", correlation_id, count);
+                    }
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, wait_for_reply)
+            .await
+            .map_err(|_| Timeout(correlation_id))
+    }
+
+    pub fn current_request(&self) -> Option<CorrelationId> {
+        self.current_request
+    }
+
+    pub fn reply(&self, event: Events) {
+        match self.current_request {
+            Some(correlation_id) => self.reply_to(correlation_id, event),
+            None => error!("no request to reply with {:?}", event),
+        }
+    }
+
+    pub fn reply_to(&self, correlation_id: CorrelationId, event: Events) {
+        self.send_event(Events::Reply(correlation_id, Box::new(event)));
+    }
+
+    pub fn lag_metrics(&self) -> Vec<LagMetric> {
+        let mut metrics = self.commands_sender.lag_metrics();
+
+        for event_metric in self.events_sender.lag_metrics() {
+            match metrics
+                .iter_mut()
+                .find(|x| x.subscriber == event_metric.subscriber)
+            {
+                Some(metric) => metric.lagged += event_metric.lagged,
+                None => metrics.push(event_metric),
+            }
+        }
+
+        metrics
+    }
+
+    fn scoped(&self, correlation_id: CorrelationId) -> Self {
+        Self {
+            current_request: Some(correlation_id),
+            ..self.clone()
+        }
+    }
 }
 
 impl Clone for BusSender {
@@ -62,25 +200,43 @@ impl Clone for BusSender {
         Self {
             commands_sender: self.commands_sender.clone(),
             events_sender: self.events_sender.clone(),
+            correlation_ids: self.correlation_ids.clone(),
+            request_timeout: self.request_timeout,
+            current_request: None,
         }
     }
 }
 
 impl MessageBus {
     pub fn new() -> Self {
-        let (commands_sender, commands_recv) = channel::<Commands>(20);
-        let (events_sender, events_recv) = channel::<Events>(64);
+        Self::with_config(MessageBusConfig::default())
+    }
+
+    pub fn with_config(config: MessageBusConfig) -> Self {
+        let (commands_sender, commands_recv) =
+            channel::<Commands>(config.commands_capacity, config.commands_policy);
+        let (events_sender, events_recv) =
+            channel::<Events>(config.events_capacity, config.events_policy);
 
         Self {
             sender: BusSender {
                 commands_sender,
                 events_sender,
+                correlation_ids: Arc::new(AtomicU32::new(0)),
+                request_timeout: DEFAULT_REQUEST_TIMEOUT,
+                current_request: None,
             },
             commands_recv: Some(commands_recv),
             events_recv: Some(events_recv),
+            shutdown: None,
         }
     }
 
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.sender.request_timeout = request_timeout;
+        self
+    }
+
     #[inline]
     pub async fn handle<TContext, THandler>(mut bus: MessageBus, mut context: TContext) -> TContext
     where
@@ -104,6 +260,10 @@ impl MessageBus {
 
         let commands_receiver = bus.commands_recv.as_mut().unwrap();
         let events_receiver = bus.events_recv.as_mut().unwrap();
+        let shutdown = &mut bus.shutdown;
+
+        commands_receiver.set_name(handler_type);
+        events_receiver.set_name(handler_type);
 
         let mut break_loop = false;
 
@@ -112,6 +272,7 @@ impl MessageBus {
                 &bus.sender,
                 commands_receiver,
                 events_receiver,
+                shutdown,
                 &mut context,
                 handler_type,
             )
@@ -131,39 +292,96 @@ impl MessageBus {
         sender: &BusSender,
         commands_receiver: &mut Receiver<Commands>,
         events_receiver: &mut Receiver<Events>,
+        shutdown: &mut Option<watch::Receiver<bool>>,
         context: &mut TContext,
         handler_type: &str,
     ) -> bool
     where
         THandler: BusHandler<TContext>,
     {
+        let supervised = shutdown.is_some();
+
         let mut break_loop = false;
+        let mut shutdown_requested = false;
 
         select! {
             command_res = commands_receiver.recv() => {
                 match command_res {
+                    // supervised modules are stopped one by one by the supervisor
+                    Ok(Commands::StartDeepSleep) if supervised => {},
                     Ok(command) => {
 
                         if matches!(command, Commands::StartDeepSleep) {
                             break_loop = true;
                         }
 
-                        THandler::command_handler(sender, context, command).await;
+                        Self::dispatch_command::<TContext, THandler>(sender, context, command).await;
+                    },
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("{} commands lagged by {}", handler_type, count)
                     },
-                    Err(err) => {error!("{:?} {:?}", err, handler_type)},
                 }
              }
              event_res = events_receiver.recv() => {
                 match event_res {
                     Ok(event) => THandler::event_handler(sender, context, event).await,
-                    Err(err) => {error!("{:?} {:?}", err, handler_type)},
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("{} events lagged by {}", handler_type, count)
+                    },
                 }
             }
+            _ = Self::wait_for_shutdown(shutdown) => {
+                shutdown_requested = true;
+            }
+        }
+
+        if shutdown_requested {
+            info!("shutting down {}", handler_type);
+
+            while let Some(command) = commands_receiver.try_recv() {
+                if !matches!(command, Commands::StartDeepSleep) {
+                    Self::dispatch_command::<TContext, THandler>(sender, context, command).await;
+                }
+            }
+
+            while let Some(event) = events_receiver.try_recv() {
+                THandler::event_handler(sender, context, event).await;
+            }
+
+            THandler::command_handler(sender, context, Commands::StartDeepSleep).await;
+
+            break_loop = true;
         }
 
         return break_loop;
     }
 
+    async fn dispatch_command<TContext, THandler>(
+        sender: &BusSender,
+        context: &mut TContext,
+        command: Commands,
+    ) where
+        THandler: BusHandler<TContext>,
+    {
+        match command {
+            Commands::Request(correlation_id, command) => {
+                let scoped_sender = sender.scoped(correlation_id);
+                THandler::command_handler(&scoped_sender, context, *command).await;
+            }
+            _ => THandler::command_handler(sender, context, command).await,
+        }
+    }
+
+    async fn wait_for_shutdown(shutdown: &mut Option<watch::Receiver<bool>>) {
+        if let Some(shutdown) = shutdown {
+            if shutdown.wait_for(|x| *x).await.is_ok() {
+                return;
+            }
+        }
+
+        std::future::pending::<()>().await
+    }
+
     pub fn send_cmd(&self, command: Commands) {
         if let Err(err) = self.sender.commands_sender.send(command) {
             error!("{:?}", err);
@@ -176,8 +394,17 @@ impl MessageBus {
         }
     }
 
+    pub async fn request(&self, command: Commands) -> Result<Events, Timeout> {
+        self.sender.request(command).await
+    }
+
+    pub fn lag_metrics(&self) -> Vec<LagMetric> {
+        self.sender.lag_metrics()
+    }
+
     pub async fn wait_for(&mut self, target_event: Events) {
         let mut events_receiver = self.sender.events_sender.subscribe();
+        events_receiver.set_name("wait_for");
 
         info!("waiting for {:?}...", target_event);
 
@@ -200,3 +427,65 @@ impl MessageBus {
         info!("resuming after {:?}", target_event);
     }
 }
+
+impl Default for MessageBusConfig {
+    fn default() -> Self {
+        Self {
+            commands_capacity: DEFAULT_COMMANDS_CAPACITY,
+            events_capacity: DEFAULT_EVENTS_CAPACITY,
+            commands_policy: default_commands_policy,
+            events_policy: default_events_policy,
+        }
+    }
+}
+
+pub fn default_commands_policy(command: &Commands) -> OverflowPolicy {
+    match command {
+        Commands::StartDeepSleep
+        | Commands::Persist(_)
+        | Commands::Restore(_)
+        | Commands::SetTime(_)
+        | Commands::SetRtcDrift(_)
+        | Commands::SetTimezoneRules(_)
+        | Commands::SetReminders(_)
+        | Commands::SetReminderSettings(_)
+        | Commands::SnoozeReminder(_, _)
+        | Commands::DismissReminder(_)
+        | Commands::SetFace(_)
+        | Commands::Request(_, _) => OverflowPolicy::NeverDrop,
+        Commands::GetTimeNow | Commands::GetTemperature => OverflowPolicy::LatestWins,
+        _ => OverflowPolicy::DropOldest,
+    }
+}
+
+pub fn default_events_policy(event: &Events) -> OverflowPolicy {
+    match event {
+        Events::TimeNow(_)
+        | Events::BatteryLevel(_)
+        | Events::Temperature(_)
+        | Events::Charging(_)
+        | Events::InSync(_)
+        | Events::SolarDays(_) => OverflowPolicy::LatestWins,
+        Events::ReferenceCalendarEvent(_)
+        | Events::ReferenceCalendarEventUpdatesBatch(_)
+        | Events::ReferenceCalendarEventDropsBatch(_)
+        | Events::ReferenceTimelyDataBatch(_)
+        | Events::CalendarEvent(_)
+        | Events::CalendarEventsBatch(_)
+        | Events::TimelyDataBatch(_)
+        | Events::EventTimelyData(_)
+        | Events::DropCalendarEventsBatch(_)
+        | Events::Restored(_)
+        | Events::ReferenceTimezone(_)
+        | Events::ReferenceWeather(_)
+        | Events::ReferenceLocation(_)
+        | Events::FaceSelected(_)
+        | Events::TouchPos(_)
+        | Events::TouchReleased
+        | Events::RtcAdjusted(_)
+        | Events::IncomingData(_)
+        | Events::Term
+        | Events::Reply(_, _) => OverflowPolicy::NeverDrop,
+        _ => OverflowPolicy::DropOldest,
+    }
+}
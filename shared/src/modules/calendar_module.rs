@@ -1,16 +1,18 @@
 use itertools::Itertools;
 use log::{error, info, warn};
-use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::slice::Iter;
 use std::sync::Arc;
 use time::{Duration, OffsetDateTime, UtcOffset};
 
+use crate::calendar::state::CalendarStateDto;
 use crate::calendar::{
     CalendarEventKey, CalendarKind, EventTimelyData, TimelyDataMarker, TimelyDataRecord,
 };
-use crate::reference_data::ReferenceTimeUtc;
-use crate::reminders::Reminder;
+use crate::message_bus;
+use crate::modules::renderer::HALF_DAY;
+use crate::reference_data::TimezoneRules;
+use crate::reminders::{Reminder, ReminderSettings};
 use crate::{
     calendar::CalendarEvent,
     error::Error,
@@ -18,16 +20,28 @@ use crate::{
     persistence::{PersistenceUnit, PersistenceUnitKind},
 };
 use crate::{calendar::CalendarEventDto, commands::Commands};
-use crate::{message_bus, reminders};
 use message_bus::{BusHandler, BusSender, MessageBus};
 pub struct CalendarModule {}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct CalendarStateDto {
-    pub version: i32,
-    pub last_sync: ReferenceTimeUtc,
-    pub events: Vec<CalendarEventDto>,
-    pub timely_data: Vec<TimelyDataRecord>,
+// recurring events are expanded into occurrences only that far ahead of now
+const EXPANSION_WINDOW: Duration = Duration::days(1);
+
+const EXPANSION_STEP: Duration = Duration::hours(1);
+
+// as many arcs as fit between the outer ring and the clock
+pub const MAX_LANES: u8 = 4;
+
+// events that didn't fit into any lane, shown as "N more"
+pub const OVERFLOW_LANE: u8 = u8::MAX;
+
+// occurrences of a recurring event share the key and differ by start
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LaneKey(CalendarEventKey, i64);
+
+// greedy interval colouring in start order, an event keeps its lane for as long as it's free
+pub struct LaneAllocator {
+    max_lanes: u8,
+    assigned: HashMap<LaneKey, u8>,
 }
 
 struct Context {
@@ -35,21 +49,13 @@ struct Context {
     timely_data: HashMap<i32, HashSet<TimelyDataRecord>>,
     now: Option<OffsetDateTime>,
     utc_offset: Option<UtcOffset>,
-}
-
-impl CalendarStateDto {
-    pub fn new(
-        events: Vec<CalendarEventDto>,
-        timely_data: Vec<TimelyDataRecord>,
-        last_sync: ReferenceTimeUtc,
-    ) -> Self {
-        Self {
-            version: 3,
-            events,
-            timely_data,
-            last_sync,
-        }
-    }
+    timezone: Option<TimezoneRules>,
+    next_transition: Option<OffsetDateTime>,
+    expanded_until: Option<OffsetDateTime>,
+    reminder_settings: ReminderSettings,
+    // settings set before the persisted ones arrived win over them
+    reminder_settings_restored: bool,
+    lanes: LaneAllocator,
 }
 
 impl BusHandler<Context> for CalendarModule {
@@ -57,18 +63,32 @@ impl BusHandler<Context> for CalendarModule {
         match event {
             Events::TimeNow(time_now) => {
                 context.now = Some(time_now);
+
+                Self::pass_transition(bus, context);
+                Self::extend_occurrences(bus, context);
             }
             Events::ReferenceCalendarEvent(reference_calendar_event) => {
+                let reference_calendar_event = localize(context, reference_calendar_event);
+
                 handle_event_update(context, &reference_calendar_event);
 
-                bus.send_event(Events::CalendarEvent(reference_calendar_event));
+                match reference_calendar_event.is_recurring() {
+                    true => Self::send_occurrences(bus, context, &[reference_calendar_event]),
+                    false => Self::send_updates(bus, context, vec![reference_calendar_event]),
+                }
             }
             Events::ReferenceCalendarEventUpdatesBatch(batch) => {
-                for event in batch.as_slice() {
+                let batch: Vec<CalendarEvent> =
+                    batch.iter().map(|x| localize(context, x.clone())).collect();
+
+                for event in batch.iter() {
                     handle_event_update(context, event);
                 }
 
-                bus.send_event(Events::CalendarEventsBatch(batch));
+                match batch.iter().any(|x| x.is_recurring()) {
+                    true => Self::send_occurrences(bus, context, &batch),
+                    false => Self::send_updates(bus, context, batch),
+                }
             }
             Events::ReferenceCalendarEventDropsBatch(batch) => {
                 handle_events_drop(context, batch.as_slice());
@@ -88,26 +108,6 @@ impl BusHandler<Context> for CalendarModule {
 
                 info!("events persisted");
             }
-
-            Events::Restored(unit) => {
-                if !matches!(unit.kind, PersistenceUnitKind::CalendarSyncInfo) {
-                    return;
-                }
-
-                if let Err(error) = unit.data {
-                    error!("{}", error);
-                    return;
-                }
-
-                if context.utc_offset.is_none() {
-                    warn!("utc_offset is not set");
-                    return;
-                }
-
-                Self::try_restore(bus, context, unit, context.utc_offset.unwrap()).await;
-
-                Self::set_reminders(context, bus);
-            }
             _ => {}
         }
     }
@@ -125,7 +125,34 @@ impl BusHandler<Context> for CalendarModule {
                     context.now = Some(context.now.unwrap().replace_offset(utc_offset));
                 }
 
-                bus.send_cmd(Commands::Restore(PersistenceUnitKind::CalendarSyncInfo));
+                Self::restore_state(bus, context, utc_offset).await;
+            }
+            Commands::SetTimezoneRules(timezone) => {
+                let now = context.now.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+                context.next_transition = timezone.next_transition_after(now);
+                context.now = context.now.map(|x| timezone.to_local(x));
+
+                let utc_offset = timezone.offset_at(now);
+                context.timezone = Some(timezone);
+
+                match context.utc_offset.replace(utc_offset) {
+                    None => Self::restore_state(bus, context, utc_offset).await,
+                    Some(_) => Self::localize_events(bus, context),
+                }
+            }
+            Commands::SetReminderSettings(settings) => {
+                bus.send_cmd(Commands::Persist(PersistenceUnit::new(
+                    PersistenceUnitKind::ReminderSettings,
+                    &settings,
+                )));
+
+                context.reminder_settings = settings;
+                context.reminder_settings_restored = true;
+
+                if context.now.is_some() {
+                    Self::set_reminders(context, bus);
+                }
             }
             _ => {}
         }
@@ -156,6 +183,75 @@ fn handle_events_drop(context: &mut Context, events_keys: &[CalendarEventKey]) {
     }
 }
 
+// recurring events are replaced by their occurrences around now, nothing is known before the first TimeNow
+fn expand_occurrences<'a>(
+    context: &Context,
+    events: impl Iterator<Item = &'a CalendarEvent>,
+) -> Vec<CalendarEvent> {
+    events
+        .flat_map(
+            |x| match (x.is_recurring(), context.now, context.expanded_until) {
+                (false, _, _) => vec![x.clone()],
+                (true, Some(now), Some(until)) => {
+                    x.occurrences_between(now, until, context.timezone.as_ref())
+                }
+                _ => vec![],
+            },
+        )
+        .collect()
+}
+
+// lanes are allocated over everything known, events that had to move are sent along with the updated ones
+fn allocate_lanes(context: &mut Context, updated: Vec<CalendarEvent>) -> Vec<CalendarEvent> {
+    let updated_keys: HashSet<LaneKey> = updated.iter().map(LaneKey::of).collect();
+
+    let known = expand_occurrences(context, context.update_events.iter());
+
+    let previous: Vec<Option<u8>> = known.iter().map(|x| context.lanes.lane_of(x)).collect();
+
+    let allocated = context.lanes.allocate(&known);
+
+    // persisted lanes keep the assignment across deep sleep
+    context.update_events = context
+        .update_events
+        .drain()
+        .map(|mut x| {
+            if let (false, Some(lane)) = (x.is_recurring(), context.lanes.lane_of(&x)) {
+                x.lane = lane;
+            }
+
+            x
+        })
+        .collect();
+
+    let mut events: Vec<CalendarEvent> = allocated
+        .into_iter()
+        .zip(previous)
+        .filter(|(x, previous)| {
+            updated_keys.contains(&LaneKey::of(x)) || previous.is_some_and(|lane| lane != x.lane)
+        })
+        .map(|(x, _)| x)
+        .collect();
+
+    // updates the lanes don't know about, e.g. recurring ones before the first TimeNow
+    let allocated_keys: HashSet<LaneKey> = events.iter().map(LaneKey::of).collect();
+
+    events.extend(
+        updated
+            .into_iter()
+            .filter(|x| !allocated_keys.contains(&LaneKey::of(x))),
+    );
+
+    events
+}
+
+fn localize(context: &Context, event: CalendarEvent) -> CalendarEvent {
+    match &context.timezone {
+        Some(timezone) => event.in_timezone(timezone),
+        None => event,
+    }
+}
+
 fn handle_timely_data(context: &mut Context, timely_records: Iter<TimelyDataRecord>) {
     for timely_record in timely_records {
         let linked_event_id = timely_record.linked_event_id;
@@ -168,7 +264,7 @@ fn handle_timely_data(context: &mut Context, timely_records: Iter<TimelyDataReco
             .timely_data
             .get_mut(&linked_event_id)
             .unwrap()
-            .insert(timely_record.clone());
+            .replace(timely_record.clone());
     }
 }
 
@@ -180,7 +276,13 @@ impl CalendarModule {
             update_events: HashSet::new(),
             now: None,
             utc_offset: None,
+            timezone: None,
+            next_transition: None,
             timely_data: HashMap::new(),
+            expanded_until: None,
+            reminder_settings: ReminderSettings::default(),
+            reminder_settings_restored: false,
+            lanes: LaneAllocator::new(MAX_LANES),
         };
 
         MessageBus::handle::<Context, Self>(bus, context).await;
@@ -188,6 +290,59 @@ impl CalendarModule {
         info!("done.");
     }
 
+    async fn restore_state(bus: &BusSender, context: &mut Context, utc_offset: UtcOffset) {
+        Self::restore_reminder_settings(bus, context).await;
+
+        let reply = bus
+            .request(Commands::Restore(PersistenceUnitKind::CalendarSyncInfo))
+            .await;
+
+        let unit = match reply {
+            Ok(Events::Restored(unit)) => unit,
+            Ok(event) => {
+                warn!("unexpected reply {:?}", event.as_ref());
+                return;
+            }
+            Err(timeout) => {
+                error!("restore timed out {:?}", timeout);
+                return;
+            }
+        };
+
+        if let Err(error) = unit.data {
+            error!("{}", error);
+            return;
+        }
+
+        Self::try_restore(bus, context, unit, utc_offset).await;
+
+        Self::set_reminders(context, bus);
+    }
+
+    async fn restore_reminder_settings(bus: &BusSender, context: &mut Context) {
+        let reply = bus
+            .request(Commands::Restore(PersistenceUnitKind::ReminderSettings))
+            .await;
+
+        let settings = match reply {
+            Ok(Events::Restored(unit)) => unit.deserialize::<ReminderSettings>().await,
+            Ok(event) => Err(Error::from(format!(
+                "unexpected reply {:?}",
+                event.as_ref()
+            ))),
+            Err(timeout) => Err(Error::from(format!("restore timed out {:?}", timeout))),
+        };
+
+        match settings {
+            Ok(settings) if !context.reminder_settings_restored => {
+                context.reminder_settings = settings;
+                context.reminder_settings_restored = true;
+            }
+            Ok(_) => {}
+            Err(error) => info!("default reminder settings, {}", error),
+        }
+    }
+
     async fn try_restore(
         bus: &BusSender,
         context: &mut Context,
@@ -205,27 +360,24 @@ impl CalendarModule {
 
                 let chunk_size = 5;
 
-                let calendar_events_restored = calendar_info_restored
+                let calendar_events_restored: Vec<CalendarEvent> = calendar_info_restored
                     .events
                     .iter()
-                    .map(|x| CalendarEvent::new(x, utc_offset))
-                    .chunks(chunk_size);
+                    .map(|x| localize(context, CalendarEvent::new(x, utc_offset)))
+                    .collect();
 
-                for chunk in calendar_events_restored.into_iter() {
-                    let mut batch = Vec::with_capacity(chunk_size);
+                let occurrences = expand_occurrences(context, calendar_events_restored.iter());
 
-                    for event in chunk.into_iter() {
-                        context.update_events.insert(event.clone());
-                        batch.push(event);
-                    }
+                context.update_events.extend(calendar_events_restored);
 
-                    bus.send_event(Events::CalendarEventsBatch(Arc::new(batch)));
-                }
+                let occurrences = allocate_lanes(context, occurrences);
 
-                if calendar_info_restored.version > 2 {
-                    handle_timely_data(context, calendar_info_restored.timely_data.iter());
-                    Self::send_timely_data(bus, context);
+                for chunk in occurrences.chunks(chunk_size) {
+                    bus.send_event(Events::CalendarEventsBatch(Arc::new(chunk.to_vec())));
                 }
+
+                handle_timely_data(context, calendar_info_restored.timely_data.iter());
+                Self::send_timely_data(bus, context);
             }
             Err(error) => {
                 error!("{:?}", error);
@@ -245,7 +397,7 @@ impl CalendarModule {
 
         let now = now.unwrap();
 
-        context.update_events.retain(|x| x.end >= now);
+        context.update_events.retain(|x| !x.is_over(now));
 
         Self::set_reminders(context, bus);
 
@@ -275,30 +427,119 @@ impl CalendarModule {
     fn set_reminders(context: &mut Context, bus: &BusSender) {
         let now = context.now.unwrap();
 
-        let reminders: Vec<_> = context
-            .update_events
+        let occurrences = expand_occurrences(context, context.update_events.iter());
+
+        let reminders: Vec<_> = occurrences
             .iter()
-            .filter(|x| x.start >= now && x.end - x.start < Duration::days(1))
             .flat_map(|x| {
-                return vec![
-                    Reminder {
-                        event_id: x.id,
-                        kind: reminders::ReminderKind::Notification,
-                        remind_at: x.start - Duration::minutes(10),
-                    },
-                    Reminder {
-                        event_id: x.id,
-                        kind: reminders::ReminderKind::Event,
-                        remind_at: x.start,
-                    },
-                ];
+                let offsets = match &x.reminder_offsets {
+                    Some(offsets) => offsets.as_slice(),
+                    None => context
+                        .reminder_settings
+                        .default_offsets(x.kind, x.is_all_day()),
+                };
+
+                offsets.iter().map(|offset| {
+                    Reminder::new(
+                        x.id,
+                        Reminder::kind_for_offset(*offset),
+                        x.start - Duration::minutes(*offset as i64),
+                    )
+                })
             })
+            .filter(|x| x.remind_at >= now)
             .sorted_by(|x, y| Ord::cmp(&x.remind_at, &y.remind_at))
             .collect();
 
         bus.send_cmd(Commands::SetReminders(reminders));
     }
 
+    // the renderer drops the previous occurrences of updated series by their key
+    fn send_occurrences(bus: &BusSender, context: &mut Context, events: &[CalendarEvent]) {
+        let recurring_keys: Vec<CalendarEventKey> = events
+            .iter()
+            .filter(|x| x.is_recurring())
+            .map(|x| x.key())
+            .collect();
+
+        bus.send_event(Events::DropCalendarEventsBatch(Arc::new(recurring_keys)));
+
+        let occurrences = expand_occurrences(context, events.iter());
+
+        Self::send_updates(bus, context, occurrences);
+    }
+
+    fn send_updates(bus: &BusSender, context: &mut Context, updated: Vec<CalendarEvent>) {
+        let events = allocate_lanes(context, updated);
+
+        bus.send_event(Events::CalendarEventsBatch(Arc::new(events)));
+    }
+
+    // offsets of everything already sent are stale once a dst transition passed, even during deep sleep
+    fn pass_transition(bus: &BusSender, context: &mut Context) {
+        let now = context.now.unwrap();
+
+        match context.next_transition {
+            Some(next_transition) if now >= next_transition => {}
+            _ => return,
+        }
+
+        let timezone = context.timezone.as_ref().unwrap();
+
+        context.next_transition = timezone.next_transition_after(now);
+        context.now = Some(timezone.to_local(now));
+
+        info!("timezone transition passed, now {}", context.now.unwrap());
+
+        Self::localize_events(bus, context);
+    }
+
+    fn localize_events(bus: &BusSender, context: &mut Context) {
+        let update_events: Vec<CalendarEvent> = context.update_events.drain().collect();
+
+        context.update_events = update_events
+            .into_iter()
+            .map(|x| localize(context, x))
+            .collect();
+
+        let occurrences = expand_occurrences(context, context.update_events.iter());
+
+        Self::send_updates(bus, context, occurrences);
+    }
+
+    fn extend_occurrences(bus: &BusSender, context: &mut Context) {
+        let now = context.now.unwrap();
+        let until = now + EXPANSION_WINDOW;
+
+        let from = match context.expanded_until {
+            Some(expanded_until) if until - expanded_until < EXPANSION_STEP => return,
+            Some(expanded_until) => Some(expanded_until),
+            None => None,
+        };
+
+        context.expanded_until = Some(until);
+
+        let occurrences: Vec<CalendarEvent> = context
+            .update_events
+            .iter()
+            .filter(|x| x.is_recurring())
+            .flat_map(|x| {
+                x.occurrences_between(from.unwrap_or(now), until, context.timezone.as_ref())
+            })
+            .filter(|x| from.is_none() || x.start > from.unwrap())
+            .collect();
+
+        if occurrences.is_empty() {
+            return;
+        }
+
+        info!("{} new occurrences", occurrences.len());
+
+        Self::send_updates(bus, context, occurrences);
+
+        Self::set_reminders(context, bus);
+    }
+
     fn send_timely_data(bus: &BusSender, context: &mut Context) {
         let now = context.now.unwrap();
 
@@ -308,23 +549,21 @@ impl CalendarModule {
             .find(|x| matches!(x.kind, CalendarKind::Weather))
             .map(|x| x.id);
 
-        for timely_data in context.timely_data.drain() {
-            let (event_id, mut records) = timely_data;
-
+        for (event_id, records) in context.timely_data.iter() {
             let sorted = records
                 .iter()
                 .sorted_by(|x, y| x.start_at_hour.cmp(&y.start_at_hour));
 
-            if temperature_event_id.is_some() && event_id == temperature_event_id.unwrap() {
-                send_current_tmpr(bus, &now, sorted);
+            if temperature_event_id == Some(*event_id) {
+                send_current_tmpr(bus, &now, sorted.clone());
             }
 
-            // let data = EventTimelyData {
-            //     linked_event_id: event_id,
-            //     timely_data: records,
-            // };
+            let data = EventTimelyData {
+                linked_event_id: *event_id,
+                timely_data: sorted.cloned().collect(),
+            };
 
-            // bus.send_event(Events::EventTimelyData(data));
+            bus.send_event(Events::EventTimelyData(data));
         }
     }
 }
@@ -350,3 +589,82 @@ fn send_current_tmpr(
         bus.send_event(Events::Temperature(tmpr.unwrap().round() as i32));
     }
 }
+
+impl LaneKey {
+    fn of(event: &CalendarEvent) -> Self {
+        LaneKey(event.key(), event.start.unix_timestamp())
+    }
+}
+
+impl LaneAllocator {
+    pub fn new(max_lanes: u8) -> Self {
+        Self {
+            max_lanes,
+            assigned: HashMap::new(),
+        }
+    }
+
+    pub fn lane_of(&self, event: &CalendarEvent) -> Option<u8> {
+        self.assigned.get(&LaneKey::of(event)).copied()
+    }
+
+    // only arcs get lanes, longer events are passed through as is
+    pub fn allocate(&mut self, events: &[CalendarEvent]) -> Vec<CalendarEvent> {
+        let mut lane_ends: Vec<Option<OffsetDateTime>> = vec![None; self.max_lanes as usize];
+        let mut assigned = HashMap::new();
+
+        let arcs: Vec<&CalendarEvent> = events
+            .iter()
+            .filter(|x| x.duration() < HALF_DAY)
+            .sorted_by_key(|x| (x.start, x.end, x.kind as u8, x.id))
+            .collect();
+
+        for (index, event) in arcs.iter().enumerate() {
+            let is_free = |lane: u8| {
+                lane_ends[lane as usize].map_or(true, |lane_end| lane_end <= event.start)
+            };
+
+            let sticky = self.lane_of(event).filter(|x| *x != OVERFLOW_LANE);
+            let preferred = sticky.unwrap_or(event.lane);
+
+            // a free lane that an overlapping event later on already holds is taken last
+            let is_claimed = |lane: u8| {
+                arcs[index + 1..]
+                    .iter()
+                    .take_while(|x| x.start < event.end)
+                    .any(|x| self.lane_of(x) == Some(lane))
+            };
+
+            let keep_preferred = preferred < self.max_lanes
+                && is_free(preferred)
+                && (sticky.is_some() || !is_claimed(preferred));
+
+            let lane = match keep_preferred {
+                true => Some(preferred),
+                false => (0..self.max_lanes)
+                    .filter(|x| is_free(*x))
+                    .min_by_key(|x| is_claimed(*x)),
+            };
+
+            let lane = match lane {
+                Some(lane) => {
+                    lane_ends[lane as usize] = Some(event.end);
+                    lane
+                }
+                None => OVERFLOW_LANE,
+            };
+
+            assigned.insert(LaneKey::of(event), lane);
+        }
+
+        self.assigned = assigned;
+
+        events
+            .iter()
+            .map(|x| match self.lane_of(x) {
+                Some(lane) => CalendarEvent { lane, ..x.clone() },
+                None => x.clone(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,54 @@
+use log::{debug, info};
+use tokio::time::Instant;
+
+use crate::commands::Commands;
+use crate::events::Events;
+use crate::gestures::GestureRecognizer;
+use crate::message_bus::{BusHandler, BusSender, MessageBus};
+
+use super::navigation::Navigation;
+
+pub struct GestureModule {}
+
+struct Context {
+    recognizer: GestureRecognizer,
+    started: Instant,
+}
+
+impl BusHandler<Context> for GestureModule {
+    async fn event_handler(bus: &BusSender, context: &mut Context, event: Events) {
+        let at = context.started.elapsed();
+
+        let gesture = match event {
+            Events::TouchPos(position) => context.recognizer.touch(position, at),
+            Events::TouchReleased => context.recognizer.release(at),
+            _ => return,
+        };
+
+        let Some(gesture) = gesture else {
+            return;
+        };
+
+        debug!("gesture {:?}", gesture);
+
+        bus.send_cmd(Commands::Navigate(Navigation::from_gesture(&gesture)));
+    }
+
+    async fn command_handler(_bus: &BusSender, _context: &mut Context, _command: Commands) {}
+}
+
+impl GestureModule {
+    // touch positions are in pixels of a display of that side
+    pub async fn start(bus: MessageBus, side: usize) {
+        info!("starting...");
+
+        let context = Context {
+            recognizer: GestureRecognizer::new(side),
+            started: Instant::now(),
+        };
+
+        MessageBus::handle::<Context, Self>(bus, context).await;
+
+        info!("done.");
+    }
+}
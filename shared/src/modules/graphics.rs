@@ -1,8 +1,9 @@
 use embedded_graphics::text::renderer::TextRenderer;
 
 use crate::display_interface::ClockDisplayInterface;
+use embedded_graphics::pixelcolor::{BinaryColor, Rgb555};
 use embedded_graphics::primitives::{PrimitiveStyle, Rectangle, StyledDrawable};
-use embedded_graphics::text::Alignment;
+use embedded_graphics::text::{Alignment, Baseline};
 use embedded_graphics::{
     image::Image,
     mono_font::{ascii::FONT_6X10, MonoTextStyle},
@@ -11,11 +12,78 @@ use embedded_graphics::{
     text::Text,
 };
 use std::marker::PhantomData;
+use u8g2_fonts::{Font, U8g2TextStyle};
+
+use super::round_text::{CircleBlock, Overflow};
+
+pub const ROUND_TEXT_OVERFLOW: Overflow = Overflow {
+    hyphen: Some("-"),
+    ellipsis: Some("..."),
+};
 
 pub struct Graphics<TDisplay> {
     _inner: PhantomData<TDisplay>,
 }
 
+// a center aligned block of text cut to the round glass, in display pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundTextLayout {
+    pub center: Point,
+    pub radius: u32,
+    pub top: i32,
+    pub padding: u32,
+    pub max_lines: usize,
+}
+
+// a line of text drawn from its position with the top baseline, every glyph is within the box
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlyphRun {
+    pub text: String,
+    pub position: Point,
+    pub bounding_box: Rectangle,
+}
+
+impl RoundTextLayout {
+    pub fn layout(&self, text: &str, font: impl Font) -> Vec<GlyphRun> {
+        let style = U8g2TextStyle::new(font, BinaryColor::On);
+
+        let block = CircleBlock {
+            radius: self.radius,
+            top: self.top - self.center.y,
+            line_height: style.line_height(),
+            padding: self.padding,
+        };
+
+        let measure = |text: &str| Self::measure(&style, text).size.width;
+
+        block
+            .wrap_with(text, self.max_lines, ROUND_TEXT_OVERFLOW, measure)
+            .into_iter()
+            .map(|line| {
+                let bounds = Self::measure(&style, &line.text);
+
+                // glyphs do not have to start right at the origin, the box is what gets placed
+                let position = Point::new(
+                    self.center.x - bounds.size.width as i32 / 2 - bounds.top_left.x,
+                    self.center.y + line.top - bounds.top_left.y,
+                );
+
+                GlyphRun {
+                    bounding_box: Rectangle::new(position + bounds.top_left, bounds.size),
+                    text: line.text,
+                    position,
+                }
+            })
+            .collect()
+    }
+
+    fn measure(style: &U8g2TextStyle<BinaryColor>, text: &str) -> Rectangle {
+        style
+            .measure_string(text, Point::zero(), Baseline::Top)
+            .bounding_box
+    }
+}
+
 impl<TDisplay> Graphics<TDisplay>
 where
     TDisplay: ClockDisplayInterface,
@@ -65,6 +133,73 @@ where
         bounding_box
     }
 
+    // coverage of every pixel around the segment scales the color down towards the black background
+    pub fn thick_line_aa(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: TDisplay::ColorModel,
+    ) {
+        let half_width = width / 2.0;
+        let margin = half_width + 1.0;
+
+        let left = (from.0.min(to.0) - margin).floor() as i32;
+        let right = (from.0.max(to.0) + margin).ceil() as i32;
+        let top = (from.1.min(to.1) - margin).floor() as i32;
+        let bottom = (from.1.max(to.1) + margin).ceil() as i32;
+
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length_squared = (dx * dx + dy * dy).max(f32::EPSILON);
+
+        let pixels = (top..=bottom)
+            .flat_map(|y| (left..=right).map(move |x| (x, y)))
+            .filter_map(|(x, y)| {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+
+                let t =
+                    (((px - from.0) * dx + (py - from.1) * dy) / length_squared).clamp(0.0, 1.0);
+                let distance = (px - from.0 - t * dx).hypot(py - from.1 - t * dy);
+
+                let coverage = (half_width + 0.5 - distance).clamp(0.0, 1.0);
+
+                if coverage <= 0.0 {
+                    return None;
+                }
+
+                Some(Pixel(Point::new(x, y), Self::scale(color, coverage)))
+            });
+
+        frame.draw_iter(pixels).unwrap();
+    }
+
+    fn scale(color: TDisplay::ColorModel, coverage: f32) -> TDisplay::ColorModel {
+        if coverage >= 1.0 {
+            return color;
+        }
+
+        let channel =
+            |value: u8, max: u8| (value as f32 / max as f32 * 31.0 * coverage).round() as u8;
+
+        TDisplay::ColorModel::from(Rgb555::new(
+            channel(color.r(), TDisplay::ColorModel::MAX_R),
+            channel(color.g(), TDisplay::ColorModel::MAX_G),
+            channel(color.b(), TDisplay::ColorModel::MAX_B),
+        ))
+    }
+
+    pub fn glyph_runs(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        runs: &[GlyphRun],
+        style: impl TextRenderer<Color = TDisplay::ColorModel> + Clone,
+    ) {
+        for run in runs {
+            Text::with_baseline(&run.text, run.position, style.clone(), Baseline::Top)
+                .draw(frame)
+                .unwrap();
+        }
+    }
+
     pub fn text(frame: &mut TDisplay::FrameBuffer<'_>, text: &str, coord: Point) {
         let style = MonoTextStyle::new(&FONT_6X10, TDisplay::ColorModel::WHITE);
 
@@ -0,0 +1,340 @@
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Alignment;
+use serde::{Deserialize, Serialize};
+
+use crate::display_interface::LayerType;
+use crate::error::Error;
+
+use super::relative::{RelativeCoordinate, RelativeSize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WidgetKind {
+    Clock,
+    Date,
+    Battery,
+    Ble,
+    Temperature,
+    NextEventCountdown,
+    Alarm,
+    PairingPin,
+    EventArcs,
+    AnalogHands,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Face {
+    Digital,
+    Analog { second_hand: bool },
+}
+
+// one per FontSet font
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FontRole {
+    Clock,
+    Day,
+    Temperature,
+    EventDetails,
+}
+
+// which point of the slot the position is, text is drawn on its baseline there
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    Center,
+    CenterRight,
+}
+
+// relative units, 1000 is the whole display side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slot {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub anchor: Anchor,
+    // moved right by half of what the followed widget drew
+    pub follows: Option<WidgetKind>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Widget {
+    pub kind: WidgetKind,
+    pub slot: Slot,
+    pub font: Option<FontRole>,
+    pub layer: LayerType,
+    // widgets of a group share their place, the first one with something to show is drawn
+    pub group: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchfaceLayout {
+    pub name: String,
+    pub widgets: Vec<Widget>,
+}
+
+impl Slot {
+    pub fn new(x: u16, y: u16, width: u16, height: u16, anchor: Anchor) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            anchor,
+            follows: None,
+        }
+    }
+
+    pub fn following(mut self, kind: WidgetKind) -> Self {
+        self.follows = Some(kind);
+        self
+    }
+
+    pub fn position(&self, side: usize, followed: Option<&Rectangle>) -> Point {
+        let shift = followed
+            .map(|x| RelativeSize::from(x.size.width) / 2u32)
+            .unwrap_or(RelativeSize::from(0u16));
+
+        RelativeCoordinate::new(shift + RelativeSize::from(self.x), self.y.into()).to_absolute(side)
+    }
+
+    pub fn bounds(&self, side: usize) -> Rectangle {
+        let position = self.position(side, None);
+        let size = Size::new(
+            RelativeSize::from(self.width).to_absolute_u32(side),
+            RelativeSize::from(self.height).to_absolute_u32(side),
+        );
+
+        let top_left = match self.anchor {
+            Anchor::TopLeft => position,
+            Anchor::Center => position - Point::new(size.width as i32, size.height as i32) / 2,
+            Anchor::CenterRight => position - Point::new(size.width as i32, size.height as i32 / 2),
+        };
+
+        Rectangle::new(top_left, size)
+    }
+
+    // every corner on the round display
+    pub fn fits_dial(&self, side: usize) -> bool {
+        let bounds = self.bounds(side);
+        let Some(bottom_right) = bounds.bottom_right() else {
+            return true;
+        };
+
+        let radius = side as i32 / 2;
+        let center = Point::new(radius, radius);
+
+        [
+            bounds.top_left,
+            bottom_right,
+            Point::new(bounds.top_left.x, bottom_right.y),
+            Point::new(bottom_right.x, bounds.top_left.y),
+        ]
+        .iter()
+        .all(|x| (*x - center).x.pow(2) + (*x - center).y.pow(2) <= radius.pow(2))
+    }
+}
+
+impl Anchor {
+    pub fn text_alignment(&self) -> Alignment {
+        match self {
+            Anchor::TopLeft => Alignment::Left,
+            Anchor::Center => Alignment::Center,
+            Anchor::CenterRight => Alignment::Right,
+        }
+    }
+}
+
+impl Widget {
+    pub fn new(kind: WidgetKind, slot: Slot, layer: LayerType) -> Self {
+        Self {
+            kind,
+            slot,
+            font: None,
+            layer,
+            group: None,
+        }
+    }
+
+    pub fn with_font(mut self, font: FontRole) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn in_group(mut self, group: u8) -> Self {
+        self.group = Some(group);
+        self
+    }
+}
+
+impl WatchfaceLayout {
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, Error> {
+        rmp_serde::from_slice(bytes).map_err(|err| Error::from(format!("bad layout: {}", err)))
+    }
+
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        rmp_serde::to_vec(self).unwrap()
+    }
+
+    // widgets of the layer in drawing order, has_content tells which ones have anything to draw
+    pub fn visible(
+        &self,
+        layer: LayerType,
+        has_content: impl Fn(WidgetKind) -> bool,
+    ) -> Vec<&Widget> {
+        let mut taken_groups = vec![];
+
+        self.widgets
+            .iter()
+            .filter(|x| x.layer == layer)
+            .filter(|x| {
+                if x.group.is_some_and(|group| taken_groups.contains(&group)) {
+                    return false;
+                }
+
+                if !has_content(x.kind) {
+                    return false;
+                }
+
+                if let Some(group) = x.group {
+                    taken_groups.push(group);
+                }
+
+                true
+            })
+            .collect()
+    }
+}
+
+impl WatchfaceLayout {
+    // hands stay inside the innermost lane, the date sits at three o'clock
+    pub fn analog() -> Self {
+        const CENTER_GROUP: u8 = 1;
+
+        Self {
+            name: "analog".to_string(),
+            widgets: vec![
+                Widget::new(
+                    WidgetKind::Battery,
+                    Slot::new(500, 720, 60, 60, Anchor::Center),
+                    LayerType::Clock,
+                ),
+                Widget::new(
+                    WidgetKind::Ble,
+                    Slot::new(250, 470, 60, 60, Anchor::TopLeft),
+                    LayerType::Clock,
+                ),
+                Widget::new(
+                    WidgetKind::Date,
+                    Slot::new(740, 530, 220, 140, Anchor::CenterRight),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Day),
+                Widget::new(
+                    WidgetKind::PairingPin,
+                    Slot::new(500, 700, 560, 140, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Clock)
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::Alarm,
+                    Slot::new(500, 330, 60, 60, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::NextEventCountdown,
+                    Slot::new(500, 330, 200, 60, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Temperature)
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::Temperature,
+                    Slot::new(500, 330, 200, 60, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Temperature)
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::AnalogHands,
+                    Slot::new(500, 500, 580, 580, Anchor::Center),
+                    LayerType::Clock,
+                ),
+                Widget::new(
+                    WidgetKind::EventArcs,
+                    Slot::new(500, 500, 1000, 1000, Anchor::Center),
+                    LayerType::Events,
+                ),
+            ],
+        }
+    }
+}
+
+impl Default for WatchfaceLayout {
+    // the original face
+    fn default() -> Self {
+        const CENTER_GROUP: u8 = 1;
+
+        Self {
+            name: "default".to_string(),
+            widgets: vec![
+                Widget::new(
+                    WidgetKind::Battery,
+                    Slot::new(504, 756, 60, 60, Anchor::Center),
+                    LayerType::Clock,
+                ),
+                Widget::new(
+                    WidgetKind::Ble,
+                    Slot::new(248, 542, 60, 60, Anchor::TopLeft),
+                    LayerType::Clock,
+                ),
+                Widget::new(
+                    WidgetKind::Clock,
+                    Slot::new(500, 500, 560, 140, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Clock),
+                Widget::new(
+                    WidgetKind::Date,
+                    Slot::new(500, 588, 160, 140, Anchor::CenterRight).following(WidgetKind::Clock),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Day),
+                Widget::new(
+                    WidgetKind::PairingPin,
+                    Slot::new(500, 700, 560, 140, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Clock)
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::Alarm,
+                    Slot::new(500, 605, 60, 60, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::NextEventCountdown,
+                    Slot::new(500, 584, 200, 60, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Temperature)
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::Temperature,
+                    Slot::new(500, 584, 200, 60, Anchor::Center),
+                    LayerType::Clock,
+                )
+                .with_font(FontRole::Temperature)
+                .in_group(CENTER_GROUP),
+                Widget::new(
+                    WidgetKind::EventArcs,
+                    Slot::new(500, 500, 1000, 1000, Anchor::Center),
+                    LayerType::Events,
+                ),
+            ],
+        }
+    }
+}
@@ -1,10 +1,18 @@
 pub mod calendar_module;
 pub mod fonts_set;
-mod graphics;
+pub mod gesture_module;
+pub mod graphics;
 pub mod icon_set;
 pub mod icon_set_240;
 pub mod icon_set_466;
+pub mod layout;
+pub mod navigation;
+pub mod persister_module;
+pub mod recorder;
 pub mod reference_time;
 mod relative;
 pub mod renderer;
 mod renderer_icons;
+pub mod round_text;
+pub mod solar_module;
+pub mod weather_module;
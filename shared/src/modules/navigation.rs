@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::CalendarEventKey;
+use crate::domain::TouchPosition;
+use crate::gestures::{Gesture, SwipeDirection};
+
+// deeper pushes drop the oldest screen above the watchface
+const MAX_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Screen {
+    Watchface,
+    Agenda,
+    EventDetail(CalendarEventKey),
+    Weather,
+    Settings,
+}
+
+// what the user asked for, each screen decides what it means for it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Navigation {
+    Select(TouchPosition),
+    Menu,
+    Back,
+    Forward,
+    Next,
+    Previous,
+    Home,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenStack {
+    screens: Vec<Screen>,
+}
+
+impl Navigation {
+    pub fn from_gesture(gesture: &Gesture) -> Self {
+        match gesture {
+            Gesture::Tap(position) => Navigation::Select(*position),
+            Gesture::LongPress(_) => Navigation::Menu,
+            Gesture::Swipe(SwipeDirection::Left) => Navigation::Forward,
+            Gesture::Swipe(SwipeDirection::Right) => Navigation::Back,
+            Gesture::Swipe(SwipeDirection::Up) => Navigation::Next,
+            Gesture::Swipe(SwipeDirection::Down) => Navigation::Previous,
+        }
+    }
+}
+
+impl ScreenStack {
+    pub fn new() -> Self {
+        Self {
+            screens: vec![Screen::Watchface],
+        }
+    }
+
+    pub fn current(&self) -> &Screen {
+        self.screens.last().unwrap()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.screens.len()
+    }
+
+    pub fn push(&mut self, screen: Screen) {
+        if *self.current() == screen {
+            return;
+        }
+
+        if self.screens.len() == MAX_DEPTH {
+            self.screens.remove(1);
+        }
+
+        self.screens.push(screen);
+    }
+
+    pub fn back(&mut self) -> bool {
+        if self.screens.len() == 1 {
+            return false;
+        }
+
+        self.screens.pop();
+        true
+    }
+
+    pub fn home(&mut self) -> bool {
+        let changed = self.screens.len() > 1;
+
+        self.screens.truncate(1);
+        changed
+    }
+
+    // moves between screens, false leaves the navigation to the current screen
+    pub fn navigate(&mut self, navigation: &Navigation) -> bool {
+        match (self.current(), navigation) {
+            (_, Navigation::Back) => self.back(),
+            (_, Navigation::Home) => self.home(),
+            (Screen::Settings, Navigation::Menu) => false,
+            (_, Navigation::Menu) => {
+                self.push(Screen::Settings);
+                true
+            }
+            (Screen::Watchface, Navigation::Forward) => {
+                self.push(Screen::Agenda);
+                true
+            }
+            (Screen::Watchface, Navigation::Next) => {
+                self.push(Screen::Weather);
+                true
+            }
+            (Screen::Watchface, Navigation::Previous) => {
+                self.push(Screen::Settings);
+                true
+            }
+            // weather slides in from below and goes back the same way
+            (Screen::Weather, Navigation::Previous) => self.back(),
+            _ => false,
+        }
+    }
+}
+
+impl Default for ScreenStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
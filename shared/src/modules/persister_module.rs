@@ -0,0 +1,143 @@
+use std::{
+    collections::hash_map::{self},
+    hash::{BuildHasher, Hash, Hasher},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use log::{error, info};
+
+use crate::message_bus::{BusHandler, MessageBus};
+use crate::storage::{ChunkedStorage, KeyValueStorage};
+use crate::{
+    commands::Commands,
+    persistence::{PersistenceUnit, PersistenceUnitDto, SchemaRegistry},
+};
+use crate::{events::Events, message_bus::BusSender};
+
+pub struct PersisterModule<TStorage> {
+    _storage: PhantomData<TStorage>,
+}
+
+pub struct Context<TStorage> {
+    storage: ChunkedStorage<TStorage>,
+    schemas: SchemaRegistry,
+}
+
+impl<TStorage> BusHandler<Context<TStorage>> for PersisterModule<TStorage>
+where
+    TStorage: KeyValueStorage + Send,
+{
+    async fn event_handler(_bus: &BusSender, _context: &mut Context<TStorage>, _event: Events) {}
+
+    async fn command_handler(bus: &BusSender, context: &mut Context<TStorage>, command: Commands) {
+        match command {
+            Commands::Persist(persistence_unit) => {
+                let kind = persistence_unit.kind;
+                info!("persisting {:?}", kind);
+
+                let payload = persistence_unit.data.unwrap();
+
+                let dto = PersistenceUnitDto {
+                    kind,
+                    data: context.schemas.seal(kind, &payload),
+                };
+
+                let read_result = context.storage.read(kind.as_ref());
+
+                if let Ok(bytes) = read_result {
+                    let existing = PersistenceUnitDto {
+                        kind: kind,
+                        data: bytes,
+                    };
+
+                    let hasher_state = hash_map::RandomState::new();
+                    let mut hasher_of_existing = hasher_state.build_hasher();
+                    let mut hasher_of_new = hasher_state.build_hasher();
+
+                    existing.hash(&mut hasher_of_existing);
+                    dto.hash(&mut hasher_of_new);
+
+                    if hasher_of_existing.finish() == hasher_of_new.finish() {
+                        info!("persisting of {:?} skipped, same hash", kind);
+
+                        return;
+                    }
+                }
+
+                let result = context.storage.write(kind.as_ref(), &dto.data);
+
+                if let Err(error) = result {
+                    error!("{:?}", error);
+                }
+
+                if let Ok(usage) = context.storage.usage_of(kind.as_ref()) {
+                    info!(
+                        "{:?} uses {} bytes in {} chunks",
+                        kind, usage.bytes, usage.chunks
+                    );
+                }
+            }
+            Commands::Restore(kind) => {
+                info!("restoring {:?}", kind);
+
+                let result = context
+                    .storage
+                    .read(kind.as_ref())
+                    .and_then(|bytes| context.schemas.open(kind, &bytes));
+
+                if let Err(error) = &result {
+                    info!("{:?} not restored, {}", kind, error);
+                }
+
+                let persistence_unit = PersistenceUnit {
+                    kind: kind,
+                    data: result.map(Arc::new),
+                };
+
+                if bus.current_request().is_some() {
+                    bus.reply(Events::Restored(persistence_unit));
+                } else {
+                    bus.send_event(Events::Restored(persistence_unit));
+                }
+            }
+            Commands::GetStorageUsage => {
+                let usage = match context.storage.usage() {
+                    Ok(usage) => usage,
+                    Err(error) => {
+                        error!("{:?}", error);
+
+                        vec![]
+                    }
+                };
+
+                if bus.current_request().is_some() {
+                    bus.reply(Events::StorageUsage(usage));
+                } else {
+                    bus.send_event(Events::StorageUsage(usage));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<TStorage> PersisterModule<TStorage>
+where
+    TStorage: KeyValueStorage + Send,
+{
+    pub async fn start(bus: MessageBus, storage: TStorage) -> TStorage {
+        info!("starting...");
+
+        let context = Context {
+            storage: ChunkedStorage::new(storage),
+            schemas: SchemaRegistry::default(),
+        };
+
+        let context = MessageBus::handle::<Context<TStorage>, Self>(bus, context).await;
+
+        info!("done.");
+
+        context.storage.into_inner()
+    }
+}
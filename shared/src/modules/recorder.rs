@@ -0,0 +1,307 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::task::yield_now;
+use tokio::time::{sleep_until, Duration, Instant};
+
+use crate::commands::Commands;
+use crate::error::Error;
+use crate::events::Events;
+use crate::message_bus::{BusHandler, BusSender, MessageBus};
+
+pub struct Recorder {}
+
+pub struct Replayer {}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum TraceMessage {
+    Event(Events),
+    Command(Commands),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TraceRecord {
+    pub at_micros: u64,
+    pub message: TraceMessage,
+}
+
+impl TraceMessage {
+    // the pairing key and pin stay out of traces, they are read off the device
+    pub fn is_recordable(&self) -> bool {
+        match self {
+            TraceMessage::Event(event) => !is_secret_event(event),
+            TraceMessage::Command(command) => !is_secret_command(command),
+        }
+    }
+}
+
+fn is_secret_event(event: &Events) -> bool {
+    match event {
+        Events::Restored(unit) => unit.kind.is_secret(),
+        Events::Reply(_, event) => is_secret_event(event),
+        Events::PairingPin(_) => true,
+        _ => false,
+    }
+}
+
+fn is_secret_command(command: &Commands) -> bool {
+    match command {
+        Commands::Persist(unit) => unit.kind.is_secret(),
+        Commands::Request(_, command) => is_secret_command(command),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ReplayPace {
+    Original,
+    Accelerated(u32),
+    NoDelay,
+}
+
+// where the recorder puts encoded records, any writer for a trace file, a ring on the device
+pub trait TraceSink: Send {
+    fn write_record(&mut self, record: &[u8]) -> std::io::Result<()>;
+
+    fn flush(&mut self) -> std::io::Result<()>;
+}
+
+impl<TWriter> TraceSink for TWriter
+where
+    TWriter: Write + Send,
+{
+    fn write_record(&mut self, record: &[u8]) -> std::io::Result<()> {
+        self.write_all(record)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Write::flush(self)
+    }
+}
+
+// the newest records that fit into max_bytes, read back in the trace file format
+pub struct TraceRing {
+    records: VecDeque<Vec<u8>>,
+    bytes: usize,
+    max_bytes: usize,
+}
+
+// a hex dump line of a trace, it may follow a log prefix on the console
+pub const TRACE_DUMP_MARKER: &str = "TRACE ";
+
+const TRACE_DUMP_LINE_BYTES: usize = 64;
+
+pub struct Context {
+    writer: Box<dyn TraceSink>,
+    started_at: Instant,
+    records_count: usize,
+}
+
+impl BusHandler<Context> for Recorder {
+    async fn event_handler(_bus: &BusSender, context: &mut Context, event: Events) {
+        Self::record(context, TraceMessage::Event(event));
+    }
+
+    async fn command_handler(_bus: &BusSender, context: &mut Context, command: Commands) {
+        let is_last = matches!(command, Commands::StartDeepSleep);
+
+        Self::record(context, TraceMessage::Command(command));
+
+        if is_last {
+            if let Err(err) = context.writer.flush() {
+                error!("{}", err);
+            }
+        }
+    }
+}
+
+impl Recorder {
+    pub async fn start<TSink>(bus: MessageBus, writer: TSink)
+    where
+        TSink: TraceSink + 'static,
+    {
+        info!("starting...");
+
+        let context = Context {
+            writer: Box::new(writer),
+            started_at: Instant::now(),
+            records_count: 0,
+        };
+
+        let context = MessageBus::handle::<Context, Self>(bus, context).await;
+
+        info!("done, {} records", context.records_count);
+    }
+
+    fn record(context: &mut Context, message: TraceMessage) {
+        if !message.is_recordable() {
+            return;
+        }
+
+        let record = TraceRecord {
+            at_micros: context.started_at.elapsed().as_micros() as u64,
+            message,
+        };
+
+        let encoded = rmp_serde::to_vec(&record).unwrap();
+
+        if let Err(err) = context.writer.write_record(&encoded) {
+            error!("{}", err);
+            return;
+        }
+
+        context.records_count += 1;
+    }
+}
+
+impl Replayer {
+    pub fn read_trace<TReader>(reader: TReader) -> Result<Vec<TraceRecord>, Error>
+    where
+        TReader: Read,
+    {
+        let mut deserializer = rmp_serde::Deserializer::new(reader);
+        let mut records = vec![];
+
+        loop {
+            match TraceRecord::deserialize(&mut deserializer) {
+                Ok(record) => records.push(record),
+                Err(rmp_serde::decode::Error::InvalidMarkerRead(err))
+                    if err.kind() == ErrorKind::UnexpectedEof =>
+                {
+                    break;
+                }
+                Err(err) => return Err(Error::from(err.to_string())),
+            }
+        }
+
+        Ok(records)
+    }
+
+    pub async fn replay(
+        bus: &MessageBus,
+        records: impl IntoIterator<Item = TraceRecord>,
+        pace: ReplayPace,
+    ) {
+        let started_at = Instant::now();
+
+        info!("replaying with {:?} pace...", pace);
+
+        for record in records {
+            let offset = match pace {
+                ReplayPace::Original => Some(Duration::from_micros(record.at_micros)),
+                ReplayPace::Accelerated(factor) => Some(Duration::from_micros(
+                    record.at_micros / factor.max(1) as u64,
+                )),
+                ReplayPace::NoDelay => None,
+            };
+
+            match offset {
+                Some(offset) => sleep_until(started_at + offset).await,
+                None => yield_now().await,
+            }
+
+            match record.message {
+                TraceMessage::Event(event) => bus.send_event(event),
+                TraceMessage::Command(command) => bus.send_cmd(command),
+            }
+        }
+
+        info!("replay done.");
+    }
+}
+
+impl TraceRing {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            records: VecDeque::new(),
+            bytes: 0,
+            max_bytes,
+        }
+    }
+
+    // picks up a trace kept from before, e.g. across deep sleep
+    pub fn from_trace(trace: &[u8], max_bytes: usize) -> Result<Self, Error> {
+        let mut ring = Self::new(max_bytes);
+
+        for record in Replayer::read_trace(trace)? {
+            ring.push(rmp_serde::to_vec(&record).unwrap());
+        }
+
+        Ok(ring)
+    }
+
+    pub fn push(&mut self, record: Vec<u8>) {
+        if record.len() > self.max_bytes {
+            return;
+        }
+
+        self.bytes += record.len();
+        self.records.push_back(record);
+
+        while self.bytes > self.max_bytes {
+            let oldest = self.records.pop_front().unwrap();
+            self.bytes -= oldest.len();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn to_trace(&self) -> Vec<u8> {
+        self.records.iter().flatten().copied().collect()
+    }
+}
+
+impl TraceSink for TraceRing {
+    fn write_record(&mut self, record: &[u8]) -> std::io::Result<()> {
+        self.push(record.to_vec());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub fn to_hex_dump(trace: &[u8]) -> Vec<String> {
+    trace
+        .chunks(TRACE_DUMP_LINE_BYTES)
+        .map(|chunk| {
+            let hex: String = chunk.iter().map(|x| format!("{:02x}", x)).collect();
+            format!("{}{}", TRACE_DUMP_MARKER, hex)
+        })
+        .collect()
+}
+
+// lines without the marker are skipped, so a whole console log can be read
+pub fn from_hex_dump(text: &str) -> Result<Vec<u8>, Error> {
+    let mut trace = vec![];
+
+    for line in text.lines() {
+        let Some((_, hex)) = line.split_once(TRACE_DUMP_MARKER) else {
+            continue;
+        };
+
+        let hex = hex.trim();
+
+        if !hex.is_ascii() || hex.len() % 2 != 0 {
+            return Err(Error::from(format!("bad trace dump line {}", hex)));
+        }
+
+        for index in (0..hex.len()).step_by(2) {
+            let byte = u8::from_str_radix(&hex[index..index + 2], 16)
+                .map_err(|err| Error::from(err.to_string()))?;
+
+            trace.push(byte);
+        }
+    }
+
+    Ok(trace)
+}
@@ -1,14 +1,21 @@
 use crate::calendar::{CalendarEvent, CalendarEventKey, TimelyDataRecord};
 use crate::contract::packets::{
-    CalendarEventsMetaPacket, DropCalendarEventPacket, ReferenceCalendarEventPacket,
-    ReferenceDataPacket, ReferenceDataPacketType, ReferenceLocationPacket, ReferenceTimePacket,
-    ReferenceTimelyDataPacket,
+    CalendarEventsMetaPacket, DropCalendarEventPacket, HandshakePacket, PairingConfirmPacket,
+    PairingRequestPacket, ReferenceCalendarEventPacket, ReferenceDataPacket,
+    ReferenceDataPacketType, ReferenceLocationPacket, ReferenceTimePacket,
+    ReferenceTimelyDataPacket, ReferenceTimezonePacket, ReferenceWeatherPacket,
 };
+use crate::contract::secure_channel::{SecureChannel, SecureLink, KEY_SIZE, PIN_MODULO};
+use crate::contract::transport::TransportReceiver;
+use crate::contract::versioning::{ProtocolNegotiation, PROTOCOL_VERSION};
 use crate::error::Error;
-use log::{error, info};
+use crate::persistence::{PersistenceUnit, PersistenceUnitKind};
+use log::{error, info, warn};
+use serde::Serialize;
 use std::ops::Add;
 use std::sync::Arc;
 use time::{OffsetDateTime, UtcOffset};
+use tokio::join;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::time::Duration;
 
@@ -24,6 +31,13 @@ pub struct Context {
 }
 
 pub struct ProcessingContext {
+    transport: TransportReceiver,
+    handshake: HandshakePacket,
+    negotiation: ProtocolNegotiation,
+    // None until the pairing key is restored, incoming data and connection changes wait in the
+    // backlog meanwhile so a reconnect can't overtake the data sent before it
+    link: Option<SecureLink>,
+    backlog: Vec<Events>,
     now_opt: Option<OffsetDateTime>,
     unprocessed_event_updates: Vec<ReferenceDataPacket>,
     unprocessed_event_drops: Vec<ReferenceDataPacket>,
@@ -36,7 +50,9 @@ pub struct ProcessingContext {
 impl BusHandler<Context> for ReferenceTime {
     async fn event_handler(_bus: &BusSender, context: &mut Context, event: Events) {
         match event {
-            Events::IncomingData(_) => {
+            Events::IncomingData(_)
+            | Events::BleClientConnected
+            | Events::BleClientDisconnected => {
                 context.tx.send(event).await.unwrap();
             }
             _ => {}
@@ -57,27 +73,39 @@ impl BusHandler<Context> for ReferenceTime {
 }
 
 impl ReferenceTime {
-    pub async fn start(bus: MessageBus) {
+    pub async fn start(bus: MessageBus, handshake: HandshakePacket) {
         info!("starting...");
 
         let (tx, rx) = channel::<Events>(30);
 
-        let context = Context { tx };
+        let context = Context { tx: tx.clone() };
 
         let message_bus = bus.clone();
         let processing_loop_task = tokio::task::spawn_blocking(|| {
-            Self::reference_processing_loop(message_bus, rx);
+            Self::reference_processing_loop(message_bus, rx, handshake);
         });
 
-        MessageBus::handle::<Context, Self>(bus, context).await;
+        join!(
+            Self::restore_secure_channel(bus.clone(), tx),
+            MessageBus::handle::<Context, Self>(bus, context)
+        );
 
         processing_loop_task.await.unwrap();
 
         info!("done.");
     }
 
-    fn reference_processing_loop(bus: MessageBus, mut rx: Receiver<Events>) {
+    fn reference_processing_loop(
+        bus: MessageBus,
+        mut rx: Receiver<Events>,
+        handshake: HandshakePacket,
+    ) {
         let mut context = ProcessingContext {
+            transport: TransportReceiver::new(),
+            handshake,
+            negotiation: ProtocolNegotiation::new(),
+            link: None,
+            backlog: vec![],
             now_opt: None,
             unprocessed_event_updates: vec![],
             unprocessed_event_drops: vec![],
@@ -94,6 +122,7 @@ impl ReferenceTime {
                 Some(event) => {
                     if matches!(event, Events::Term) {
                         info!("received term");
+                        Self::persist_secure_channel(&bus, &context);
                         break;
                     }
 
@@ -108,86 +137,297 @@ impl ReferenceTime {
         info!("processing loop done.");
     }
 
+    async fn restore_secure_channel(bus: MessageBus, tx: Sender<Events>) {
+        let kind = PersistenceUnitKind::PairingKey;
+
+        let unit = match bus.request(Commands::Restore(kind)).await {
+            Ok(Events::Restored(unit)) => unit,
+            Ok(event) => PersistenceUnit {
+                kind,
+                data: Err(Error::from(format!(
+                    "unexpected reply {:?}",
+                    event.as_ref()
+                ))),
+            },
+            Err(timeout) => PersistenceUnit {
+                kind,
+                data: Err(Error::from(format!("restore timed out {:?}", timeout))),
+            },
+        };
+
+        if tx.send(Events::Restored(unit)).await.is_err() {
+            warn!("processing loop is gone");
+        }
+    }
+
     fn handle_incoming_data(bus: &MessageBus, context: &mut ProcessingContext, event: Events) {
         match event {
+            Events::IncomingData(_)
+            | Events::BleClientConnected
+            | Events::BleClientDisconnected
+                if context.link.is_none() =>
+            {
+                context.backlog.push(event);
+            }
             Events::IncomingData(data) => {
-                let deserialize_result = rmp_serde::from_slice(&data);
-                if let Err(err) = deserialize_result {
+                let received = context.transport.receive(&data);
+
+                for reply in received.replies {
+                    bus.send_cmd(Commands::SendData(Arc::new(reply)));
+                }
+
+                for packet in received.packets {
+                    Self::handle_reference_packet(bus, context, &packet);
+                }
+            }
+            // a reconnected companion numbers its messages from scratch
+            Events::BleClientConnected => {
+                context.transport = TransportReceiver::new();
+                Self::send_packet(bus, ReferenceDataPacketType::Handshake, &context.handshake);
+            }
+            // the replay window is persisted once a sync is over rather than with every packet
+            // to spare the flash, a reset in the middle of a sync rewinds it to the last one
+            Events::BleClientDisconnected => {
+                context.transport = TransportReceiver::new();
+                context.negotiation.reset();
+                Self::persist_secure_channel(bus, context);
+            }
+            Events::Restored(unit) => {
+                Self::handle_restored(bus, context, unit);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_restored(bus: &MessageBus, context: &mut ProcessingContext, unit: PersistenceUnit) {
+        let channel = match unit.data {
+            Ok(data) => match rmp_serde::from_slice::<SecureChannel>(&data) {
+                Ok(channel) => Some(channel),
+                Err(err) => {
                     error!("{}", err);
-                    return;
+                    None
                 }
+            },
+            Err(err) => {
+                info!("not paired: {}", err);
+                None
+            }
+        };
 
-                let reference_data: ReferenceDataPacket = deserialize_result.unwrap();
+        context.link = Some(SecureLink::new(channel));
 
-                match reference_data.packet_type {
-                    ReferenceDataPacketType::Time => {
-                        let now_result =
-                            Self::handle_reference_time(bus, reference_data.packet_payload);
+        for event in std::mem::take(&mut context.backlog) {
+            Self::handle_incoming_data(bus, context, event);
+        }
+    }
 
-                        if let Err(error) = now_result {
-                            error!("{}", error);
-                            return;
-                        }
+    fn send_packet<T>(bus: &MessageBus, packet_type: ReferenceDataPacketType, obj: T)
+    where
+        T: Serialize,
+    {
+        let packet = ReferenceDataPacket::wrap_with_version(PROTOCOL_VERSION, packet_type, obj);
 
-                        context.now_opt = Some(now_result.unwrap());
-                    }
-                    ReferenceDataPacketType::Location => {
-                        Self::handle_reference_location(bus, reference_data.packet_payload);
+        bus.send_cmd(Commands::SendData(Arc::new(packet.serialize())));
+    }
+
+    fn persist_secure_channel(bus: &MessageBus, context: &ProcessingContext) {
+        let channel = context.link.as_ref().and_then(|x| x.channel());
+
+        if let Some(channel) = channel {
+            bus.send_cmd(Commands::Persist(PersistenceUnit::new(
+                PersistenceUnitKind::PairingKey,
+                channel,
+            )));
+        }
+    }
+
+    fn handle_reference_packet(bus: &MessageBus, context: &mut ProcessingContext, data: &[u8]) {
+        let deserialize_result = rmp_serde::from_slice(data);
+        if let Err(err) = deserialize_result {
+            error!("{}", err);
+            return;
+        }
+
+        let reference_data: ReferenceDataPacket = deserialize_result.unwrap();
+
+        let link = context.link.as_mut().unwrap();
+
+        let reference_data = match link.open(&context.negotiation, reference_data) {
+            Ok(reference_data) => reference_data,
+            Err(err) => {
+                error!("{}", err);
+                return;
+            }
+        };
+
+        match reference_data.packet_type {
+            ReferenceDataPacketType::Handshake => {
+                Self::handle_handshake(context, &reference_data);
+            }
+            ReferenceDataPacketType::PairingRequest => {
+                Self::handle_pairing_request(bus, context, &reference_data);
+            }
+            ReferenceDataPacketType::PairingConfirm => {
+                Self::handle_pairing_confirm(bus, context, &reference_data);
+            }
+            ReferenceDataPacketType::Time => {
+                let now_result = Self::handle_reference_time(bus, context, &reference_data);
+
+                if let Err(error) = now_result {
+                    error!("{}", error);
+                    return;
+                }
+
+                context.now_opt = Some(now_result.unwrap());
+            }
+            ReferenceDataPacketType::Location => {
+                Self::handle_reference_location(bus, context, &reference_data);
+            }
+            ReferenceDataPacketType::Timezone => {
+                match context
+                    .negotiation
+                    .decode::<ReferenceTimezonePacket>(&reference_data)
+                {
+                    Ok(packet) => {
+                        info!(
+                            "timezone {} with {} transitions",
+                            packet.timezone.name,
+                            packet.timezone.transitions.len()
+                        );
+
+                        bus.send_event(Events::ReferenceTimezone(packet.timezone.sorted()));
                     }
-                    ReferenceDataPacketType::CalendarEventsMeta => {
-                        let deserialize_result =
-                            rmp_serde::from_slice(&reference_data.packet_payload);
-                        if let Err(err) = deserialize_result {
-                            error!("{}", err);
-                            return;
-                        }
-
-                        let events_meta: CalendarEventsMetaPacket = deserialize_result.unwrap();
-
-                        context.update_events_count = events_meta.update_events_count;
-                        context.drop_events_count = events_meta.drop_events_count;
-                        context.timely_data_count = events_meta.timely_data_count;
-
-                        info!("expecting:");
-                        info!("\t{} events", context.update_events_count);
-                        info!("\t{} drops", context.drop_events_count);
-                        info!("\t{} timely records", context.timely_data_count);
+                    Err(err) => error!("{}", err),
+                }
+            }
+            ReferenceDataPacketType::Weather => {
+                match context
+                    .negotiation
+                    .decode::<ReferenceWeatherPacket>(&reference_data)
+                {
+                    Ok(packet) => {
+                        bus.send_event(Events::ReferenceWeather(packet.forecast.sorted()));
                     }
-                    ReferenceDataPacketType::CalendarEvent => {
-                        context.unprocessed_event_updates.push(reference_data);
+                    Err(err) => error!("{}", err),
+                }
+            }
+            ReferenceDataPacketType::CalendarEventsMeta => {
+                let deserialize_result = context
+                    .negotiation
+                    .decode::<CalendarEventsMetaPacket>(&reference_data);
+                if let Err(err) = deserialize_result {
+                    error!("{}", err);
+                    return;
+                }
 
-                        if Self::is_reference_data_ready(&context) {
-                            Self::handle_sync_completed(context, bus);
-                        }
-                    }
-                    ReferenceDataPacketType::DropCalendarEvent => {
-                        context.unprocessed_event_drops.push(reference_data);
+                let events_meta = deserialize_result.unwrap();
 
-                        if Self::is_reference_data_ready(&context) {
-                            Self::handle_sync_completed(context, bus);
-                        }
-                    }
-                    ReferenceDataPacketType::TimelyData => {
-                        context.unprocessed_timely_data.push(reference_data);
+                context.update_events_count = events_meta.update_events_count;
+                context.drop_events_count = events_meta.drop_events_count;
+                context.timely_data_count = events_meta.timely_data_count;
 
-                        if Self::is_reference_data_ready(&context) {
-                            Self::handle_sync_completed(context, bus);
-                        }
-                    }
-                    _ => {}
+                info!("expecting:");
+                info!("\t{} events", context.update_events_count);
+                info!("\t{} drops", context.drop_events_count);
+                info!("\t{} timely records", context.timely_data_count);
+            }
+            ReferenceDataPacketType::CalendarEvent => {
+                context.unprocessed_event_updates.push(reference_data);
+
+                if Self::is_reference_data_ready(context) {
+                    Self::handle_sync_completed(context, bus);
+                }
+            }
+            ReferenceDataPacketType::DropCalendarEvent => {
+                context.unprocessed_event_drops.push(reference_data);
+
+                if Self::is_reference_data_ready(context) {
+                    Self::handle_sync_completed(context, bus);
+                }
+            }
+            ReferenceDataPacketType::TimelyData => {
+                context.unprocessed_timely_data.push(reference_data);
+
+                if Self::is_reference_data_ready(context) {
+                    Self::handle_sync_completed(context, bus);
                 }
             }
             _ => {}
         }
     }
 
-    fn handle_reference_time(bus: &MessageBus, data: Vec<u8>) -> Result<OffsetDateTime, Error> {
-        let deserialize_result = rmp_serde::from_slice(&data);
-        if let Err(err) = deserialize_result {
-            return Err(Error::from(err.to_string().as_str()));
+    fn handle_handshake(context: &mut ProcessingContext, packet: &ReferenceDataPacket) {
+        let companion = context.negotiation.decode::<HandshakePacket>(packet);
+
+        match companion.and_then(|x| context.negotiation.accept(&x)) {
+            Ok(version) => info!("negotiated protocol v{}", version),
+            Err(err) => error!("{}", err),
+        }
+    }
+
+    fn handle_pairing_request(
+        bus: &MessageBus,
+        context: &mut ProcessingContext,
+        packet: &ReferenceDataPacket,
+    ) {
+        let request = match context.negotiation.decode::<PairingRequestPacket>(packet) {
+            Ok(request) => request,
+            Err(err) => {
+                error!("{}", err);
+                return;
+            }
+        };
+
+        let mut secret = [0u8; KEY_SIZE];
+        let mut pin = [0u8; 4];
+
+        if let Err(err) = getrandom::getrandom(&mut secret).and(getrandom::getrandom(&mut pin)) {
+            error!("{}", err);
+            return;
         }
 
-        let reference_time: ReferenceTimePacket = deserialize_result.unwrap();
+        let pin = u32::from_le_bytes(pin) % PIN_MODULO;
+
+        let link = context.link.as_mut().unwrap();
+        let response = link.start_pairing(&request, secret, pin);
+
+        bus.send_event(Events::PairingPin(pin));
+
+        Self::send_packet(bus, ReferenceDataPacketType::PairingResponse, response);
+    }
+
+    fn handle_pairing_confirm(
+        bus: &MessageBus,
+        context: &mut ProcessingContext,
+        packet: &ReferenceDataPacket,
+    ) {
+        let confirm = context
+            .negotiation
+            .decode::<PairingConfirmPacket>(packet)
+            .and_then(|x| context.link.as_mut().unwrap().confirm_pairing(&x));
+
+        match confirm {
+            Ok(confirmation) => {
+                info!("paired");
+
+                Self::persist_secure_channel(bus, context);
+                Self::send_packet(bus, ReferenceDataPacketType::PairingConfirm, confirmation);
+
+                bus.send_event(Events::Paired(true));
+            }
+            Err(err) => {
+                error!("{}", err);
+                bus.send_event(Events::Paired(false));
+            }
+        }
+    }
+
+    fn handle_reference_time(
+        bus: &MessageBus,
+        context: &ProcessingContext,
+        packet: &ReferenceDataPacket,
+    ) -> Result<OffsetDateTime, Error> {
+        let reference_time = context.negotiation.decode::<ReferenceTimePacket>(packet)?;
 
         let time = reference_time.time;
 
@@ -205,16 +445,24 @@ impl ReferenceTime {
         return Ok(now);
     }
 
-    fn handle_reference_location(_bus: &MessageBus, data: Vec<u8>) {
-        let deserialize_result = rmp_serde::from_slice(&data);
+    fn handle_reference_location(
+        bus: &MessageBus,
+        context: &ProcessingContext,
+        packet: &ReferenceDataPacket,
+    ) {
+        let deserialize_result = context
+            .negotiation
+            .decode::<ReferenceLocationPacket>(packet);
         if let Err(err) = deserialize_result {
             error!("{}", err);
             return;
         }
 
-        let reference_location: ReferenceLocationPacket = deserialize_result.unwrap();
+        let reference_location = deserialize_result.unwrap();
 
-        info!("{:?}", reference_location)
+        info!("{:?}", reference_location);
+
+        bus.send_event(Events::ReferenceLocation(reference_location.coordinates));
     }
 
     fn handle_sync_completed(context: &mut ProcessingContext, bus: &MessageBus) {
@@ -229,6 +477,8 @@ impl ReferenceTime {
             Self::handle_unprocessed_timely_data(bus, context);
         }
 
+        Self::persist_secure_channel(bus, context);
+
         bus.send_event(Events::InSync(true));
     }
 
@@ -237,12 +487,13 @@ impl ReferenceTime {
         context: &mut ProcessingContext,
         offset: UtcOffset,
     ) {
+        let negotiation = &context.negotiation;
+
         let events_iter: Vec<_> = context
             .unprocessed_event_updates
             .drain(..)
             .filter_map(|x| {
-                let res: Result<ReferenceCalendarEventPacket, rmp_serde::decode::Error> =
-                    rmp_serde::from_slice(&x.packet_payload);
+                let res = negotiation.decode::<ReferenceCalendarEventPacket>(&x);
 
                 match res {
                     Ok(event_dto) => Some(CalendarEvent::new(&event_dto.calendar_event, offset)),
@@ -262,14 +513,20 @@ impl ReferenceTime {
     }
 
     fn handle_unprocessed_event_drops(bus: &MessageBus, context: &mut ProcessingContext) {
+        let negotiation = &context.negotiation;
+
         let events_iter: Vec<_> = context
             .unprocessed_event_drops
             .drain(..)
-            .map(|x| {
-                let drop: DropCalendarEventPacket =
-                    rmp_serde::from_slice(&x.packet_payload).unwrap();
-                CalendarEventKey(drop.kind, drop.event_id)
-            })
+            .filter_map(
+                |x| match negotiation.decode::<DropCalendarEventPacket>(&x) {
+                    Ok(drop) => Some(CalendarEventKey(drop.kind, drop.event_id)),
+                    Err(err) => {
+                        error!("{}", err);
+                        None
+                    }
+                },
+            )
             .collect();
 
         for chunk in events_iter.chunks(5) {
@@ -290,21 +547,26 @@ impl ReferenceTime {
     }
 
     fn handle_unprocessed_timely_data(bus: &MessageBus, context: &mut ProcessingContext) {
+        let negotiation = &context.negotiation;
+
         let events_iter: Vec<_> = context
             .unprocessed_timely_data
             .drain(..)
-            .map(|x| {
-                let packet: ReferenceTimelyDataPacket =
-                    rmp_serde::from_slice(&x.packet_payload).unwrap();
-
-                TimelyDataRecord {
-                    linked_event_id: packet.linked_event_id,
-                    start_at_hour: packet.start_at_hour,
-                    duration: time::Duration::hours(packet.duration_hours as i64),
-                    value: packet.value,
-                    data_marker: packet.data_marker,
-                }
-            })
+            .filter_map(
+                |x| match negotiation.decode::<ReferenceTimelyDataPacket>(&x) {
+                    Ok(packet) => Some(TimelyDataRecord {
+                        linked_event_id: packet.linked_event_id,
+                        start_at_hour: packet.start_at_hour,
+                        duration: time::Duration::hours(packet.duration_hours as i64),
+                        value: packet.value,
+                        data_marker: packet.data_marker,
+                    }),
+                    Err(err) => {
+                        error!("{}", err);
+                        None
+                    }
+                },
+            )
             .collect();
 
         for chunk in events_iter.chunks(5) {
@@ -2,11 +2,12 @@ use embedded_graphics::image::Image;
 use embedded_graphics::pixelcolor::raw::RawU16;
 use embedded_graphics::pixelcolor::Rgb555;
 use embedded_graphics::text::renderer::CharacterStyle;
-use embedded_graphics::text::Text;
+use embedded_graphics::text::renderer::TextRenderer;
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
 use embedded_graphics_framebuf::FrameBuf;
 use embedded_icon::mdi::size24px;
 use enumflags2::BitFlags;
-use time::{Duration, OffsetDateTime};
+use time::{Date, Duration, OffsetDateTime, Time};
 
 use time::macros::format_description;
 
@@ -19,12 +20,16 @@ use tokio::sync::mpsc::{channel, Receiver, Sender};
 use u8g2_fonts::U8g2TextStyle;
 
 use crate::calendar::{self, CalendarEvent, CalendarEventKey};
-use crate::calendar::{CalendarEventIcon, TimelyDataRecord};
+use crate::calendar::{CalendarEventIcon, TimelyDataMarker, TimelyDataRecord};
 use crate::commands::Commands;
 use crate::display_interface::{ClockDisplayInterface, LayerType, RenderMode};
+use crate::domain::TouchPosition;
 use crate::events::Events;
 use crate::fasttrack::FastTrackRtcData;
 use crate::message_bus::{BusHandler, BusSender, MessageBus};
+use crate::persistence::{PersistenceUnit, PersistenceUnitKind};
+use crate::solar::{below_horizon, Horizon, SolarDay};
+use crate::weather::CurrentWeather;
 use embedded_graphics::primitives::{PrimitiveStyle, StyledDrawable};
 use embedded_graphics::{prelude::*, primitives};
 use std::collections::{BTreeSet, HashMap, HashSet};
@@ -32,15 +37,83 @@ use std::f32::consts::PI;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
+use super::calendar_module::{MAX_LANES, OVERFLOW_LANE};
 use super::fonts_set::FontSet;
-use super::graphics::Graphics;
+use super::graphics::{Graphics, RoundTextLayout, ROUND_TEXT_OVERFLOW};
 use super::icon_set::IconSet;
+use super::layout::{Face, FontRole, WatchfaceLayout, Widget, WidgetKind};
+use super::navigation::{Navigation, Screen, ScreenStack};
 use super::relative::{RelativeCoordinate, RelativeSize};
 use super::renderer_icons::render_battery_level_icon;
 use super::renderer_icons::render_event_icon;
+use super::round_text::{CircleBlock, WrappedLine};
 
 pub const HALF_DAY: Duration = Duration::hours(12);
 
+// outside of the outer lane, right at the edge of the dial
+const PRECIPITATION_BAND_DIAMETER: u16 = 980;
+const PRECIPITATION_BAND_THICKNESS: u16 = 16;
+
+// in the gap between the two innermost lanes
+const SPARKLINE_INNER_RADIUS: u16 = 312;
+const SPARKLINE_OUTER_RADIUS: u16 = 340;
+const SPARKLINE_THICKNESS: u16 = 6;
+
+// between the outer lane and the precipitation band
+const NIGHT_BAND_DIAMETER: u16 = 958;
+const NIGHT_BAND_THICKNESS: u16 = 8;
+const SUN_TICK_LENGTH: u16 = 48;
+
+// rgb565
+const TWILIGHT_COLOR: u32 = 0x3193;
+const NIGHT_COLOR: u32 = 0x1008;
+const SUN_TICK_COLOR: u32 = 0xFE60;
+
+// length from the center and width, the hands stay inside the innermost lane
+const HOUR_HAND: (u16, u16) = (180, 28);
+const MINUTE_HAND: (u16, u16) = (270, 18);
+const SECOND_HAND: (u16, u16) = (285, 7);
+const SECOND_HAND_TAIL: u16 = 60;
+const HANDS_CAP_DIAMETER: u16 = 36;
+
+// screens other than the watchface are rows of text under a title
+const SCREEN_TITLE_Y: u16 = 200;
+const SCREEN_FIRST_ROW_Y: u16 = 330;
+const SCREEN_ROW_HEIGHT: u16 = 120;
+
+// the agenda starts near the top of the glass and keeps a margin from its edge
+const AGENDA_TOP: u16 = 100;
+const AGENDA_PADDING: u16 = 30;
+const AGENDA_TITLE_LINES: usize = 2;
+
+const DETAILS_TITLE_LINES: usize = 2;
+const DETAILS_DESCRIPTION_LINES: usize = 3;
+
+const SETTINGS_FACES: [(&str, Face); 3] = [
+    ("Digital", Face::Digital),
+    ("Analog", Face::Analog { second_hand: false }),
+    ("Analog, seconds", Face::Analog { second_hand: true }),
+];
+
+// upper bound of each step and its rgb565 color, mm per hour
+const PRECIPITATION_SCALE: [(f32, u32); 4] = [
+    (0.5, 0x867F),
+    (2.5, 0x1C9F),
+    (7.5, 0x001F),
+    (f32::MAX, 0xA11E),
+];
+
+const MIN_PRECIPITATION: f32 = 0.1;
+
+// upper bound of each step and its rgb565 color, degrees celsius
+const TEMPERATURE_SCALE: [(f32, u32); 5] = [
+    (0.0, 0x64BD),
+    (10.0, 0x07FF),
+    (20.0, 0xAFE5),
+    (30.0, 0xFD20),
+    (f32::MAX, 0xF800),
+];
+
 pub struct Renderer<TDisplay, TFontSet: FontSet, TIconSet: IconSet> {
     _inner: PhantomData<TDisplay>,
     _font_set: PhantomData<TFontSet>,
@@ -50,6 +123,7 @@ pub struct Renderer<TDisplay, TFontSet: FontSet, TIconSet: IconSet> {
 struct Context {
     tx: Sender<Events>,
     pause: bool,
+    face_restored: bool,
 }
 
 #[derive(Debug)]
@@ -65,8 +139,11 @@ struct ViewModel {
     battery_level: Option<u16>,
     ble_connected: Option<bool>,
     temperature: Option<i32>,
+    weather_now: Option<CurrentWeather>,
+    pairing_pin: Option<u32>,
     calendar_events: BTreeSet<CalendarEvent>,
     timely_data: HashMap<i32, Vec<TimelyDataRecord>>,
+    solar_days: Vec<SolarDay>,
 
     force_render_events: bool,
 
@@ -76,6 +153,23 @@ struct ViewModel {
 
     alarm_counter: i16,
     gesture: u8,
+
+    face: Face,
+    // anything but a new second since the last full render of the clock layer
+    clock_dirty: bool,
+    second_hand: Option<Angle>,
+
+    screens: ScreenStack,
+    agenda_offset: usize,
+    // sent on the bus once the change is applied
+    commands: Vec<Commands>,
+}
+
+// where a time lands on the dial, the digital face keeps now at the top, the analog one is a clock
+#[derive(Clone, Copy)]
+struct Dial {
+    now: OffsetDateTime,
+    origin: OffsetDateTime,
 }
 
 #[derive(Debug)]
@@ -83,6 +177,18 @@ pub struct TimeViewModel {
     pub time: Option<OffsetDateTime>,
 }
 
+#[derive(Debug, Clone, Copy)]
+enum AgendaEntry<'a> {
+    Day(Date),
+    Event(&'a CalendarEvent),
+}
+
+// an entry of the agenda with its lines placed on the screen, the event header comes first
+struct AgendaRow<'a> {
+    entry: AgendaEntry<'a>,
+    lines: Vec<WrappedLine>,
+}
+
 struct EventTagStyle<TColor> {
     icon: CalendarEventIcon,
     event_tag_size: RelativeSize,
@@ -116,11 +222,29 @@ impl<TColor> EventTagStyle<TColor> {
 impl<TDisplay, TFontSet: FontSet, TIconSet: IconSet> BusHandler<Context>
     for Renderer<TDisplay, TFontSet, TIconSet>
 {
-    async fn event_handler(_bus: &BusSender, context: &mut Context, event: Events) {
+    async fn event_handler(bus: &BusSender, context: &mut Context, event: Events) {
         if context.pause && matches!(event, Events::TimeNow(_)) {
             return;
         }
 
+        if let Events::Restored(unit) = &event {
+            if unit.kind == PersistenceUnitKind::Watchface && !context.face_restored {
+                context.face_restored = true;
+
+                match unit.clone().deserialize::<Face>().await {
+                    Ok(face) => context.tx.send(Events::FaceSelected(face)).await.unwrap(),
+                    Err(error) => info!("face not restored, {}", error),
+                }
+            }
+
+            return;
+        }
+
+        // the persister starts after the first render, asked again until it answers
+        if !context.face_restored && matches!(event, Events::TimeNow(_)) {
+            bus.send_cmd(Commands::Restore(PersistenceUnitKind::Watchface));
+        }
+
         if !Self::is_renderable(&event) {
             return;
         }
@@ -129,8 +253,20 @@ impl<TDisplay, TFontSet: FontSet, TIconSet: IconSet> BusHandler<Context>
         context.tx.send(event).await.unwrap();
     }
 
-    async fn command_handler(_bus: &BusSender, context: &mut Context, command: Commands) {
+    async fn command_handler(bus: &BusSender, context: &mut Context, command: Commands) {
         match command {
+            Commands::SetFace(face) => {
+                bus.send_cmd(Commands::Persist(PersistenceUnit::new(
+                    PersistenceUnitKind::Watchface,
+                    &face,
+                )));
+
+                context.face_restored = true;
+                context.tx.send(Events::FaceSelected(face)).await.unwrap();
+            }
+            Commands::Navigate(navigation) => {
+                context.tx.send(Events::Navigate(navigation)).await.unwrap();
+            }
             Commands::PauseRendering => {
                 context.pause = true;
             }
@@ -150,6 +286,7 @@ impl<TDisplay, TFontSet: FontSet, TIconSet: IconSet> Renderer<TDisplay, TFontSet
         match event {
             Events::TimeNow(_)
             | Events::Temperature(_)
+            | Events::WeatherNow(_)
             | Events::BatteryLevel(_)
             | Events::Charging(_)
             | Events::BleClientConnected
@@ -161,7 +298,12 @@ impl<TDisplay, TFontSet: FontSet, TIconSet: IconSet> Renderer<TDisplay, TFontSet
             | Events::AccelerometerInterrupt(_)
             | Events::RtcAlarmInterrupt(_)
             | Events::Key1Press
-            | Events::EventTimelyData(_) => {
+            | Events::EventTimelyData(_)
+            | Events::SolarDays(_)
+            | Events::FaceSelected(_)
+            | Events::Navigate(_)
+            | Events::PairingPin(_)
+            | Events::Paired(_) => {
                 return true;
             }
             _ => false,
@@ -176,15 +318,28 @@ where
     TIconSet: IconSet,
 {
     pub async fn start(bus: MessageBus, display: TDisplay, rtc_data: FastTrackRtcData) {
-        info!("starting...");
+        Self::start_with_layout(bus, display, rtc_data, WatchfaceLayout::default()).await;
+    }
+
+    pub async fn start_with_layout(
+        bus: MessageBus,
+        display: TDisplay,
+        rtc_data: FastTrackRtcData,
+        layout: WatchfaceLayout,
+    ) {
+        info!("starting with {} face...", layout.name);
 
         let (tx, rx) = channel::<Events>(16);
 
-        let context = Context { tx, pause: false };
+        let context = Context {
+            tx,
+            pause: false,
+            face_restored: false,
+        };
 
         let message_bus = bus.clone();
         let render_loop_task = tokio::task::spawn_blocking(move || {
-            Self::render_loop(message_bus, rx, display, rtc_data);
+            Self::render_loop(message_bus, rx, display, rtc_data, layout);
         });
 
         MessageBus::handle::<Context, Self>(bus, context).await;
@@ -196,15 +351,284 @@ where
         info!("done.");
     }
 
+    // the clock and date widgets of the default face, without the rest of the view model
     pub fn render_datetime(frame: &mut TDisplay::FrameBuffer<'_>, vm: &TimeViewModel) {
-        if vm.time.is_none() {
+        let Some(time) = vm.time else {
             return;
-        }
+        };
 
         info!("rendering datetime {:?}", vm.time);
 
-        let bounds = Self::render_time(frame, vm);
-        Self::render_day(frame, vm, &bounds);
+        let layout = WatchfaceLayout::default();
+        let mut drawn = HashMap::new();
+
+        for widget in layout.widgets.iter() {
+            if !matches!(widget.kind, WidgetKind::Clock | WidgetKind::Date) {
+                continue;
+            }
+
+            let followed = widget.slot.follows.and_then(|x| drawn.get(&x));
+            let position = widget.slot.position(TDisplay::FRAME_BUFFER_SIDE, followed);
+
+            let bounds = Self::render_datetime_widget(frame, &time, widget, position);
+            drawn.insert(widget.kind, bounds);
+        }
+    }
+
+    fn render_widgets(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &mut ViewModel,
+        layout: &WatchfaceLayout,
+        layer: LayerType,
+    ) {
+        let visible = layout.visible(layer, |kind| Self::has_content(vm, kind));
+
+        let mut drawn: HashMap<WidgetKind, primitives::Rectangle> = HashMap::new();
+
+        for widget in visible {
+            let followed = widget.slot.follows.and_then(|x| drawn.get(&x));
+            let position = widget.slot.position(TDisplay::FRAME_BUFFER_SIDE, followed);
+
+            let bounds = Self::render_widget(frame, vm, widget, position);
+            drawn.insert(widget.kind, bounds);
+        }
+    }
+
+    fn has_content(vm: &ViewModel, kind: WidgetKind) -> bool {
+        match kind {
+            WidgetKind::Clock
+            | WidgetKind::Date
+            | WidgetKind::EventArcs
+            | WidgetKind::AnalogHands => vm.time_vm.time.is_some(),
+            WidgetKind::Battery => vm.is_charging == Some(true) || vm.battery_level.is_some(),
+            WidgetKind::Ble => vm.ble_connected == Some(true),
+            WidgetKind::Temperature => vm.temperature.is_some(),
+            WidgetKind::NextEventCountdown => Self::try_get_duration_to_next(vm).is_some(),
+            WidgetKind::Alarm => vm.alarm_counter > 0,
+            WidgetKind::PairingPin => vm.pairing_pin.is_some(),
+        }
+    }
+
+    // what the widget drew, the slot when it is not text
+    fn render_widget(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &mut ViewModel,
+        widget: &Widget,
+        position: Point,
+    ) -> primitives::Rectangle {
+        let slot_bounds = widget.slot.bounds(TDisplay::FRAME_BUFFER_SIDE);
+
+        match widget.kind {
+            WidgetKind::Clock | WidgetKind::Date => {
+                let time = vm.time_vm.time.unwrap();
+
+                Self::render_datetime_widget(frame, &time, widget, position)
+            }
+            WidgetKind::Battery => {
+                Self::render_battery_level(frame, vm, position);
+                slot_bounds
+            }
+            WidgetKind::Ble => {
+                Self::render_ble_connected(frame, position);
+                slot_bounds
+            }
+            WidgetKind::Temperature => Self::render_temperature(frame, vm, widget, position),
+            WidgetKind::NextEventCountdown => {
+                let duration = Self::try_get_duration_to_next(vm).unwrap();
+
+                Self::render_duration_to_next(frame, duration, widget, position)
+            }
+            WidgetKind::Alarm => {
+                Self::render_alarm(frame, position);
+                vm.alarm_counter -= 1;
+                slot_bounds
+            }
+            WidgetKind::PairingPin => Self::render_pairing_pin(frame, vm, widget, position),
+            WidgetKind::EventArcs => {
+                Self::render_events(frame, vm);
+                slot_bounds
+            }
+            WidgetKind::AnalogHands => {
+                Self::render_hands(frame, vm);
+                slot_bounds
+            }
+        }
+    }
+
+    // an analog face only moves its second hand while nothing but the seconds change
+    fn is_second_hand_only(vm: &ViewModel) -> bool {
+        matches!(vm.mode, VisualMode::Normal)
+            && matches!(vm.face, Face::Analog { .. })
+            && !vm.clock_dirty
+    }
+
+    // erases the previous second hand and draws again whatever it was covering
+    fn render_second_hand_update(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &mut ViewModel,
+        layout: &WatchfaceLayout,
+    ) {
+        let Some(previous) = vm.second_hand else {
+            return;
+        };
+
+        let erased =
+            Self::render_second_hand(frame, previous, TDisplay::ColorModel::default(), 2.0);
+
+        let crossed: Vec<&Widget> = layout
+            .visible(LayerType::Clock, |kind| Self::has_content(vm, kind))
+            .into_iter()
+            .filter(|x| {
+                matches!(x.kind, WidgetKind::AnalogHands)
+                    || !x
+                        .slot
+                        .bounds(TDisplay::FRAME_BUFFER_SIDE)
+                        .intersection(&erased)
+                        .is_zero_sized()
+            })
+            .collect();
+
+        for widget in crossed {
+            let position = widget.slot.position(TDisplay::FRAME_BUFFER_SIDE, None);
+
+            Self::render_widget(frame, vm, widget, position);
+        }
+    }
+
+    fn render_hands(frame: &mut TDisplay::FrameBuffer<'_>, vm: &mut ViewModel) {
+        let time = vm.time_vm.time.unwrap();
+
+        let minutes = time.minute() as f32;
+        let hours = (time.hour() % 12) as f32 + minutes / 60.0;
+
+        for (turn, (length, width)) in [(hours / 12.0, HOUR_HAND), (minutes / 60.0, MINUTE_HAND)] {
+            Self::render_hand(
+                frame,
+                Angle::from_radians(turn * PI * 2.0),
+                0,
+                length,
+                width,
+                TDisplay::ColorModel::WHITE,
+                0.0,
+            );
+        }
+
+        if matches!(vm.face, Face::Analog { second_hand: true }) {
+            let angle = Angle::from_radians(time.second() as f32 / 60.0 * PI * 2.0);
+
+            Self::render_second_hand(frame, angle, TDisplay::ColorModel::RED, 0.0);
+            vm.second_hand = Some(angle);
+        }
+
+        let cap =
+            RelativeSize::from(HANDS_CAP_DIAMETER).to_absolute_u32(TDisplay::FRAME_BUFFER_SIDE);
+
+        primitives::Circle::with_center(
+            Self::get_center_point().to_absolute(TDisplay::FRAME_BUFFER_SIDE),
+            cap,
+        )
+        .draw_styled(
+            &PrimitiveStyle::with_fill(TDisplay::ColorModel::WHITE),
+            frame,
+        )
+        .unwrap();
+    }
+
+    fn render_second_hand(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        angle: Angle,
+        color: TDisplay::ColorModel,
+        extra_width: f32,
+    ) -> primitives::Rectangle {
+        let (length, width) = SECOND_HAND;
+
+        Self::render_hand(
+            frame,
+            angle,
+            SECOND_HAND_TAIL,
+            length,
+            width,
+            color,
+            extra_width,
+        )
+    }
+
+    // from the tail behind the center to the tip, what the line covered is returned
+    fn render_hand(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        angle: Angle,
+        tail: u16,
+        length: u16,
+        width: u16,
+        color: TDisplay::ColorModel,
+        extra_width: f32,
+    ) -> primitives::Rectangle {
+        let side = TDisplay::FRAME_BUFFER_SIDE;
+        let center = side as f32 / 2.0;
+
+        let (sin, cos) = angle.to_radians().sin_cos();
+
+        let point = |radius: f32| (center + radius * sin, center - radius * cos);
+
+        let from = point(-RelativeSize::from(tail).as_f32() * side as f32 / 1000.0);
+        let to = point(RelativeSize::from(length).as_f32() * side as f32 / 1000.0);
+        let width = RelativeSize::from(width).as_f32() * side as f32 / 1000.0 + extra_width;
+
+        Graphics::<TDisplay>::thick_line_aa(frame, from, to, width, color);
+
+        let margin = (width / 2.0 + 1.0).ceil() as i32;
+
+        primitives::Rectangle::with_corners(
+            Point::new(
+                from.0.min(to.0) as i32 - margin,
+                from.1.min(to.1) as i32 - margin,
+            ),
+            Point::new(
+                from.0.max(to.0) as i32 + margin,
+                from.1.max(to.1) as i32 + margin,
+            ),
+        )
+    }
+
+    fn render_text(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        text: &str,
+        widget: &Widget,
+        position: Point,
+        color: TDisplay::ColorModel,
+    ) -> primitives::Rectangle {
+        let alignment = widget.slot.anchor.text_alignment();
+
+        match widget.font.unwrap_or(FontRole::Temperature) {
+            FontRole::Clock => Graphics::<TDisplay>::text_aligned(
+                frame,
+                text,
+                position,
+                U8g2TextStyle::new(TFontSet::get_clock_font(), color),
+                alignment,
+            ),
+            FontRole::Day => Graphics::<TDisplay>::text_aligned(
+                frame,
+                text,
+                position,
+                U8g2TextStyle::new(TFontSet::get_day_font(), color),
+                alignment,
+            ),
+            FontRole::Temperature => Graphics::<TDisplay>::text_aligned(
+                frame,
+                text,
+                position,
+                U8g2TextStyle::new(TFontSet::get_temperature_font(), color),
+                alignment,
+            ),
+            FontRole::EventDetails => Graphics::<TDisplay>::text_aligned(
+                frame,
+                text,
+                position,
+                U8g2TextStyle::new(TFontSet::get_event_details_font(), color),
+                alignment,
+            ),
+        }
     }
 
     fn render_clock_face_marks(
@@ -250,25 +674,27 @@ where
         }
     }
 
-    fn render_time(
+    fn render_datetime_widget(
         frame: &mut TDisplay::FrameBuffer<'_>,
-        vm: &TimeViewModel,
+        time: &OffsetDateTime,
+        widget: &Widget,
+        position: Point,
     ) -> primitives::Rectangle {
-        let time_template = format_description!(version = 2, "[hour repr:24]:[minute]:[second]");
-
-        let time_text_style =
-            U8g2TextStyle::new(TFontSet::get_clock_font(), TDisplay::ColorModel::WHITE);
-
-        let time_as_text = vm.time.unwrap().format(&time_template).unwrap();
-
-        let position = Self::get_center_point();
+        let text = if matches!(widget.kind, WidgetKind::Date) {
+            time.format(&format_description!(version = 2, "[weekday repr:short]"))
+        } else {
+            time.format(&format_description!(
+                version = 2,
+                "[hour repr:24]:[minute]:[second]"
+            ))
+        };
 
-        Graphics::<TDisplay>::text_aligned(
+        Self::render_text(
             frame,
-            &time_as_text,
-            position.to_absolute(TDisplay::FRAME_BUFFER_SIDE),
-            time_text_style,
-            embedded_graphics::text::Alignment::Center,
+            &text.unwrap(),
+            widget,
+            position,
+            TDisplay::ColorModel::WHITE,
         )
     }
 
@@ -276,31 +702,6 @@ where
         (1000 / 2, 1000 / 2).into()
     }
 
-    fn render_day(
-        frame: &mut TDisplay::FrameBuffer<'_>,
-        vm: &TimeViewModel,
-        time_text_bounds: &primitives::Rectangle,
-    ) {
-        let day_template = format_description!(version = 2, "[weekday repr:short]");
-
-        let day_text_style = U8g2TextStyle::new(TFontSet::get_day_font(), RgbColor::WHITE);
-
-        let day_as_text = vm.time.unwrap().format(&day_template).unwrap();
-
-        let half_width = RelativeSize::from(time_text_bounds.size.width) / 2u32;
-
-        let top_left =
-            RelativeCoordinate::new(half_width + RelativeSize::from(500u16), 588u16.into());
-
-        Graphics::<TDisplay>::text_aligned(
-            frame,
-            &day_as_text,
-            top_left.to_absolute(TDisplay::FRAME_BUFFER_SIDE),
-            day_text_style,
-            embedded_graphics::text::Alignment::Right,
-        );
-    }
-
     fn render_radial_line<C>(
         frame: &mut TDisplay::FrameBuffer<'_>,
         angle: Angle,
@@ -333,59 +734,42 @@ where
         p2
     }
 
-    pub fn render_temperature(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel) {
-        if vm.temperature.is_none() {
-            return;
-        }
-
+    fn render_temperature(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &ViewModel,
+        widget: &Widget,
+        position: Point,
+    ) -> primitives::Rectangle {
         let text = format!(
             "{}{}",
             vm.temperature.unwrap(),
             char::from_u32(0x00b0).unwrap(),
         );
 
-        let style_time = U8g2TextStyle::new(TFontSet::get_temperature_font(), RgbColor::WHITE);
+        Self::render_text(frame, &text, widget, position, TDisplay::ColorModel::WHITE)
+    }
 
-        let point = Self::get_center_point() + (0, 84).into();
+    fn render_pairing_pin(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &ViewModel,
+        widget: &Widget,
+        position: Point,
+    ) -> primitives::Rectangle {
+        let text = format!("{:06}", vm.pairing_pin.unwrap());
 
-        Graphics::<TDisplay>::text_aligned(
-            frame,
-            &text,
-            point.to_absolute(TDisplay::FRAME_BUFFER_SIDE),
-            style_time,
-            embedded_graphics::text::Alignment::Center,
-        );
+        Self::render_text(frame, &text, widget, position, TDisplay::ColorModel::WHITE)
     }
 
-    pub fn render_alarm(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel) {
-        let point = Self::get_center_point() + (0, 105).into();
-
+    fn render_alarm(frame: &mut TDisplay::FrameBuffer<'_>, position: Point) {
         render_event_icon::<TDisplay, TIconSet>(
             frame,
             CalendarEventIcon::Alarm,
-            point.to_absolute(TDisplay::FRAME_BUFFER_SIDE),
+            position,
             12,
             RgbColor::RED,
         );
     }
 
-    pub fn try_render_next_event_alert(
-        frame: &mut TDisplay::FrameBuffer<'_>,
-        vm: &ViewModel,
-    ) -> bool {
-        let duration_to_next = Self::try_get_duration_to_next(vm);
-
-        if duration_to_next.is_none() {
-            return false;
-        }
-
-        let now = vm.time_vm.time.unwrap();
-
-        Self::render_duration_to_next(frame, &now, duration_to_next.unwrap());
-
-        return true;
-    }
-
     fn try_get_duration_to_next(vm: &ViewModel) -> Option<Duration> {
         if vm.time_vm.time.is_none() {
             return None;
@@ -425,17 +809,16 @@ where
 
     fn render_duration_to_next(
         frame: &mut TDisplay::FrameBuffer<'_>,
-        now: &OffsetDateTime,
         duration: Duration,
-    ) {
+        widget: &Widget,
+        position: Point,
+    ) -> primitives::Rectangle {
         let color = if duration < Duration::minutes(5) {
             RgbColor::RED
         } else {
             RgbColor::WHITE
         };
 
-        let text_style = U8g2TextStyle::new(TFontSet::get_temperature_font(), color);
-
         let text = format!(
             "{}'{}{}",
             duration.whole_minutes(),
@@ -443,63 +826,36 @@ where
             char::from_u32(0xe220 + 7).unwrap()
         );
 
-        let top_left = Self::get_center_point() + (0, 84).into();
-
-        Graphics::<TDisplay>::text_aligned(
-            frame,
-            &text,
-            top_left.to_absolute(TDisplay::FRAME_BUFFER_SIDE),
-            text_style,
-            embedded_graphics::text::Alignment::Center,
-        );
+        Self::render_text(frame, &text, widget, position, color)
     }
 
-    pub fn render_battery_level(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel) {
-        let point = RelativeCoordinate::from((504u16, 756u16)); //Point::new(120, 180);
-        let absolute_point = point.to_absolute(TDisplay::FRAME_BUFFER_SIDE);
-
-        if let Some(is_charging) = vm.is_charging {
-            if is_charging {
-                Graphics::<TDisplay>::icon_center(
-                    frame,
-                    absolute_point,
-                    &size24px::BatteryCharging::new(TDisplay::ColorModel::WHITE),
-                );
-
-                return;
-            }
-        }
+    fn render_battery_level(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &ViewModel,
+        position: Point,
+    ) {
+        if vm.is_charging == Some(true) {
+            Graphics::<TDisplay>::icon_center(
+                frame,
+                position,
+                &size24px::BatteryCharging::new(TDisplay::ColorModel::WHITE),
+            );
 
-        if vm.battery_level.is_none() {
             return;
         }
 
         render_battery_level_icon::<TDisplay, TIconSet>(
             frame,
             vm.battery_level.unwrap(),
-            absolute_point,
+            position,
             TDisplay::ColorModel::WHITE,
         );
     }
 
-    pub fn render_ble_connected(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel) {
-        if vm.ble_connected.is_none() {
-            return;
-        }
-
-        let is_ble_connected = vm.ble_connected.unwrap();
-        if is_ble_connected {
-            let icon = TIconSet::get_bluetooth_icon(TDisplay::ColorModel::WHITE);
+    fn render_ble_connected(frame: &mut TDisplay::FrameBuffer<'_>, position: Point) {
+        let icon = TIconSet::get_bluetooth_icon(TDisplay::ColorModel::WHITE);
 
-            let center = Self::get_center_point();
-            let coord = center - (252, 0).into() + (0, 42).into();
-
-            Graphics::<TDisplay>::icon(
-                frame,
-                coord.to_absolute(TDisplay::FRAME_BUFFER_SIDE),
-                &icon,
-            );
-        };
+        Graphics::<TDisplay>::icon(frame, position, &icon);
     }
 
     fn render_loop(
@@ -507,6 +863,7 @@ where
         mut rx: Receiver<Events>,
         display_param: TDisplay,
         rtc_data: FastTrackRtcData,
+        layout: WatchfaceLayout,
     ) {
         info!("renderer loop started");
 
@@ -519,6 +876,8 @@ where
             is_charging: None,
             ble_connected: None,
             temperature: None,
+            weather_now: None,
+            pairing_pin: None,
             calendar_events: BTreeSet::new(),
             force_render_events: false,
             mode: VisualMode::Normal,
@@ -527,8 +886,17 @@ where
             alarm_counter: if rtc_data.alarm_status { 10 } else { 0 },
             gesture: 0,
             timely_data: HashMap::new(),
+            solar_days: vec![],
+            face: Face::Digital,
+            clock_dirty: true,
+            second_hand: None,
+            screens: ScreenStack::new(),
+            agenda_offset: 0,
+            commands: vec![],
         };
 
+        let analog_layout = WatchfaceLayout::analog();
+
         let mut static_rendered = false;
 
         loop {
@@ -551,11 +919,25 @@ where
                             render_layers_mask |= LayerType::Events;
                         }
 
+                        let face_layout = match state.face {
+                            Face::Digital => &layout,
+                            Face::Analog { .. } => &analog_layout,
+                        };
+
+                        // other screens cover the dial
+                        let merge_layers_mask = match state.screens.current() {
+                            Screen::Watchface => {
+                                LayerType::Static | LayerType::Clock | LayerType::Events
+                            }
+                            _ => LayerType::Clock.into(),
+                        };
+
                         Self::render(
                             &mut display,
                             &mut state,
+                            face_layout,
                             render_layers_mask,
-                            LayerType::Static | LayerType::Clock | LayerType::Events,
+                            merge_layers_mask,
                         );
 
                         if !state.is_past_first_frame {
@@ -579,11 +961,16 @@ where
             debug!("handling event {:?}", event);
 
             Self::try_apply_change(event, &mut state);
+
+            for command in state.commands.drain(..) {
+                bus.send_cmd(command);
+            }
         }
 
         Self::render(
             &mut display,
             &mut state,
+            &layout,
             LayerType::Static.into(),
             LayerType::Static.into(),
         );
@@ -594,6 +981,14 @@ where
     fn try_apply_change(event: Events, view_model: &mut ViewModel) -> bool {
         let mut state_changed = true;
 
+        let only_seconds = match (&event, view_model.time_vm.time) {
+            (Events::TimeNow(now), Some(time)) => {
+                now.date() == time.date()
+                    && (now.hour(), now.minute()) == (time.hour(), time.minute())
+            }
+            _ => false,
+        };
+
         match event {
             Events::TimeNow(now) => {
                 if let Some(time) = view_model.time_vm.time {
@@ -607,6 +1002,10 @@ where
             Events::Temperature(tmpr) => {
                 view_model.temperature = Some(tmpr);
             }
+            Events::WeatherNow(current) => {
+                view_model.temperature = Some(current.temperature.round() as i32);
+                view_model.weather_now = Some(current);
+            }
             Events::BatteryLevel(level) => {
                 view_model.battery_level = Some(level);
             }
@@ -631,6 +1030,14 @@ where
             Events::DropCalendarEventsBatch(batch) => {
                 drop_events(view_model, batch);
             }
+            // the button pages through the agenda and leaves it after the last entry
+            Events::Key1Press if matches!(view_model.screens.current(), Screen::Agenda) => {
+                if !Self::scroll_agenda(view_model, 1) {
+                    view_model.screens.home();
+                    view_model.agenda_offset = 0;
+                }
+            }
+            Events::Key1Press if view_model.screens.home() => {}
             Events::Key1Press => {
                 view_model.mode = if matches!(view_model.mode, VisualMode::Normal) {
                     VisualMode::Details
@@ -638,6 +1045,9 @@ where
                     VisualMode::Normal
                 }
             }
+            Events::Navigate(navigation) => {
+                Self::navigate(view_model, navigation);
+            }
             Events::Reminder(_reminder) => {
                 view_model.alarm_counter = 10;
             }
@@ -647,6 +1057,21 @@ where
             Events::EventTimelyData(data) => {
                 append_timely_data(view_model, data);
             }
+            Events::SolarDays(days) => {
+                view_model.solar_days = days;
+                view_model.force_render_events = true;
+            }
+            Events::FaceSelected(face) => {
+                view_model.face = face;
+                view_model.second_hand = None;
+                view_model.force_render_events = true;
+            }
+            Events::PairingPin(pin) => {
+                view_model.pairing_pin = Some(pin);
+            }
+            Events::Paired(_) => {
+                view_model.pairing_pin = None;
+            }
             _ => {
                 state_changed = false;
             }
@@ -656,12 +1081,24 @@ where
             view_model.calendar_events.retain(|x| x.end >= now);
         }
 
+        // the event on screen is over or was dropped
+        if let Screen::EventDetail(key) = view_model.screens.current() {
+            if !view_model.calendar_events.iter().any(|x| x.key() == *key) {
+                view_model.screens.back();
+            }
+        }
+
+        if state_changed && !only_seconds {
+            view_model.clock_dirty = true;
+        }
+
         return state_changed;
     }
 
     fn render(
         display: &mut TDisplay,
         vm: &mut ViewModel,
+        layout: &WatchfaceLayout,
         render_layers_mask: BitFlags<LayerType>,
         merge_layers_mask: BitFlags<LayerType>,
     ) {
@@ -700,20 +1137,30 @@ where
             });
         }
 
-        if render_layers_mask.contains(LayerType::Clock) {
+        let screen = vm.screens.current().clone();
+
+        if !matches!(screen, Screen::Watchface) {
+            // nothing on the screens moves with the seconds
+            if render_layers_mask.contains(LayerType::Clock) && vm.clock_dirty {
+                vm.clock_dirty = false;
+
+                display.render(LayerType::Clock, RenderMode::Invalidate, |mut frame| {
+                    Self::render_screen(&mut frame, vm, &screen);
+                    frame
+                });
+            }
+        } else if render_layers_mask.contains(LayerType::Clock) && Self::is_second_hand_only(vm) {
+            display.render(LayerType::Clock, RenderMode::Ammend, |mut frame| {
+                Self::render_second_hand_update(&mut frame, vm, layout);
+                frame
+            });
+        } else if render_layers_mask.contains(LayerType::Clock) {
+            vm.clock_dirty = false;
+
             display.render(LayerType::Clock, RenderMode::Invalidate, |mut frame| {
                 match vm.mode {
                     VisualMode::Normal => {
-                        Self::render_battery_level(&mut frame, vm);
-                        Self::render_ble_connected(&mut frame, vm);
-                        Self::render_datetime(&mut frame, &vm.time_vm);
-
-                        if vm.alarm_counter > 0 {
-                            Self::render_alarm(&mut frame, vm);
-                            vm.alarm_counter -= 1;
-                        } else if !Self::try_render_next_event_alert(&mut frame, vm) {
-                            Self::render_temperature(&mut frame, vm);
-                        }
+                        Self::render_widgets(&mut frame, vm, layout, LayerType::Clock);
                     }
                     VisualMode::Details => {
                         //Self::render_debug_info(&mut frame, vm);
@@ -726,8 +1173,15 @@ where
         }
 
         if render_layers_mask.contains(LayerType::Events) {
-            display.render(LayerType::Events, RenderMode::Ammend, |mut frame| {
-                Self::render_events(&mut frame, vm);
+            // only what the layer showed before and what it shows now gets flushed
+            let mode = if vm.force_render_events && vm.time_vm.time.is_some() {
+                RenderMode::Invalidate
+            } else {
+                RenderMode::Ammend
+            };
+
+            display.render(LayerType::Events, mode, |mut frame| {
+                Self::render_widgets(&mut frame, vm, layout, LayerType::Events);
                 frame
             });
         }
@@ -735,18 +1189,462 @@ where
         display.commit(merge_layers_mask);
     }
 
-    fn render_current_events_details(frame: &mut TDisplay::FrameBuffer<'_>, vm: &mut ViewModel) {
-        let now = vm.time_vm.time.unwrap();
+    // screen to screen moves are up to the stack, the rest is about the current screen
+    fn navigate(vm: &mut ViewModel, navigation: Navigation) {
+        if vm.screens.navigate(&navigation) {
+            if matches!(vm.screens.current(), Screen::Watchface) {
+                vm.agenda_offset = 0;
+            }
 
-        let current_events = vm
-            .calendar_events
-            .iter()
-            .filter(|x| x.start <= now && x.end > now);
+            return;
+        }
 
-        let mut text_style_underline = U8g2TextStyle::new(
-            TFontSet::get_event_details_font(),
-            TDisplay::ColorModel::WHITE,
-        );
+        match (vm.screens.current(), navigation) {
+            (Screen::Agenda, Navigation::Next) => {
+                Self::scroll_agenda(vm, 1);
+            }
+            (Screen::Agenda, Navigation::Previous) => {
+                Self::scroll_agenda(vm, -1);
+            }
+            (Screen::Agenda, Navigation::Select(position)) => {
+                let y = position.y - TDisplay::FRAME_BUFFER_SIDE as i32 / 2;
+                let line_height = Self::agenda_text_style().line_height() as i32;
+
+                let key = Self::agenda_layout(vm)
+                    .into_iter()
+                    .find(|row| {
+                        let top = row.lines.first().unwrap().top;
+                        let bottom = row.lines.last().unwrap().top + line_height;
+
+                        (top..bottom).contains(&y)
+                    })
+                    .and_then(|row| match row.entry {
+                        AgendaEntry::Event(event) => Some(event.key()),
+                        AgendaEntry::Day(_) => None,
+                    });
+
+                if let Some(key) = key {
+                    vm.screens.push(Screen::EventDetail(key));
+                }
+            }
+            (Screen::Settings, Navigation::Select(position)) => {
+                if let Some((_, face)) =
+                    Self::screen_row_at(position).and_then(|x| SETTINGS_FACES.get(x))
+                {
+                    vm.commands.push(Commands::SetFace(*face));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // upcoming events, the soonest first
+    fn agenda(vm: &ViewModel) -> impl Iterator<Item = &CalendarEvent> {
+        vm.calendar_events.iter().rev()
+    }
+
+    // false when already at either end
+    fn scroll_agenda(vm: &mut ViewModel, delta: isize) -> bool {
+        let count = Self::agenda_entries(vm).len();
+
+        match vm.agenda_offset.checked_add_signed(delta) {
+            Some(offset) if offset < count => {
+                vm.agenda_offset = offset;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // a day header before the first event of every day, ongoing events belong to today
+    fn agenda_entries(vm: &ViewModel) -> Vec<AgendaEntry<'_>> {
+        let now = vm.time_vm.time;
+
+        let mut entries = vec![];
+        let mut current_day = None;
+
+        for event in Self::agenda(vm) {
+            let day = Self::local_time(vm, event.start.max(now.unwrap_or(event.start))).date();
+
+            if current_day != Some(day) {
+                current_day = Some(day);
+                entries.push(AgendaEntry::Day(day));
+            }
+
+            entries.push(AgendaEntry::Event(event));
+        }
+
+        entries
+    }
+
+    fn local_time(vm: &ViewModel, time: OffsetDateTime) -> OffsetDateTime {
+        match vm.time_vm.time {
+            Some(now) => time.to_offset(now.offset()),
+            None => time,
+        }
+    }
+
+    fn agenda_text_style() -> U8g2TextStyle<TDisplay::ColorModel> {
+        U8g2TextStyle::new(
+            TFontSet::get_event_details_font(),
+            TDisplay::ColorModel::WHITE,
+        )
+    }
+
+    fn measure_text(style: &U8g2TextStyle<TDisplay::ColorModel>, text: &str) -> u32 {
+        style
+            .measure_string(text, Point::zero(), Baseline::Top)
+            .bounding_box
+            .size
+            .width
+    }
+
+    // entries from the scroll offset down for as long as they fit on the glass
+    fn agenda_layout(vm: &ViewModel) -> Vec<AgendaRow<'_>> {
+        let side = TDisplay::FRAME_BUFFER_SIDE;
+        let style = Self::agenda_text_style();
+        let line_height = style.line_height();
+
+        let mut block = CircleBlock {
+            radius: side as u32 / 2,
+            top: RelativeSize::from(AGENDA_TOP).to_absolute(side) - side as i32 / 2,
+            line_height,
+            padding: RelativeSize::from(AGENDA_PADDING).to_absolute_u32(side),
+        };
+
+        let measure = |text: &str| Self::measure_text(&style, text);
+
+        let mut rows = vec![];
+
+        for entry in Self::agenda_entries(vm).into_iter().skip(vm.agenda_offset) {
+            let lines = match entry {
+                AgendaEntry::Day(day) => {
+                    let label = day
+                        .format(&format_description!(
+                            version = 2,
+                            "[weekday repr:short] [day padding:none] [month repr:short]"
+                        ))
+                        .unwrap();
+
+                    block.wrap(&label, 1, measure)
+                }
+                AgendaEntry::Event(event) => {
+                    let range = Self::agenda_time_range(vm, event);
+
+                    // chip and icon go in front of the time range
+                    let header_width = measure(&range) + 3 * line_height;
+
+                    if header_width > block.line_width(0) {
+                        vec![]
+                    } else {
+                        let header = WrappedLine {
+                            text: range,
+                            top: block.line_top(0),
+                            width: header_width,
+                        };
+
+                        let title_block = CircleBlock {
+                            top: block.line_top(1),
+                            ..block
+                        };
+
+                        let title = title_block.wrap_with(
+                            &event.title,
+                            AGENDA_TITLE_LINES,
+                            ROUND_TEXT_OVERFLOW,
+                            measure,
+                        );
+
+                        if title.is_empty() {
+                            vec![]
+                        } else {
+                            [header].into_iter().chain(title).collect()
+                        }
+                    }
+                }
+            };
+
+            let Some(last) = lines.last() else {
+                break;
+            };
+
+            block.top = last.top + (line_height + line_height / 2) as i32;
+
+            rows.push(AgendaRow { entry, lines });
+        }
+
+        // a day header with none of its events below it says nothing
+        if matches!(
+            rows.last(),
+            Some(AgendaRow {
+                entry: AgendaEntry::Day(_),
+                ..
+            })
+        ) {
+            rows.pop();
+        }
+
+        rows
+    }
+
+    fn agenda_time_range(vm: &ViewModel, event: &CalendarEvent) -> String {
+        let time = format_description!(version = 2, "[hour repr:24]:[minute]");
+
+        format!(
+            "{} - {}",
+            Self::local_time(vm, event.start).format(&time).unwrap(),
+            Self::local_time(vm, event.end).format(&time).unwrap()
+        )
+    }
+
+    fn screen_row_at(position: TouchPosition) -> Option<usize> {
+        let y = position.y * 1000 / TDisplay::FRAME_BUFFER_SIDE as i32;
+        let top = (SCREEN_FIRST_ROW_Y - SCREEN_ROW_HEIGHT / 2) as i32;
+
+        if y < top {
+            return None;
+        }
+
+        Some(((y - top) / SCREEN_ROW_HEIGHT as i32) as usize)
+    }
+
+    fn render_screen(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel, screen: &Screen) {
+        match screen {
+            Screen::Watchface => {}
+            Screen::Agenda => Self::render_agenda(frame, vm),
+            Screen::EventDetail(key) => Self::render_event_detail(frame, vm, key),
+            Screen::Weather => Self::render_weather(frame, vm),
+            Screen::Settings => Self::render_settings(frame, vm),
+        }
+    }
+
+    fn render_screen_text(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        text: &str,
+        y: u16,
+        color: TDisplay::ColorModel,
+    ) {
+        let position = RelativeCoordinate::new(500u16.into(), y.into())
+            .to_absolute(TDisplay::FRAME_BUFFER_SIDE);
+
+        Graphics::<TDisplay>::text_aligned(
+            frame,
+            text,
+            position,
+            U8g2TextStyle::new(TFontSet::get_event_details_font(), color),
+            embedded_graphics::text::Alignment::Center,
+        );
+    }
+
+    fn render_screen_rows(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        title: &str,
+        rows: &[(String, TDisplay::ColorModel)],
+    ) {
+        Self::render_screen_text(frame, title, SCREEN_TITLE_Y, TDisplay::ColorModel::WHITE);
+
+        for (index, (text, color)) in rows.iter().enumerate() {
+            let y = SCREEN_FIRST_ROW_Y + index as u16 * SCREEN_ROW_HEIGHT;
+
+            Self::render_screen_text(frame, text, y, *color);
+        }
+    }
+
+    fn render_agenda(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel) {
+        let rows = Self::agenda_layout(vm);
+
+        if rows.is_empty() {
+            Self::render_screen_rows(
+                frame,
+                "Agenda",
+                &[("No events".to_string(), TDisplay::ColorModel::WHITE)],
+            );
+            return;
+        }
+
+        let center = Self::get_center_point().to_absolute(TDisplay::FRAME_BUFFER_SIDE);
+
+        for row in rows {
+            let (header_color, title_lines) = match row.entry {
+                AgendaEntry::Day(_) => (TDisplay::ColorModel::CYAN, &row.lines[..]),
+                AgendaEntry::Event(event) => {
+                    Self::render_agenda_event_header(frame, event, &row.lines[0], center);
+                    (TDisplay::ColorModel::WHITE, &row.lines[1..])
+                }
+            };
+
+            let style = U8g2TextStyle::new(TFontSet::get_event_details_font(), header_color);
+
+            for line in title_lines {
+                Text::with_text_style(
+                    &line.text,
+                    center + Point::new(0, line.top),
+                    style.clone(),
+                    TextStyleBuilder::new()
+                        .alignment(Alignment::Center)
+                        .baseline(Baseline::Top)
+                        .build(),
+                )
+                .draw(frame)
+                .unwrap();
+            }
+        }
+    }
+
+    // color chip, icon and time range centered together
+    fn render_agenda_event_header(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        event: &CalendarEvent,
+        line: &WrappedLine,
+        center: Point,
+    ) {
+        let line_height = Self::agenda_text_style().line_height() as i32;
+
+        let left = center.x - line.width as i32 / 2;
+        let middle = center.y + line.top + line_height / 2;
+
+        let color = if event.color == 0 {
+            TDisplay::ColorModel::WHITE
+        } else {
+            TDisplay::ColorModel::from(RawU16::from_u32(event.color))
+        };
+
+        primitives::Circle::with_center(
+            Point::new(left + line_height / 4, middle),
+            (line_height / 2) as u32,
+        )
+        .draw_styled(&PrimitiveStyle::with_fill(color), frame)
+        .unwrap();
+
+        render_event_icon::<TDisplay, TIconSet>(
+            frame,
+            event.icon,
+            Point::new(left + line_height, middle),
+            0,
+            TDisplay::ColorModel::WHITE,
+        );
+
+        Text::with_baseline(
+            &line.text,
+            Point::new(left + 2 * line_height, center.y + line.top),
+            Self::agenda_text_style(),
+            Baseline::Top,
+        )
+        .draw(frame)
+        .unwrap();
+    }
+
+    fn render_event_detail(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &ViewModel,
+        key: &CalendarEventKey,
+    ) {
+        let Some(event) = vm.calendar_events.iter().find(|x| x.key() == *key) else {
+            return;
+        };
+
+        let top = RelativeSize::from(SCREEN_TITLE_Y).to_absolute(TDisplay::FRAME_BUFFER_SIDE);
+        let style = Self::agenda_text_style();
+
+        let top = Self::render_round_text(
+            frame,
+            &event.title,
+            top,
+            DETAILS_TITLE_LINES,
+            U8g2TextStyle::new(
+                TFontSet::get_event_details_font(),
+                TDisplay::ColorModel::CYAN,
+            ),
+        );
+
+        let range = Self::agenda_time_range(vm, event);
+        let top = Self::render_round_text(frame, &range, top, 1, style.clone());
+
+        // the rest of the glass is for the description
+        Self::render_round_text(frame, &event.description, top, usize::MAX, style);
+    }
+
+    // wrapped on the glass below the top, returns where the next block goes
+    fn render_round_text(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        text: &str,
+        top: i32,
+        max_lines: usize,
+        style: U8g2TextStyle<TDisplay::ColorModel>,
+    ) -> i32 {
+        let side = TDisplay::FRAME_BUFFER_SIDE;
+        let line_height = style.line_height() as i32;
+
+        let layout = RoundTextLayout {
+            center: Self::get_center_point().to_absolute(side),
+            radius: side as u32 / 2,
+            top,
+            padding: RelativeSize::from(AGENDA_PADDING).to_absolute_u32(side),
+            max_lines,
+        };
+
+        let runs = layout.layout(text, TFontSet::get_event_details_font());
+
+        Graphics::<TDisplay>::glyph_runs(frame, &runs, style);
+
+        runs.last()
+            .map_or(top, |x| x.position.y + line_height + line_height / 2)
+    }
+
+    fn render_weather(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel) {
+        let Some(weather) = &vm.weather_now else {
+            Self::render_screen_rows(
+                frame,
+                "Weather",
+                &[("No forecast".to_string(), TDisplay::ColorModel::WHITE)],
+            );
+            return;
+        };
+
+        let rows = [
+            format!("{:?}", weather.condition),
+            format!("{}°", weather.temperature.round() as i32),
+            format!("rain {}%", weather.precipitation_probability),
+            format!("wind {:.0} m/s", weather.wind_speed),
+        ];
+
+        let rows: Vec<(String, TDisplay::ColorModel)> = rows
+            .into_iter()
+            .map(|x| (x, TDisplay::ColorModel::WHITE))
+            .collect();
+
+        Self::render_screen_rows(frame, "Weather", &rows);
+    }
+
+    fn render_settings(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel) {
+        let rows: Vec<(String, TDisplay::ColorModel)> = SETTINGS_FACES
+            .iter()
+            .map(|(name, face)| {
+                let color = if *face == vm.face {
+                    TDisplay::ColorModel::GREEN
+                } else {
+                    TDisplay::ColorModel::WHITE
+                };
+
+                (name.to_string(), color)
+            })
+            .collect();
+
+        Self::render_screen_rows(frame, "Face", &rows);
+    }
+
+    fn render_current_events_details(frame: &mut TDisplay::FrameBuffer<'_>, vm: &mut ViewModel) {
+        let now = vm.time_vm.time.unwrap();
+
+        let current_events = vm
+            .calendar_events
+            .iter()
+            .filter(|x| x.start <= now && x.end > now);
+
+        let mut text_style_underline = U8g2TextStyle::new(
+            TFontSet::get_event_details_font(),
+            TDisplay::ColorModel::WHITE,
+        );
 
         text_style_underline
             .set_underline_color(embedded_graphics::text::DecorationColor::TextColor);
@@ -756,33 +1654,28 @@ where
             TDisplay::ColorModel::WHITE,
         );
 
-        let zero_point = Self::get_center_point().to_absolute(TDisplay::FRAME_BUFFER_SIDE);
-
-        let mut correction: i32 = 0;
+        let mut top = RelativeSize::from(SCREEN_TITLE_Y).to_absolute(TDisplay::FRAME_BUFFER_SIDE);
 
-        for (index, event) in current_events.enumerate() {
-            Text::with_alignment(
-                event.title.as_str(),
-                zero_point + Point::new(0, ((index as i32 + correction as i32) * 2) * 12),
-                &text_style_underline,
-                embedded_graphics::text::Alignment::Center,
-            )
-            .draw(frame)
-            .unwrap();
+        for event in current_events {
+            top = Self::render_round_text(
+                frame,
+                &event.title,
+                top,
+                DETAILS_TITLE_LINES,
+                text_style_underline.clone(),
+            );
 
             if event.description.is_empty() {
-                correction -= 1;
                 continue;
             }
 
-            Text::with_alignment(
-                event.description.as_str(),
-                Point::new(0, -20 + ((index as i32 + correction) * 2 + 1) * 12) + zero_point,
-                &text_style_normal,
-                embedded_graphics::text::Alignment::Center,
-            )
-            .draw(frame)
-            .unwrap();
+            top = Self::render_round_text(
+                frame,
+                &event.description,
+                top,
+                DETAILS_DESCRIPTION_LINES,
+                text_style_normal.clone(),
+            );
         }
     }
 
@@ -791,11 +1684,13 @@ where
             return;
         }
 
-        if vm.force_render_events {
-            frame.clear(TDisplay::ColorModel::default()).unwrap();
-        }
-
         let now = vm.time_vm.time.unwrap();
+        let dial = Dial::new(now, vm.face);
+
+        Self::render_night_band(frame, vm, &dial);
+        Self::render_sun_ticks(frame, vm, &dial);
+        Self::render_precipitation_band(frame, vm, &dial);
+        Self::render_temperature_sparkline(frame, vm, &dial);
 
         info!("rendering {} events...", vm.calendar_events.len());
 
@@ -819,7 +1714,7 @@ where
             .clone()
             .filter(|x| (x.end - now) < HALF_DAY && (x.end - x.start) < HALF_DAY);
 
-        Self::render_current_finite_events(current_finite_events, frame, &now);
+        Self::render_current_finite_events(current_finite_events.clone(), frame, &dial);
 
         let current_ambient_events: Vec<&CalendarEvent> = current_events
             .filter(|x| x.end - x.start >= HALF_DAY)
@@ -831,7 +1726,9 @@ where
             (x.start > now) && (x.end - x.start) < HALF_DAY && (x.start - now) < HALF_DAY
         });
 
-        Self::render_todays_events(today_events, frame, &now);
+        Self::render_todays_events(today_events.clone(), frame, &dial);
+
+        Self::render_lane_overflow(frame, current_finite_events.chain(today_events), &dial);
 
         vm.force_render_events = false;
     }
@@ -885,29 +1782,29 @@ where
     fn render_current_finite_events<'a>(
         events: impl Iterator<Item = &'a CalendarEvent>,
         frame: &mut TDisplay::FrameBuffer<'_>,
-        now: &OffsetDateTime,
+        dial: &Dial,
     ) {
         for event in events {
-            Self::render_todays_event(frame, &event, &now);
+            Self::render_todays_event(frame, event, dial);
         }
     }
 
     fn render_todays_events<'a>(
         events: impl Iterator<Item = &'a CalendarEvent>,
         frame: &mut TDisplay::FrameBuffer<'_>,
-        now: &OffsetDateTime,
+        dial: &Dial,
     ) {
         for event in events {
-            Self::render_todays_event(frame, &event, &now);
+            Self::render_todays_event(frame, event, dial);
         }
     }
 
     fn render_todays_event(
         frame: &mut TDisplay::FrameBuffer<'_>,
         event: &CalendarEvent,
-        now_ref: &OffsetDateTime,
+        dial: &Dial,
     ) {
-        let now = *now_ref;
+        let now = dial.now;
 
         let color = if event.color == 0 {
             TDisplay::ColorModel::WHITE
@@ -921,14 +1818,16 @@ where
             event.end
         };
 
+        if event.lane == OVERFLOW_LANE {
+            return;
+        }
+
         let lane_line_thickness = RelativeSize::from(16);
-        let lane_spacing = RelativeSize::from(117); //28;
-        let outer_lane_diameter = RelativeSize::from(945); //225;
-        let event_arc_diameter = outer_lane_diameter - (lane_spacing * event.lane as u16);
+        let event_arc_diameter = Self::get_lane_diameter(event.lane.min(MAX_LANES - 1));
 
         Self::render_time_range_arc(
             frame,
-            &now,
+            dial,
             &event.start,
             &visual_end,
             event_arc_diameter.to_absolute(TDisplay::FRAME_BUFFER_SIDE) as u32,
@@ -936,52 +1835,84 @@ where
             color,
         );
 
-        let event_start_rel = event.start - now;
-
         let style = EventTagStyle::default(event.icon, TDisplay::ColorModel::BLACK);
 
         if event.start <= now {
-            let zero_point = Self::get_center_point().to_absolute(TDisplay::FRAME_BUFFER_SIDE);
             let outer_radius: RelativeSize = event_arc_diameter / 2;
-            let p1 = Point::new(0, outer_radius.to_absolute(TDisplay::FRAME_BUFFER_SIDE));
-            Self::render_event_icon(frame, zero_point - p1, &style);
+            let point = Self::get_dial_point(
+                dial.angle(&now),
+                outer_radius.to_absolute(TDisplay::FRAME_BUFFER_SIDE) as f32,
+            );
+            Self::render_event_icon(frame, point, &style);
         } else {
             if event.start - now <= Duration::minutes(30) {}
 
-            let start_angle = Angle::from_radians(
-                (event_start_rel.whole_minutes() as f32 / HALF_DAY.whole_minutes() as f32)
-                    * PI
-                    * 2.0,
-            );
+            let start_angle = dial.angle(&event.start);
 
             Self::render_event_tag(frame, start_angle, event_arc_diameter, style);
         };
     }
 
-    fn render_time_range_arc(
+    fn get_lane_diameter(lane: u8) -> RelativeSize {
+        let lane_spacing = RelativeSize::from(117); //28;
+        let outer_lane_diameter = RelativeSize::from(945); //225;
+
+        outer_lane_diameter - (lane_spacing * lane as u16)
+    }
+
+    // "+N" inside the innermost lane, where the first event that didn't fit starts
+    fn render_lane_overflow<'a>(
         frame: &mut TDisplay::FrameBuffer<'_>,
-        now: &OffsetDateTime,
-        start: &OffsetDateTime,
-        end: &OffsetDateTime,
-        diameter: u32,
-        thickness: u32,
-        color: TDisplay::ColorModel,
+        events: impl Iterator<Item = &'a CalendarEvent>,
+        dial: &Dial,
     ) {
-        let event_start_rel = if start > now {
-            *start - *now
-        } else {
-            Duration::ZERO
+        let now = &dial.now;
+        let overflow: Vec<&CalendarEvent> = events.filter(|x| x.lane == OVERFLOW_LANE).collect();
+
+        let Some(first_start) = overflow.iter().map(|x| x.start.max(*now)).min() else {
+            return;
         };
 
-        let event_end_rel = *end - *now;
+        let angle = dial.angle(&first_start);
 
-        let start_angle = Angle::from_radians(
-            (event_start_rel.whole_minutes() as f32 / HALF_DAY.whole_minutes() as f32) * PI * 2.0,
+        let radius: RelativeSize = Self::get_lane_diameter(MAX_LANES) / 2;
+        let point = Self::get_dial_point(
+            angle,
+            radius.to_absolute(TDisplay::FRAME_BUFFER_SIDE) as f32,
         );
 
-        let end_angle = Angle::from_radians(
-            (event_end_rel.whole_minutes() as f32 / HALF_DAY.whole_minutes() as f32) * PI * 2.0,
+        let style = U8g2TextStyle::new(TFontSet::get_event_details_font(), RgbColor::WHITE);
+
+        Graphics::<TDisplay>::text_aligned(
+            frame,
+            &format!("+{}", overflow.len()),
+            point,
+            style,
+            embedded_graphics::text::Alignment::Center,
         );
+    }
+
+    fn get_dial_point(angle: Angle, radius: f32) -> Point {
+        let zero_point = Self::get_center_point().to_absolute(TDisplay::FRAME_BUFFER_SIDE);
+
+        zero_point
+            + Point::new(
+                (radius * angle.to_radians().sin()) as i32,
+                -(radius * angle.to_radians().cos()) as i32,
+            )
+    }
+
+    fn render_time_range_arc(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        dial: &Dial,
+        start: &OffsetDateTime,
+        end: &OffsetDateTime,
+        diameter: u32,
+        thickness: u32,
+        color: TDisplay::ColorModel,
+    ) {
+        let start_angle = dial.angle(start.max(&dial.now));
+        let end_angle = dial.angle(end);
 
         let angle_sweep = end_angle - start_angle;
 
@@ -1005,6 +1936,190 @@ where
         }
     }
 
+    // twilight first, the darker night covers its middle part
+    fn render_night_band(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel, dial: &Dial) {
+        let now = &dial.now;
+
+        if vm.solar_days.is_empty() {
+            return;
+        }
+
+        let horizon = *now + HALF_DAY;
+
+        let diameter = RelativeSize::from(NIGHT_BAND_DIAMETER);
+        let thickness = RelativeSize::from(NIGHT_BAND_THICKNESS);
+
+        for (sun_below, color) in [
+            (Horizon::Sunrise, TWILIGHT_COLOR),
+            (Horizon::CivilTwilight, NIGHT_COLOR),
+        ] {
+            for (start, end) in below_horizon(&vm.solar_days, sun_below, *now, horizon) {
+                Self::render_time_range_arc(
+                    frame,
+                    dial,
+                    &start,
+                    &end,
+                    diameter.to_absolute_u32(TDisplay::FRAME_BUFFER_SIDE),
+                    thickness
+                        .to_absolute_u32(TDisplay::FRAME_BUFFER_SIDE)
+                        .max(1),
+                    TDisplay::ColorModel::from(RawU16::from_u32(color)),
+                );
+            }
+        }
+    }
+
+    fn render_sun_ticks(frame: &mut TDisplay::FrameBuffer<'_>, vm: &ViewModel, dial: &Dial) {
+        let now = &dial.now;
+
+        let horizon = *now + HALF_DAY;
+
+        let radius = RelativeSize::from(500u16).to_absolute(TDisplay::FRAME_BUFFER_SIDE) as f32;
+        let length =
+            RelativeSize::from(SUN_TICK_LENGTH).to_absolute(TDisplay::FRAME_BUFFER_SIDE) as f32;
+
+        let style = PrimitiveStyle::with_stroke(
+            TDisplay::ColorModel::from(RawU16::from_u32(SUN_TICK_COLOR)),
+            2,
+        );
+
+        let ticks = vm
+            .solar_days
+            .iter()
+            .flat_map(|x| [x.sunrise(), x.sunset()])
+            .flatten()
+            .filter(|x| x >= now && *x < horizon);
+
+        for at in ticks {
+            Self::render_radial_line::<TDisplay::ColorModel>(
+                frame,
+                dial.angle(&at),
+                radius,
+                length,
+                style,
+            );
+        }
+    }
+
+    fn render_precipitation_band(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &ViewModel,
+        dial: &Dial,
+    ) {
+        let now = &dial.now;
+
+        let horizon = *now + HALF_DAY;
+
+        let diameter = RelativeSize::from(PRECIPITATION_BAND_DIAMETER);
+        let thickness = RelativeSize::from(PRECIPITATION_BAND_THICKNESS);
+
+        let records = vm.timely_data.values().flatten().filter(|x| {
+            matches!(x.data_marker, TimelyDataMarker::Precipitation) && x.value >= MIN_PRECIPITATION
+        });
+
+        for record in records {
+            let (start, end) = record.window(*now);
+
+            if start >= horizon {
+                continue;
+            }
+
+            Self::render_time_range_arc(
+                frame,
+                dial,
+                &start,
+                &end.min(horizon),
+                diameter.to_absolute_u32(TDisplay::FRAME_BUFFER_SIDE),
+                thickness.to_absolute_u32(TDisplay::FRAME_BUFFER_SIDE),
+                scale_color::<TDisplay::ColorModel>(&PRECIPITATION_SCALE, record.value),
+            );
+        }
+    }
+
+    // one point per hourly record in the middle of its window, the radius follows the temperature
+    fn render_temperature_sparkline(
+        frame: &mut TDisplay::FrameBuffer<'_>,
+        vm: &ViewModel,
+        dial: &Dial,
+    ) {
+        let now = &dial.now;
+
+        let horizon = *now + HALF_DAY;
+
+        let mut samples: Vec<(OffsetDateTime, f32)> = vm
+            .timely_data
+            .values()
+            .flatten()
+            .filter(|x| matches!(x.data_marker, TimelyDataMarker::Temperature))
+            .filter_map(|x| {
+                let (start, end) = x.window(*now);
+                let middle: OffsetDateTime = start + (end - start) / 2;
+
+                (start < horizon).then(|| (middle.clamp(*now, horizon), x.value))
+            })
+            .collect();
+
+        samples.sort_by_key(|x| x.0);
+        samples.dedup_by_key(|x| x.0);
+
+        let Some(min) = samples.iter().map(|x| x.1).reduce(f32::min) else {
+            return;
+        };
+        let max = samples.iter().map(|x| x.1).fold(min, f32::max);
+
+        let inner = RelativeSize::from(SPARKLINE_INNER_RADIUS)
+            .to_absolute(TDisplay::FRAME_BUFFER_SIDE) as f32;
+        let outer = RelativeSize::from(SPARKLINE_OUTER_RADIUS)
+            .to_absolute(TDisplay::FRAME_BUFFER_SIDE) as f32;
+
+        let thickness = RelativeSize::from(SPARKLINE_THICKNESS)
+            .to_absolute_u32(TDisplay::FRAME_BUFFER_SIDE)
+            .max(1);
+
+        let points: Vec<(Point, f32)> = samples
+            .iter()
+            .map(|(at, value)| {
+                let level = if max > min {
+                    (value - min) / (max - min)
+                } else {
+                    0.5
+                };
+
+                let angle = dial.angle(at);
+
+                (
+                    Self::get_dial_point(angle, inner + (outer - inner) * level),
+                    *value,
+                )
+            })
+            .collect();
+
+        if let [(point, value)] = points.as_slice() {
+            let style = PrimitiveStyle::with_fill(scale_color::<TDisplay::ColorModel>(
+                &TEMPERATURE_SCALE,
+                *value,
+            ));
+
+            primitives::Circle::with_center(*point, thickness * 2)
+                .draw_styled(&style, frame)
+                .unwrap();
+        }
+
+        for segment in points.windows(2) {
+            let (from, from_value) = segment[0];
+            let (to, to_value) = segment[1];
+
+            let color = scale_color::<TDisplay::ColorModel>(
+                &TEMPERATURE_SCALE,
+                (from_value + to_value) / 2.0,
+            );
+
+            primitives::Line::new(from, to)
+                .draw_styled(&PrimitiveStyle::with_stroke(color, thickness), frame)
+                .unwrap();
+        }
+    }
+
     fn render_event_tag(
         frame: &mut TDisplay::FrameBuffer<'_>,
         angle: Angle,
@@ -1149,4 +2264,41 @@ fn append_timely_data(view_model: &mut ViewModel, data: calendar::EventTimelyDat
     view_model
         .timely_data
         .insert(*linked_event_id, data.timely_data);
+
+    view_model.force_render_events = true;
+}
+
+impl Dial {
+    fn new(now: OffsetDateTime, face: Face) -> Self {
+        let origin = match face {
+            Face::Digital => now,
+            Face::Analog { .. } if now.hour() >= 12 => now.replace_time(Time::MIDNIGHT) + HALF_DAY,
+            Face::Analog { .. } => now.replace_time(Time::MIDNIGHT),
+        };
+
+        Self { now, origin }
+    }
+
+    // 12 hours make a full turn, clockwise from the top
+    fn angle(&self, at: &OffsetDateTime) -> Angle {
+        let relative = *at - self.origin;
+
+        Angle::from_radians(
+            (relative.whole_minutes() as f32 / HALF_DAY.whole_minutes() as f32) * PI * 2.0,
+        )
+    }
+}
+
+fn scale_color<TColor>(scale: &[(f32, u32)], value: f32) -> TColor
+where
+    TColor: From<RawU16>,
+{
+    let color = scale
+        .iter()
+        .find(|(upper, _)| value < *upper)
+        .or(scale.last())
+        .map(|x| x.1)
+        .unwrap_or_default();
+
+    TColor::from(RawU16::from_u32(color))
 }
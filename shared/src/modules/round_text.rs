@@ -0,0 +1,192 @@
+// text on the round glass, coordinates are relative to the center of the circle with y going down
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedLine {
+    pub text: String,
+    pub top: i32,
+    pub width: u32,
+}
+
+// lines of the same height stacked from the top of the block down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircleBlock {
+    pub radius: u32,
+    pub top: i32,
+    pub line_height: u32,
+    pub padding: u32,
+}
+
+// what marks a word broken across lines and text cut short, nothing by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overflow {
+    pub hyphen: Option<&'static str>,
+    pub ellipsis: Option<&'static str>,
+}
+
+// the edge of the band further from the center is where the glass cuts it
+pub fn chord_width(radius: u32, top: i32, bottom: i32) -> u32 {
+    let far = top.unsigned_abs().max(bottom.unsigned_abs()) as u64;
+    let radius = radius as u64;
+
+    if far >= radius {
+        return 0;
+    }
+
+    (2.0 * ((radius * radius - far * far) as f32).sqrt()) as u32
+}
+
+impl CircleBlock {
+    pub fn line_top(&self, line: usize) -> i32 {
+        self.top + (line as u32 * self.line_height) as i32
+    }
+
+    pub fn line_width(&self, line: usize) -> u32 {
+        let top = self.line_top(line);
+
+        chord_width(self.radius, top, top + self.line_height as i32)
+            .saturating_sub(2 * self.padding)
+    }
+
+    // whole words while they fit, a word wider than a line is broken where it stops fitting,
+    // rows at the top too narrow for a single character are skipped and still count towards
+    // max_lines, what is left once the circle or the lines run out is dropped
+    pub fn wrap(
+        &self,
+        text: &str,
+        max_lines: usize,
+        measure: impl Fn(&str) -> u32,
+    ) -> Vec<WrappedLine> {
+        self.wrap_with(text, max_lines, Overflow::default(), measure)
+    }
+
+    pub fn wrap_with(
+        &self,
+        text: &str,
+        max_lines: usize,
+        overflow: Overflow,
+        measure: impl Fn(&str) -> u32,
+    ) -> Vec<WrappedLine> {
+        let (mut lines, truncated) = self.break_lines(text, max_lines, overflow, &measure);
+
+        if let (true, Some(ellipsis), Some(last)) = (truncated, overflow.ellipsis, lines.last_mut())
+        {
+            Self::ellipsize(last, ellipsis, &measure);
+        }
+
+        lines
+    }
+
+    // the lines and whether some of the text did not make it
+    fn break_lines(
+        &self,
+        text: &str,
+        max_lines: usize,
+        overflow: Overflow,
+        measure: &impl Fn(&str) -> u32,
+    ) -> (Vec<WrappedLine>, bool) {
+        let mut lines = vec![];
+        let mut current = String::new();
+        // rows too narrow at the top of the circle stay empty, so lines and rows may differ
+        let mut row = 0;
+
+        let mut words: Vec<String> = text.split_whitespace().rev().map(String::from).collect();
+
+        while let Some(word) = words.pop() {
+            if row == max_lines {
+                return (lines, true);
+            }
+
+            let width = self.line_width(row);
+
+            let candidate = if current.is_empty() {
+                word.clone()
+            } else {
+                format!("{} {}", current, word)
+            };
+
+            if measure(&candidate) <= width {
+                current = candidate;
+                continue;
+            }
+
+            if !current.is_empty() {
+                lines.push(self.line(row, std::mem::take(&mut current)));
+                row += 1;
+                words.push(word);
+                continue;
+            }
+
+            let fits = |piece: &str| measure(piece) <= width;
+
+            let split = Self::split_point(&word, fits);
+
+            let Some(split) = split else {
+                // not even a single character fits, rows only get wider down to the center
+                // and narrower past it, where the circle is over
+                if self.line_top(row) < 0 {
+                    row += 1;
+                    words.push(word);
+                    continue;
+                }
+
+                return (lines, true);
+            };
+
+            // a hyphen only where it still fits, a bare break otherwise
+            let hyphenated = overflow.hyphen.and_then(|hyphen| {
+                Self::split_point(&word[..split], |x| fits(&format!("{}{}", x, hyphen)))
+                    .filter(|_| split < word.len())
+                    .map(|x| (x, format!("{}{}", &word[..x], hyphen)))
+            });
+
+            let (split, piece) = hyphenated.unwrap_or((split, word[..split].to_string()));
+
+            lines.push(self.line(row, piece));
+            row += 1;
+
+            if split < word.len() {
+                words.push(word[split..].to_string());
+            }
+        }
+
+        if !current.is_empty() && row < max_lines {
+            lines.push(self.line(row, current));
+        }
+
+        (lines, false)
+    }
+
+    // the longest prefix that fits, never empty
+    fn split_point(word: &str, fits: impl Fn(&str) -> bool) -> Option<usize> {
+        word.char_indices()
+            .map(|(index, _)| index)
+            .skip(1)
+            .chain([word.len()])
+            .take_while(|index| fits(&word[..*index]))
+            .last()
+    }
+
+    // characters come off the end until the ellipsis fits after them
+    fn ellipsize(line: &mut WrappedLine, ellipsis: &str, measure: &impl Fn(&str) -> u32) {
+        let mut text = line.text.clone();
+
+        while !text.is_empty() && measure(&format!("{}{}", text, ellipsis)) > line.width {
+            text.pop();
+        }
+
+        let text = text.trim_end_matches(['-', ' ']);
+        let ellipsized = format!("{}{}", text, ellipsis);
+
+        if measure(&ellipsized) <= line.width {
+            line.text = ellipsized;
+        }
+    }
+
+    fn line(&self, line: usize, text: String) -> WrappedLine {
+        WrappedLine {
+            text,
+            top: self.line_top(line),
+            width: self.line_width(line),
+        }
+    }
+}
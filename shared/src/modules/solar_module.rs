@@ -0,0 +1,112 @@
+use log::{error, info, warn};
+use time::{Date, UtcOffset};
+
+use crate::commands::Commands;
+use crate::events::Events;
+use crate::message_bus::{BusHandler, BusSender, MessageBus};
+use crate::persistence::{PersistenceUnit, PersistenceUnitKind};
+use crate::reference_data::GpsCoordinates;
+use crate::solar::solar_days;
+
+// today and tomorrow cover the 12 hour dial at any time of day
+const SOLAR_DAYS: u8 = 2;
+
+pub struct SolarModule {}
+
+struct Context {
+    coordinates: Option<GpsCoordinates>,
+    // date and offset the last solar days were calculated for
+    calculated_for: Option<(Date, UtcOffset)>,
+    restored: bool,
+}
+
+impl BusHandler<Context> for SolarModule {
+    async fn event_handler(bus: &BusSender, context: &mut Context, event: Events) {
+        match event {
+            Events::TimeNow(now) => {
+                if !context.restored {
+                    context.restored = true;
+                    Self::restore_location(bus, context).await;
+                }
+
+                if context.calculated_for == Some((now.date(), now.offset())) {
+                    return;
+                }
+
+                let Some(coordinates) = &context.coordinates else {
+                    return;
+                };
+
+                let days = solar_days(now, coordinates, SOLAR_DAYS);
+
+                info!(
+                    "sunrise {:?}, sunset {:?}",
+                    days[0].sunrise(),
+                    days[0].sunset()
+                );
+
+                context.calculated_for = Some((now.date(), now.offset()));
+
+                bus.send_event(Events::SolarDays(days));
+            }
+            Events::ReferenceLocation(coordinates) => {
+                info!("location {:?}", coordinates);
+
+                bus.send_cmd(Commands::Persist(PersistenceUnit::new(
+                    PersistenceUnitKind::Location,
+                    &coordinates,
+                )));
+
+                context.restored = true;
+                context.coordinates = Some(coordinates);
+                // recalculated on the next tick
+                context.calculated_for = None;
+            }
+            _ => {}
+        }
+    }
+
+    async fn command_handler(_bus: &BusSender, _context: &mut Context, _command: Commands) {}
+}
+
+impl SolarModule {
+    pub async fn start(bus: MessageBus) {
+        info!("starting...");
+
+        let context = Context {
+            coordinates: None,
+            calculated_for: None,
+            restored: false,
+        };
+
+        MessageBus::handle::<Context, Self>(bus, context).await;
+
+        info!("done.");
+    }
+
+    async fn restore_location(bus: &BusSender, context: &mut Context) {
+        let reply = bus
+            .request(Commands::Restore(PersistenceUnitKind::Location))
+            .await;
+
+        let unit = match reply {
+            Ok(Events::Restored(unit)) => unit,
+            Ok(event) => {
+                warn!("unexpected reply {:?}", event.as_ref());
+                return;
+            }
+            Err(timeout) => {
+                error!("restore timed out {:?}", timeout);
+                return;
+            }
+        };
+
+        match unit.deserialize::<GpsCoordinates>().await {
+            Ok(coordinates) => {
+                info!("location restored");
+                context.coordinates = Some(coordinates);
+            }
+            Err(error) => info!("location not restored, {}", error),
+        }
+    }
+}
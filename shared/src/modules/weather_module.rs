@@ -0,0 +1,118 @@
+use log::{error, info, warn};
+use time::OffsetDateTime;
+
+use crate::commands::Commands;
+use crate::events::Events;
+use crate::message_bus::{BusHandler, BusSender, MessageBus};
+use crate::persistence::{PersistenceUnit, PersistenceUnitKind};
+use crate::weather::{CurrentWeather, WeatherForecast};
+
+pub struct WeatherModule {}
+
+struct Context {
+    forecast: Option<WeatherForecast>,
+    current: Option<CurrentWeather>,
+    now: Option<OffsetDateTime>,
+    restored: bool,
+}
+
+impl BusHandler<Context> for WeatherModule {
+    async fn event_handler(bus: &BusSender, context: &mut Context, event: Events) {
+        match event {
+            Events::TimeNow(now) => {
+                context.now = Some(now);
+
+                if !context.restored {
+                    context.restored = true;
+                    Self::restore_forecast(bus, context).await;
+                }
+
+                Self::send_current(bus, context);
+            }
+            Events::ReferenceWeather(forecast) => {
+                info!(
+                    "forecast of {} hours and {} days",
+                    forecast.hours.len(),
+                    forecast.daylight.len()
+                );
+
+                bus.send_cmd(Commands::Persist(PersistenceUnit::new(
+                    PersistenceUnitKind::WeatherForecast,
+                    &forecast,
+                )));
+
+                // a forecast from the companion wins over whatever the restore brings later
+                context.restored = true;
+                context.forecast = Some(forecast);
+
+                Self::send_current(bus, context);
+            }
+            _ => {}
+        }
+    }
+
+    async fn command_handler(_bus: &BusSender, _context: &mut Context, _command: Commands) {}
+}
+
+impl WeatherModule {
+    pub async fn start(bus: MessageBus) {
+        info!("starting...");
+
+        let context = Context {
+            forecast: None,
+            current: None,
+            now: None,
+            restored: false,
+        };
+
+        MessageBus::handle::<Context, Self>(bus, context).await;
+
+        info!("done.");
+    }
+
+    async fn restore_forecast(bus: &BusSender, context: &mut Context) {
+        let reply = bus
+            .request(Commands::Restore(PersistenceUnitKind::WeatherForecast))
+            .await;
+
+        let unit = match reply {
+            Ok(Events::Restored(unit)) => unit,
+            Ok(event) => {
+                warn!("unexpected reply {:?}", event.as_ref());
+                return;
+            }
+            Err(timeout) => {
+                error!("restore timed out {:?}", timeout);
+                return;
+            }
+        };
+
+        match unit.deserialize::<WeatherForecast>().await {
+            Ok(forecast) => {
+                info!("forecast restored");
+                context.forecast = Some(forecast);
+            }
+            Err(error) => info!("forecast not restored, {}", error),
+        }
+    }
+
+    // only changes are sent, that is once an hour or when the forecast changes
+    fn send_current(bus: &BusSender, context: &mut Context) {
+        let (Some(forecast), Some(now)) = (&context.forecast, context.now) else {
+            return;
+        };
+
+        let current = forecast.current(now);
+
+        if current == context.current {
+            return;
+        }
+
+        context.current = current.clone();
+
+        match current {
+            Some(current) => bus.send_event(Events::WeatherNow(current)),
+            None => info!("forecast is over"),
+        }
+    }
+}
@@ -1,20 +1,37 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 use strum_macros::AsRefStr;
 use tokio::task::JoinError;
 
+use crate::calendar::state::migrate_calendar_state_v1;
+use crate::crc::crc32;
 use crate::error::Error;
+use crate::reminders::migrate_reminder_set_v1;
 
-#[derive(Debug, Serialize, Deserialize, Clone, AsRefStr, Hash, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, AsRefStr, Hash, Copy, PartialEq, Eq)]
 pub enum PersistenceUnitKind {
     RtcSyncInfo,
     CalendarEventInfo,
     CalendarSyncInfo,
     TimelyData,
+    PairingKey,
+    Reminders,
+    TimezoneRules,
+    WeatherForecast,
+    Location,
+    Watchface,
+    ReminderSettings,
 }
 
-#[derive(Debug)]
+impl PersistenceUnitKind {
+    // units that must not leave the watch, e.g. in a recorded trace
+    pub fn is_secret(&self) -> bool {
+        matches!(self, PersistenceUnitKind::PairingKey)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PersistenceUnit {
     pub kind: PersistenceUnitKind,
     pub data: Result<Arc<Vec<u8>>, Error>,
@@ -88,3 +105,147 @@ impl Into<PersistenceUnitDto> for PersistenceUnit {
         }
     }
 }
+
+// msgpack never uses this byte, so anything persisted before the envelope existed is told apart by it
+const ENVELOPE_MARKER: u8 = 0xc1;
+const LEGACY_VERSION: u16 = 1;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PersistenceEnvelope {
+    pub kind: PersistenceUnitKind,
+    pub version: u16,
+    pub checksum: u32,
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+}
+
+// rewrites the payload of version n into version n + 1
+pub type Migration = fn(&[u8]) -> Result<Vec<u8>, Error>;
+
+struct UnitSchema {
+    version: u16,
+    migrations: HashMap<u16, Migration>,
+}
+
+pub struct SchemaRegistry {
+    schemas: HashMap<PersistenceUnitKind, UnitSchema>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self {
+            schemas: HashMap::new(),
+        }
+    }
+
+    pub fn register(mut self, kind: PersistenceUnitKind, version: u16) -> Self {
+        self.schemas.insert(
+            kind,
+            UnitSchema {
+                version,
+                migrations: HashMap::new(),
+            },
+        );
+
+        self
+    }
+
+    pub fn migration(mut self, kind: PersistenceUnitKind, from: u16, migration: Migration) -> Self {
+        self.schemas
+            .get_mut(&kind)
+            .expect("schema is registered before its migrations")
+            .migrations
+            .insert(from, migration);
+
+        self
+    }
+
+    pub fn version(&self, kind: PersistenceUnitKind) -> u16 {
+        self.schemas
+            .get(&kind)
+            .map(|x| x.version)
+            .unwrap_or(LEGACY_VERSION)
+    }
+
+    pub fn seal(&self, kind: PersistenceUnitKind, payload: &[u8]) -> Vec<u8> {
+        let envelope = PersistenceEnvelope {
+            kind,
+            version: self.version(kind),
+            checksum: crc32(payload),
+            payload: payload.to_vec(),
+        };
+
+        let mut bytes = vec![ENVELOPE_MARKER];
+        bytes.extend(rmp_serde::to_vec(&envelope).unwrap());
+
+        bytes
+    }
+
+    // validates the envelope and brings the payload up to the current schema version
+    pub fn open(&self, kind: PersistenceUnitKind, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        let (version, mut payload) = match bytes.split_first() {
+            Some((&ENVELOPE_MARKER, sealed)) => {
+                let envelope: PersistenceEnvelope = rmp_serde::from_slice(sealed)
+                    .map_err(|err| Error::from(format!("broken envelope: {}", err)))?;
+
+                if envelope.kind != kind {
+                    return Err(Error::from(format!(
+                        "expected {:?}, found {:?}",
+                        kind, envelope.kind
+                    )));
+                }
+
+                if envelope.checksum != crc32(&envelope.payload) {
+                    return Err(Error::from(format!("{:?} checksum mismatch", kind)));
+                }
+
+                (envelope.version, envelope.payload)
+            }
+            _ => (LEGACY_VERSION, bytes.to_vec()),
+        };
+
+        let current = self.version(kind);
+
+        if version > current {
+            return Err(Error::from(format!(
+                "{:?} v{} is newer than v{}",
+                kind, version, current
+            )));
+        }
+
+        for from in version..current {
+            let migration = self
+                .schemas
+                .get(&kind)
+                .and_then(|x| x.migrations.get(&from))
+                .ok_or_else(|| Error::from(format!("no {:?} migration from v{}", kind, from)))?;
+
+            payload = migration(&payload)?;
+        }
+
+        Ok(payload)
+    }
+}
+
+impl Default for SchemaRegistry {
+    fn default() -> Self {
+        Self::new()
+            .register(PersistenceUnitKind::RtcSyncInfo, 1)
+            .register(PersistenceUnitKind::CalendarEventInfo, 1)
+            .register(PersistenceUnitKind::CalendarSyncInfo, 2)
+            .migration(
+                PersistenceUnitKind::CalendarSyncInfo,
+                1,
+                migrate_calendar_state_v1,
+            )
+            .register(PersistenceUnitKind::TimelyData, 1)
+            .register(PersistenceUnitKind::PairingKey, 1)
+            .register(PersistenceUnitKind::Reminders, 2)
+            .migration(PersistenceUnitKind::Reminders, 1, migrate_reminder_set_v1)
+            .register(PersistenceUnitKind::TimezoneRules, 1)
+            .register(PersistenceUnitKind::WeatherForecast, 1)
+            .register(PersistenceUnitKind::Location, 1)
+            .register(PersistenceUnitKind::Watchface, 1)
+            .register(PersistenceUnitKind::ReminderSettings, 1)
+    }
+}
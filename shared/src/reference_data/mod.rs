@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use time::{OffsetDateTime, UtcOffset};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset};
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct ReferenceTimeOffset {
@@ -34,8 +34,100 @@ impl ReferenceTimeUtc {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct GpsCoordinates {
     pub lat: f32,
     pub lon: f32,
 }
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TimezoneTransition {
+    pub at: ReferenceTimeUtc,
+    pub offset_seconds: i32,
+}
+
+// the upcoming transitions of an IANA zone, the last offset holds past the last transition
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct TimezoneRules {
+    pub name: String,
+    pub initial_offset_seconds: i32,
+    pub transitions: Vec<TimezoneTransition>,
+}
+
+impl TimezoneRules {
+    pub fn fixed(offset: UtcOffset) -> Self {
+        Self {
+            name: String::new(),
+            initial_offset_seconds: offset.whole_seconds(),
+            transitions: vec![],
+        }
+    }
+
+    pub fn sorted(mut self) -> Self {
+        self.transitions.sort_by_key(|x| x.at.unix_epoch_seconds);
+        self
+    }
+
+    pub fn offset_at(&self, time: OffsetDateTime) -> UtcOffset {
+        let timestamp = time.unix_timestamp();
+
+        let passed = self
+            .transitions
+            .partition_point(|x| x.at.unix_epoch_seconds <= timestamp);
+
+        let offset_seconds = match passed {
+            0 => self.initial_offset_seconds,
+            passed => self.transitions[passed - 1].offset_seconds,
+        };
+
+        UtcOffset::from_whole_seconds(offset_seconds).unwrap_or(UtcOffset::UTC)
+    }
+
+    pub fn to_local(&self, time: OffsetDateTime) -> OffsetDateTime {
+        time.to_offset(self.offset_at(time))
+    }
+
+    pub fn next_transition_after(&self, time: OffsetDateTime) -> Option<OffsetDateTime> {
+        let timestamp = time.unix_timestamp();
+
+        self.transitions
+            .iter()
+            .find(|x| x.at.unix_epoch_seconds > timestamp)
+            .map(|x| x.at.clone().into())
+    }
+
+    // a wall clock kept in the given offset, moved to the offset in effect at its instant,
+    // none while no transition passed since the offset was set
+    pub fn shift_wall_clock(
+        &self,
+        wall: PrimitiveDateTime,
+        offset: UtcOffset,
+    ) -> Option<OffsetDateTime> {
+        let time = wall.assume_offset(offset);
+        let current = self.offset_at(time);
+
+        match current == offset {
+            true => None,
+            false => Some(time.to_offset(current)),
+        }
+    }
+
+    // a wall clock time skipped by a transition resolves to the same instant before it,
+    // a repeated one to its first occurrence
+    pub fn resolve_local(&self, local: PrimitiveDateTime) -> OffsetDateTime {
+        let probe = local.assume_utc();
+
+        let before = self.offset_at(probe - Duration::days(1));
+        let after = self.offset_at(probe + Duration::days(1));
+
+        for offset in [before, after] {
+            let time = local.assume_offset(offset);
+
+            if self.offset_at(time) == offset {
+                return time;
+            }
+        }
+
+        self.to_local(local.assume_offset(before))
+    }
+}
@@ -1,17 +1,25 @@
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use time::OffsetDateTime;
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::calendar::CalendarKind;
+use crate::error::Error;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ReminderKind {
     Event,
     Alert,
     Notification,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// scheduled_at is when the calendar wants it, it tells apart occurrences and offsets of one event
+// and stays put when a snooze moves remind_at
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Reminder {
     pub remind_at: OffsetDateTime,
     pub kind: ReminderKind,
     pub event_id: i32,
+    pub scheduled_at: OffsetDateTime,
 }
 
 impl Ord for Reminder {
@@ -27,3 +35,217 @@ impl PartialOrd for Reminder {
 }
 
 impl Eq for Reminder {}
+
+// minutes before the event start, negative ones fire after it started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KindReminderSettings {
+    pub kind: CalendarKind,
+    pub offsets: Vec<i16>,
+    pub all_day_offsets: Vec<i16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReminderSettings {
+    pub kinds: Vec<KindReminderSettings>,
+}
+
+// layout before scheduled_at
+#[derive(Debug, Deserialize)]
+struct ReminderV1 {
+    remind_at: OffsetDateTime,
+    kind: ReminderKind,
+    event_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReminderSetV1 {
+    scheduled: Vec<ReminderV1>,
+    snoozed: Vec<ReminderV1>,
+    dismissed: Vec<ReminderV1>,
+    fired_until: Option<OffsetDateTime>,
+}
+
+// reminders armed by the rtc, fired and dismissed ones are not re-armed when the calendar resends them
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReminderSet {
+    scheduled: Vec<Reminder>,
+    snoozed: Vec<Reminder>,
+    dismissed: Vec<Reminder>,
+    fired_until: Option<OffsetDateTime>,
+}
+
+impl Reminder {
+    pub fn kind_for_offset(offset_minutes: i16) -> ReminderKind {
+        match offset_minutes {
+            0 => ReminderKind::Event,
+            _ => ReminderKind::Notification,
+        }
+    }
+
+    pub fn new(event_id: i32, kind: ReminderKind, scheduled_at: OffsetDateTime) -> Self {
+        Self {
+            remind_at: scheduled_at,
+            kind,
+            event_id,
+            scheduled_at,
+        }
+    }
+
+    fn is_same(&self, other: &Reminder) -> bool {
+        self.event_id == other.event_id
+            && self.kind == other.kind
+            && self.scheduled_at == other.scheduled_at
+    }
+}
+
+impl From<ReminderV1> for Reminder {
+    fn from(legacy: ReminderV1) -> Self {
+        Reminder::new(legacy.event_id, legacy.kind, legacy.remind_at)
+    }
+}
+
+// a snoozed reminder of v1 lost its schedule, the snooze time stands in for it
+pub fn migrate_reminder_set_v1(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let legacy: ReminderSetV1 =
+        rmp_serde::from_slice(bytes).map_err(|err| Error::from(err.to_string()))?;
+
+    let convert = |reminders: Vec<ReminderV1>| reminders.into_iter().map(Reminder::from).collect();
+
+    let set = ReminderSet {
+        scheduled: convert(legacy.scheduled),
+        snoozed: convert(legacy.snoozed),
+        dismissed: convert(legacy.dismissed),
+        fired_until: legacy.fired_until,
+    };
+
+    Ok(rmp_serde::to_vec(&set).unwrap())
+}
+
+impl ReminderSettings {
+    pub fn default_offsets(&self, kind: CalendarKind, all_day: bool) -> &[i16] {
+        let settings = self.kinds.iter().find(|x| x.kind == kind);
+
+        match settings {
+            Some(settings) if all_day => &settings.all_day_offsets,
+            Some(settings) => &settings.offsets,
+            None => &[],
+        }
+    }
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        Self {
+            kinds: vec![
+                KindReminderSettings {
+                    kind: CalendarKind::Unknown,
+                    offsets: vec![10, 0],
+                    all_day_offsets: vec![],
+                },
+                KindReminderSettings {
+                    kind: CalendarKind::Phone,
+                    offsets: vec![10, 0],
+                    all_day_offsets: vec![-9 * 60],
+                },
+                KindReminderSettings {
+                    kind: CalendarKind::Trains,
+                    offsets: vec![10, 0],
+                    all_day_offsets: vec![],
+                },
+                KindReminderSettings {
+                    kind: CalendarKind::Weather,
+                    offsets: vec![],
+                    all_day_offsets: vec![],
+                },
+            ],
+        }
+    }
+}
+
+impl ReminderSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // replaces everything scheduled by the calendar, snoozed reminders are kept
+    pub fn set(&mut self, reminders: Vec<Reminder>) {
+        self.scheduled = reminders
+            .into_iter()
+            .filter(|x| !self.is_fired(x) && !self.dismissed.iter().any(|y| y.is_same(x)))
+            .sorted()
+            .dedup()
+            .collect();
+    }
+
+    // a restored set may arrive after the calendar already scheduled the current reminders
+    pub fn restore(&mut self, restored: ReminderSet) {
+        self.fired_until = self.fired_until.max(restored.fired_until);
+
+        for reminder in restored.snoozed {
+            if !self.snoozed.iter().any(|x| x.is_same(&reminder)) {
+                self.snoozed.push(reminder);
+            }
+        }
+
+        self.dismissed.extend(restored.dismissed);
+
+        let scheduled = match self.scheduled.is_empty() {
+            true => restored.scheduled,
+            false => std::mem::take(&mut self.scheduled),
+        };
+
+        self.set(scheduled);
+    }
+
+    pub fn next(&self) -> Option<&Reminder> {
+        self.scheduled.iter().chain(self.snoozed.iter()).min()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scheduled.is_empty() && self.snoozed.is_empty()
+    }
+
+    pub fn due(&mut self, now: OffsetDateTime) -> Vec<Reminder> {
+        let (due, scheduled) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition::<Vec<_>, _>(|x| x.remind_at <= now);
+        let (snoozed_due, snoozed) = std::mem::take(&mut self.snoozed)
+            .into_iter()
+            .partition::<Vec<_>, _>(|x| x.remind_at <= now);
+
+        self.scheduled = scheduled;
+        self.snoozed = snoozed;
+
+        if due.is_empty() && snoozed_due.is_empty() {
+            return vec![];
+        }
+
+        self.fired_until = self.fired_until.max(Some(now));
+        self.dismissed.retain(|x| x.remind_at > now);
+
+        due.into_iter().chain(snoozed_due).sorted().collect()
+    }
+
+    pub fn snooze(&mut self, reminder: Reminder, until: OffsetDateTime) {
+        self.snoozed.retain(|x| !x.is_same(&reminder));
+
+        self.snoozed.push(Reminder {
+            remind_at: until,
+            ..reminder
+        });
+    }
+
+    pub fn dismiss(&mut self, reminder: &Reminder) {
+        self.snoozed.retain(|x| !x.is_same(reminder));
+        self.scheduled.retain(|x| !x.is_same(reminder));
+
+        if !self.is_fired(reminder) {
+            self.dismissed.push(reminder.clone());
+        }
+    }
+
+    fn is_fired(&self, reminder: &Reminder) -> bool {
+        self.fired_until
+            .is_some_and(|fired_until| reminder.remind_at <= fired_until)
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime, UtcOffset};
+
+pub const MAX_SYNC_HISTORY: usize = 16;
+
+pub const BASE_SYNC_INTERVAL: Duration = Duration::minutes(10);
+pub const MAX_SYNC_INTERVAL: Duration = Duration::hours(12);
+
+// the pcf8563 is specified well below this, anything worse is a power loss or a manual time change
+const MAX_PLAUSIBLE_PPM: f64 = 200.0;
+// both clocks are read with whole second resolution
+const QUANTIZATION_SECONDS: f64 = 2.0;
+const MAX_RESIDUAL_SECONDS: f64 = 1.5;
+
+// intervals need to differ in length to tell the rate from the per sync truncation
+const MIN_ESTIMATE_SPREAD_SECONDS: f64 = 4.0 * 3600.0;
+const MIN_ESTIMATE_INTERVALS: usize = 3;
+
+// tolerated error between two syncs, the estimate is trusted up to the margin
+const TOLERATED_ERROR_SECONDS: f64 = 1.0;
+const ESTIMATE_MARGIN_PPM: f64 = 10.0;
+
+// rtc reading right before it was overwritten with the reference time, both unix seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SyncSample {
+    pub rtc: i64,
+    pub reference: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SyncHistory {
+    samples: Vec<SyncSample>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Hash, Serialize, Deserialize)]
+pub struct RtcSyncInfo {
+    pub last_sync: i64,
+    pub offset: i32,
+    pub in_sync: bool,
+    #[serde(default)]
+    pub history: SyncHistory,
+}
+
+// positive ppm means the rtc runs fast, the anchor is the reference time the rtc was last set to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DriftEstimate {
+    pub ppm: f64,
+    pub anchor: i64,
+}
+
+impl Into<OffsetDateTime> for &RtcSyncInfo {
+    fn into(self) -> OffsetDateTime {
+        let last_sync = OffsetDateTime::from_unix_timestamp(self.last_sync + self.offset as i64)
+            .unwrap()
+            .replace_offset(UtcOffset::from_whole_seconds(self.offset).unwrap());
+        last_sync
+    }
+}
+
+impl SyncHistory {
+    pub fn new() -> Self {
+        Self { samples: vec![] }
+    }
+
+    pub fn samples(&self) -> &[SyncSample] {
+        &self.samples
+    }
+
+    pub fn push(&mut self, sample: SyncSample) {
+        self.samples.push(sample);
+
+        if self.samples.len() > MAX_SYNC_HISTORY {
+            self.samples.drain(..self.samples.len() - MAX_SYNC_HISTORY);
+        }
+    }
+
+    pub fn estimate(&self) -> Option<DriftEstimate> {
+        let last = self.samples.last()?;
+
+        // the rtc is reset on every sync, so each interval measures the drift since the previous one,
+        // writing it also drops the sub second part, which shows up as a constant error per interval
+        let intervals: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .map(|(previous, sample)| {
+                (
+                    (sample.reference - previous.reference) as f64,
+                    (sample.rtc - sample.reference) as f64,
+                )
+            })
+            .filter(|(interval, error)| {
+                *interval > 0.0
+                    && error.abs() <= interval * MAX_PLAUSIBLE_PPM / 1e6 + QUANTIZATION_SECONDS
+            })
+            .collect();
+
+        let (rate, offset) = fit(&intervals)?;
+
+        // refit without intervals that are off by more than the readings explain
+        let inliers: Vec<(f64, f64)> = intervals
+            .into_iter()
+            .filter(|(interval, error)| {
+                (error - offset - rate * interval).abs() <= MAX_RESIDUAL_SECONDS
+            })
+            .collect();
+
+        let (rate, _) = fit(&inliers)?;
+
+        Some(DriftEstimate {
+            ppm: rate * 1e6,
+            anchor: last.reference,
+        })
+    }
+}
+
+impl DriftEstimate {
+    pub fn anchored_at(self, reference: i64) -> Self {
+        Self {
+            ppm: self.ppm,
+            anchor: reference,
+        }
+    }
+
+    pub fn correct(&self, rtc: OffsetDateTime) -> OffsetDateTime {
+        let elapsed = (rtc.unix_timestamp() - self.anchor) as f64;
+
+        if elapsed <= 0.0 {
+            return rtc;
+        }
+
+        let correction = elapsed * self.ppm / (1e6 + self.ppm);
+
+        rtc - Duration::seconds(correction.round() as i64)
+    }
+
+    // inverse of correct, the rtc reading at which the reference reaches the given time
+    pub fn to_rtc(&self, reference: OffsetDateTime) -> OffsetDateTime {
+        let elapsed = (reference.unix_timestamp() - self.anchor) as f64;
+
+        if elapsed <= 0.0 {
+            return reference;
+        }
+
+        let correction = elapsed * self.ppm / 1e6;
+
+        reference + Duration::seconds(correction.round() as i64)
+    }
+
+    pub fn sync_interval(estimate: Option<&Self>) -> Duration {
+        let Some(estimate) = estimate else {
+            return BASE_SYNC_INTERVAL;
+        };
+
+        let seconds = TOLERATED_ERROR_SECONDS * 1e6 / (estimate.ppm.abs() + ESTIMATE_MARGIN_PPM);
+
+        Duration::seconds(seconds as i64).clamp(BASE_SYNC_INTERVAL, MAX_SYNC_INTERVAL)
+    }
+}
+
+// least squares line through (interval, error), the intercept takes the per sync truncation
+fn fit(intervals: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let shortest = intervals.iter().map(|x| x.0).reduce(f64::min)?;
+    let longest = intervals.iter().map(|x| x.0).reduce(f64::max)?;
+
+    if intervals.len() < MIN_ESTIMATE_INTERVALS || longest - shortest < MIN_ESTIMATE_SPREAD_SECONDS
+    {
+        return None;
+    }
+
+    let count = intervals.len() as f64;
+    let mean_interval = intervals.iter().map(|x| x.0).sum::<f64>() / count;
+    let mean_error = intervals.iter().map(|x| x.1).sum::<f64>() / count;
+
+    let covariance: f64 = intervals
+        .iter()
+        .map(|(interval, error)| (interval - mean_interval) * (error - mean_error))
+        .sum();
+    let variance: f64 = intervals
+        .iter()
+        .map(|(interval, _)| (interval - mean_interval).powi(2))
+        .sum();
+
+    let rate = covariance / variance;
+
+    Some((rate, mean_error - rate * mean_interval))
+}
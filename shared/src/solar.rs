@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+use time::{Date, Duration, OffsetDateTime, Time, UtcOffset};
+
+use crate::reference_data::GpsCoordinates;
+
+// zenith angles of the sun centre, the sunrise one accounts for refraction and the solar disc
+const SUNRISE_ZENITH: f64 = 90.833;
+const CIVIL_TWILIGHT_ZENITH: f64 = 96.0;
+
+const J2000: f64 = 2451545.0;
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2440587.5;
+const EARTH_AXIAL_TILT: f64 = 23.4397;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Horizon {
+    Sunrise,
+    CivilTwilight,
+}
+
+// sun above the horizon from rise to set, polar days and nights have no crossing
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SunCrossing {
+    Crosses {
+        rise: OffsetDateTime,
+        set: OffsetDateTime,
+    },
+    AlwaysAbove,
+    AlwaysBelow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SolarDay {
+    pub date: Date,
+    pub noon: OffsetDateTime,
+    pub daylight: SunCrossing,
+    pub civil_twilight: SunCrossing,
+}
+
+impl SolarDay {
+    // the date is local, the offset only matters for the returned times
+    pub fn calculate(date: Date, offset: UtcOffset, coordinates: &GpsCoordinates) -> Self {
+        let latitude = coordinates.lat as f64;
+        let longitude = coordinates.lon as f64;
+
+        let days = (date.to_julian_day() as f64 - J2000) - longitude / 360.0;
+
+        let anomaly = (357.5291 + 0.98560028 * days)
+            .rem_euclid(360.0)
+            .to_radians();
+        let center =
+            1.9148 * anomaly.sin() + 0.02 * (2.0 * anomaly).sin() + 0.0003 * (3.0 * anomaly).sin();
+        let ecliptic_longitude = (anomaly.to_degrees() + center + 180.0 + 102.9372)
+            .rem_euclid(360.0)
+            .to_radians();
+
+        let transit =
+            J2000 + days + 0.0053 * anomaly.sin() - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+        let declination_sin = ecliptic_longitude.sin() * EARTH_AXIAL_TILT.to_radians().sin();
+
+        let crossing = |zenith: f64| {
+            let latitude = latitude.to_radians();
+            let declination_cos = (1.0 - declination_sin.powi(2)).sqrt();
+
+            let hour_angle_cos = (zenith.to_radians().cos() - latitude.sin() * declination_sin)
+                / (latitude.cos() * declination_cos);
+
+            if hour_angle_cos < -1.0 {
+                return SunCrossing::AlwaysAbove;
+            }
+
+            if hour_angle_cos > 1.0 {
+                return SunCrossing::AlwaysBelow;
+            }
+
+            let half_day = hour_angle_cos.acos().to_degrees() / 360.0;
+
+            SunCrossing::Crosses {
+                rise: from_julian_day(transit - half_day, offset),
+                set: from_julian_day(transit + half_day, offset),
+            }
+        };
+
+        Self {
+            date,
+            noon: from_julian_day(transit, offset),
+            daylight: crossing(SUNRISE_ZENITH),
+            civil_twilight: crossing(CIVIL_TWILIGHT_ZENITH),
+        }
+    }
+
+    pub fn crossing(&self, horizon: Horizon) -> SunCrossing {
+        match horizon {
+            Horizon::Sunrise => self.daylight,
+            Horizon::CivilTwilight => self.civil_twilight,
+        }
+    }
+
+    pub fn sunrise(&self) -> Option<OffsetDateTime> {
+        match self.daylight {
+            SunCrossing::Crosses { rise, .. } => Some(rise),
+            _ => None,
+        }
+    }
+
+    pub fn sunset(&self) -> Option<OffsetDateTime> {
+        match self.daylight {
+            SunCrossing::Crosses { set, .. } => Some(set),
+            _ => None,
+        }
+    }
+
+    fn above(&self, horizon: Horizon) -> Option<(OffsetDateTime, OffsetDateTime)> {
+        match self.crossing(horizon) {
+            SunCrossing::Crosses { rise, set } => Some((rise, set)),
+            SunCrossing::AlwaysAbove => {
+                let midnight = self
+                    .noon
+                    .replace_date(self.date)
+                    .replace_time(Time::MIDNIGHT);
+
+                Some((midnight, midnight + Duration::DAY))
+            }
+            SunCrossing::AlwaysBelow => None,
+        }
+    }
+}
+
+// consecutive days starting with the one of now
+pub fn solar_days(now: OffsetDateTime, coordinates: &GpsCoordinates, count: u8) -> Vec<SolarDay> {
+    (0..count as i64)
+        .filter_map(|x| now.date().checked_add(Duration::days(x)))
+        .map(|date| SolarDay::calculate(date, now.offset(), coordinates))
+        .collect()
+}
+
+// the parts of the range the sun spends below the horizon, anything not covered by days counts as below
+pub fn below_horizon(
+    days: &[SolarDay],
+    horizon: Horizon,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+    let mut above: Vec<(OffsetDateTime, OffsetDateTime)> =
+        days.iter().filter_map(|x| x.above(horizon)).collect();
+
+    above.sort_by_key(|x| x.0);
+
+    let mut below = vec![];
+    let mut cursor = from;
+
+    for (rise, set) in above {
+        if set <= cursor {
+            continue;
+        }
+
+        if rise >= to {
+            break;
+        }
+
+        if rise > cursor {
+            below.push((cursor, rise));
+        }
+
+        cursor = set;
+    }
+
+    if cursor < to {
+        below.push((cursor, to));
+    }
+
+    below
+}
+
+fn from_julian_day(julian_day: f64, offset: UtcOffset) -> OffsetDateTime {
+    let seconds = ((julian_day - UNIX_EPOCH_JULIAN_DAY) * 86400.0).round() as i64;
+
+    OffsetDateTime::from_unix_timestamp(seconds)
+        .unwrap()
+        .to_offset(offset)
+}
@@ -0,0 +1,343 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{crc::crc32, error::Error};
+
+// the largest blob a single nvs page holds, bigger ones fail once the partition is fragmented
+pub const DEFAULT_CHUNK_SIZE: usize = 1984;
+
+const MANIFEST_SLOT: &str = "m";
+const CHUNK_SLOTS: [&str; 2] = ["a", "b"];
+
+pub trait KeyValueStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>, Error>;
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error>;
+    fn delete(&mut self, key: &str) -> Result<(), Error>;
+    fn list(&self) -> Result<Vec<String>, Error>;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl KeyValueStorage for MemoryStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>, Error> {
+        self.entries
+            .get(key)
+            .cloned()
+            .ok_or_else(|| Error::from(format!("no value for {}", key)))
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        self.entries.insert(key.to_string(), data.to_vec());
+
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        self.entries.remove(key);
+
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        Ok(self.entries.keys().cloned().collect())
+    }
+}
+
+// one file per key, keys are unit kinds or hex chunk keys so they are safe file names
+pub struct FileStorage {
+    root: PathBuf,
+}
+
+impl FileStorage {
+    pub fn create(root: impl Into<PathBuf>) -> Result<Self, Error> {
+        let root = root.into();
+
+        fs::create_dir_all(&root)?;
+
+        Ok(Self { root })
+    }
+}
+
+impl KeyValueStorage for FileStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(fs::read(self.root.join(key))?)
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        // a crash mid write leaves the previous value in place
+        let temp = self.root.join(format!("{}.tmp", key));
+
+        fs::write(&temp, data)?;
+        fs::rename(&temp, self.root.join(key))?;
+
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        match fs::remove_file(self.root.join(key)) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(Error::from(error)),
+            _ => Ok(()),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        let mut keys = vec![];
+
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+
+            if !path.is_file() || path.extension().is_some_and(|x| x == "tmp") {
+                continue;
+            }
+
+            if let Some(name) = path.file_name().and_then(|x| x.to_str()) {
+                keys.push(name.to_string());
+            }
+        }
+
+        keys.sort();
+
+        Ok(keys)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StorageUsage {
+    pub key: String,
+    pub bytes: usize,
+    pub chunks: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ChunkManifest {
+    key: String,
+    generation: u32,
+    chunks: u32,
+    length: u32,
+    checksum: u32,
+}
+
+impl ChunkManifest {
+    fn slot(&self) -> &'static str {
+        CHUNK_SLOTS[self.generation as usize % CHUNK_SLOTS.len()]
+    }
+}
+
+// splits values over numbered keys, the chunks of a write go to the slot the live manifest
+// does not point to and the manifest is written last, so a crash leaves the previous value intact
+pub struct ChunkedStorage<TStorage> {
+    inner: TStorage,
+    chunk_size: usize,
+}
+
+impl<TStorage> ChunkedStorage<TStorage>
+where
+    TStorage: KeyValueStorage,
+{
+    pub fn new(inner: TStorage) -> Self {
+        Self::with_chunk_size(inner, DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(inner: TStorage, chunk_size: usize) -> Self {
+        Self {
+            inner,
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    pub fn inner(&self) -> &TStorage {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> TStorage {
+        self.inner
+    }
+
+    pub fn usage(&self) -> Result<Vec<StorageUsage>, Error> {
+        self.list()?.iter().map(|key| self.usage_of(key)).collect()
+    }
+
+    pub fn usage_of(&self, key: &str) -> Result<StorageUsage, Error> {
+        let usage = match self.manifest(key)? {
+            Some(manifest) => StorageUsage {
+                key: key.to_string(),
+                bytes: manifest.length as usize,
+                chunks: manifest.chunks as usize,
+            },
+            None => StorageUsage {
+                key: key.to_string(),
+                bytes: self.inner.read(key)?.len(),
+                chunks: 1,
+            },
+        };
+
+        Ok(usage)
+    }
+
+    // nvs keys are limited to 15 characters, the stem keeps the chunks of long keys apart
+    fn stem(key: &str) -> String {
+        format!("{:08x}.", crc32(key.as_bytes()))
+    }
+
+    fn manifest_key(key: &str) -> String {
+        format!("{}{}", Self::stem(key), MANIFEST_SLOT)
+    }
+
+    fn chunk_key(key: &str, slot: &str, index: u32) -> String {
+        format!("{}{}{}", Self::stem(key), slot, index)
+    }
+
+    // a missing manifest means the value is absent or still under its plain key from before chunking
+    fn manifest(&self, key: &str) -> Result<Option<ChunkManifest>, Error> {
+        let Ok(bytes) = self.inner.read(&Self::manifest_key(key)) else {
+            return Ok(None);
+        };
+
+        let manifest: ChunkManifest = rmp_serde::from_slice(&bytes)
+            .map_err(|err| Error::from(format!("broken manifest of {}: {}", key, err)))?;
+
+        if manifest.key != key {
+            return Err(Error::from(format!(
+                "manifest of {} belongs to {}",
+                key, manifest.key
+            )));
+        }
+
+        Ok(Some(manifest))
+    }
+
+    fn is_chunk_layer_key(key: &str) -> bool {
+        key.split_once('.')
+            .is_some_and(|(stem, _)| stem.len() == 8 && stem.chars().all(|x| x.is_ascii_hexdigit()))
+    }
+
+    // drops everything under the stem the live manifest does not reference
+    fn collect_garbage(&mut self, key: &str, live: Option<&ChunkManifest>) -> Result<(), Error> {
+        let stem = Self::stem(key);
+        let manifest_key = Self::manifest_key(key);
+
+        let live_chunks: Vec<String> = live
+            .map(|manifest| {
+                (0..manifest.chunks)
+                    .map(|index| Self::chunk_key(key, manifest.slot(), index))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for stored in self.inner.list()? {
+            let is_live =
+                (stored == manifest_key && live.is_some()) || live_chunks.contains(&stored);
+
+            if stored.starts_with(&stem) && !is_live {
+                self.inner.delete(&stored)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<TStorage> KeyValueStorage for ChunkedStorage<TStorage>
+where
+    TStorage: KeyValueStorage,
+{
+    fn read(&self, key: &str) -> Result<Vec<u8>, Error> {
+        let Some(manifest) = self.manifest(key)? else {
+            return self.inner.read(key);
+        };
+
+        let mut data = Vec::with_capacity(manifest.length as usize);
+
+        for index in 0..manifest.chunks {
+            let chunk = self
+                .inner
+                .read(&Self::chunk_key(key, manifest.slot(), index))
+                .map_err(|err| Error::from(format!("{} chunk {} missing: {}", key, index, err)))?;
+
+            data.extend(chunk);
+        }
+
+        if data.len() != manifest.length as usize || crc32(&data) != manifest.checksum {
+            return Err(Error::from(format!(
+                "{} chunks do not match the manifest",
+                key
+            )));
+        }
+
+        Ok(data)
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        let previous = self.manifest(key).ok().flatten();
+
+        let manifest = ChunkManifest {
+            key: key.to_string(),
+            generation: previous
+                .as_ref()
+                .map_or(0, |x| x.generation.wrapping_add(1)),
+            chunks: data.chunks(self.chunk_size).count() as u32,
+            length: data.len() as u32,
+            checksum: crc32(data),
+        };
+
+        for (index, chunk) in data.chunks(self.chunk_size).enumerate() {
+            self.inner
+                .write(&Self::chunk_key(key, manifest.slot(), index as u32), chunk)?;
+        }
+
+        let bytes = rmp_serde::to_vec(&manifest).map_err(|err| Error::from(err.to_string()))?;
+        self.inner.write(&Self::manifest_key(key), &bytes)?;
+
+        if previous.is_none() {
+            self.inner.delete(key)?;
+        }
+
+        self.collect_garbage(key, Some(&manifest))
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        self.inner.delete(&Self::manifest_key(key))?;
+        self.inner.delete(key)?;
+
+        self.collect_garbage(key, None)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        let mut keys = vec![];
+
+        for stored in self.inner.list()? {
+            if !Self::is_chunk_layer_key(&stored) {
+                keys.push(stored);
+                continue;
+            }
+
+            if !stored.ends_with(&format!(".{}", MANIFEST_SLOT)) {
+                continue;
+            }
+
+            let manifest: Result<ChunkManifest, _> =
+                rmp_serde::from_slice(&self.inner.read(&stored)?);
+
+            if let Ok(manifest) = manifest {
+                keys.push(manifest.key);
+            }
+        }
+
+        keys.sort();
+        keys.dedup();
+
+        Ok(keys)
+    }
+}
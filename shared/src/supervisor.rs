@@ -0,0 +1,415 @@
+use std::{
+    any::Any,
+    collections::VecDeque,
+    future::Future,
+    mem::discriminant,
+    panic::AssertUnwindSafe,
+    sync::{Arc, Mutex},
+};
+
+use futures::{future::LocalBoxFuture, stream::FuturesUnordered, FutureExt, StreamExt};
+use log::{error, info, warn};
+use tokio::sync::watch;
+
+use crate::{commands::Commands, error::Error, events::Events, message_bus::MessageBus};
+
+// restarts catch the unwinding panic, a build with panic=abort (the firmware) never restarts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+    Never,
+    OnPanic { max_restarts: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleHealth {
+    Pending,
+    Starting,
+    Running,
+    ShuttingDown,
+    Stopped,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleStatus {
+    pub name: &'static str,
+    pub health: ModuleHealth,
+    pub restarts: u32,
+}
+
+#[derive(Clone)]
+pub struct SupervisorHealth {
+    statuses: Arc<Mutex<Vec<ModuleStatus>>>,
+}
+
+pub struct ModuleSpec<'a> {
+    name: &'static str,
+    dependencies: Vec<&'static str>,
+    ready_on: Option<Events>,
+    restart_policy: RestartPolicy,
+    factory: Box<dyn FnMut(MessageBus) -> LocalBoxFuture<'a, ()> + 'a>,
+}
+
+pub struct Supervisor<'a> {
+    bus: MessageBus,
+    modules: Vec<ModuleSpec<'a>>,
+    health: SupervisorHealth,
+}
+
+enum Signal {
+    Ready(usize),
+    Finished(usize, bool),
+    ShutdownRequested,
+}
+
+struct Slot {
+    shutdown: Option<watch::Sender<bool>>,
+    running: bool,
+    ready: bool,
+}
+
+impl<'a> ModuleSpec<'a> {
+    pub fn new<TFactory, TFuture>(name: &'static str, mut factory: TFactory) -> Self
+    where
+        TFactory: FnMut(MessageBus) -> TFuture + 'a,
+        TFuture: Future<Output = ()> + 'a,
+    {
+        Self {
+            name,
+            dependencies: vec![],
+            ready_on: None,
+            restart_policy: RestartPolicy::Never,
+            factory: Box::new(move |bus| factory(bus).boxed_local()),
+        }
+    }
+
+    pub fn depends_on(mut self, name: &'static str) -> Self {
+        self.dependencies.push(name);
+        self
+    }
+
+    pub fn ready_on(mut self, event: Events) -> Self {
+        self.ready_on = Some(event);
+        self
+    }
+
+    pub fn restart(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+}
+
+impl SupervisorHealth {
+    pub fn get(&self, name: &str) -> Option<ModuleStatus> {
+        self.statuses
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|x| x.name == name)
+            .cloned()
+    }
+
+    pub fn all(&self) -> Vec<ModuleStatus> {
+        self.statuses.lock().unwrap().clone()
+    }
+
+    fn push(&self, name: &'static str) {
+        self.statuses.lock().unwrap().push(ModuleStatus {
+            name,
+            health: ModuleHealth::Pending,
+            restarts: 0,
+        });
+    }
+
+    fn set(&self, index: usize, health: ModuleHealth) {
+        let mut statuses = self.statuses.lock().unwrap();
+        let status = &mut statuses[index];
+
+        info!("{} {:?} -> {:?}", status.name, status.health, health);
+
+        status.health = health;
+    }
+
+    fn restarts(&self, index: usize) -> u32 {
+        self.statuses.lock().unwrap()[index].restarts
+    }
+
+    fn count_restart(&self, index: usize) {
+        self.statuses.lock().unwrap()[index].restarts += 1;
+    }
+}
+
+impl<'a> Supervisor<'a> {
+    // only a clone is kept, the receivers of a new bus would queue everything nobody reads
+    pub fn new(bus: MessageBus) -> Self {
+        Self {
+            bus: bus.clone(),
+            modules: vec![],
+            health: SupervisorHealth {
+                statuses: Arc::new(Mutex::new(vec![])),
+            },
+        }
+    }
+
+    pub fn register(&mut self, module: ModuleSpec<'a>) {
+        self.health.push(module.name);
+        self.modules.push(module);
+    }
+
+    pub fn bus(&self) -> MessageBus {
+        self.bus.clone()
+    }
+
+    pub fn health(&self) -> SupervisorHealth {
+        self.health.clone()
+    }
+
+    pub async fn run(mut self) -> Result<(), Error> {
+        let order = self.start_order()?;
+
+        info!("starting {} modules...", order.len());
+
+        let mut slots: Vec<Slot> = self
+            .modules
+            .iter()
+            .map(|_| Slot {
+                shutdown: None,
+                running: false,
+                ready: false,
+            })
+            .collect();
+
+        let mut signals: FuturesUnordered<LocalBoxFuture<'a, Signal>> = FuturesUnordered::new();
+
+        let mut commands = self.bus.subscribe_commands("supervisor");
+
+        signals.push(
+            async move {
+                loop {
+                    if let Ok(Commands::StartDeepSleep) = commands.recv().await {
+                        return Signal::ShutdownRequested;
+                    }
+                }
+            }
+            .boxed_local(),
+        );
+
+        self.start_unblocked(&order, &mut slots, &mut signals);
+
+        let mut shutdown_queue: Option<VecDeque<usize>> = None;
+
+        while slots.iter().any(|x| x.running) {
+            let Some(signal) = signals.next().await else {
+                break;
+            };
+
+            match signal {
+                Signal::Ready(index) => {
+                    if slots[index].running && shutdown_queue.is_none() {
+                        slots[index].ready = true;
+                        self.health.set(index, ModuleHealth::Running);
+                        self.start_unblocked(&order, &mut slots, &mut signals);
+                    }
+                }
+                Signal::Finished(index, panicked) => {
+                    slots[index].running = false;
+                    slots[index].shutdown = None;
+
+                    let module = &self.modules[index];
+
+                    let can_restart = match module.restart_policy {
+                        RestartPolicy::OnPanic { max_restarts } => {
+                            self.health.restarts(index) < max_restarts
+                        }
+                        RestartPolicy::Never => false,
+                    };
+
+                    if panicked && can_restart && shutdown_queue.is_none() {
+                        warn!("restarting {}...", module.name);
+                        self.health.count_restart(index);
+                        self.start_module(index, &mut slots, &mut signals);
+                    } else if panicked {
+                        self.health.set(index, ModuleHealth::Failed);
+                    } else {
+                        self.health.set(index, ModuleHealth::Stopped);
+
+                        // a module that is done on its own doesn't block its dependents
+                        if shutdown_queue.is_none() {
+                            slots[index].ready = true;
+                            self.start_unblocked(&order, &mut slots, &mut signals);
+                        }
+                    }
+                }
+                Signal::ShutdownRequested => {
+                    info!("shutting down...");
+                    shutdown_queue = Some(order.iter().rev().copied().collect());
+                }
+            }
+
+            if let Some(queue) = shutdown_queue.as_mut() {
+                self.shutdown_next(queue, &mut slots);
+            }
+        }
+
+        for status in self.health.all() {
+            if status.health == ModuleHealth::Pending {
+                warn!("{} was never started", status.name);
+            }
+        }
+
+        info!("done.");
+
+        Ok(())
+    }
+
+    fn start_order(&self) -> Result<Vec<usize>, Error> {
+        let mut dependencies: Vec<Vec<usize>> = vec![];
+
+        for module in self.modules.iter() {
+            let mut indices = vec![];
+
+            for dependency in module.dependencies.iter() {
+                match self.modules.iter().position(|x| x.name == *dependency) {
+                    Some(index) => indices.push(index),
+                    None => {
+                        return Err(Error::from(format!(
+                            "{} depends on unknown module {}",
+                            module.name, dependency
+                        )))
+                    }
+                }
+            }
+
+            dependencies.push(indices);
+        }
+
+        let mut order = vec![];
+
+        while order.len() < self.modules.len() {
+            let next = (0..self.modules.len()).find(|index| {
+                !order.contains(index) && dependencies[*index].iter().all(|x| order.contains(x))
+            });
+
+            match next {
+                Some(index) => order.push(index),
+                None => return Err(Error::from("modules have circular dependencies")),
+            }
+        }
+
+        Ok(order)
+    }
+
+    fn start_unblocked(
+        &mut self,
+        order: &[usize],
+        slots: &mut [Slot],
+        signals: &mut FuturesUnordered<LocalBoxFuture<'a, Signal>>,
+    ) {
+        for index in order.iter().copied() {
+            let module = &self.modules[index];
+
+            if !matches!(
+                self.health.get(module.name).map(|x| x.health),
+                Some(ModuleHealth::Pending)
+            ) {
+                continue;
+            }
+
+            let is_unblocked = module.dependencies.iter().all(|dependency| {
+                let dependency_index = self.modules.iter().position(|x| x.name == *dependency);
+                dependency_index.is_some_and(|x| slots[x].ready)
+            });
+
+            if is_unblocked {
+                self.start_module(index, slots, signals);
+            }
+        }
+    }
+
+    fn start_module(
+        &mut self,
+        index: usize,
+        slots: &mut [Slot],
+        signals: &mut FuturesUnordered<LocalBoxFuture<'a, Signal>>,
+    ) {
+        let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+
+        let module = &mut self.modules[index];
+        let name = module.name;
+
+        // subscribed before the module starts so the readiness event can't slip by
+        let ready_signal = module.ready_on.clone().map(|ready_on| {
+            let mut events = self.bus.subscribe_events(name);
+
+            async move {
+                loop {
+                    if let Ok(event) = events.recv().await {
+                        if discriminant(&event) == discriminant(&ready_on) {
+                            return Signal::Ready(index);
+                        }
+                    }
+                }
+            }
+            .boxed_local()
+        });
+
+        let task = (module.factory)(self.bus.supervised(shutdown_receiver));
+
+        signals.push(
+            AssertUnwindSafe(task)
+                .catch_unwind()
+                .map(move |result| match result {
+                    Ok(_) => Signal::Finished(index, false),
+                    Err(panic) => {
+                        error!("{} panicked: {}", name, Self::panic_message(&panic));
+                        Signal::Finished(index, true)
+                    }
+                })
+                .boxed_local(),
+        );
+
+        slots[index] = Slot {
+            shutdown: Some(shutdown_sender),
+            running: true,
+            ready: ready_signal.is_none(),
+        };
+
+        match ready_signal {
+            Some(ready_signal) => {
+                self.health.set(index, ModuleHealth::Starting);
+                signals.push(ready_signal);
+            }
+            None => self.health.set(index, ModuleHealth::Running),
+        }
+    }
+
+    fn shutdown_next(&self, queue: &mut VecDeque<usize>, slots: &mut [Slot]) {
+        while let Some(index) = queue.front().copied() {
+            let slot = &mut slots[index];
+
+            if !slot.running {
+                queue.pop_front();
+                continue;
+            }
+
+            if let Some(shutdown) = slot.shutdown.as_ref() {
+                if !*shutdown.borrow() {
+                    self.health.set(index, ModuleHealth::ShuttingDown);
+                    shutdown.send_replace(true);
+                }
+            }
+
+            return;
+        }
+    }
+
+    fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+        if let Some(message) = panic.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = panic.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown".to_string()
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use time::{Duration, OffsetDateTime};
+
+use crate::reference_data::ReferenceTimeUtc;
+
+pub const FORECAST_STEP: Duration = Duration::hours(1);
+
+#[derive(Debug, Serialize_repr, Deserialize_repr, PartialEq, Clone, Copy, Hash)]
+#[repr(u8)]
+pub enum WeatherCondition {
+    Unknown = 0,
+    Clear = 1,
+    PartlyCloudy = 2,
+    Cloudy = 3,
+    Fog = 4,
+    Drizzle = 5,
+    Rain = 6,
+    Snow = 7,
+    Thunderstorm = 8,
+}
+
+// temperature in degrees celsius, wind speed in m/s and the direction it blows from in degrees
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct HourlyForecast {
+    pub at: ReferenceTimeUtc,
+    pub condition: WeatherCondition,
+    pub temperature: f32,
+    pub precipitation_probability: u8,
+    pub wind_speed: f32,
+    pub wind_direction: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Daylight {
+    pub sunrise: ReferenceTimeUtc,
+    pub sunset: ReferenceTimeUtc,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct WeatherForecast {
+    pub issued_at: ReferenceTimeUtc,
+    pub hours: Vec<HourlyForecast>,
+    pub daylight: Vec<Daylight>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CurrentWeather {
+    pub condition: WeatherCondition,
+    pub temperature: f32,
+    pub precipitation_probability: u8,
+    pub wind_speed: f32,
+    pub wind_direction: u16,
+    pub is_daylight: bool,
+}
+
+impl WeatherForecast {
+    pub fn sorted(mut self) -> Self {
+        self.hours.sort_by_key(|x| x.at.unix_epoch_seconds);
+        self.daylight.sort_by_key(|x| x.sunrise.unix_epoch_seconds);
+        self
+    }
+
+    pub fn hour_at(&self, now: OffsetDateTime) -> Option<&HourlyForecast> {
+        let timestamp = now.unix_timestamp();
+
+        self.hours.iter().rev().find(|x| {
+            x.at.unix_epoch_seconds <= timestamp
+                && timestamp < x.at.unix_epoch_seconds + FORECAST_STEP.whole_seconds()
+        })
+    }
+
+    pub fn is_daylight(&self, now: OffsetDateTime) -> bool {
+        let timestamp = now.unix_timestamp();
+
+        self.daylight.iter().any(|x| {
+            x.sunrise.unix_epoch_seconds <= timestamp && timestamp < x.sunset.unix_epoch_seconds
+        })
+    }
+
+    // none once the forecast ran out, a stale forecast is worse than no forecast
+    pub fn current(&self, now: OffsetDateTime) -> Option<CurrentWeather> {
+        let hour = self.hour_at(now)?;
+
+        Some(CurrentWeather {
+            condition: hour.condition,
+            temperature: hour.temperature,
+            precipitation_probability: hour.precipitation_probability,
+            wind_speed: hour.wind_speed,
+            wind_direction: hour.wind_direction,
+            is_daylight: self.is_daylight(now),
+        })
+    }
+}
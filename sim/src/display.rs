@@ -1,16 +1,22 @@
 use std::{convert::Infallible, time::Instant};
 
+use blinky_shared::commands::Commands;
+use blinky_shared::dirty_region::{
+    layer_index, merge_layers, window_pixels, DirtyLayers, DirtyTracker, PixelCounter,
+};
 use blinky_shared::display_interface::{ClockDisplayInterface, LayerType, RenderMode};
+use blinky_shared::domain::TouchPosition;
+use blinky_shared::events::Events;
+use blinky_shared::message_bus::MessageBus;
 use embedded_graphics::{
     draw_target::DrawTarget,
     geometry::{Point, Size},
     pixelcolor::{Rgb565, RgbColor},
-    primitives::Rectangle,
     Pixel,
 };
 use embedded_graphics_framebuf::FrameBuf;
 use embedded_graphics_simulator::{
-    BinaryColorTheme, OutputSettingsBuilder, SimulatorDisplay, Window,
+    BinaryColorTheme, OutputSettingsBuilder, SimulatorDisplay, SimulatorEvent, Window,
 };
 use enumflags2::{BitFlag, BitFlags};
 use log::{debug, info};
@@ -21,8 +27,11 @@ pub struct SimDisplay {
     display: SimulatorDisplay<Rgb565>,
     buffer_base: Box<[Rgb565]>,
     buffer_layers: Vec<Box<[Rgb565]>>,
+    dirty_layers: DirtyLayers,
     is_first_render: bool,
     window: Window,
+    touch: Option<MessageBus>,
+    pressed: bool,
 }
 
 unsafe impl Send for SimDisplay {}
@@ -52,8 +61,47 @@ impl SimDisplay {
             display,
             buffer_layers,
             buffer_base: Self::prepare_frame_buf(),
+            dirty_layers: DirtyLayers::new(Self::FRAME_BUFFER_SIDE),
             is_first_render: true,
             window,
+            touch: None,
+            pressed: false,
+        }
+    }
+
+    // the mouse stands in for the touchpad, held button is a finger on the glass
+    pub fn with_touch(mut self, bus: MessageBus) -> Self {
+        self.touch = Some(bus);
+        self
+    }
+
+    fn poll_touch(&mut self) {
+        let Some(bus) = &self.touch else {
+            return;
+        };
+
+        let position = |point: Point| TouchPosition {
+            x: point.x,
+            y: point.y,
+        };
+
+        for event in self.window.events() {
+            match event {
+                SimulatorEvent::MouseButtonDown { point, .. } => {
+                    self.pressed = true;
+                    bus.send_event(Events::TouchPos(position(point)));
+                }
+                SimulatorEvent::MouseMove { point } if self.pressed => {
+                    bus.send_event(Events::TouchPos(position(point)));
+                }
+                SimulatorEvent::MouseButtonUp { point, .. } if self.pressed => {
+                    self.pressed = false;
+                    bus.send_event(Events::TouchPos(position(point)));
+                    bus.send_event(Events::TouchReleased);
+                }
+                SimulatorEvent::Quit => bus.send_cmd(Commands::StartDeepSleep),
+                _ => {}
+            }
         }
     }
 }
@@ -63,8 +111,9 @@ impl ClockDisplayInterface for SimDisplay {
 
     type ColorModel = Rgb565;
 
-    type FrameBuffer<'b> =
-        FrameBuf<Self::ColorModel, &'b mut [Self::ColorModel; Self::FRAME_BUFFER_SIZE]>;
+    type FrameBuffer<'b> = DirtyTracker<
+        FrameBuf<Self::ColorModel, &'b mut [Self::ColorModel; Self::FRAME_BUFFER_SIZE]>,
+    >;
 
     const FRAME_BUFFER_SIDE: usize = 466;
 
@@ -76,11 +125,7 @@ impl ClockDisplayInterface for SimDisplay {
         mode: RenderMode,
         func: impl FnOnce(Self::FrameBuffer<'c>) -> Self::FrameBuffer<'c>,
     ) {
-        let layer_value = layer as i32;
-
-        let log2 = f32::log2(layer_value as f32);
-
-        let layer_index: usize = f32::round(log2) as usize;
+        let layer_index = layer_index(layer);
 
         debug!("rendering {:?} to index {:?}", layer, layer_index);
 
@@ -96,45 +141,33 @@ impl ClockDisplayInterface for SimDisplay {
             frame.reset();
         }
 
-        func(frame);
+        let (_, drawn) = func(DirtyTracker::new(frame)).into_parts();
 
         let timing_frame = now.elapsed();
 
-        debug!("render timing: frame {}", timing_frame.as_millis());
+        debug!(
+            "render timing: frame {}, dirty {:?}",
+            timing_frame.as_millis(),
+            drawn.rects()
+        );
+
+        self.dirty_layers.rendered(layer, mode, drawn);
     }
 
     fn commit(&mut self, merge_layers_mask: BitFlags<LayerType>) {
-        let layers_count = self.buffer_layers.len();
-
-        let base_layer = self.buffer_base.as_mut();
-
-        base_layer.fill(Rgb565::BLACK);
-
-        for layer_index in 0..layers_count {
-            if !merge_layers_mask.contains(BitFlags::from_bits_truncate(1 << layer_index)) {
-                continue;
-            }
-
-            let layer = self.buffer_layers[layer_index].as_ref();
-
-            for pixel_index in 0..layer.len() {
-                if layer[pixel_index] == RgbColor::BLACK {
-                    continue;
-                }
-
-                base_layer[pixel_index] = layer[pixel_index];
-            }
+        let dirty = self.dirty_layers.take(merge_layers_mask);
+
+        for rect in dirty.rects() {
+            merge_layers(
+                &mut self.buffer_base,
+                &self.buffer_layers,
+                merge_layers_mask,
+                Self::FRAME_BUFFER_SIDE,
+                rect,
+            );
         }
 
-        let rect = Rectangle::new(
-            Point::zero(),
-            Size::new(
-                Self::FRAME_BUFFER_SIDE as u32,
-                Self::FRAME_BUFFER_SIDE as u32,
-            ),
-        );
-
-        let data = self.buffer_base.as_mut();
+        let data = self.buffer_base.as_ref();
 
         if self.is_first_render {
             let iter = data.iter().enumerate().filter_map(|item| {
@@ -154,12 +187,26 @@ impl ClockDisplayInterface for SimDisplay {
 
             self.is_first_render = false;
         } else {
-            let iter = data.iter().map(|x| *x);
+            for rect in dirty.rects() {
+                let iter = window_pixels(data, Self::FRAME_BUFFER_SIDE, rect);
 
-            self.display.fill_contiguous(&rect, iter).unwrap();
+                self.display.fill_contiguous(rect, iter).unwrap();
+            }
         }
 
+        debug!(
+            "pushed {} pixels in {} windows",
+            dirty.area(),
+            dirty.rects().len()
+        );
+
         self.window.update(&self.display);
+
+        self.poll_touch();
+    }
+
+    fn pixel_counter(&self) -> PixelCounter {
+        self.dirty_layers.counter()
     }
 }
 
@@ -1,27 +1,51 @@
 #![feature(vec_push_within_capacity)]
 #![feature(duration_constructors)]
 
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 use std::ops::Add;
 use std::sync::Arc;
 
-use blinky_shared::calendar::{CalendarEvent, CalendarEventKey, CalendarKind};
+use blinky_shared::calendar::{
+    CalendarEvent, CalendarEventKey, CalendarKind, TimelyDataMarker, TimelyDataRecord,
+};
 use blinky_shared::display_interface::ClockDisplayInterface;
 use blinky_shared::events::Events;
 use blinky_shared::fasttrack::FastTrackRtcData;
 use blinky_shared::message_bus::MessageBus;
+use blinky_shared::modules::calendar_module::CalendarModule;
 use blinky_shared::modules::fonts_set::FontSet466;
+use blinky_shared::modules::gesture_module::GestureModule;
 use blinky_shared::modules::icon_set_466::IconsSet466;
+use blinky_shared::modules::layout::{Face, WatchfaceLayout};
+use blinky_shared::modules::persister_module::PersisterModule;
+use blinky_shared::modules::recorder::{
+    from_hex_dump, Recorder, ReplayPace, Replayer, TraceRecord,
+};
+use blinky_shared::modules::solar_module::SolarModule;
+use blinky_shared::modules::weather_module::WeatherModule;
+use blinky_shared::reference_data::GpsCoordinates;
+use blinky_shared::storage::FileStorage;
+use blinky_shared::supervisor::{ModuleSpec, Supervisor};
+use blinky_shared::weather::{Daylight, HourlyForecast, WeatherCondition, WeatherForecast};
 use blinky_shared::{commands::Commands, modules::renderer::Renderer};
 use display::SimDisplay;
 use env_logger::{Builder, Target};
 use log::{info, LevelFilter};
 use time::macros::datetime;
 use time::{OffsetDateTime, Time, UtcOffset};
-use tokio::join;
 use tokio::time::{sleep, Duration};
 
 mod display;
 
+const STORAGE_DIR_ENV: &str = "BLINKY_SIM_STORAGE";
+const DEFAULT_STORAGE_DIR: &str = "sim_storage";
+const RECORD_PATH_ENV: &str = "BLINKY_SIM_RECORD";
+// a console log of a firmware built with the trace feature, the trace is hex dumped in it
+const CONSOLE_LOG_EXTENSION: &str = ".log";
+// msgpack encoded WatchfaceLayout, the default face without it
+const FACE_PATH_ENV: &str = "BLINKY_SIM_FACE";
+
 extern crate blinky_shared;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -41,30 +65,110 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
-    let message_bus = MessageBus::new();
+    let trace = match std::env::args().nth(1) {
+        Some(path) => Some(read_trace(&path)?),
+        None => None,
+    };
 
-    let message_bus_clone = message_bus.clone();
+    let mut supervisor = Supervisor::new(MessageBus::new());
+
+    let message_bus = supervisor.bus();
+
+    if let Ok(path) = std::env::var(RECORD_PATH_ENV) {
+        let mut writer = Some(BufWriter::new(File::create(&path)?));
 
-    let display = SimDisplay::create();
+        info!("recording trace to {}", path);
 
-    let rtc_data = FastTrackRtcData {
-        alarm_status: false,
-        now: None,
+        supervisor.register(ModuleSpec::new("recorder", move |bus| {
+            Recorder::start(bus, writer.take().unwrap())
+        }));
+    }
+
+    let mut display = Some(SimDisplay::create().with_touch(message_bus.clone()));
+
+    let layout = match std::env::var(FACE_PATH_ENV) {
+        Ok(path) => WatchfaceLayout::from_msgpack(&std::fs::read(path)?).map_err(|err| err.0)?,
+        Err(_) => WatchfaceLayout::default(),
     };
 
-    let renderer_task = Renderer::<SimDisplay, FontSet466, IconsSet466>::start(
-        message_bus_clone,
-        display,
-        rtc_data,
+    supervisor.register(
+        ModuleSpec::new("renderer", move |bus| {
+            let rtc_data = FastTrackRtcData {
+                alarm_status: false,
+                now: None,
+            };
+
+            Renderer::<SimDisplay, FontSet466, IconsSet466>::start_with_layout(
+                bus,
+                display.take().unwrap(),
+                rtc_data,
+                layout.clone(),
+            )
+        })
+        .ready_on(Events::FirstRender),
+    );
+
+    // calendar state survives sim restarts the same way it survives deep sleep on the watch
+    let storage_dir =
+        std::env::var(STORAGE_DIR_ENV).unwrap_or_else(|_| DEFAULT_STORAGE_DIR.to_string());
+
+    supervisor.register(
+        ModuleSpec::new("persister", move |bus| {
+            let storage = FileStorage::create(&storage_dir).unwrap();
+
+            async move {
+                PersisterModule::start(bus, storage).await;
+            }
+        })
+        .depends_on("renderer"),
+    );
+
+    supervisor.register(
+        ModuleSpec::new("calendar", CalendarModule::start)
+            .depends_on("renderer")
+            .depends_on("persister"),
+    );
+
+    supervisor.register(
+        ModuleSpec::new("weather", WeatherModule::start)
+            .depends_on("renderer")
+            .depends_on("persister"),
+    );
+
+    supervisor.register(
+        ModuleSpec::new("gestures", |bus| {
+            GestureModule::start(bus, SimDisplay::FRAME_BUFFER_SIDE)
+        })
+        .depends_on("renderer"),
+    );
+
+    supervisor.register(
+        ModuleSpec::new("solar", SolarModule::start)
+            .depends_on("renderer")
+            .depends_on("persister"),
     );
 
     let message_bus_clone = message_bus.clone();
     tokio::task::spawn_blocking(move || {
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        message_bus_clone.send_cmd(Commands::StartDeepSleep);
+        // "a" and "d" switch the face, anything else ends the simulation
+        loop {
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input).unwrap();
+
+            match input.trim() {
+                "a" => message_bus_clone
+                    .send_cmd(Commands::SetFace(Face::Analog { second_hand: true })),
+                "d" => message_bus_clone.send_cmd(Commands::SetFace(Face::Digital)),
+                _ => {
+                    message_bus_clone.send_cmd(Commands::StartDeepSleep);
+                    break;
+                }
+            }
+        }
     });
 
+    let message_bus_clone = message_bus.clone();
+
     let startup_sequence = async move {
         sleep(Duration::from_millis(1000)).await;
 
@@ -83,12 +187,13 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
             + Duration::from_secs(5);
 
         message_bus.send_event(Events::TimeNow(now));
+        message_bus.send_cmd(Commands::SetTimezone(now.offset().whole_seconds()));
         message_bus.send_cmd(Commands::ResumeRendering);
         message_bus.send_event(Events::BatteryLevel(80));
         message_bus.send_event(Events::BleClientConnected);
         message_bus.send_event(Events::Temperature(20));
 
-        message_bus.send_event(Events::CalendarEvent(CalendarEvent {
+        message_bus.send_event(Events::ReferenceCalendarEvent(CalendarEvent {
             id: 0,
             kind: CalendarKind::Phone,
             start: now + Duration::from_secs(10),
@@ -98,9 +203,11 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
             icon: blinky_shared::calendar::CalendarEventIcon::Rain,
             color: 255,
             lane: 1,
+            recurrence: None,
+            reminder_offsets: None,
         }));
 
-        message_bus.send_event(Events::CalendarEvent(CalendarEvent {
+        message_bus.send_event(Events::ReferenceCalendarEvent(CalendarEvent {
             id: 1,
             kind: CalendarKind::Phone,
             start: now + Duration::from_hours(3),
@@ -110,9 +217,11 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
             icon: blinky_shared::calendar::CalendarEventIcon::Rain,
             color: 0,
             lane: 1,
+            recurrence: None,
+            reminder_offsets: None,
         }));
 
-        message_bus.send_event(Events::CalendarEvent(CalendarEvent {
+        message_bus.send_event(Events::ReferenceCalendarEvent(CalendarEvent {
             id: 2,
             kind: CalendarKind::Phone,
             start: now + Duration::from_hours(3),
@@ -122,6 +231,8 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
             icon: blinky_shared::calendar::CalendarEventIcon::CalendarAlert,
             color: 0,
             lane: 2,
+            recurrence: None,
+            reminder_offsets: None,
         }));
 
         let sample_event = CalendarEvent {
@@ -134,9 +245,85 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
             icon: blinky_shared::calendar::CalendarEventIcon::Car,
             color: 0,
             lane: 0,
+            recurrence: None,
+            reminder_offsets: None,
         };
 
-        message_bus.send_event(Events::CalendarEvent(sample_event.clone()));
+        message_bus.send_event(Events::ReferenceCalendarEvent(sample_event.clone()));
+
+        // an hourly forecast, drawn as the precipitation band and the temperature sparkline
+        message_bus.send_event(Events::ReferenceCalendarEvent(CalendarEvent {
+            id: 5,
+            kind: CalendarKind::Weather,
+            start: now - Duration::from_hours(1),
+            end: now + Duration::from_hours(24),
+            title: "Weather".to_string(),
+            description: "".to_string(),
+            icon: blinky_shared::calendar::CalendarEventIcon::Temperature,
+            color: 0,
+            lane: 0,
+            recurrence: None,
+            reminder_offsets: None,
+        }));
+
+        let forecast = (0..24u8)
+            .flat_map(|hour| {
+                let precipitation = match hour {
+                    14 => 0.3,
+                    15 => 1.2,
+                    16 => 3.0,
+                    17 => 8.0,
+                    18 => 0.8,
+                    _ => 0.0,
+                };
+
+                let temperature =
+                    14.0 + 8.0 * ((hour as f32 - 15.0) / 24.0 * 2.0 * std::f32::consts::PI).cos();
+
+                [
+                    (precipitation, TimelyDataMarker::Precipitation),
+                    (temperature, TimelyDataMarker::Temperature),
+                ]
+                .map(|(value, data_marker)| TimelyDataRecord {
+                    linked_event_id: 5,
+                    start_at_hour: hour,
+                    duration: time::Duration::hours(1),
+                    value,
+                    data_marker,
+                })
+            })
+            .collect();
+
+        message_bus.send_event(Events::ReferenceTimelyDataBatch(Arc::new(forecast)));
+        message_bus.send_event(Events::InSync(true));
+
+        message_bus.send_event(Events::ReferenceLocation(GpsCoordinates {
+            lat: 52.2297,
+            lon: 21.0122,
+        }));
+
+        let midnight = now.replace_time(Time::MIDNIGHT);
+
+        let hours = (0..24)
+            .map(|hour| HourlyForecast {
+                at: (midnight + time::Duration::hours(hour)).into(),
+                condition: WeatherCondition::PartlyCloudy,
+                temperature: 14.0
+                    + 8.0 * ((hour as f32 - 15.0) / 24.0 * 2.0 * std::f32::consts::PI).cos(),
+                precipitation_probability: 20,
+                wind_speed: 3.5,
+                wind_direction: 240,
+            })
+            .collect();
+
+        message_bus.send_event(Events::ReferenceWeather(WeatherForecast {
+            issued_at: now.into(),
+            hours,
+            daylight: vec![Daylight {
+                sunrise: (midnight + time::Duration::hours(6)).into(),
+                sunset: (midnight + time::Duration::minutes(20 * 60 + 30)).into(),
+            }],
+        }));
 
         let mut toggler = false;
 
@@ -151,11 +338,38 @@ async fn main_async() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    let startup_sequence_task = tokio::spawn(startup_sequence);
+    let startup_sequence_task = match trace {
+        Some(records) => tokio::spawn(async move {
+            Replayer::replay(&message_bus_clone, records, ReplayPace::Original).await;
+        }),
+        None => tokio::spawn(startup_sequence),
+    };
 
-    join!(renderer_task);
+    supervisor.run().await.map_err(|err| err.0)?;
 
     startup_sequence_task.abort();
 
     Ok(())
 }
+
+fn read_trace(path: &str) -> Result<Vec<TraceRecord>, Box<dyn std::error::Error>> {
+    let records = match path.ends_with(CONSOLE_LOG_EXTENSION) {
+        true => {
+            let log = std::fs::read_to_string(path)
+                .map_err(|err| format!("cannot open trace {}: {}", path, err))?;
+
+            from_hex_dump(&log).and_then(|trace| Replayer::read_trace(trace.as_slice()))
+        }
+        false => {
+            let file =
+                File::open(path).map_err(|err| format!("cannot open trace {}: {}", path, err))?;
+
+            Replayer::read_trace(BufReader::new(file))
+        }
+    }
+    .map_err(|err| format!("cannot read trace {}: {}", path, err.0))?;
+
+    info!("replaying {} records from {}", records.len(), path);
+
+    Ok(records)
+}
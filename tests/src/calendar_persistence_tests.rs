@@ -26,6 +26,8 @@ async fn should_serialize_and_deserialize_persistence_unit() {
         kind: CalendarKind::Phone,
         description: "".to_string(),
         lane: 0,
+        recurrence: None,
+        reminder_offsets: None,
     };
 
     let persistence_unit = PersistenceUnit::new(PersistenceUnitKind::CalendarSyncInfo, &event);
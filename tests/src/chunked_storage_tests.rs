@@ -0,0 +1,182 @@
+use blinky_shared::{
+    error::Error,
+    storage::{ChunkedStorage, KeyValueStorage, MemoryStorage, StorageUsage},
+};
+
+const CHUNK_SIZE: usize = 16;
+
+// fails every write after the given number of them, like a reset in the middle of persisting
+struct InterruptedStorage {
+    inner: MemoryStorage,
+    writes_left: usize,
+}
+
+impl KeyValueStorage for InterruptedStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>, Error> {
+        self.inner.read(key)
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        if self.writes_left == 0 {
+            return Err(Error::from("interrupted"));
+        }
+
+        self.writes_left -= 1;
+        self.inner.write(key, data)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        self.inner.delete(key)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.inner.list()
+    }
+}
+
+#[test]
+fn should_split_large_values_and_reassemble_them() {
+    let mut storage = ChunkedStorage::with_chunk_size(MemoryStorage::new(), CHUNK_SIZE);
+
+    let calendar = blob(100, 1);
+    let reminders = blob(16, 2);
+
+    storage.write("CalendarSyncInfo", &calendar).unwrap();
+    storage.write("Reminders", &reminders).unwrap();
+    storage.write("PairingKey", &[]).unwrap();
+
+    assert_eq!(storage.read("CalendarSyncInfo").unwrap(), calendar);
+    assert_eq!(storage.read("Reminders").unwrap(), reminders);
+    assert_eq!(storage.read("PairingKey").unwrap(), Vec::<u8>::new());
+    assert!(storage.read("TimezoneRules").is_err());
+
+    assert_eq!(
+        storage.list().unwrap(),
+        vec!["CalendarSyncInfo", "PairingKey", "Reminders"]
+    );
+
+    assert_eq!(
+        storage.usage().unwrap(),
+        vec![
+            usage("CalendarSyncInfo", 100, 7),
+            usage("PairingKey", 0, 0),
+            usage("Reminders", 16, 1),
+        ]
+    );
+
+    // manifests plus chunks, all within the nvs key length
+    let stored = storage.inner().list().unwrap();
+
+    assert_eq!(stored.len(), 3 + 7 + 1);
+    assert!(stored.iter().all(|x| x.len() <= 15));
+}
+
+#[test]
+fn should_collect_stale_chunks_of_bigger_older_writes() {
+    let mut storage = ChunkedStorage::with_chunk_size(MemoryStorage::new(), CHUNK_SIZE);
+
+    storage.write("CalendarSyncInfo", &blob(160, 1)).unwrap();
+    storage.write("CalendarSyncInfo", &blob(200, 2)).unwrap();
+    storage.write("CalendarSyncInfo", &blob(20, 3)).unwrap();
+
+    assert_eq!(storage.read("CalendarSyncInfo").unwrap(), blob(20, 3));
+    assert_eq!(storage.inner().list().unwrap().len(), 1 + 2);
+
+    storage.delete("CalendarSyncInfo").unwrap();
+
+    assert!(storage.read("CalendarSyncInfo").is_err());
+    assert!(storage.inner().list().unwrap().is_empty());
+}
+
+#[test]
+fn should_keep_previous_value_when_write_is_interrupted() {
+    let inner = InterruptedStorage {
+        inner: MemoryStorage::new(),
+        writes_left: usize::MAX,
+    };
+
+    let mut storage = ChunkedStorage::with_chunk_size(inner, CHUNK_SIZE);
+
+    storage.write("CalendarSyncInfo", &blob(40, 1)).unwrap();
+
+    let mut inner = storage.into_inner();
+    inner.writes_left = 2;
+
+    let mut storage = ChunkedStorage::with_chunk_size(inner, CHUNK_SIZE);
+
+    assert!(storage.write("CalendarSyncInfo", &blob(64, 2)).is_err());
+    assert_eq!(storage.read("CalendarSyncInfo").unwrap(), blob(40, 1));
+
+    let mut inner = storage.into_inner();
+    inner.writes_left = usize::MAX;
+
+    let mut storage = ChunkedStorage::with_chunk_size(inner, CHUNK_SIZE);
+
+    storage.write("CalendarSyncInfo", &blob(20, 3)).unwrap();
+
+    assert_eq!(storage.read("CalendarSyncInfo").unwrap(), blob(20, 3));
+    assert_eq!(storage.inner().list().unwrap().len(), 1 + 2);
+}
+
+#[test]
+fn should_reject_damaged_or_missing_chunks() {
+    let mut storage = ChunkedStorage::with_chunk_size(MemoryStorage::new(), CHUNK_SIZE);
+
+    storage.write("CalendarSyncInfo", &blob(40, 1)).unwrap();
+
+    let chunks: Vec<String> = storage
+        .inner()
+        .list()
+        .unwrap()
+        .into_iter()
+        .filter(|x| !x.ends_with(".m"))
+        .collect();
+
+    let mut damaged = storage.inner().clone();
+    let mut chunk = damaged.read(&chunks[1]).unwrap();
+    chunk[3] ^= 0xff;
+    damaged.write(&chunks[1], &chunk).unwrap();
+
+    assert!(ChunkedStorage::new(damaged)
+        .read("CalendarSyncInfo")
+        .is_err());
+
+    let mut truncated = storage.into_inner();
+    truncated.delete(&chunks[2]).unwrap();
+
+    assert!(ChunkedStorage::new(truncated)
+        .read("CalendarSyncInfo")
+        .is_err());
+}
+
+#[test]
+fn should_read_and_replace_values_written_before_chunking() {
+    let mut inner = MemoryStorage::new();
+    inner.write("RtcSyncInfo", &[1, 2, 3]).unwrap();
+
+    let mut storage = ChunkedStorage::with_chunk_size(inner, CHUNK_SIZE);
+
+    assert_eq!(storage.read("RtcSyncInfo").unwrap(), vec![1, 2, 3]);
+    assert_eq!(storage.list().unwrap(), vec!["RtcSyncInfo"]);
+    assert_eq!(storage.usage().unwrap(), vec![usage("RtcSyncInfo", 3, 1)]);
+
+    storage.write("RtcSyncInfo", &blob(20, 4)).unwrap();
+
+    assert_eq!(storage.read("RtcSyncInfo").unwrap(), blob(20, 4));
+    assert!(storage.inner().read("RtcSyncInfo").is_err());
+    assert_eq!(storage.list().unwrap(), vec!["RtcSyncInfo"]);
+}
+
+fn blob(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|x| (x as u8).wrapping_mul(31) ^ seed)
+        .collect()
+}
+
+fn usage(key: &str, bytes: usize, chunks: usize) -> StorageUsage {
+    StorageUsage {
+        key: key.to_string(),
+        bytes,
+        chunks,
+    }
+}
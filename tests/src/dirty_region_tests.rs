@@ -0,0 +1,135 @@
+use blinky_shared::{
+    dirty_region::{DirtyLayers, DirtyRegion, DirtyTracker, MAX_DIRTY_RECTS},
+    display_interface::{LayerType, RenderMode},
+};
+use embedded_graphics::{
+    pixelcolor::Rgb565,
+    prelude::{Point, PointsIter, Primitive, RgbColor, Size},
+    primitives::{Circle, PrimitiveStyle, Rectangle},
+    Drawable,
+};
+use embedded_graphics_framebuf::FrameBuf;
+
+fn rect(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+    Rectangle::new(Point::new(x, y), Size::new(width, height))
+}
+
+fn covers(region: &DirtyRegion, rect: &Rectangle) -> bool {
+    rect.points()
+        .all(|point| region.rects().iter().any(|x| x.contains(point)))
+}
+
+#[test]
+fn should_merge_neighbouring_glyphs() {
+    let mut region = DirtyRegion::new();
+
+    // digits of the clock drawn one by one
+    for digit in 0..6 {
+        region.add(rect(100 + digit * 32, 200, 30, 64));
+    }
+
+    assert_eq!(region.rects(), &[rect(100, 200, 190, 64)]);
+
+    region.add(rect(120, 220, 10, 10));
+
+    assert_eq!(region.rects().len(), 1);
+}
+
+#[test]
+fn should_keep_distant_rects_apart() {
+    let mut region = DirtyRegion::new();
+
+    region.add(rect(10, 10, 20, 20));
+    region.add(rect(400, 400, 20, 20));
+
+    assert_eq!(region.rects().len(), 2);
+    assert_eq!(region.area(), 800);
+
+    region.add(Rectangle::zero());
+
+    assert_eq!(region.rects().len(), 2);
+}
+
+#[test]
+fn should_cap_rects_without_losing_pixels() {
+    let mut region = DirtyRegion::new();
+
+    let added: Vec<Rectangle> = (0..40)
+        .map(|x| rect((x * 97) % 440, (x * 61) % 440, 8 + (x % 5) as u32, 6))
+        .collect();
+
+    for rect in added.iter() {
+        region.add(*rect);
+    }
+
+    assert!(region.rects().len() <= MAX_DIRTY_RECTS);
+    assert!(added.iter().all(|x| covers(&region, x)));
+    assert!(region.area() < 466 * 466);
+
+    region.clip(&rect(0, 0, 240, 240));
+
+    assert!(region
+        .rects()
+        .iter()
+        .all(|x| rect(0, 0, 240, 240).intersection(x) == *x));
+}
+
+#[test]
+fn should_track_drawn_bounds() {
+    let mut data = [Rgb565::BLACK; 64 * 64];
+    let mut tracker = DirtyTracker::new(FrameBuf::new(&mut data, 64, 64));
+
+    Circle::new(Point::new(10, 20), 12)
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::WHITE))
+        .draw(&mut tracker)
+        .unwrap();
+
+    // partly off the frame
+    Circle::new(Point::new(58, -4), 10)
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::WHITE))
+        .draw(&mut tracker)
+        .unwrap();
+
+    let (_, drawn) = tracker.into_parts();
+
+    assert_eq!(drawn.rects(), &[rect(10, 20, 12, 12), rect(58, 0, 6, 6)]);
+}
+
+#[test]
+fn should_flush_previous_content_of_invalidated_layer() {
+    let mut layers = DirtyLayers::new(466);
+    let all = LayerType::Static | LayerType::Clock | LayerType::Events;
+
+    layers.rendered(
+        LayerType::Static,
+        RenderMode::Invalidate,
+        DirtyRegion::full(466),
+    );
+
+    // nothing to compare against on the first commit
+    assert_eq!(layers.take(all).area(), 466 * 466);
+
+    let mut clock = DirtyRegion::new();
+    clock.add(rect(100, 200, 190, 64));
+
+    layers.rendered(LayerType::Clock, RenderMode::Invalidate, clock.clone());
+    assert_eq!(layers.take(all), clock);
+
+    let mut moved = DirtyRegion::new();
+    moved.add(rect(300, 300, 20, 20));
+
+    layers.rendered(LayerType::Clock, RenderMode::Invalidate, moved);
+    assert_eq!(layers.take(all).area(), 190 * 64 + 400);
+
+    layers.rendered(LayerType::Events, RenderMode::Ammend, DirtyRegion::new());
+    assert!(layers.take(all).is_empty());
+
+    // another set of layers shows something else everywhere
+    assert_eq!(layers.take(LayerType::Clock.into()).area(), 466 * 466);
+
+    let counter = layers.counter();
+
+    assert_eq!(counter.frames, 5);
+    assert_eq!(counter.last_frame, 466 * 466);
+    assert_eq!(counter.total, 2 * 466 * 466 + 190 * 64 * 2 + 400);
+}
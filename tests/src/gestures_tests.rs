@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use blinky_shared::{
+    domain::TouchPosition,
+    gestures::{Gesture, GestureRecognizer, SwipeDirection},
+};
+
+const SIDE: usize = 466;
+
+#[test]
+fn should_recognize_tap() {
+    let mut recognizer = GestureRecognizer::new(SIDE);
+
+    let gestures = play(&mut recognizer, &[(100, 200, 0), (104, 198, 40)], 120);
+
+    assert_eq!(gestures, vec![Gesture::Tap(position(100, 200))]);
+}
+
+#[test]
+fn should_recognize_long_press_while_held() {
+    let mut recognizer = GestureRecognizer::new(SIDE);
+
+    assert_eq!(recognizer.touch(position(233, 233), ms(0)), None);
+    assert_eq!(recognizer.touch(position(235, 231), ms(300)), None);
+    assert_eq!(
+        recognizer.touch(position(234, 232), ms(650)),
+        Some(Gesture::LongPress(position(233, 233)))
+    );
+
+    // once per contact, nothing on release
+    assert_eq!(recognizer.touch(position(234, 232), ms(900)), None);
+    assert_eq!(recognizer.release(ms(950)), None);
+}
+
+#[test]
+fn should_recognize_long_press_on_release() {
+    let mut recognizer = GestureRecognizer::new(SIDE);
+
+    let gestures = play(&mut recognizer, &[(50, 60, 0)], 700);
+
+    assert_eq!(gestures, vec![Gesture::LongPress(position(50, 60))]);
+}
+
+#[test]
+fn should_recognize_swipes() {
+    let traces = [
+        (
+            vec![(300, 230, 0), (250, 235, 40), (150, 240, 80)],
+            SwipeDirection::Left,
+        ),
+        (
+            vec![(150, 230, 0), (250, 225, 40), (320, 220, 80)],
+            SwipeDirection::Right,
+        ),
+        (
+            vec![(230, 350, 0), (235, 250, 40), (240, 120, 80)],
+            SwipeDirection::Up,
+        ),
+        (
+            vec![(230, 100, 0), (225, 200, 40), (220, 330, 80)],
+            SwipeDirection::Down,
+        ),
+    ];
+
+    let mut recognizer = GestureRecognizer::new(SIDE);
+
+    for (trace, direction) in traces {
+        let gestures = play(&mut recognizer, &trace, 100);
+
+        assert_eq!(gestures, vec![Gesture::Swipe(direction)]);
+    }
+}
+
+#[test]
+fn should_recognize_slow_swipe() {
+    let mut recognizer = GestureRecognizer::new(SIDE);
+
+    let gestures = play(&mut recognizer, &[(100, 230, 0), (300, 230, 900)], 1000);
+
+    assert_eq!(gestures, vec![Gesture::Swipe(SwipeDirection::Right)]);
+}
+
+#[test]
+fn should_ignore_short_drag() {
+    let mut recognizer = GestureRecognizer::new(SIDE);
+
+    let gestures = play(&mut recognizer, &[(200, 200, 0), (240, 210, 60)], 100);
+
+    assert!(gestures.is_empty());
+}
+
+#[test]
+fn should_ignore_release_without_touch() {
+    let mut recognizer = GestureRecognizer::new(SIDE);
+
+    assert_eq!(recognizer.release(ms(10)), None);
+}
+
+// (x, y, ms) positions followed by a release
+fn play(
+    recognizer: &mut GestureRecognizer,
+    trace: &[(i32, i32, u64)],
+    release_at: u64,
+) -> Vec<Gesture> {
+    let mut gestures: Vec<Gesture> = trace
+        .iter()
+        .filter_map(|(x, y, at)| recognizer.touch(position(*x, *y), ms(*at)))
+        .collect();
+
+    gestures.extend(recognizer.release(ms(release_at)));
+
+    gestures
+}
+
+fn position(x: i32, y: i32) -> TouchPosition {
+    TouchPosition { x, y }
+}
+
+fn ms(value: u64) -> Duration {
+    Duration::from_millis(value)
+}
@@ -0,0 +1,175 @@
+use std::{
+    convert::Infallible,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use blinky_shared::{
+    dirty_region::{layer_index, merge_layers, DirtyLayers, DirtyTracker, PixelCounter},
+    display_interface::{ClockDisplayInterface, LayerType, RenderMode},
+    error::Error,
+};
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics_framebuf::FrameBuf;
+use enumflags2::BitFlags;
+use tokio::sync::watch;
+
+pub struct HeadlessDisplay<const SIDE: usize, const SIZE: usize> {
+    buffer_layers: Vec<Box<[Rgb565]>>,
+    dirty_layers: DirtyLayers,
+    frame: Arc<Mutex<Box<[Rgb565]>>>,
+    commits: watch::Sender<PixelCounter>,
+}
+
+pub struct HeadlessDisplayProbe<const SIDE: usize, const SIZE: usize> {
+    frame: Arc<Mutex<Box<[Rgb565]>>>,
+    commits: watch::Receiver<PixelCounter>,
+}
+
+impl<const SIDE: usize, const SIZE: usize> HeadlessDisplay<SIDE, SIZE> {
+    pub fn create() -> (Self, HeadlessDisplayProbe<SIDE, SIZE>) {
+        let frame = Arc::new(Mutex::new(Self::prepare_frame_buf()));
+        let (commits_tx, commits_rx) = watch::channel(PixelCounter::default());
+
+        let display = Self {
+            buffer_layers: vec![
+                Self::prepare_frame_buf(),
+                Self::prepare_frame_buf(),
+                Self::prepare_frame_buf(),
+            ],
+            dirty_layers: DirtyLayers::new(SIDE),
+            frame: frame.clone(),
+            commits: commits_tx,
+        };
+
+        let probe = HeadlessDisplayProbe {
+            frame,
+            commits: commits_rx,
+        };
+
+        (display, probe)
+    }
+
+    fn prepare_frame_buf() -> Box<[Rgb565]> {
+        vec![Rgb565::BLACK; SIZE].into_boxed_slice()
+    }
+}
+
+impl<const SIDE: usize, const SIZE: usize> ClockDisplayInterface for HeadlessDisplay<SIDE, SIZE> {
+    type Error = Infallible;
+
+    type ColorModel = Rgb565;
+
+    type FrameBuffer<'b> =
+        DirtyTracker<FrameBuf<Self::ColorModel, &'b mut [Self::ColorModel; SIZE]>>;
+
+    const FRAME_BUFFER_SIDE: usize = SIDE;
+
+    const FRAME_BUFFER_SIZE: usize = SIZE;
+
+    fn render<'c, 'd: 'c>(
+        &'d mut self,
+        layer: LayerType,
+        mode: RenderMode,
+        func: impl FnOnce(Self::FrameBuffer<'c>) -> Self::FrameBuffer<'c>,
+    ) {
+        let data = self.buffer_layers[layer_index(layer)].as_mut();
+
+        let buf: &'c mut [Self::ColorModel; SIZE] = data.try_into().unwrap();
+
+        let mut frame = FrameBuf::new(buf, SIDE, SIDE);
+
+        if matches!(mode, RenderMode::Invalidate) {
+            frame.reset();
+        }
+
+        let (_, drawn) = func(DirtyTracker::new(frame)).into_parts();
+
+        self.dirty_layers.rendered(layer, mode, drawn);
+    }
+
+    fn commit(&mut self, layers_mask: BitFlags<LayerType>) {
+        {
+            let mut frame = self.frame.lock().unwrap();
+
+            let dirty = self.dirty_layers.take(layers_mask);
+
+            for rect in dirty.rects() {
+                merge_layers(&mut frame, &self.buffer_layers, layers_mask, SIDE, rect);
+            }
+        }
+
+        let counter = self.dirty_layers.counter();
+        self.commits.send_modify(|x| *x = counter);
+    }
+
+    fn pixel_counter(&self) -> PixelCounter {
+        self.dirty_layers.counter()
+    }
+}
+
+impl<const SIDE: usize, const SIZE: usize> HeadlessDisplayProbe<SIDE, SIZE> {
+    pub async fn wait_for_commit(&mut self) {
+        self.commits.changed().await.unwrap();
+    }
+
+    pub fn pixel_counter(&self) -> PixelCounter {
+        *self.commits.borrow()
+    }
+
+    pub fn frame(&self) -> Vec<Rgb565> {
+        self.frame.lock().unwrap().to_vec()
+    }
+}
+
+pub fn save_png(path: &Path, frame: &[Rgb565], side: usize) -> Result<(), Error> {
+    let file = File::create(path)?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), side as u32, side as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let data: Vec<u8> = frame
+        .iter()
+        .flat_map(|x| {
+            let (r, g, b) = (x.r(), x.g(), x.b());
+            [
+                (r << 3) | (r >> 2),
+                (g << 2) | (g >> 4),
+                (b << 3) | (b >> 2),
+            ]
+        })
+        .collect();
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|err| Error::from(err.to_string()))
+}
+
+pub fn load_png(path: &Path) -> Result<(usize, Vec<Rgb565>), Error> {
+    let file = File::open(path)?;
+
+    let decoder = png::Decoder::new(BufReader::new(file));
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| Error::from(err.to_string()))?;
+
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut data)
+        .map_err(|err| Error::from(err.to_string()))?;
+
+    if info.color_type != png::ColorType::Rgb || info.bit_depth != png::BitDepth::Eight {
+        return Err(Error::from("unsupported png format, expected 8-bit rgb"));
+    }
+
+    let frame = data[..info.buffer_size()]
+        .chunks_exact(3)
+        .map(|x| Rgb565::new(x[0] >> 3, x[1] >> 2, x[2] >> 3))
+        .collect();
+
+    Ok((info.width as usize, frame))
+}
@@ -1,8 +1,24 @@
 mod calendar_persistence_tests;
+mod chunked_storage_tests;
 mod contract_serialization_tests;
+mod dirty_region_tests;
+mod gestures_tests;
+mod headless_display;
+mod message_bus_tests;
 mod modules;
+mod persistence_migration_tests;
+mod protocol_versioning_tests;
+mod recurrence_tests;
+mod reminders_tests;
+mod rtc_drift_tests;
+mod secure_channel_tests;
+mod solar_tests;
 mod spy_module;
+mod supervisor_tests;
 mod termperature_decoder_tests;
+mod timezone_tests;
+mod transport_tests;
+mod weather_tests;
 
 extern crate blinky_shared;
 
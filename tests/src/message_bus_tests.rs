@@ -0,0 +1,243 @@
+use std::{pin::Pin, time::Duration};
+
+use blinky_shared::{
+    bus_channel::{channel, OverflowPolicy, RecvError, MAX_BACKLOG},
+    commands::Commands,
+    domain::TouchPosition,
+    events::Events,
+    message_bus::{
+        default_commands_policy, default_events_policy, BusHandler, BusSender, ContextStub,
+        MessageBus, MessageBusConfig,
+    },
+    persistence::{PersistenceUnit, PersistenceUnitKind},
+};
+use time::{macros::datetime, OffsetDateTime};
+use tokio::time::sleep;
+
+struct ClockStub {}
+
+impl BusHandler<ContextStub> for ClockStub {
+    async fn event_handler(_bus: &BusSender, _context: &mut ContextStub, _event: Events) {}
+
+    async fn command_handler(bus: &BusSender, _context: &mut ContextStub, command: Commands) {
+        match command {
+            Commands::GetTimeNow => {
+                bus.reply(Events::TimeNow(some_now()));
+            }
+            Commands::GetTemperature => {
+                let correlation_id = bus.current_request().unwrap();
+
+                // a reply to someone else's request must not be picked up
+                bus.reply_to(
+                    blinky_shared::message_bus::CorrelationId(correlation_id.0 + 100),
+                    Events::Temperature(-1),
+                );
+                bus.reply(Events::Temperature(21));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_receive_reply_to_request() {
+    let message_bus = MessageBus::new();
+    let message_bus_clone = message_bus.clone();
+
+    let clock_task = MessageBus::handle::<ContextStub, ClockStub>(message_bus, ContextStub {});
+
+    let request_sequence = async move {
+        let reply = message_bus_clone.request(Commands::GetTimeNow).await;
+
+        assert!(matches!(reply, Ok(Events::TimeNow(now)) if now == some_now()));
+
+        let reply = message_bus_clone.request(Commands::GetTemperature).await;
+
+        assert!(matches!(reply, Ok(Events::Temperature(21))));
+
+        message_bus_clone.send_cmd(Commands::StartDeepSleep);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(async {
+            clock_task.await;
+        }),
+        Box::pin(request_sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+}
+
+#[tokio::test]
+async fn should_time_out_when_nobody_replies() {
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(50));
+
+    let reply = message_bus.request(Commands::SyncCalendar).await;
+
+    assert!(reply.is_err());
+}
+
+#[tokio::test]
+async fn should_drop_oldest_messages_on_overflow() {
+    let (sender, mut receiver) = channel::<u32>(3, |_| OverflowPolicy::DropOldest);
+
+    for x in 0..5 {
+        sender.send(x).unwrap();
+    }
+
+    assert_eq!(receiver.recv().await, Err(RecvError::Lagged(2)));
+    assert_eq!(receiver.recv().await, Ok(2));
+    assert_eq!(receiver.recv().await, Ok(3));
+    assert_eq!(receiver.recv().await, Ok(4));
+    assert!(receiver.is_empty());
+}
+
+#[tokio::test]
+async fn should_coalesce_latest_wins_messages_on_overflow() {
+    let (sender, mut receiver) = channel::<Events>(3, default_events_policy);
+
+    let now = some_now();
+
+    sender.send(Events::TimeNow(now)).unwrap();
+    sender.send(Events::Key1Press).unwrap();
+    sender
+        .send(Events::TimeNow(now + Duration::from_secs(1)))
+        .unwrap();
+    sender
+        .send(Events::TimeNow(now + Duration::from_secs(2)))
+        .unwrap();
+
+    assert_eq!(receiver.recv().await.err(), Some(RecvError::Lagged(1)));
+    assert!(matches!(receiver.recv().await, Ok(Events::Key1Press)));
+    assert!(
+        matches!(receiver.recv().await, Ok(Events::TimeNow(x)) if x == now + Duration::from_secs(1))
+    );
+    assert!(
+        matches!(receiver.recv().await, Ok(Events::TimeNow(x)) if x == now + Duration::from_secs(2))
+    );
+}
+
+#[tokio::test]
+async fn should_never_drop_critical_commands() {
+    let (sender, mut receiver) = channel::<Commands>(2, default_commands_policy);
+
+    sender.send(Commands::StartDeepSleep).unwrap();
+    sender
+        .send(Commands::Restore(PersistenceUnitKind::CalendarSyncInfo))
+        .unwrap();
+    sender.send(Commands::SyncCalendar).unwrap();
+    sender.send(Commands::StartDeepSleep).unwrap();
+
+    assert_eq!(receiver.recv().await.err(), Some(RecvError::Lagged(1)));
+    assert!(matches!(
+        receiver.recv().await,
+        Ok(Commands::StartDeepSleep)
+    ));
+    assert!(matches!(
+        receiver.recv().await,
+        Ok(Commands::Restore(PersistenceUnitKind::CalendarSyncInfo))
+    ));
+    assert!(matches!(
+        receiver.recv().await,
+        Ok(Commands::StartDeepSleep)
+    ));
+    assert_eq!(receiver.len(), 0);
+}
+
+#[tokio::test]
+async fn should_keep_every_critical_command_under_flood() {
+    let (sender, mut receiver) = channel::<Commands>(2, default_commands_policy);
+
+    for x in 0..100 {
+        sender
+            .send(Commands::Persist(PersistenceUnit::new(
+                PersistenceUnitKind::CalendarSyncInfo,
+                &x,
+            )))
+            .unwrap();
+        sender.send(Commands::SyncCalendar).unwrap();
+    }
+
+    sender.send(Commands::StartDeepSleep).unwrap();
+
+    let mut persisted = vec![];
+    let mut last = None;
+
+    while let Some(command) = receiver.try_recv() {
+        if let Commands::Persist(unit) = command {
+            persisted.push(unit.deserialize::<i32>().await.unwrap());
+        } else {
+            last = Some(command);
+        }
+    }
+
+    assert_eq!(persisted, (0..100).collect::<Vec<_>>());
+    assert!(matches!(last, Some(Commands::StartDeepSleep)));
+    assert_eq!(receiver.recv().await.err(), Some(RecvError::Lagged(100)));
+}
+
+#[tokio::test]
+async fn should_bound_critical_backlog_of_idle_subscriber() {
+    let (sender, mut receiver) = channel::<Events>(64, default_events_policy);
+
+    for x in 0..10_000 {
+        sender
+            .send(Events::TouchPos(TouchPosition { x, y: 0 }))
+            .unwrap();
+    }
+
+    assert_eq!(receiver.len(), MAX_BACKLOG);
+    assert_eq!(
+        receiver.recv().await.err(),
+        Some(RecvError::Lagged(10_000 - MAX_BACKLOG as u32))
+    );
+
+    // the newest ones are kept
+    assert!(matches!(
+        receiver.recv().await,
+        Ok(Events::TouchPos(point)) if point.x == 10_000 - MAX_BACKLOG as i32
+    ));
+}
+
+#[tokio::test]
+async fn should_report_lag_per_module() {
+    let message_bus = MessageBus::with_config(MessageBusConfig {
+        events_capacity: 2,
+        ..Default::default()
+    });
+    let message_bus_clone = message_bus.clone();
+
+    for _ in 0..5 {
+        message_bus.send_event(Events::Key1Press);
+    }
+
+    let clock_task = MessageBus::handle::<ContextStub, ClockStub>(message_bus, ContextStub {});
+
+    let sequence = async move {
+        sleep(Duration::from_millis(20)).await;
+
+        let metrics = message_bus_clone.lag_metrics();
+
+        let clock_metric = metrics
+            .iter()
+            .find(|x| x.subscriber.ends_with("ClockStub"))
+            .unwrap();
+
+        assert_eq!(clock_metric.lagged, 3);
+
+        message_bus_clone.send_cmd(Commands::StartDeepSleep);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(async {
+            clock_task.await;
+        }),
+        Box::pin(sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+}
+
+fn some_now() -> OffsetDateTime {
+    datetime!(2000-01-01 10:00:00 UTC)
+}
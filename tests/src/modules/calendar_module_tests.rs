@@ -1,14 +1,23 @@
 use std::{pin::Pin, sync::Arc};
 
 use blinky_shared::{
-    calendar::{CalendarEvent, CalendarEventIcon, CalendarKind, TimelyDataRecord},
+    calendar::{
+        recurrence::{Frequency, RecurrenceRule},
+        state::CalendarStateDto,
+        CalendarEvent, CalendarEventDto, CalendarEventIcon, CalendarEventKey, CalendarKind,
+        TimelyDataMarker, TimelyDataRecord,
+    },
+    commands::Commands,
+    error::Error,
     events::Events,
-    message_bus::MessageBus,
+    message_bus::{BusHandler, BusSender, MessageBus},
     modules::calendar_module::CalendarModule,
+    persistence::{PersistenceUnit, PersistenceUnitKind},
+    reminders::{KindReminderSettings, Reminder, ReminderKind, ReminderSettings},
 };
-use time::{Date, Duration, OffsetDateTime, Time};
+use time::{macros::datetime, Date, Duration, OffsetDateTime, Time};
 
-use crate::spy_module::SpyModule;
+use crate::{spy_module::SpyModule, timezone_tests::berlin_2024};
 
 #[tokio::test]
 async fn should_() {
@@ -21,8 +30,8 @@ async fn should_() {
 
     let startup_sequence = async move {
         let now = OffsetDateTime::new_utc(
-            Date::from_calendar_date(2000, 1, 1),
-            Time::from_hms(3, 0, 0),
+            Date::from_calendar_date(2000, time::Month::January, 1).unwrap(),
+            Time::from_hms(3, 0, 0).unwrap(),
         );
 
         let event = Events::TimeNow(now);
@@ -50,6 +59,8 @@ async fn should_() {
                 color: 0,
                 description: "".to_string(),
                 lane: 0,
+                recurrence: None,
+                reminder_offsets: None,
             }]));
 
         message_bus.send_event(reference_calendar_events);
@@ -70,3 +81,582 @@ async fn should_() {
 
     futures::future::join_all(tasks).await;
 }
+
+#[tokio::test]
+async fn should_send_timely_data_of_each_linked_event_sorted_by_hour() {
+    let now = datetime!(2000-01-01 03:00 UTC);
+
+    let message_bus = MessageBus::new();
+
+    let calendar_task = CalendarModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::PersistedCalendarEvents(Arc::new(vec![])),
+    );
+
+    let record = |hour: u8, value: f32, data_marker: TimelyDataMarker| TimelyDataRecord {
+        linked_event_id: 1,
+        start_at_hour: hour,
+        duration: Duration::hours(1),
+        value,
+        data_marker,
+    };
+
+    let sequence = async move {
+        message_bus.send_event(Events::TimeNow(now));
+
+        message_bus.send_event(Events::ReferenceTimelyDataBatch(Arc::new(vec![
+            record(5, 1.5, TimelyDataMarker::Precipitation),
+            record(4, 12.0, TimelyDataMarker::Temperature),
+            record(3, 11.0, TimelyDataMarker::Temperature),
+            record(3, 11.5, TimelyDataMarker::Temperature),
+        ])));
+
+        message_bus.send_event(Events::ReferenceCalendarEventUpdatesBatch(Arc::new(vec![
+            CalendarEvent {
+                kind: CalendarKind::Weather,
+                id: 1,
+                title: "Weather".to_string(),
+                start: now,
+                end: now + Duration::days(1),
+                icon: CalendarEventIcon::Temperature,
+                color: 0,
+                description: "".to_string(),
+                lane: 0,
+                recurrence: None,
+                reminder_offsets: None,
+            },
+        ])));
+
+        message_bus.send_event(Events::InSync(true));
+    };
+
+    futures::join!(calendar_task, spy_task, sequence);
+
+    let timely_data = spy
+        .get_result()
+        .find_map(|x| match x {
+            Events::EventTimelyData(data) => Some(data.clone()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(timely_data.linked_event_id, 1);
+    assert_eq!(
+        timely_data
+            .timely_data
+            .iter()
+            .map(|x| (x.start_at_hour, x.value))
+            .collect::<Vec<_>>(),
+        vec![(3, 11.5), (4, 12.0), (5, 1.5)]
+    );
+
+    assert!(spy
+        .get_result()
+        .any(|x| matches!(x, Events::Temperature(12))));
+}
+
+#[test]
+fn should_place_timely_records_in_the_next_window_of_their_hour() {
+    let now = datetime!(2024-05-01 10:30 +02:00);
+
+    let record = |hour: u8| TimelyDataRecord {
+        linked_event_id: 1,
+        start_at_hour: hour,
+        duration: Duration::hours(1),
+        value: 0.0,
+        data_marker: TimelyDataMarker::Precipitation,
+    };
+
+    assert_eq!(
+        record(10).window(now),
+        (
+            datetime!(2024-05-01 10:00 +02:00),
+            datetime!(2024-05-01 11:00 +02:00)
+        )
+    );
+    assert_eq!(
+        record(23).window(now),
+        (
+            datetime!(2024-05-01 23:00 +02:00),
+            datetime!(2024-05-02 00:00 +02:00)
+        )
+    );
+    assert_eq!(
+        record(9).window(now),
+        (
+            datetime!(2024-05-02 09:00 +02:00),
+            datetime!(2024-05-02 10:00 +02:00)
+        )
+    );
+}
+
+struct PersisterStub {}
+
+struct PersisterStubContext {
+    calendar_state: CalendarStateDto,
+    reminder_settings: Option<ReminderSettings>,
+}
+
+impl BusHandler<PersisterStubContext> for PersisterStub {
+    async fn event_handler(_bus: &BusSender, _context: &mut PersisterStubContext, _event: Events) {}
+
+    async fn command_handler(
+        bus: &BusSender,
+        context: &mut PersisterStubContext,
+        command: Commands,
+    ) {
+        match command {
+            Commands::Restore(PersistenceUnitKind::CalendarSyncInfo) => {
+                let unit = PersistenceUnit::new(
+                    PersistenceUnitKind::CalendarSyncInfo,
+                    &context.calendar_state,
+                );
+
+                bus.reply(Events::Restored(unit));
+            }
+            Commands::Restore(PersistenceUnitKind::ReminderSettings) => {
+                let unit = match &context.reminder_settings {
+                    Some(settings) => {
+                        PersistenceUnit::new(PersistenceUnitKind::ReminderSettings, settings)
+                    }
+                    None => PersistenceUnit {
+                        kind: PersistenceUnitKind::ReminderSettings,
+                        data: Err(Error::from("not found")),
+                    },
+                };
+
+                bus.reply(Events::Restored(unit));
+            }
+            Commands::Persist(unit) if unit.kind == PersistenceUnitKind::ReminderSettings => {
+                context.reminder_settings = Some(unit.deserialize().await.unwrap());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_restore_persisted_events_on_timezone_set() {
+    let message_bus = MessageBus::new();
+
+    let now = OffsetDateTime::new_utc(
+        Date::from_calendar_date(2000, time::Month::January, 1).unwrap(),
+        Time::from_hms(3, 0, 0).unwrap(),
+    );
+
+    let persisted_event = CalendarEventDto {
+        kind: CalendarKind::Phone,
+        id: 42,
+        title: "persisted".to_string(),
+        start: (now + Duration::hours(1)).into(),
+        end: (now + Duration::hours(2)).into(),
+        icon: CalendarEventIcon::Meeting,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: None,
+        reminder_offsets: None,
+    };
+
+    let persister_context = PersisterStubContext {
+        calendar_state: CalendarStateDto::new(vec![persisted_event], vec![], now.into()),
+        reminder_settings: None,
+    };
+
+    let persister_task = MessageBus::handle::<PersisterStubContext, PersisterStub>(
+        message_bus.clone(),
+        persister_context,
+    );
+
+    let calendar_module_task = CalendarModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::CalendarEventsBatch(Arc::new(vec![])),
+    );
+
+    let startup_sequence = async move {
+        message_bus.send_event(Events::TimeNow(now));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_cmd(Commands::SetTimezone(0));
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(async {
+            persister_task.await;
+        }),
+        Box::pin(calendar_module_task),
+        Box::pin(spy_task),
+        Box::pin(startup_sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+
+    let batch = spy
+        .get_result()
+        .find_map(|x| match x {
+            Events::CalendarEventsBatch(batch) => Some(batch.clone()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(batch.len(), 1);
+    assert_eq!(batch[0].id, 42);
+}
+
+struct RemindersStub {}
+
+impl BusHandler<Vec<Vec<Reminder>>> for RemindersStub {
+    async fn event_handler(_bus: &BusSender, _context: &mut Vec<Vec<Reminder>>, _event: Events) {}
+
+    async fn command_handler(
+        _bus: &BusSender,
+        context: &mut Vec<Vec<Reminder>>,
+        command: Commands,
+    ) {
+        if let Commands::SetReminders(reminders) = command {
+            context.push(reminders);
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_expand_recurring_events_around_now() {
+    let message_bus = MessageBus::new();
+
+    let now = OffsetDateTime::new_utc(
+        Date::from_calendar_date(2000, time::Month::January, 1).unwrap(),
+        Time::from_hms(3, 0, 0).unwrap(),
+    );
+
+    let daily = CalendarEvent {
+        kind: CalendarKind::Phone,
+        id: 5,
+        title: "standup".to_string(),
+        start: now - Duration::days(30) + Duration::hours(2),
+        end: now - Duration::days(30) + Duration::hours(3),
+        icon: CalendarEventIcon::Meeting,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: Some(RecurrenceRule::new(Frequency::Daily, 1)),
+        reminder_offsets: None,
+    };
+
+    let calendar_module_task = CalendarModule::start(message_bus.clone());
+
+    let reminders_task =
+        MessageBus::handle::<Vec<Vec<Reminder>>, RemindersStub>(message_bus.clone(), vec![]);
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::PersistedCalendarEvents(Arc::new(vec![])),
+    );
+
+    let startup_sequence = async move {
+        message_bus.send_event(Events::TimeNow(now));
+        message_bus.send_event(Events::ReferenceCalendarEventUpdatesBatch(Arc::new(vec![
+            daily,
+        ])));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_event(Events::TimeNow(now + Duration::hours(3)));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_event(Events::InSync(true));
+    };
+
+    let (_, reminders, _, _) = futures::join!(
+        calendar_module_task,
+        reminders_task,
+        spy_task,
+        startup_sequence
+    );
+
+    let batches: Vec<Vec<OffsetDateTime>> = spy
+        .get_result()
+        .filter_map(|x| match x {
+            Events::CalendarEventsBatch(batch) => Some(batch.iter().map(|x| x.start).collect()),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        batches,
+        vec![
+            vec![now + Duration::hours(2)],
+            vec![now + Duration::hours(26)]
+        ]
+    );
+
+    assert!(spy.get_result().any(|x| matches!(
+        x,
+        Events::DropCalendarEventsBatch(keys) if keys.as_slice() == [CalendarEventKey(CalendarKind::Phone, 5)]
+    )));
+
+    let next = now + Duration::hours(26);
+
+    assert_eq!(
+        reminders.last().unwrap(),
+        &vec![
+            Reminder::new(5, ReminderKind::Notification, next - Duration::minutes(10)),
+            Reminder::new(5, ReminderKind::Event, next),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_set_reminders_from_event_offsets_and_kind_defaults() {
+    let message_bus = MessageBus::new();
+
+    let now = OffsetDateTime::new_utc(
+        Date::from_calendar_date(2000, time::Month::January, 1).unwrap(),
+        Time::from_hms(3, 0, 0).unwrap(),
+    );
+
+    let tomorrow = now.replace_hour(0).unwrap() + Duration::days(1);
+
+    let events = vec![
+        CalendarEvent {
+            kind: CalendarKind::Phone,
+            id: 1,
+            title: "custom".to_string(),
+            start: now + Duration::hours(2),
+            end: now + Duration::hours(3),
+            icon: CalendarEventIcon::Meeting,
+            color: 0,
+            description: "".to_string(),
+            lane: 0,
+            recurrence: None,
+            reminder_offsets: Some(vec![30, -5]),
+        },
+        CalendarEvent {
+            kind: CalendarKind::Phone,
+            id: 2,
+            title: "all day".to_string(),
+            start: tomorrow,
+            end: tomorrow + Duration::days(1),
+            icon: CalendarEventIcon::Birthday,
+            color: 0,
+            description: "".to_string(),
+            lane: 0,
+            recurrence: None,
+            reminder_offsets: None,
+        },
+    ];
+
+    let calendar_module_task = CalendarModule::start(message_bus.clone());
+
+    let reminders_task =
+        MessageBus::handle::<Vec<Vec<Reminder>>, RemindersStub>(message_bus.clone(), vec![]);
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::PersistedCalendarEvents(Arc::new(vec![])),
+    );
+
+    let startup_sequence = async move {
+        message_bus.send_event(Events::TimeNow(now));
+        message_bus.send_event(Events::ReferenceCalendarEventUpdatesBatch(Arc::new(events)));
+        message_bus.send_event(Events::InSync(true));
+    };
+
+    let (_, reminders, _, _) = futures::join!(
+        calendar_module_task,
+        reminders_task,
+        spy_task,
+        startup_sequence
+    );
+
+    assert_eq!(
+        reminders.last().unwrap(),
+        &vec![
+            Reminder::new(1, ReminderKind::Notification, now + Duration::minutes(90)),
+            Reminder::new(1, ReminderKind::Notification, now + Duration::minutes(125)),
+            Reminder::new(2, ReminderKind::Notification, tomorrow + Duration::hours(9)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_restore_and_replace_reminder_settings() {
+    let message_bus = MessageBus::new();
+
+    let now = OffsetDateTime::new_utc(
+        Date::from_calendar_date(2000, time::Month::January, 1).unwrap(),
+        Time::from_hms(3, 0, 0).unwrap(),
+    );
+
+    let start = now + Duration::hours(2);
+
+    let persisted_event = CalendarEventDto {
+        kind: CalendarKind::Phone,
+        id: 42,
+        title: "persisted".to_string(),
+        start: start.into(),
+        end: (start + Duration::hours(1)).into(),
+        icon: CalendarEventIcon::Meeting,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: None,
+        reminder_offsets: None,
+    };
+
+    let phone_offsets = |offsets| ReminderSettings {
+        kinds: vec![KindReminderSettings {
+            kind: CalendarKind::Phone,
+            offsets,
+            all_day_offsets: vec![],
+        }],
+    };
+
+    let persister_context = PersisterStubContext {
+        calendar_state: CalendarStateDto::new(vec![persisted_event], vec![], now.into()),
+        reminder_settings: Some(phone_offsets(vec![60])),
+    };
+
+    let persister_task = MessageBus::handle::<PersisterStubContext, PersisterStub>(
+        message_bus.clone(),
+        persister_context,
+    );
+
+    let calendar_module_task = CalendarModule::start(message_bus.clone());
+
+    let reminders_task =
+        MessageBus::handle::<Vec<Vec<Reminder>>, RemindersStub>(message_bus.clone(), vec![]);
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::PersistedCalendarEvents(Arc::new(vec![])),
+    );
+
+    let startup_sequence = async move {
+        message_bus.send_event(Events::TimeNow(now));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_cmd(Commands::SetTimezone(0));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_cmd(Commands::SetReminderSettings(phone_offsets(vec![30])));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_event(Events::InSync(true));
+    };
+
+    let (persister, _, reminders, _, _) = futures::join!(
+        persister_task,
+        calendar_module_task,
+        reminders_task,
+        spy_task,
+        startup_sequence
+    );
+
+    let remind_at: Vec<Vec<OffsetDateTime>> = reminders
+        .iter()
+        .map(|x| x.iter().map(|x| x.remind_at).collect())
+        .collect();
+
+    assert_eq!(remind_at[0], vec![start - Duration::minutes(60)]);
+    assert_eq!(remind_at[1], vec![start - Duration::minutes(30)]);
+    assert_eq!(remind_at.last(), Some(&vec![start - Duration::minutes(30)]));
+
+    assert_eq!(persister.reminder_settings, Some(phone_offsets(vec![30])));
+}
+
+#[tokio::test]
+async fn should_relocalize_events_when_timezone_transition_passes() {
+    let message_bus = MessageBus::new();
+
+    let before = datetime!(2024-03-31 00:30 UTC);
+    let start = datetime!(2024-03-31 06:00 UTC);
+
+    let persisted_event = CalendarEventDto {
+        kind: CalendarKind::Phone,
+        id: 42,
+        title: "breakfast".to_string(),
+        start: start.into(),
+        end: (start + Duration::hours(1)).into(),
+        icon: CalendarEventIcon::Default,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: None,
+        reminder_offsets: None,
+    };
+
+    let persister_context = PersisterStubContext {
+        calendar_state: CalendarStateDto::new(vec![persisted_event], vec![], before.into()),
+        reminder_settings: None,
+    };
+
+    let persister_task = MessageBus::handle::<PersisterStubContext, PersisterStub>(
+        message_bus.clone(),
+        persister_context,
+    );
+
+    let calendar_module_task = CalendarModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(message_bus.clone(), Events::Temperature(0));
+
+    let startup_sequence = async move {
+        message_bus.send_event(Events::TimeNow(before));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_cmd(Commands::SetTimezoneRules(berlin_2024()));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_event(Events::TimeNow(before + Duration::hours(1)));
+
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        message_bus.send_event(Events::Temperature(0));
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(async {
+            persister_task.await;
+        }),
+        Box::pin(calendar_module_task),
+        Box::pin(spy_task),
+        Box::pin(startup_sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+
+    let starts: Vec<OffsetDateTime> = spy
+        .get_result()
+        .filter_map(|x| match x {
+            Events::CalendarEventsBatch(batch) => Some(batch[0].start),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        starts,
+        vec![
+            datetime!(2024-03-31 07:00 +01:00),
+            datetime!(2024-03-31 08:00 +02:00)
+        ]
+    );
+    assert!(starts
+        .iter()
+        .all(|x| x.unix_timestamp() == start.unix_timestamp()));
+}
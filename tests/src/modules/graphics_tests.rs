@@ -0,0 +1,128 @@
+use blinky_shared::modules::{
+    fonts_set::{FontSet, FontSet240, FontSet466},
+    graphics::{GlyphRun, RoundTextLayout},
+};
+use embedded_graphics::{geometry::Point, primitives::Rectangle};
+
+const TEXT: &str = "Quarterly planning with the infrastructure team in the big meeting \
+    room on the third floor, bring the laptops and the printed roadmap";
+
+#[test]
+fn should_keep_glyphs_inside_circle_240() {
+    for top in [8, 30, 60, 120, 180, 215] {
+        let layout = some_layout(240, top, 10);
+        let runs = layout.layout(TEXT, FontSet240::get_event_details_font());
+
+        assert_inside_circle(&layout, &runs);
+    }
+}
+
+#[test]
+fn should_keep_glyphs_inside_circle_466() {
+    for top in [10, 46, 93, 200, 330, 420] {
+        let layout = some_layout(466, top, 10);
+        let runs = layout.layout(TEXT, FontSet466::get_event_details_font());
+
+        assert_inside_circle(&layout, &runs);
+    }
+}
+
+#[test]
+fn should_keep_glyphs_inside_circle_with_clock_font() {
+    let layout = some_layout(466, 60, 10);
+    let runs = layout.layout("12 34 56 78 90 1234567890", FontSet466::get_clock_font());
+
+    assert!(!runs.is_empty());
+    assert_inside_circle(&layout, &runs);
+}
+
+#[test]
+fn should_center_runs_and_stack_them() {
+    let layout = some_layout(466, 93, 10);
+    let runs = layout.layout(TEXT, FontSet466::get_event_details_font());
+
+    assert!(runs.len() > 3);
+
+    for run in runs.iter() {
+        let left = layout.center.x - run.bounding_box.top_left.x;
+        let right =
+            run.bounding_box.top_left.x + run.bounding_box.size.width as i32 - layout.center.x;
+
+        assert!((left - right).abs() <= 1, "{:?} is off center", run);
+    }
+
+    // every box starts where its line does
+    assert!(runs.windows(2).all(|x| x[0].position.y < x[1].position.y));
+    assert_eq!(runs[0].bounding_box.top_left.y, 93);
+}
+
+#[test]
+fn should_hyphenate_words_wider_than_a_line() {
+    let layout = some_layout(240, 100, 10);
+    let word = "Donaudampfschifffahrtsgesellschaftskapitaenswitwe";
+
+    let runs = layout.layout(word, FontSet240::get_event_details_font());
+
+    assert!(runs.len() > 1);
+    assert!(runs[..runs.len() - 1].iter().all(|x| x.text.ends_with('-')));
+
+    let joined: String = runs.iter().map(|x| x.text.trim_end_matches('-')).collect();
+    assert_eq!(joined, word);
+
+    assert_inside_circle(&layout, &runs);
+}
+
+#[test]
+fn should_ellipsize_what_does_not_fit() {
+    let layout = some_layout(466, 200, 2);
+    let runs = layout.layout(TEXT, FontSet466::get_event_details_font());
+
+    assert_eq!(runs.len(), 2);
+    assert!(runs[1].text.ends_with("..."));
+    assert_inside_circle(&layout, &runs);
+
+    let layout = some_layout(466, 200, 10);
+    let runs = layout.layout("Lunch", FontSet466::get_event_details_font());
+
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].text, "Lunch");
+}
+
+fn some_layout(side: i32, top: i32, max_lines: usize) -> RoundTextLayout {
+    RoundTextLayout {
+        center: Point::new(side / 2, side / 2),
+        radius: side as u32 / 2,
+        top,
+        padding: 4,
+        max_lines,
+    }
+}
+
+// the circle is convex, so the corners of every box are enough
+fn assert_inside_circle(layout: &RoundTextLayout, runs: &[GlyphRun]) {
+    let radius = layout.radius as i32;
+
+    for run in runs {
+        for corner in corners(&run.bounding_box) {
+            let offset = corner - layout.center;
+
+            assert!(
+                offset.x * offset.x + offset.y * offset.y <= radius * radius,
+                "{:?} of {:?} is outside the circle",
+                corner,
+                run
+            );
+        }
+    }
+}
+
+fn corners(rect: &Rectangle) -> [Point; 4] {
+    let bottom_right = rect.bottom_right().unwrap_or(rect.top_left);
+
+    [
+        rect.top_left,
+        Point::new(bottom_right.x, rect.top_left.y),
+        Point::new(rect.top_left.x, bottom_right.y),
+        bottom_right,
+    ]
+}
@@ -0,0 +1,181 @@
+use blinky_shared::{
+    calendar::{CalendarEvent, CalendarEventIcon, CalendarKind},
+    modules::calendar_module::{LaneAllocator, MAX_LANES, OVERFLOW_LANE},
+};
+use time::{macros::datetime, Duration, OffsetDateTime};
+
+const PROPERTY_RUNS: usize = 200;
+
+#[test]
+fn should_reuse_lane_for_back_to_back_events() {
+    let events = vec![
+        some_event(1, 9 * 60, 10 * 60),
+        some_event(2, 10 * 60, 11 * 60),
+        some_event(3, 11 * 60, 12 * 60),
+    ];
+
+    let lanes = lanes_of(&LaneAllocator::new(MAX_LANES).allocate(&events));
+
+    assert_eq!(lanes, vec![(1, 0), (2, 0), (3, 0)]);
+}
+
+#[test]
+fn should_stack_overlapping_events() {
+    let events = vec![
+        some_event(1, 9 * 60, 12 * 60),
+        some_event(2, 10 * 60, 11 * 60),
+        some_event(3, 10 * 60 + 30, 13 * 60),
+        // fits into the lane of 2 once it ended
+        some_event(4, 11 * 60, 12 * 60),
+    ];
+
+    let lanes = lanes_of(&LaneAllocator::new(MAX_LANES).allocate(&events));
+
+    assert_eq!(lanes, vec![(1, 0), (2, 1), (3, 2), (4, 1)]);
+}
+
+#[test]
+fn should_keep_lanes_when_earlier_event_arrives() {
+    let mut allocator = LaneAllocator::new(MAX_LANES);
+
+    let mut events = vec![
+        some_event(1, 10 * 60, 12 * 60),
+        some_event(2, 11 * 60, 13 * 60),
+    ];
+
+    assert_eq!(lanes_of(&allocator.allocate(&events)), vec![(1, 0), (2, 1)]);
+
+    events.push(some_event(3, 9 * 60, 10 * 60 + 30));
+
+    assert_eq!(
+        lanes_of(&allocator.allocate(&events)),
+        vec![(1, 0), (2, 1), (3, 1)]
+    );
+}
+
+#[test]
+fn should_keep_persisted_lanes_after_restart() {
+    let mut first = some_event(1, 10 * 60, 12 * 60);
+    first.lane = 2;
+
+    let mut second = some_event(2, 9 * 60, 11 * 60);
+    second.lane = 0;
+
+    let lanes = lanes_of(&LaneAllocator::new(MAX_LANES).allocate(&[first, second]));
+
+    assert_eq!(lanes, vec![(1, 2), (2, 0)]);
+}
+
+#[test]
+fn should_collapse_overflow_and_fix_bad_lanes() {
+    let mut events: Vec<CalendarEvent> = (0..MAX_LANES as i32 + 2)
+        .map(|x| some_event(x + 1, 9 * 60 + x as i64, 12 * 60))
+        .collect();
+
+    events[0].lane = 200;
+
+    let allocated = LaneAllocator::new(MAX_LANES).allocate(&events);
+
+    let overflow: Vec<i32> = allocated
+        .iter()
+        .filter(|x| x.lane == OVERFLOW_LANE)
+        .map(|x| x.id)
+        .collect();
+
+    assert_eq!(overflow, vec![MAX_LANES as i32 + 1, MAX_LANES as i32 + 2]);
+    assert!(allocated
+        .iter()
+        .all(|x| x.lane < MAX_LANES || x.lane == OVERFLOW_LANE));
+}
+
+#[test]
+fn should_pass_long_events_through() {
+    let mut all_day = some_event(1, 0, 24 * 60);
+    all_day.lane = 7;
+
+    let allocated = LaneAllocator::new(MAX_LANES).allocate(&[all_day]);
+
+    assert_eq!(allocated[0].lane, 7);
+}
+
+#[test]
+fn should_allocate_non_overlapping_lanes_in_any_order() {
+    let mut random = XorShift(0x853c_49e6_748f_ea9b);
+
+    for _ in 0..PROPERTY_RUNS {
+        let events: Vec<CalendarEvent> = (0..1 + random.below(12))
+            .map(|x| {
+                let start = random.below(12 * 60) as i64;
+                let mut event = some_event(x as i32, start, start + 1 + random.below(180) as i64);
+                event.lane = random.below(8) as u8;
+                event
+            })
+            .collect();
+
+        let allocated = LaneAllocator::new(MAX_LANES).allocate(&events);
+
+        let mut shuffled = events.clone();
+        shuffled.reverse();
+
+        let mut reversed = lanes_of(&LaneAllocator::new(MAX_LANES).allocate(&shuffled));
+        reversed.sort();
+
+        assert_eq!(lanes_of(&allocated), reversed);
+
+        for a in allocated.iter().filter(|x| x.lane != OVERFLOW_LANE) {
+            assert!(a.lane < MAX_LANES);
+
+            let clash = allocated
+                .iter()
+                .any(|b| b.id != a.id && b.lane == a.lane && b.start < a.end && a.start < b.end);
+
+            assert!(!clash, "{:?}", lanes_of(&allocated));
+        }
+
+        // an event only overflows when every lane is busy at its start
+        for a in allocated.iter().filter(|x| x.lane == OVERFLOW_LANE) {
+            let busy = allocated
+                .iter()
+                .filter(|b| b.lane != OVERFLOW_LANE && b.start <= a.start && a.start < b.end)
+                .count();
+
+            assert_eq!(busy, MAX_LANES as usize);
+        }
+    }
+}
+
+fn lanes_of(events: &[CalendarEvent]) -> Vec<(i32, u8)> {
+    let mut lanes: Vec<(i32, u8)> = events.iter().map(|x| (x.id, x.lane)).collect();
+    lanes.sort();
+    lanes
+}
+
+fn some_event(id: i32, start_minute: i64, end_minute: i64) -> CalendarEvent {
+    let midnight: OffsetDateTime = datetime!(2024-01-01 00:00 UTC);
+
+    CalendarEvent {
+        kind: CalendarKind::Phone,
+        id,
+        title: format!("event {}", id),
+        start: midnight + Duration::minutes(start_minute),
+        end: midnight + Duration::minutes(end_minute),
+        icon: CalendarEventIcon::Default,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: None,
+        reminder_offsets: None,
+    }
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        self.0 % bound
+    }
+}
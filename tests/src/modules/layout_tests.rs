@@ -0,0 +1,145 @@
+use blinky_shared::{
+    display_interface::LayerType,
+    modules::layout::{Anchor, FontRole, Slot, WatchfaceLayout, Widget, WidgetKind},
+};
+use embedded_graphics::{
+    prelude::{Point, Size},
+    primitives::Rectangle,
+};
+
+#[test]
+fn should_resolve_slot_for_display_side() {
+    let clock = Slot::new(500, 500, 560, 140, Anchor::Center);
+
+    assert_eq!(clock.position(240, None), Point::new(120, 120));
+    assert_eq!(clock.position(466, None), Point::new(233, 233));
+
+    assert_eq!(
+        clock.bounds(240),
+        Rectangle::new(Point::new(53, 104), Size::new(134, 33))
+    );
+
+    let ble = Slot::new(248, 542, 60, 60, Anchor::TopLeft);
+
+    assert_eq!(
+        ble.bounds(466),
+        Rectangle::new(Point::new(115, 252), Size::new(27, 27))
+    );
+
+    let date = Slot::new(500, 588, 160, 140, Anchor::CenterRight);
+
+    assert_eq!(date.bounds(466).top_left.x + 74, date.position(466, None).x);
+}
+
+#[test]
+fn should_follow_drawn_widget() {
+    let date = Slot::new(500, 588, 160, 140, Anchor::CenterRight).following(WidgetKind::Clock);
+
+    let clock_text = Rectangle::new(Point::new(105, 197), Size::new(256, 64));
+
+    assert_eq!(date.position(466, None), Point::new(233, 274));
+    assert_eq!(date.position(466, Some(&clock_text)), Point::new(292, 274));
+}
+
+#[test]
+fn should_fit_default_face_on_dial() {
+    let layout = WatchfaceLayout::default();
+
+    for side in [240, 466] {
+        for widget in layout
+            .widgets
+            .iter()
+            .filter(|x| x.layer == LayerType::Clock)
+        {
+            assert!(
+                widget.slot.fits_dial(side),
+                "{:?} is off the dial at {}",
+                widget.kind,
+                side
+            );
+        }
+    }
+
+    assert!(!Slot::new(900, 900, 60, 60, Anchor::Center).fits_dial(466));
+}
+
+#[test]
+fn should_show_first_widget_with_content_per_group() {
+    let layout = WatchfaceLayout::default();
+
+    let kinds = |has: &[WidgetKind]| -> Vec<WidgetKind> {
+        layout
+            .visible(LayerType::Clock, |kind| has.contains(&kind))
+            .iter()
+            .map(|x| x.kind)
+            .collect()
+    };
+
+    assert_eq!(
+        kinds(&[
+            WidgetKind::Clock,
+            WidgetKind::Date,
+            WidgetKind::Temperature,
+            WidgetKind::NextEventCountdown,
+        ]),
+        vec![
+            WidgetKind::Clock,
+            WidgetKind::Date,
+            WidgetKind::NextEventCountdown
+        ]
+    );
+
+    assert_eq!(
+        kinds(&[
+            WidgetKind::Temperature,
+            WidgetKind::PairingPin,
+            WidgetKind::Alarm
+        ]),
+        vec![WidgetKind::PairingPin]
+    );
+
+    assert_eq!(
+        kinds(&[WidgetKind::Battery, WidgetKind::Temperature]),
+        vec![WidgetKind::Battery, WidgetKind::Temperature]
+    );
+
+    let events: Vec<WidgetKind> = layout
+        .visible(LayerType::Events, |_| true)
+        .iter()
+        .map(|x| x.kind)
+        .collect();
+
+    assert_eq!(events, vec![WidgetKind::EventArcs]);
+}
+
+#[test]
+fn should_roundtrip_face_through_msgpack() {
+    let face = WatchfaceLayout {
+        name: "minimal".to_string(),
+        widgets: vec![
+            Widget::new(
+                WidgetKind::Clock,
+                Slot::new(500, 420, 560, 140, Anchor::Center),
+                LayerType::Clock,
+            )
+            .with_font(FontRole::Clock),
+            Widget::new(
+                WidgetKind::Temperature,
+                Slot::new(500, 640, 200, 60, Anchor::Center),
+                LayerType::Clock,
+            )
+            .with_font(FontRole::Day)
+            .in_group(3),
+        ],
+    };
+
+    let decoded = WatchfaceLayout::from_msgpack(&face.to_msgpack()).unwrap();
+
+    assert_eq!(decoded, face);
+    assert_eq!(
+        WatchfaceLayout::from_msgpack(&WatchfaceLayout::default().to_msgpack()).unwrap(),
+        WatchfaceLayout::default()
+    );
+
+    assert!(WatchfaceLayout::from_msgpack(&[0x91, 0x01]).is_err());
+}
@@ -1,2 +1,10 @@
 mod calendar_module_tests;
+mod graphics_tests;
+mod lane_allocator_tests;
+mod layout_tests;
+mod navigation_tests;
+mod persister_module_tests;
+mod recorder_tests;
 mod reference_time_module_tests;
+mod renderer_tests;
+mod round_text_tests;
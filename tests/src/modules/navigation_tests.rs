@@ -0,0 +1,85 @@
+use blinky_shared::{
+    calendar::{CalendarEventKey, CalendarKind},
+    domain::TouchPosition,
+    gestures::{Gesture, SwipeDirection},
+    modules::navigation::{Navigation, Screen, ScreenStack},
+};
+
+#[test]
+fn should_map_gestures_to_navigation() {
+    let position = TouchPosition { x: 10, y: 20 };
+
+    let cases = [
+        (Gesture::Tap(position), Navigation::Select(position)),
+        (Gesture::LongPress(position), Navigation::Menu),
+        (Gesture::Swipe(SwipeDirection::Left), Navigation::Forward),
+        (Gesture::Swipe(SwipeDirection::Right), Navigation::Back),
+        (Gesture::Swipe(SwipeDirection::Up), Navigation::Next),
+        (Gesture::Swipe(SwipeDirection::Down), Navigation::Previous),
+    ];
+
+    for (gesture, navigation) in cases {
+        assert_eq!(Navigation::from_gesture(&gesture), navigation);
+    }
+}
+
+#[test]
+fn should_move_between_screens() {
+    let mut screens = ScreenStack::new();
+
+    assert!(screens.navigate(&Navigation::Forward));
+    assert_eq!(*screens.current(), Screen::Agenda);
+
+    // the agenda scrolls on its own
+    assert!(!screens.navigate(&Navigation::Next));
+
+    assert!(screens.navigate(&Navigation::Back));
+    assert_eq!(*screens.current(), Screen::Watchface);
+
+    assert!(screens.navigate(&Navigation::Next));
+    assert_eq!(*screens.current(), Screen::Weather);
+
+    assert!(screens.navigate(&Navigation::Previous));
+    assert_eq!(*screens.current(), Screen::Watchface);
+
+    assert!(!screens.navigate(&Navigation::Back));
+    assert_eq!(screens.depth(), 1);
+}
+
+#[test]
+fn should_open_settings_from_anywhere() {
+    let mut screens = ScreenStack::new();
+
+    screens.navigate(&Navigation::Forward);
+
+    assert!(screens.navigate(&Navigation::Menu));
+    assert_eq!(*screens.current(), Screen::Settings);
+
+    assert!(!screens.navigate(&Navigation::Menu));
+    assert_eq!(screens.depth(), 3);
+
+    assert!(screens.navigate(&Navigation::Home));
+    assert_eq!(*screens.current(), Screen::Watchface);
+}
+
+#[test]
+fn should_keep_the_watchface_at_the_bottom() {
+    let mut screens = ScreenStack::new();
+
+    screens.push(Screen::Agenda);
+    screens.push(Screen::Agenda);
+    assert_eq!(screens.depth(), 2);
+
+    for id in 0..5 {
+        screens.push(Screen::EventDetail(CalendarEventKey(
+            CalendarKind::Phone,
+            id,
+        )));
+    }
+
+    assert_eq!(screens.depth(), 4);
+
+    while screens.back() {}
+
+    assert_eq!(*screens.current(), Screen::Watchface);
+}
@@ -0,0 +1,191 @@
+use std::{sync::Arc, time::Duration};
+
+use blinky_shared::{
+    calendar::{CalendarEvent, CalendarEventIcon, CalendarKind},
+    commands::Commands,
+    error::Error,
+    events::Events,
+    message_bus::MessageBus,
+    modules::{calendar_module::CalendarModule, persister_module::PersisterModule},
+    persistence::{PersistenceUnit, PersistenceUnitKind},
+    storage::{ChunkedStorage, FileStorage, KeyValueStorage, MemoryStorage, StorageUsage},
+};
+use time::macros::datetime;
+
+use crate::spy_module::SpyModule;
+
+struct CountingStorage {
+    inner: MemoryStorage,
+    writes: usize,
+}
+
+impl KeyValueStorage for CountingStorage {
+    fn read(&self, key: &str) -> Result<Vec<u8>, Error> {
+        self.inner.read(key)
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), Error> {
+        self.writes += 1;
+        self.inner.write(key, data)
+    }
+
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        self.inner.delete(key)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.inner.list()
+    }
+}
+
+#[tokio::test]
+async fn should_restore_persisted_unit_and_skip_unchanged_writes() {
+    let message_bus = MessageBus::new();
+
+    let storage = CountingStorage {
+        inner: MemoryStorage::new(),
+        writes: 0,
+    };
+
+    let persister_task = PersisterModule::start(message_bus.clone(), storage);
+
+    let sequence = async move {
+        let unit = PersistenceUnit::new(PersistenceUnitKind::Reminders, &vec![1, 2, 3]);
+
+        message_bus.send_cmd(Commands::Persist(unit.clone()));
+        message_bus.send_cmd(Commands::Persist(unit));
+        message_bus.send_cmd(Commands::Persist(PersistenceUnit::new(
+            PersistenceUnitKind::Reminders,
+            &vec![4],
+        )));
+
+        let restored = message_bus
+            .request(Commands::Restore(PersistenceUnitKind::Reminders))
+            .await;
+
+        let missing = message_bus
+            .request(Commands::Restore(PersistenceUnitKind::PairingKey))
+            .await;
+
+        let usage = message_bus.request(Commands::GetStorageUsage).await;
+
+        message_bus.send_cmd(Commands::StartDeepSleep);
+
+        (restored, missing, usage)
+    };
+
+    let (storage, (restored, missing, usage)) = futures::join!(persister_task, sequence);
+
+    let restored = match restored {
+        Ok(Events::Restored(unit)) => unit.deserialize::<Vec<i32>>().await.unwrap(),
+        _ => panic!("unexpected reply"),
+    };
+
+    assert_eq!(restored, vec![4]);
+    assert!(matches!(missing, Ok(Events::Restored(unit)) if unit.data.is_err()));
+
+    assert!(matches!(
+        usage,
+        Ok(Events::StorageUsage(usage)) if usage == vec![StorageUsage {
+            key: "Reminders".to_string(),
+            bytes: 22,
+            chunks: 1,
+        }]
+    ));
+
+    // a chunk and the manifest per write
+    assert_eq!(storage.writes, 4);
+    assert_eq!(
+        ChunkedStorage::new(storage).list().unwrap(),
+        vec!["Reminders".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn should_restore_calendar_state_after_restart() {
+    let now = datetime!(2000-01-01 03:00 UTC);
+
+    let event = CalendarEvent {
+        kind: CalendarKind::Phone,
+        id: 3,
+        title: "dentist".to_string(),
+        start: now + time::Duration::hours(1),
+        end: now + time::Duration::hours(2),
+        icon: CalendarEventIcon::Default,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: None,
+        reminder_offsets: None,
+    };
+
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(100));
+
+    let persister_task = PersisterModule::start(message_bus.clone(), MemoryStorage::new());
+    let calendar_task = CalendarModule::start(message_bus.clone());
+
+    let first_run = async move {
+        message_bus.send_event(Events::TimeNow(now));
+        message_bus.send_event(Events::ReferenceCalendarEvent(event));
+        message_bus.send_event(Events::InSync(true));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        message_bus.send_cmd(Commands::StartDeepSleep);
+    };
+
+    let (storage, _, _) = futures::join!(persister_task, calendar_task, first_run);
+
+    let message_bus = MessageBus::new();
+
+    let persister_task = PersisterModule::start(message_bus.clone(), storage);
+    let calendar_task = CalendarModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::CalendarEventsBatch(Arc::new(vec![])),
+    );
+
+    let second_run = async move {
+        message_bus.send_event(Events::TimeNow(now));
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        message_bus.send_cmd(Commands::SetTimezone(0));
+    };
+
+    futures::join!(persister_task, calendar_task, spy_task, second_run);
+
+    let restored = spy
+        .get_result()
+        .find_map(|x| match x {
+            Events::CalendarEventsBatch(batch) => Some(batch.clone()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(restored.len(), 1);
+    assert_eq!(restored[0].title, "dentist");
+}
+
+#[test]
+fn should_keep_file_storage_entries_across_instances() {
+    let root = std::env::temp_dir().join(format!("blinky_storage_{}", std::process::id()));
+
+    let mut storage = FileStorage::create(&root).unwrap();
+
+    storage.write("RtcSyncInfo", &[1, 2]).unwrap();
+    storage.write("Reminders", &[3]).unwrap();
+    storage.write("Reminders", &[4, 5]).unwrap();
+    storage.delete("RtcSyncInfo").unwrap();
+    storage.delete("PairingKey").unwrap();
+
+    let storage = FileStorage::create(&root).unwrap();
+
+    assert_eq!(storage.list().unwrap(), vec!["Reminders".to_string()]);
+    assert_eq!(storage.read("Reminders").unwrap(), vec![4, 5]);
+    assert!(storage.read("RtcSyncInfo").is_err());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
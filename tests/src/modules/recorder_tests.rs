@@ -0,0 +1,292 @@
+use std::{
+    io::Write,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use blinky_shared::{
+    calendar::{CalendarEvent, CalendarEventIcon, CalendarKind},
+    commands::Commands,
+    events::Events,
+    message_bus::{CorrelationId, MessageBus},
+    modules::{
+        calendar_module::CalendarModule,
+        recorder::{
+            from_hex_dump, to_hex_dump, Recorder, ReplayPace, Replayer, TraceMessage, TraceRecord,
+            TraceRing, TraceSink,
+        },
+    },
+    persistence::{PersistenceUnit, PersistenceUnitKind},
+};
+use time::{macros::datetime, OffsetDateTime};
+use tokio::time::sleep;
+
+use crate::spy_module::SpyModule;
+
+#[derive(Clone)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn should_record_events_and_commands_to_trace() {
+    let message_bus = MessageBus::new();
+    let message_bus_clone = message_bus.clone();
+
+    let buffer = SharedBuffer(Arc::new(Mutex::new(vec![])));
+
+    let recorder_task = Recorder::start(message_bus, buffer.clone());
+
+    let startup_sequence = async move {
+        message_bus_clone.send_event(Events::TimeNow(some_now()));
+        message_bus_clone.send_event(Events::BatteryLevel(80));
+        message_bus_clone.send_cmd(Commands::Restore(PersistenceUnitKind::CalendarSyncInfo));
+
+        sleep(Duration::from_millis(50)).await;
+
+        message_bus_clone.send_cmd(Commands::StartDeepSleep);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> =
+        vec![Box::pin(recorder_task), Box::pin(startup_sequence)];
+
+    futures::future::join_all(tasks).await;
+
+    let trace = buffer.0.lock().unwrap().clone();
+    let records = Replayer::read_trace(trace.as_slice()).unwrap();
+
+    assert_eq!(records.len(), 4);
+
+    let events: Vec<&Events> = records
+        .iter()
+        .filter_map(|x| match &x.message {
+            TraceMessage::Event(event) => Some(event),
+            _ => None,
+        })
+        .collect();
+
+    assert!(matches!(events[0], Events::TimeNow(now) if *now == some_now()));
+    assert!(matches!(events[1], Events::BatteryLevel(80)));
+
+    let commands: Vec<&Commands> = records
+        .iter()
+        .filter_map(|x| match &x.message {
+            TraceMessage::Command(command) => Some(command),
+            _ => None,
+        })
+        .collect();
+
+    assert!(matches!(
+        commands[0],
+        Commands::Restore(PersistenceUnitKind::CalendarSyncInfo)
+    ));
+    assert!(matches!(commands[1], Commands::StartDeepSleep));
+
+    assert!(records.windows(2).all(|x| x[0].at_micros <= x[1].at_micros));
+}
+
+#[tokio::test]
+async fn should_leave_secrets_out_of_trace() {
+    let message_bus = MessageBus::new();
+    let message_bus_clone = message_bus.clone();
+
+    let buffer = SharedBuffer(Arc::new(Mutex::new(vec![])));
+
+    let recorder_task = Recorder::start(message_bus, buffer.clone());
+
+    let startup_sequence = async move {
+        let key = PersistenceUnit::new(PersistenceUnitKind::PairingKey, &[7u8; 32]);
+
+        message_bus_clone.send_event(Events::PairingPin(123_456));
+        message_bus_clone.send_cmd(Commands::Persist(key.clone()));
+        message_bus_clone.send_event(Events::Reply(
+            CorrelationId(1),
+            Box::new(Events::Restored(key)),
+        ));
+        message_bus_clone.send_cmd(Commands::Restore(PersistenceUnitKind::PairingKey));
+
+        sleep(Duration::from_millis(50)).await;
+
+        message_bus_clone.send_cmd(Commands::StartDeepSleep);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> =
+        vec![Box::pin(recorder_task), Box::pin(startup_sequence)];
+
+    futures::future::join_all(tasks).await;
+
+    let trace = buffer.0.lock().unwrap().clone();
+    let records = Replayer::read_trace(trace.as_slice()).unwrap();
+
+    assert_eq!(records.len(), 2);
+    assert!(matches!(
+        records[0].message,
+        TraceMessage::Command(Commands::Restore(PersistenceUnitKind::PairingKey))
+    ));
+    assert!(matches!(
+        records[1].message,
+        TraceMessage::Command(Commands::StartDeepSleep)
+    ));
+}
+
+#[test]
+fn should_keep_newest_records_in_trace_ring() {
+    let records: Vec<TraceRecord> = (0..50)
+        .map(|x| TraceRecord {
+            at_micros: x * 1_000,
+            message: TraceMessage::Event(Events::BatteryLevel(x as u16)),
+        })
+        .collect();
+
+    let encoded: Vec<Vec<u8>> = records
+        .iter()
+        .map(|x| rmp_serde::to_vec(x).unwrap())
+        .collect();
+
+    let max_bytes = encoded[40..].iter().map(|x| x.len()).sum();
+    let mut ring = TraceRing::new(max_bytes);
+
+    for record in encoded {
+        ring.write_record(&record).unwrap();
+    }
+
+    let kept = Replayer::read_trace(ring.to_trace().as_slice()).unwrap();
+
+    assert_eq!(
+        kept.iter().map(|x| x.at_micros).collect::<Vec<_>>(),
+        records[40..]
+            .iter()
+            .map(|x| x.at_micros)
+            .collect::<Vec<_>>()
+    );
+
+    // across deep sleep the ring picks up where it was
+    let restored = TraceRing::from_trace(&ring.to_trace(), max_bytes).unwrap();
+
+    assert_eq!(restored.len(), 10);
+    assert_eq!(restored.to_trace(), ring.to_trace());
+}
+
+#[test]
+fn should_read_trace_from_console_dump() {
+    let mut ring = TraceRing::new(4096);
+
+    for x in 0..20 {
+        let record = TraceRecord {
+            at_micros: x,
+            message: TraceMessage::Command(Commands::Restore(PersistenceUnitKind::Reminders)),
+        };
+
+        ring.write_record(&rmp_serde::to_vec(&record).unwrap())
+            .unwrap();
+    }
+
+    let trace = ring.to_trace();
+
+    // the dump is interleaved with other log lines and prefixed by the logger
+    let mut log = String::from("I (31) boot: starting\n");
+    for line in to_hex_dump(&trace) {
+        log.push_str(&format!("I (120) blinky::rtc_trace: {}\n", line));
+        log.push_str("I (121) blinky: unrelated\n");
+    }
+
+    assert_eq!(from_hex_dump(&log).unwrap(), trace);
+
+    let records = Replayer::read_trace(from_hex_dump(&log).unwrap().as_slice()).unwrap();
+
+    assert_eq!(records.len(), 20);
+    assert!(matches!(
+        records[19].message,
+        TraceMessage::Command(Commands::Restore(PersistenceUnitKind::Reminders))
+    ));
+
+    assert!(from_hex_dump("TRACE 0").is_err());
+}
+
+#[tokio::test]
+async fn should_replay_trace_against_calendar_module() {
+    let now = some_now();
+
+    let records = vec![
+        TraceRecord {
+            at_micros: 0,
+            message: TraceMessage::Event(Events::TimeNow(now)),
+        },
+        TraceRecord {
+            at_micros: 1_000,
+            message: TraceMessage::Event(Events::ReferenceCalendarEventUpdatesBatch(Arc::new(
+                vec![some_event(7, now)],
+            ))),
+        },
+    ];
+
+    let mut trace = vec![];
+    for record in records.iter() {
+        rmp_serde::encode::write(&mut trace, record).unwrap();
+    }
+
+    let records = Replayer::read_trace(trace.as_slice()).unwrap();
+
+    let message_bus = MessageBus::new();
+
+    let calendar_module_task = CalendarModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::CalendarEventsBatch(Arc::new(vec![])),
+    );
+
+    let replay_task = async move {
+        Replayer::replay(&message_bus, records, ReplayPace::Accelerated(10)).await;
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(calendar_module_task),
+        Box::pin(spy_task),
+        Box::pin(replay_task),
+    ];
+
+    futures::future::join_all(tasks).await;
+
+    let batch = spy
+        .get_result()
+        .find_map(|x| match x {
+            Events::CalendarEventsBatch(batch) => Some(batch.clone()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(batch.len(), 1);
+    assert_eq!(batch[0].id, 7);
+}
+
+fn some_now() -> OffsetDateTime {
+    datetime!(2000-01-01 10:00:00 UTC)
+}
+
+fn some_event(id: i32, now: OffsetDateTime) -> CalendarEvent {
+    CalendarEvent {
+        kind: CalendarKind::Phone,
+        id,
+        title: "event".to_string(),
+        start: now + time::Duration::hours(1),
+        end: now + time::Duration::hours(2),
+        icon: CalendarEventIcon::Meeting,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: None,
+        reminder_offsets: None,
+    }
+}
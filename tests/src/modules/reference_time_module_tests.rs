@@ -1,7 +1,10 @@
 use std::{pin::Pin, sync::Arc, time::Duration};
 
 use blinky_shared::{
-    contract::packets::{ReferenceDataPacket, ReferenceDataPacketType, ReferenceTimePacket},
+    calendar::CalendarKind,
+    contract::packets::{
+        HandshakePacket, ReferenceDataPacket, ReferenceDataPacketType, ReferenceTimePacket,
+    },
     events::Events,
     message_bus::MessageBus,
     modules::reference_time::ReferenceTime,
@@ -14,14 +17,15 @@ use crate::spy_module::{SpyModule, SpyResult};
 
 #[tokio::test]
 async fn should_emit_ReferenceTime_event_to_the_message_bus() {
-    let message_bus = MessageBus::new();
+    // nothing answers the pairing key restore
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(50));
 
     let mb = message_bus.clone();
     let message_bus_clone = message_bus.clone();
 
     let some_time = get_some_time();
 
-    let reference_time_task = ReferenceTime::start(mb);
+    let reference_time_task = ReferenceTime::start(mb, some_handshake());
 
     let time_clone = some_time.clone();
 
@@ -83,3 +87,7 @@ fn some_refetence_time_event() -> Events {
     let offset_date_time = OffsetDateTime::now_utc();
     Events::ReferenceTime(offset_date_time)
 }
+
+fn some_handshake() -> HandshakePacket {
+    HandshakePacket::watch(240, vec![CalendarKind::Phone])
+}
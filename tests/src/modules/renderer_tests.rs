@@ -0,0 +1,525 @@
+use std::{env, path::PathBuf, sync::Arc};
+
+use blinky_shared::{
+    calendar::{
+        CalendarEvent, CalendarEventIcon, CalendarKind, EventTimelyData, TimelyDataMarker,
+        TimelyDataRecord,
+    },
+    commands::Commands,
+    domain::TouchPosition,
+    events::Events,
+    fasttrack::FastTrackRtcData,
+    message_bus::MessageBus,
+    modules::{
+        fonts_set::{FontSet, FontSet240, FontSet466},
+        icon_set::IconSet,
+        icon_set_240::IconsSet240,
+        icon_set_466::IconsSet466,
+        layout::Face,
+        navigation::Navigation,
+        renderer::Renderer,
+    },
+    reference_data::GpsCoordinates,
+    solar::solar_days,
+};
+use embedded_graphics::pixelcolor::Rgb565;
+use time::{macros::datetime, Duration, OffsetDateTime};
+
+use crate::headless_display::{load_png, save_png, HeadlessDisplay};
+
+const UPDATE_GOLDEN_ENV: &str = "BLINKY_UPDATE_GOLDEN";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_watchface_240() {
+    let frame =
+        render_script::<FontSet240, IconsSet240, 240, { 240 * 240 }>(watchface_script()).await;
+
+    assert_matches_golden("watchface_240", &frame, 240);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_watchface_466() {
+    let frame =
+        render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(watchface_script()).await;
+
+    assert_matches_golden("watchface_466", &frame, 466);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_pairing_pin_240() {
+    let script = vec![Events::TimeNow(some_now()), Events::PairingPin(4821)];
+
+    let frame = render_script::<FontSet240, IconsSet240, 240, { 240 * 240 }>(script).await;
+
+    assert_matches_golden("pairing_pin_240", &frame, 240);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_timely_data_466() {
+    let now = some_now();
+
+    let mut timely_data: Vec<TimelyDataRecord> = [(11, 0.3), (12, 1.5), (13, 4.0), (14, 9.0)]
+        .into_iter()
+        .map(|(hour, value)| some_record(hour, value, TimelyDataMarker::Precipitation))
+        .collect();
+
+    for hour in 8..24 {
+        let value = -4.0 + 2.5 * (hour as f32 - 8.0);
+        timely_data.push(some_record(hour, value, TimelyDataMarker::Temperature));
+    }
+
+    let script = vec![
+        Events::TimeNow(now),
+        Events::CalendarEventsBatch(Arc::new(vec![some_event(
+            1,
+            now + Duration::hours(2),
+            now + Duration::hours(3),
+            CalendarEventIcon::Rain,
+            0,
+            0,
+        )])),
+        Events::EventTimelyData(EventTimelyData {
+            linked_event_id: 7,
+            timely_data,
+        }),
+        Events::TimeNow(now + Duration::seconds(1)),
+    ];
+
+    let frame = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert_matches_golden("timely_data_466", &frame, 466);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_night_band_466() {
+    let now = some_now();
+
+    let london = GpsCoordinates {
+        lat: 51.5074,
+        lon: -0.1278,
+    };
+
+    let script = vec![
+        Events::TimeNow(now),
+        Events::SolarDays(solar_days(now, &london, 2)),
+        Events::TimeNow(now + Duration::seconds(1)),
+    ];
+
+    let frame = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert_matches_golden("night_band_466", &frame, 466);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_analog_face_466() {
+    let mut script = vec![Events::FaceSelected(Face::Analog { second_hand: true })];
+    script.extend(watchface_script());
+
+    let frame = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert_matches_golden("analog_466", &frame, 466);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_move_second_hand_without_leaving_traces() {
+    let now = some_now();
+    let face = Events::FaceSelected(Face::Analog { second_hand: true });
+
+    let mut ticking = vec![face.clone(), Events::Temperature(20)];
+    ticking.extend((0..20).map(|x| Events::TimeNow(now + Duration::seconds(x))));
+
+    let fresh = vec![
+        face,
+        Events::Temperature(20),
+        Events::TimeNow(now + Duration::seconds(19)),
+    ];
+
+    let ticked = render_script::<FontSet240, IconsSet240, 240, { 240 * 240 }>(ticking).await;
+    let rendered = render_script::<FontSet240, IconsSet240, 240, { 240 * 240 }>(fresh).await;
+
+    let traces = ticked
+        .iter()
+        .zip(rendered.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+
+    assert_eq!(traces, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_push_only_changed_pixels() {
+    let now = some_now();
+
+    let mut script = watchface_script();
+    script.push(Events::TimeNow(now + Duration::seconds(2)));
+    script.push(Events::FaceSelected(Face::Analog { second_hand: true }));
+    script.push(Events::TimeNow(now + Duration::seconds(3)));
+
+    let (_, pushed) =
+        render_script_pushed::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    let whole = 466 * 466;
+
+    // the first frame and nothing else goes out whole
+    assert_eq!(pushed[0], whole);
+    assert!(pushed[1..].iter().all(|x| *x < whole));
+
+    let digital_tick = pushed[pushed.len() - 3];
+    let analog_tick = pushed[pushed.len() - 1];
+
+    assert!(
+        digital_tick < whole / 4,
+        "{} pushed for a digital tick",
+        digital_tick
+    );
+    assert!(
+        analog_tick < whole / 4,
+        "{} pushed for an analog tick",
+        analog_tick
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_settings_screen_466() {
+    let mut script = watchface_script();
+    script.push(Events::Navigate(Navigation::Menu));
+
+    let frame = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert_matches_golden("settings_466", &frame, 466);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_restore_watchface_after_navigating_back() {
+    let now = some_now();
+
+    let mut script = watchface_script();
+    script.push(Events::Navigate(Navigation::Forward));
+    script.push(Events::Navigate(Navigation::Menu));
+    script.push(Events::TimeNow(now + Duration::seconds(2)));
+    script.push(Events::Navigate(Navigation::Home));
+
+    let frame = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    let mut script = watchface_script();
+    script.push(Events::TimeNow(now + Duration::seconds(2)));
+
+    let expected = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert!(frame == expected);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_agenda_466() {
+    let mut script = agenda_script();
+    script.push(Events::Navigate(Navigation::Forward));
+
+    let frame = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert_matches_golden("agenda_466", &frame, 466);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_agenda_240() {
+    let mut script = agenda_script();
+    script.push(Events::Navigate(Navigation::Forward));
+
+    let frame = render_script::<FontSet240, IconsSet240, 240, { 240 * 240 }>(script).await;
+
+    assert_matches_golden("agenda_240", &frame, 240);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_scroll_agenda_by_button_and_swipe() {
+    let mut script = agenda_script();
+    script.push(Events::Navigate(Navigation::Forward));
+    script.push(Events::Key1Press);
+    script.push(Events::Key1Press);
+
+    let pressed = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    let mut script = agenda_script();
+    script.push(Events::Navigate(Navigation::Forward));
+    script.push(Events::Navigate(Navigation::Next));
+    script.push(Events::Navigate(Navigation::Next));
+    script.push(Events::Navigate(Navigation::Next));
+    script.push(Events::Navigate(Navigation::Previous));
+
+    let swiped = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert!(pressed == swiped);
+
+    assert_matches_golden("agenda_scrolled_466", &swiped, 466);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn should_render_event_detail_466() {
+    let mut script = agenda_script();
+    script.push(Events::Navigate(Navigation::Forward));
+
+    // the title of the third event
+    script.push(Events::Navigate(Navigation::Select(TouchPosition {
+        x: 233,
+        y: 245,
+    })));
+
+    let frame = render_script::<FontSet466, IconsSet466, 466, { 466 * 466 }>(script).await;
+
+    assert_matches_golden("event_detail_466", &frame, 466);
+}
+
+async fn render_script<TFontSet, TIconSet, const SIDE: usize, const SIZE: usize>(
+    script: Vec<Events>,
+) -> Vec<Rgb565>
+where
+    TFontSet: FontSet + 'static,
+    TIconSet: IconSet + 'static,
+{
+    render_script_pushed::<TFontSet, TIconSet, SIDE, SIZE>(script)
+        .await
+        .0
+}
+
+// a renderer stuck on an event fails the test instead of hanging it
+const COMMIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// the frame after the last event and the pixels pushed by every commit
+async fn render_script_pushed<TFontSet, TIconSet, const SIDE: usize, const SIZE: usize>(
+    script: Vec<Events>,
+) -> (Vec<Rgb565>, Vec<u32>)
+where
+    TFontSet: FontSet + 'static,
+    TIconSet: IconSet + 'static,
+{
+    let message_bus = MessageBus::new();
+    let message_bus_clone = message_bus.clone();
+
+    let (display, mut probe) = HeadlessDisplay::<SIDE, SIZE>::create();
+
+    let rtc_data = FastTrackRtcData {
+        now: Some(some_now()),
+        alarm_status: false,
+    };
+
+    let renderer_task = tokio::spawn(
+        Renderer::<HeadlessDisplay<SIDE, SIZE>, TFontSet, TIconSet>::start(
+            message_bus,
+            display,
+            rtc_data,
+        ),
+    );
+
+    tokio::time::timeout(COMMIT_TIMEOUT, probe.wait_for_commit())
+        .await
+        .expect("no initial frame committed");
+
+    let mut pushed = vec![probe.pixel_counter().last_frame];
+
+    // every renderable event wakes the render loop up for exactly one frame
+    for (index, event) in script.into_iter().enumerate() {
+        message_bus_clone.send_event(event);
+
+        if tokio::time::timeout(COMMIT_TIMEOUT, probe.wait_for_commit())
+            .await
+            .is_err()
+        {
+            panic!("no frame committed after script event {}", index);
+        }
+
+        pushed.push(probe.pixel_counter().last_frame);
+    }
+
+    let frame = probe.frame();
+
+    message_bus_clone.send_cmd(Commands::StartDeepSleep);
+    renderer_task.await.unwrap();
+
+    (frame, pushed)
+}
+
+fn watchface_script() -> Vec<Events> {
+    let now = some_now();
+
+    vec![
+        Events::TimeNow(now),
+        Events::BatteryLevel(80),
+        Events::BleClientConnected,
+        Events::Temperature(20),
+        Events::CalendarEventsBatch(Arc::new(vec![
+            some_event(
+                1,
+                now - Duration::minutes(30),
+                now + Duration::hours(1),
+                CalendarEventIcon::Meeting,
+                0xF800,
+                0,
+            ),
+            some_event(
+                2,
+                now + Duration::hours(3),
+                now + Duration::hours(5),
+                CalendarEventIcon::Train,
+                0,
+                1,
+            ),
+            some_event(
+                3,
+                now + Duration::hours(4),
+                now + Duration::hours(7),
+                CalendarEventIcon::Car,
+                0x07E0,
+                2,
+            ),
+            some_event(
+                4,
+                now - Duration::hours(2),
+                now + Duration::days(1),
+                CalendarEventIcon::Birthday,
+                0x001F,
+                0,
+            ),
+        ])),
+        Events::TimeNow(now + Duration::seconds(1)),
+    ]
+}
+
+// events over three days, one of them long enough to wrap
+fn agenda_script() -> Vec<Events> {
+    let now = some_now();
+
+    let mut long_one = some_event(
+        3,
+        now + Duration::hours(4),
+        now + Duration::hours(6),
+        CalendarEventIcon::Train,
+        0x07E0,
+        0,
+    );
+    long_one.title = "Train to the far away town with a very long name".to_string();
+    long_one.description = "Platform 4, coach 12, seat 61. Change at the junction \
+        for the regional line, the connection waits up to ten minutes for delayed trains \
+        and the last one leaves at midnight"
+        .to_string();
+
+    vec![
+        Events::TimeNow(now),
+        Events::CalendarEventsBatch(Arc::new(vec![
+            some_event(
+                1,
+                now - Duration::minutes(30),
+                now + Duration::hours(1),
+                CalendarEventIcon::Meeting,
+                0xF800,
+                0,
+            ),
+            some_event(
+                2,
+                now + Duration::hours(2),
+                now + Duration::hours(3),
+                CalendarEventIcon::Car,
+                0,
+                0,
+            ),
+            long_one,
+            some_event(
+                4,
+                now + Duration::days(1),
+                now + Duration::days(1) + Duration::hours(1),
+                CalendarEventIcon::Birthday,
+                0x001F,
+                0,
+            ),
+            some_event(
+                5,
+                now + Duration::days(2),
+                now + Duration::days(2) + Duration::hours(2),
+                CalendarEventIcon::Meeting,
+                0xFFE0,
+                0,
+            ),
+        ])),
+    ]
+}
+
+fn some_now() -> OffsetDateTime {
+    datetime!(2000-01-01 10:15:30 +2)
+}
+
+fn some_event(
+    id: i32,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+    icon: CalendarEventIcon,
+    color: u32,
+    lane: u8,
+) -> CalendarEvent {
+    CalendarEvent {
+        kind: CalendarKind::Phone,
+        id,
+        title: format!("event {}", id),
+        start,
+        end,
+        icon,
+        color,
+        description: "".to_string(),
+        lane,
+        recurrence: None,
+        reminder_offsets: None,
+    }
+}
+
+fn some_record(hour: u8, value: f32, data_marker: TimelyDataMarker) -> TimelyDataRecord {
+    TimelyDataRecord {
+        linked_event_id: 7,
+        start_at_hour: hour,
+        duration: Duration::hours(1),
+        value,
+        data_marker,
+    }
+}
+
+fn assert_matches_golden(name: &str, frame: &[Rgb565], side: usize) {
+    let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{}.png", name));
+
+    if env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        save_png(&golden_path, frame, side).unwrap();
+        return;
+    }
+
+    let (golden_side, golden) = load_png(&golden_path).unwrap_or_else(|err| {
+        panic!(
+            "failed to load {:?}: {}, run with {}=1 to create it",
+            golden_path, err, UPDATE_GOLDEN_ENV
+        )
+    });
+
+    assert_eq!(golden_side, side, "golden image size mismatch");
+
+    let mismatched: Vec<usize> = frame
+        .iter()
+        .zip(golden.iter())
+        .enumerate()
+        .filter(|(_, (actual, expected))| actual != expected)
+        .map(|(index, _)| index)
+        .collect();
+
+    if mismatched.is_empty() {
+        return;
+    }
+
+    let actual_path = env::temp_dir().join(format!("{}.actual.png", name));
+    save_png(&actual_path, frame, side).unwrap();
+
+    let first = mismatched[0];
+
+    panic!(
+        "{} pixels differ from {:?}, first at ({}, {}), actual frame saved to {:?}",
+        mismatched.len(),
+        golden_path,
+        first % side,
+        first / side,
+        actual_path
+    );
+}
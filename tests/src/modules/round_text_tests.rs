@@ -0,0 +1,131 @@
+use blinky_shared::modules::round_text::{chord_width, CircleBlock, Overflow};
+
+const CHAR_WIDTH: u32 = 12;
+
+#[test]
+fn should_measure_chords() {
+    assert_eq!(chord_width(100, -1, 1), 199);
+    assert_eq!(chord_width(100, 60, 80), 120);
+    assert_eq!(chord_width(100, -80, -60), 120);
+    assert_eq!(chord_width(100, -100, -90), 0);
+    assert_eq!(chord_width(100, 120, 140), 0);
+}
+
+#[test]
+fn should_narrow_lines_away_from_center() {
+    let block = some_block(-233);
+
+    let widths: Vec<u32> = (0..20).map(|x| block.line_width(x)).collect();
+
+    assert_eq!(widths[0], 0);
+    assert!(widths[..9].windows(2).all(|x| x[0] <= x[1]));
+    assert!(widths[10..].windows(2).all(|x| x[0] >= x[1]));
+    assert_eq!(widths[19], 0);
+}
+
+#[test]
+fn should_wrap_words_within_chords() {
+    let block = some_block(-200);
+    let text = "the quick brown fox jumps over the lazy dog and runs around the whole dial twice";
+
+    let lines = block.wrap(text, 10, measure);
+
+    assert!(lines.len() > 2);
+
+    for (index, line) in lines.iter().enumerate() {
+        assert_eq!(line.top, -200 + index as i32 * 24);
+        assert_eq!(line.width, block.line_width(index));
+        assert!(measure(&line.text) <= line.width, "{:?} overflows", line);
+    }
+
+    // nothing lost or reordered
+    let joined: Vec<&str> = lines.iter().map(|x| x.text.as_str()).collect();
+    assert_eq!(joined.join(" "), text);
+
+    // the first line is narrower than the next one, so it breaks earlier
+    assert!(lines[0].text.len() < lines[1].text.len());
+}
+
+#[test]
+fn should_break_words_longer_than_a_line() {
+    let block = some_block(-12);
+
+    let lines = block.wrap(&"x".repeat(60), 3, measure);
+
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].text.len() + lines[1].text.len(), 60);
+    assert!(lines.iter().all(|x| measure(&x.text) <= x.width));
+}
+
+#[test]
+fn should_stop_at_line_limit_and_circle_edge() {
+    let block = some_block(-100);
+
+    let text = "word ".repeat(200);
+
+    assert_eq!(block.wrap(&text, 2, measure).len(), 2);
+
+    let lines = block.wrap(&text, 100, measure);
+    let last = lines.last().unwrap();
+
+    assert!(last.top + 24 <= 233);
+    assert!(lines.iter().all(|x| measure(&x.text) <= x.width));
+}
+
+#[test]
+fn should_skip_rows_too_narrow_at_the_top() {
+    let block = some_block(-233);
+    let text = "the quick brown fox jumps over the lazy dog";
+
+    let lines = block.wrap(text, 10, measure);
+
+    // the first row is cut down to nothing by the glass, the text starts below it
+    assert!(lines[0].top > -233);
+
+    for line in lines.iter() {
+        let row = ((line.top + 233) / 24) as usize;
+
+        assert_eq!(line.width, block.line_width(row));
+        assert!(measure(&line.text) <= line.width, "{:?} overflows", line);
+    }
+
+    let joined: Vec<&str> = lines.iter().map(|x| x.text.as_str()).collect();
+    assert_eq!(joined.join(" "), text);
+
+    // skipped rows still count towards the limit
+    assert!(block.wrap(text, 1, measure).is_empty());
+}
+
+#[test]
+fn should_hyphenate_and_ellipsize_when_asked() {
+    let block = some_block(-12);
+    let overflow = Overflow {
+        hyphen: Some("-"),
+        ellipsis: Some("..."),
+    };
+
+    let lines = block.wrap_with(&"x".repeat(60), 3, overflow, measure);
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].text.ends_with('-'));
+    assert_eq!(lines[0].text.len() - 1 + lines[1].text.len(), 60);
+
+    let lines = block.wrap_with(&"word ".repeat(50), 2, overflow, measure);
+
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].text.ends_with("..."));
+    assert!(lines.iter().all(|x| measure(&x.text) <= x.width));
+}
+
+fn some_block(top: i32) -> CircleBlock {
+    CircleBlock {
+        radius: 233,
+        top,
+        line_height: 24,
+        padding: 10,
+    }
+}
+
+fn measure(text: &str) -> u32 {
+    text.chars().count() as u32 * CHAR_WIDTH
+}
@@ -0,0 +1,345 @@
+use blinky_shared::{
+    calendar::{state::CalendarStateDto, CalendarEventDto, CalendarEventIcon, CalendarKind},
+    contract::secure_channel::{Role, SecureChannel},
+    error::Error,
+    persistence::{PersistenceUnitKind, SchemaRegistry},
+    reference_data::{ReferenceTimeUtc, TimezoneRules, TimezoneTransition},
+    reminders::{Reminder, ReminderKind, ReminderSet},
+    rtc_drift::{RtcSyncInfo, SyncHistory, SyncSample},
+};
+use serde::{Deserialize, Serialize};
+use time::macros::datetime;
+
+// calendar state as written before the envelope, with the inner version 2, 3 and 4
+const CALENDAR_STATE_V1_INNER_2: &[u8] = &[
+    0x93, 0x02, 0x91, 0xce, 0x66, 0x31, 0xf6, 0x80, 0x91, 0x99, 0x01, 0x07, 0xa7, 0x73, 0x74, 0x61,
+    0x6e, 0x64, 0x75, 0x70, 0x91, 0xce, 0x66, 0x32, 0x04, 0x90, 0x91, 0xce, 0x66, 0x32, 0x08, 0x14,
+    0xa7, 0x4d, 0x65, 0x65, 0x74, 0x69, 0x6e, 0x67, 0xcd, 0xf8, 0x00, 0xa5, 0x64, 0x61, 0x69, 0x6c,
+    0x79, 0x01,
+];
+
+const CALENDAR_STATE_V1_INNER_3: &[u8] = &[
+    0x94, 0x03, 0x91, 0xce, 0x66, 0x31, 0xf6, 0x80, 0x91, 0x99, 0x01, 0x07, 0xa7, 0x73, 0x74, 0x61,
+    0x6e, 0x64, 0x75, 0x70, 0x91, 0xce, 0x66, 0x32, 0x04, 0x90, 0x91, 0xce, 0x66, 0x32, 0x08, 0x14,
+    0xa7, 0x4d, 0x65, 0x65, 0x74, 0x69, 0x6e, 0x67, 0xcd, 0xf8, 0x00, 0xa5, 0x64, 0x61, 0x69, 0x6c,
+    0x79, 0x01, 0x91, 0x95, 0x07, 0x09, 0x92, 0xcd, 0x0e, 0x10, 0x00, 0xca, 0x41, 0xac, 0x00, 0x00,
+    0x02,
+];
+
+const CALENDAR_STATE_V1_INNER_4: &[u8] = &[
+    0x94, 0x04, 0x91, 0xce, 0x66, 0x31, 0xf6, 0x80, 0x91, 0x9b, 0x01, 0x07, 0xa7, 0x73, 0x74, 0x61,
+    0x6e, 0x64, 0x75, 0x70, 0x91, 0xce, 0x66, 0x32, 0x04, 0x90, 0x91, 0xce, 0x66, 0x32, 0x08, 0x14,
+    0xa7, 0x4d, 0x65, 0x65, 0x74, 0x69, 0x6e, 0x67, 0xcd, 0xf8, 0x00, 0xa5, 0x64, 0x61, 0x69, 0x6c,
+    0x79, 0x01, 0xc0, 0x91, 0x05, 0x91, 0x95, 0x07, 0x09, 0x92, 0xcd, 0x0e, 0x10, 0x00, 0xca, 0x41,
+    0xac, 0x00, 0x00, 0x02,
+];
+
+const CALENDAR_STATE_V2: &[u8] = &[
+    0xc1, 0x94, 0xb0, 0x43, 0x61, 0x6c, 0x65, 0x6e, 0x64, 0x61, 0x72, 0x53, 0x79, 0x6e, 0x63, 0x49,
+    0x6e, 0x66, 0x6f, 0x02, 0xce, 0x0d, 0xf4, 0xc9, 0x24, 0xc4, 0x43, 0x93, 0x91, 0xce, 0x66, 0x31,
+    0xf6, 0x80, 0x91, 0x9b, 0x01, 0x07, 0xa7, 0x73, 0x74, 0x61, 0x6e, 0x64, 0x75, 0x70, 0x91, 0xce,
+    0x66, 0x32, 0x04, 0x90, 0x91, 0xce, 0x66, 0x32, 0x08, 0x14, 0xa7, 0x4d, 0x65, 0x65, 0x74, 0x69,
+    0x6e, 0x67, 0xcd, 0xf8, 0x00, 0xa5, 0x64, 0x61, 0x69, 0x6c, 0x79, 0x01, 0xc0, 0x91, 0x05, 0x91,
+    0x95, 0x07, 0x09, 0x92, 0xcd, 0x0e, 0x10, 0x00, 0xca, 0x41, 0xac, 0x00, 0x00, 0x02,
+];
+
+// sync info as written before the envelope, without and with the drift history
+const RTC_SYNC_INFO_V1_LEGACY: &[u8] =
+    &[0x93, 0xce, 0x66, 0x31, 0xf6, 0x80, 0xcd, 0x1c, 0x20, 0xc3];
+
+const RTC_SYNC_INFO_V1_LEGACY_HISTORY: &[u8] = &[
+    0x94, 0xce, 0x66, 0x31, 0xf6, 0x80, 0xcd, 0x1c, 0x20, 0xc3, 0x91, 0x91, 0x92, 0xce, 0x66, 0x31,
+    0xf6, 0x83, 0xce, 0x66, 0x31, 0xf6, 0x80,
+];
+
+const RTC_SYNC_INFO_V1: &[u8] = &[
+    0xc1, 0x94, 0xab, 0x52, 0x74, 0x63, 0x53, 0x79, 0x6e, 0x63, 0x49, 0x6e, 0x66, 0x6f, 0x01, 0xce,
+    0x8d, 0xb2, 0x61, 0x33, 0xc4, 0x17, 0x94, 0xce, 0x66, 0x31, 0xf6, 0x80, 0xcd, 0x1c, 0x20, 0xc3,
+    0x91, 0x91, 0x92, 0xce, 0x66, 0x31, 0xf6, 0x83, 0xce, 0x66, 0x31, 0xf6, 0x80,
+];
+
+const REMINDERS_V1: &[u8] = &[
+    0xc1, 0x94, 0xa9, 0x52, 0x65, 0x6d, 0x69, 0x6e, 0x64, 0x65, 0x72, 0x73, 0x01, 0xce, 0xfe, 0x79,
+    0x82, 0x98, 0xc4, 0x20, 0x94, 0x91, 0x93, 0x99, 0xcd, 0x07, 0xe8, 0x7a, 0x08, 0x37, 0x00, 0x00,
+    0x02, 0x00, 0x00, 0xac, 0x4e, 0x6f, 0x74, 0x69, 0x66, 0x69, 0x63, 0x61, 0x74, 0x69, 0x6f, 0x6e,
+    0x07, 0x90, 0x90, 0xc0,
+];
+
+const REMINDERS_V2: &[u8] = &[
+    0xc1, 0x94, 0xa9, 0x52, 0x65, 0x6d, 0x69, 0x6e, 0x64, 0x65, 0x72, 0x73, 0x02, 0xce, 0x6c, 0xd7,
+    0x47, 0x13, 0xc4, 0x2c, 0x94, 0x91, 0x94, 0x99, 0xcd, 0x07, 0xe8, 0x7a, 0x08, 0x37, 0x00, 0x00,
+    0x02, 0x00, 0x00, 0xac, 0x4e, 0x6f, 0x74, 0x69, 0x66, 0x69, 0x63, 0x61, 0x74, 0x69, 0x6f, 0x6e,
+    0x07, 0x99, 0xcd, 0x07, 0xe8, 0x7a, 0x08, 0x37, 0x00, 0x00, 0x02, 0x00, 0x00, 0x90, 0x90, 0xc0,
+];
+
+const TIMEZONE_RULES_V1: &[u8] = &[
+    0xc1, 0x94, 0xad, 0x54, 0x69, 0x6d, 0x65, 0x7a, 0x6f, 0x6e, 0x65, 0x52, 0x75, 0x6c, 0x65, 0x73,
+    0x01, 0xce, 0x8c, 0x84, 0x97, 0xbb, 0xc4, 0x1d, 0x93, 0xad, 0x45, 0x75, 0x72, 0x6f, 0x70, 0x65,
+    0x2f, 0x42, 0x65, 0x72, 0x6c, 0x69, 0x6e, 0xcd, 0x0e, 0x10, 0x91, 0x92, 0x91, 0xce, 0x66, 0x08,
+    0xb5, 0x90, 0xcd, 0x1c, 0x20,
+];
+
+const PAIRING_KEY_V1: &[u8] = &[
+    0xc1, 0x94, 0xaa, 0x50, 0x61, 0x69, 0x72, 0x69, 0x6e, 0x67, 0x4b, 0x65, 0x79, 0x01, 0xce, 0x46,
+    0x57, 0xbe, 0x97, 0xc4, 0x27, 0x95, 0x01, 0xc4, 0x20, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07,
+    0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07,
+    0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x07, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn should_migrate_legacy_calendar_state_of_every_inner_version() {
+    let registry = SchemaRegistry::default();
+
+    let cases = [
+        (CALENDAR_STATE_V1_INNER_2, None, 0),
+        (CALENDAR_STATE_V1_INNER_3, None, 1),
+        (CALENDAR_STATE_V1_INNER_4, Some(vec![5]), 1),
+    ];
+
+    for (bytes, reminder_offsets, timely_data) in cases {
+        let state: CalendarStateDto = open(&registry, PersistenceUnitKind::CalendarSyncInfo, bytes);
+
+        assert_eq!(state.last_sync, last_sync());
+        assert_eq!(
+            state.events,
+            vec![CalendarEventDto {
+                reminder_offsets,
+                ..event()
+            }]
+        );
+        assert_eq!(state.timely_data.len(), timely_data);
+    }
+}
+
+#[test]
+fn should_open_frozen_fixtures_of_current_versions() {
+    let registry = SchemaRegistry::default();
+
+    let state: CalendarStateDto = open(
+        &registry,
+        PersistenceUnitKind::CalendarSyncInfo,
+        CALENDAR_STATE_V2,
+    );
+
+    assert_eq!(state.events, vec![event()]);
+    assert_eq!(state.timely_data[0].value, 21.5);
+
+    let legacy: RtcSyncInfo = open(
+        &registry,
+        PersistenceUnitKind::RtcSyncInfo,
+        RTC_SYNC_INFO_V1_LEGACY,
+    );
+
+    assert_eq!(
+        legacy,
+        RtcSyncInfo {
+            history: SyncHistory::new(),
+            ..rtc_sync_info()
+        }
+    );
+
+    for bytes in [RTC_SYNC_INFO_V1_LEGACY_HISTORY, RTC_SYNC_INFO_V1] {
+        let info: RtcSyncInfo = open(&registry, PersistenceUnitKind::RtcSyncInfo, bytes);
+
+        assert_eq!(info, rtc_sync_info());
+    }
+
+    // v1 reminders get their schedule from remind_at
+    for bytes in [REMINDERS_V1, REMINDERS_V2] {
+        let reminders: ReminderSet = open(&registry, PersistenceUnitKind::Reminders, bytes);
+        assert_eq!(reminders, reminder_set());
+    }
+
+    let timezone: TimezoneRules = open(
+        &registry,
+        PersistenceUnitKind::TimezoneRules,
+        TIMEZONE_RULES_V1,
+    );
+    assert_eq!(timezone, timezone_rules());
+
+    let channel: SecureChannel = open(&registry, PersistenceUnitKind::PairingKey, PAIRING_KEY_V1);
+    assert_eq!(channel, SecureChannel::new(Role::Watch, [7; 32]));
+}
+
+// a layout change without a version bump breaks this before it breaks a watch
+#[test]
+fn should_seal_current_versions_into_frozen_fixtures() {
+    let registry = SchemaRegistry::default();
+
+    let state = CalendarStateDto::new(vec![event()], vec![], last_sync());
+    let state = rmp_serde::to_vec(&state).unwrap();
+    let sealed = registry.seal(PersistenceUnitKind::CalendarSyncInfo, &state);
+
+    let fixture: CalendarStateDto = open(
+        &registry,
+        PersistenceUnitKind::CalendarSyncInfo,
+        CALENDAR_STATE_V2,
+    );
+    let fixture = CalendarStateDto::new(fixture.events, vec![], fixture.last_sync);
+
+    assert_eq!(
+        registry
+            .open(PersistenceUnitKind::CalendarSyncInfo, &sealed)
+            .unwrap(),
+        rmp_serde::to_vec(&fixture).unwrap()
+    );
+
+    assert_eq!(
+        seal(
+            &registry,
+            PersistenceUnitKind::RtcSyncInfo,
+            &rtc_sync_info()
+        ),
+        RTC_SYNC_INFO_V1
+    );
+    assert_eq!(
+        seal(&registry, PersistenceUnitKind::Reminders, &reminder_set()),
+        REMINDERS_V2
+    );
+    assert_eq!(
+        seal(
+            &registry,
+            PersistenceUnitKind::TimezoneRules,
+            &timezone_rules()
+        ),
+        TIMEZONE_RULES_V1
+    );
+    assert_eq!(
+        seal(
+            &registry,
+            PersistenceUnitKind::PairingKey,
+            &SecureChannel::new(Role::Watch, [7; 32])
+        ),
+        PAIRING_KEY_V1
+    );
+}
+
+#[test]
+fn should_reject_damaged_foreign_and_newer_units() {
+    let registry = SchemaRegistry::default();
+
+    let mut damaged = RTC_SYNC_INFO_V1.to_vec();
+    *damaged.last_mut().unwrap() ^= 1;
+
+    assert!(registry
+        .open(PersistenceUnitKind::RtcSyncInfo, &damaged)
+        .is_err());
+    assert!(registry
+        .open(PersistenceUnitKind::Reminders, RTC_SYNC_INFO_V1)
+        .is_err());
+
+    let downgraded = SchemaRegistry::new().register(PersistenceUnitKind::CalendarSyncInfo, 1);
+
+    assert!(downgraded
+        .open(PersistenceUnitKind::CalendarSyncInfo, CALENDAR_STATE_V2)
+        .is_err());
+
+    let unmigrated = SchemaRegistry::new().register(PersistenceUnitKind::RtcSyncInfo, 2);
+
+    assert!(unmigrated
+        .open(PersistenceUnitKind::RtcSyncInfo, RTC_SYNC_INFO_V1)
+        .is_err());
+}
+
+#[test]
+fn should_run_migrations_in_version_order() {
+    fn append_two(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok([bytes, &[2]].concat())
+    }
+
+    fn append_three(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        Ok([bytes, &[3]].concat())
+    }
+
+    let registry = SchemaRegistry::new()
+        .register(PersistenceUnitKind::TimelyData, 3)
+        .migration(PersistenceUnitKind::TimelyData, 2, append_three)
+        .migration(PersistenceUnitKind::TimelyData, 1, append_two);
+
+    assert_eq!(
+        registry
+            .open(PersistenceUnitKind::TimelyData, &[0x90])
+            .unwrap(),
+        vec![0x90, 2, 3]
+    );
+
+    let sealed = registry.seal(PersistenceUnitKind::TimelyData, &[0x90]);
+
+    assert_eq!(
+        registry
+            .open(PersistenceUnitKind::TimelyData, &sealed)
+            .unwrap(),
+        vec![0x90]
+    );
+}
+
+fn open<T>(registry: &SchemaRegistry, kind: PersistenceUnitKind, bytes: &[u8]) -> T
+where
+    for<'a> T: Deserialize<'a>,
+{
+    let payload = registry.open(kind, bytes).unwrap();
+
+    rmp_serde::from_slice(&payload).unwrap()
+}
+
+fn seal<T: Serialize>(registry: &SchemaRegistry, kind: PersistenceUnitKind, value: &T) -> Vec<u8> {
+    registry.seal(kind, &rmp_serde::to_vec(value).unwrap())
+}
+
+fn last_sync() -> ReferenceTimeUtc {
+    datetime!(2024-05-01 08:00 UTC).into()
+}
+
+fn event() -> CalendarEventDto {
+    CalendarEventDto {
+        kind: CalendarKind::Phone,
+        id: 7,
+        title: "standup".to_string(),
+        start: datetime!(2024-05-01 09:00 UTC).into(),
+        end: datetime!(2024-05-01 09:15 UTC).into(),
+        icon: CalendarEventIcon::Meeting,
+        color: 0xF800,
+        description: "daily".to_string(),
+        lane: 1,
+        recurrence: None,
+        reminder_offsets: Some(vec![5]),
+    }
+}
+
+fn rtc_sync_info() -> RtcSyncInfo {
+    let mut history = SyncHistory::new();
+    history.push(SyncSample {
+        rtc: 1714550403,
+        reference: 1714550400,
+    });
+
+    RtcSyncInfo {
+        last_sync: 1714550400,
+        offset: 7200,
+        in_sync: true,
+        history,
+    }
+}
+
+fn reminder_set() -> ReminderSet {
+    let mut reminders = ReminderSet::new();
+
+    reminders.set(vec![Reminder::new(
+        7,
+        ReminderKind::Notification,
+        datetime!(2024-05-01 08:55 +02:00),
+    )]);
+
+    reminders
+}
+
+fn timezone_rules() -> TimezoneRules {
+    TimezoneRules {
+        name: "Europe/Berlin".to_string(),
+        initial_offset_seconds: 3600,
+        transitions: vec![TimezoneTransition {
+            at: datetime!(2024-03-31 01:00 UTC).into(),
+            offset_seconds: 7200,
+        }],
+    }
+}
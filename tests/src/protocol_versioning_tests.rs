@@ -0,0 +1,258 @@
+use std::{pin::Pin, time::Duration};
+
+use blinky_shared::{
+    calendar::{
+        recurrence::{Frequency, RecurrenceRule},
+        CalendarEventDto, CalendarEventIcon, CalendarKind,
+    },
+    commands::Commands,
+    contract::{
+        packets::{
+            HandshakePacket, ReferenceCalendarEventPacket, ReferenceDataPacket,
+            ReferenceDataPacketType, ReferenceTimePacket,
+        },
+        versioning::{
+            ProtocolNegotiation, LEGACY_PROTOCOL_VERSION, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+            RECURRENCE_PROTOCOL_VERSION,
+        },
+    },
+    events::Events,
+    message_bus::{BusHandler, BusSender, MessageBus},
+    modules::reference_time::ReferenceTime,
+    reference_data::{ReferenceTimeOffset, ReferenceTimeUtc},
+};
+use serde::Serialize;
+
+// a calendar event sent by a v7 companion, before recurrence and reminder offsets
+const CALENDAR_EVENT_V7: &[u8] = &[
+    0x94, 0x07, 0x03, 0x1a, 0xc4, 0x1a, 0x91, 0x99, 0x01, 0x07, 0xa5, 0x53, 0x74, 0x61, 0x6e, 0x64,
+    0x91, 0xce, 0x66, 0x33, 0x1f, 0xff, 0x91, 0xce, 0x66, 0x33, 0x2e, 0x0f, 0x01, 0x00, 0xa0, 0x00,
+];
+
+#[derive(Serialize)]
+struct MinimalHandshake {
+    protocol_version: i32,
+}
+
+#[derive(Serialize)]
+struct CompanionCalendarEvent {
+    calendar_event: CalendarEventDto,
+}
+
+#[test]
+fn should_negotiate_lowest_common_version() {
+    let mut negotiation = ProtocolNegotiation::new();
+
+    let mut companion = some_handshake();
+    companion.protocol_version = PROTOCOL_VERSION + 5;
+
+    assert_eq!(negotiation.accept(&companion).unwrap(), PROTOCOL_VERSION);
+
+    companion.protocol_version = LEGACY_PROTOCOL_VERSION;
+
+    assert_eq!(
+        negotiation.accept(&companion).unwrap(),
+        LEGACY_PROTOCOL_VERSION
+    );
+    assert_eq!(
+        negotiation.negotiated_version(),
+        Some(LEGACY_PROTOCOL_VERSION)
+    );
+}
+
+#[test]
+fn should_reject_incompatible_companion() {
+    let mut negotiation = ProtocolNegotiation::new();
+
+    let mut too_old = some_handshake();
+    too_old.protocol_version = MIN_PROTOCOL_VERSION - 1;
+
+    assert!(negotiation.accept(&too_old).is_err());
+
+    let mut too_new = some_handshake();
+    too_new.protocol_version = PROTOCOL_VERSION + 2;
+    too_new.min_protocol_version = PROTOCOL_VERSION + 1;
+
+    assert!(negotiation.accept(&too_new).is_err());
+    assert_eq!(negotiation.negotiated_version(), None);
+}
+
+#[test]
+fn should_reject_packets_above_negotiated_version() {
+    let mut negotiation = ProtocolNegotiation::new();
+
+    let mut companion = some_handshake();
+    companion.protocol_version = LEGACY_PROTOCOL_VERSION;
+
+    negotiation.accept(&companion).unwrap();
+
+    let legacy = ReferenceDataPacket::wrap_with_version(
+        LEGACY_PROTOCOL_VERSION,
+        ReferenceDataPacketType::Time,
+        some_reference_time(),
+    );
+    let current = ReferenceDataPacket::wrap_with_version(
+        PROTOCOL_VERSION,
+        ReferenceDataPacketType::Time,
+        some_reference_time(),
+    );
+
+    let decoded = negotiation.decode::<ReferenceTimePacket>(&legacy).unwrap();
+
+    assert_eq!(decoded.time, some_reference_time().time);
+    assert!(negotiation.decode::<ReferenceTimePacket>(&current).is_err());
+}
+
+#[test]
+fn should_decode_minimal_companion_handshake() {
+    let packet = ReferenceDataPacket::wrap_with_version(
+        PROTOCOL_VERSION,
+        ReferenceDataPacketType::Handshake,
+        MinimalHandshake {
+            protocol_version: PROTOCOL_VERSION,
+        },
+    );
+
+    let handshake = ProtocolNegotiation::new()
+        .decode::<HandshakePacket>(&packet)
+        .unwrap();
+
+    assert_eq!(handshake.protocol_version, PROTOCOL_VERSION);
+    assert!(handshake.supported_packet_types.is_empty());
+    assert!(handshake.calendar_kinds.is_empty());
+}
+
+#[test]
+fn should_decode_calendar_event_of_previous_version() {
+    let packet: ReferenceDataPacket = rmp_serde::from_slice(CALENDAR_EVENT_V7).unwrap();
+
+    let decoded = ProtocolNegotiation::new()
+        .decode::<ReferenceCalendarEventPacket>(&packet)
+        .unwrap();
+
+    let mut expected = some_calendar_event();
+    expected.recurrence = None;
+    expected.reminder_offsets = None;
+
+    assert_eq!(decoded.calendar_event, expected);
+}
+
+#[test]
+fn should_decode_recurrence_of_current_version() {
+    let packet = ReferenceDataPacket::wrap_with_version(
+        RECURRENCE_PROTOCOL_VERSION,
+        ReferenceDataPacketType::CalendarEvent,
+        CompanionCalendarEvent {
+            calendar_event: some_calendar_event(),
+        },
+    );
+
+    let decoded = ProtocolNegotiation::new()
+        .decode::<ReferenceCalendarEventPacket>(&packet)
+        .unwrap();
+
+    assert_eq!(decoded.calendar_event, some_calendar_event());
+}
+
+#[test]
+fn should_refuse_recurrence_in_previous_version() {
+    let packet = ReferenceDataPacket::wrap_with_version(
+        RECURRENCE_PROTOCOL_VERSION - 1,
+        ReferenceDataPacketType::CalendarEvent,
+        CompanionCalendarEvent {
+            calendar_event: some_calendar_event(),
+        },
+    );
+
+    let decoded = ProtocolNegotiation::new().decode::<ReferenceCalendarEventPacket>(&packet);
+
+    assert!(decoded.is_err());
+}
+
+struct HandshakeStub {}
+
+impl BusHandler<Vec<Vec<u8>>> for HandshakeStub {
+    async fn event_handler(_bus: &BusSender, _context: &mut Vec<Vec<u8>>, _event: Events) {}
+
+    async fn command_handler(bus: &BusSender, context: &mut Vec<Vec<u8>>, command: Commands) {
+        if let Commands::SendData(data) = command {
+            context.push(data.to_vec());
+            bus.send_cmd(Commands::StartDeepSleep);
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_send_handshake_when_companion_connects() {
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(50));
+    let message_bus_clone = message_bus.clone();
+
+    let reference_time_task = ReferenceTime::start(message_bus.clone(), some_handshake());
+
+    let handshake_task = async move {
+        let sent = MessageBus::handle::<Vec<Vec<u8>>, HandshakeStub>(message_bus, vec![]).await;
+
+        let packet: ReferenceDataPacket = rmp_serde::from_slice(&sent[0]).unwrap();
+
+        assert_eq!(packet.packet_type, ReferenceDataPacketType::Handshake);
+        assert_eq!(packet.version, PROTOCOL_VERSION);
+
+        let handshake = ProtocolNegotiation::new()
+            .decode::<HandshakePacket>(&packet)
+            .unwrap();
+
+        assert_eq!(handshake, some_handshake());
+    };
+
+    let startup_sequence = async move {
+        message_bus_clone.send_event(Events::BleClientConnected);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(reference_time_task),
+        Box::pin(handshake_task),
+        Box::pin(startup_sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+}
+
+fn some_handshake() -> HandshakePacket {
+    HandshakePacket::watch(466, vec![CalendarKind::Phone, CalendarKind::Weather])
+}
+
+fn some_reference_time() -> ReferenceTimePacket {
+    ReferenceTimePacket {
+        time: ReferenceTimeOffset {
+            now: 946_720_800,
+            offset_seconds: 60 * 60 * 2,
+        },
+    }
+}
+
+fn some_calendar_event() -> CalendarEventDto {
+    CalendarEventDto {
+        kind: CalendarKind::Phone,
+        id: 7,
+        title: "Stand".to_string(),
+        start: ReferenceTimeUtc {
+            unix_epoch_seconds: 0x6633_1fff,
+        },
+        end: ReferenceTimeUtc {
+            unix_epoch_seconds: 0x6633_2e0f,
+        },
+        icon: CalendarEventIcon::Meeting,
+        color: 0,
+        description: String::new(),
+        lane: 0,
+        recurrence: Some(RecurrenceRule {
+            frequency: Frequency::Weekly,
+            interval: 1,
+            by_day: 0b0001_0101,
+            count: None,
+            until: None,
+            exdates: vec![],
+        }),
+        reminder_offsets: Some(vec![10, 60]),
+    }
+}
@@ -0,0 +1,349 @@
+use blinky_shared::calendar::{
+    recurrence::{Frequency, RecurrenceRule},
+    CalendarEvent, CalendarEventIcon, CalendarKind,
+};
+use time::{macros::datetime, Date, Duration, OffsetDateTime, Time, Weekday};
+
+const PROPERTY_RUNS: usize = 300;
+
+const HORIZON_DAYS: i64 = 3000;
+
+#[test]
+fn should_repeat_weekly_on_selected_days_until_count() {
+    let start = datetime!(2024-01-01 09:30 UTC);
+
+    let mut rule = RecurrenceRule::new(Frequency::Weekly, 1);
+    rule.by_day = RecurrenceRule::weekday_bit(Weekday::Monday)
+        | RecurrenceRule::weekday_bit(Weekday::Wednesday)
+        | RecurrenceRule::weekday_bit(Weekday::Friday);
+    rule.count = Some(5);
+
+    let occurrences: Vec<_> = rule.occurrences(start).collect();
+
+    assert_eq!(
+        occurrences,
+        vec![
+            datetime!(2024-01-01 09:30 UTC),
+            datetime!(2024-01-03 09:30 UTC),
+            datetime!(2024-01-05 09:30 UTC),
+            datetime!(2024-01-08 09:30 UTC),
+            datetime!(2024-01-10 09:30 UTC),
+        ]
+    );
+}
+
+#[test]
+fn should_skip_months_without_the_start_day() {
+    let start = datetime!(2024-01-31 08:00 UTC);
+
+    let rule = RecurrenceRule::new(Frequency::Monthly, 1);
+
+    let occurrences: Vec<_> = rule.occurrences(start).take(4).collect();
+
+    assert_eq!(
+        occurrences,
+        vec![
+            datetime!(2024-01-31 08:00 UTC),
+            datetime!(2024-03-31 08:00 UTC),
+            datetime!(2024-05-31 08:00 UTC),
+            datetime!(2024-07-31 08:00 UTC),
+        ]
+    );
+}
+
+#[test]
+fn should_include_until_and_drop_exdates() {
+    let start = datetime!(2024-03-01 07:00 UTC);
+
+    let mut rule = RecurrenceRule::new(Frequency::Daily, 2);
+    rule.until = Some(datetime!(2024-03-07 07:00 UTC).into());
+    rule.exdates = vec![datetime!(2024-03-03 07:00 UTC).into()];
+
+    let occurrences: Vec<_> = rule.occurrences(start).collect();
+
+    assert_eq!(
+        occurrences,
+        vec![
+            datetime!(2024-03-01 07:00 UTC),
+            datetime!(2024-03-05 07:00 UTC),
+            datetime!(2024-03-07 07:00 UTC),
+        ]
+    );
+}
+
+#[test]
+fn should_count_excluded_occurrences() {
+    let start = datetime!(2024-03-01 07:00 UTC);
+
+    let mut rule = RecurrenceRule::new(Frequency::Yearly, 1);
+    rule.count = Some(3);
+    rule.exdates = vec![datetime!(2025-03-01 07:00 UTC).into()];
+
+    let occurrences: Vec<_> = rule.occurrences(start).collect();
+
+    assert_eq!(
+        occurrences,
+        vec![
+            datetime!(2024-03-01 07:00 UTC),
+            datetime!(2026-03-01 07:00 UTC),
+        ]
+    );
+}
+
+#[test]
+fn should_skip_years_without_the_start_day() {
+    let start = datetime!(2024-02-29 07:00 UTC);
+
+    let rule = RecurrenceRule::new(Frequency::Yearly, 3);
+
+    let occurrences: Vec<_> = rule.occurrences(start).take(3).collect();
+
+    assert_eq!(
+        occurrences,
+        vec![
+            datetime!(2024-02-29 07:00 UTC),
+            datetime!(2036-02-29 07:00 UTC),
+            datetime!(2048-02-29 07:00 UTC),
+        ]
+    );
+}
+
+#[test]
+fn should_expand_event_occurrences_in_range() {
+    let start = datetime!(2024-01-01 23:00 +02:00);
+
+    let mut rule = RecurrenceRule::new(Frequency::Daily, 1);
+    rule.exdates = vec![datetime!(2024-01-11 23:00 +02:00).into()];
+
+    let event = CalendarEvent {
+        kind: CalendarKind::Phone,
+        id: 7,
+        title: "daily".to_string(),
+        start,
+        end: start + Duration::hours(2),
+        icon: CalendarEventIcon::Default,
+        color: 0,
+        description: "".to_string(),
+        lane: 0,
+        recurrence: Some(rule),
+        reminder_offsets: None,
+    };
+
+    let occurrences = event.occurrences_between(
+        datetime!(2024-01-11 00:30 +02:00),
+        datetime!(2024-01-12 23:00 +02:00),
+        None,
+    );
+
+    let starts: Vec<_> = occurrences.iter().map(|x| x.start).collect();
+
+    assert_eq!(
+        starts,
+        vec![
+            datetime!(2024-01-10 23:00 +02:00),
+            datetime!(2024-01-12 23:00 +02:00),
+        ]
+    );
+    assert!(occurrences
+        .iter()
+        .all(|x| x.id == 7 && x.recurrence.is_none() && x.duration() == Duration::hours(2)));
+
+    assert!(!event.is_over(datetime!(2030-01-01 00:00 +02:00)));
+}
+
+#[test]
+fn should_generate_the_same_occurrences_as_brute_force() {
+    let mut random = XorShift(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..PROPERTY_RUNS {
+        let (start, rule) = random_rule(&mut random);
+
+        let horizon = start + Duration::days(HORIZON_DAYS);
+
+        let expected = brute_force(&rule, start, horizon);
+        let actual: Vec<_> = rule
+            .occurrences(start)
+            .take_while(|x| *x <= horizon)
+            .collect();
+
+        assert_eq!(actual, expected, "{:?} from {}", rule, start);
+
+        assert!(actual.windows(2).all(|x| x[0] < x[1]));
+        assert!(actual
+            .iter()
+            .all(|x| *x >= start && x.time() == start.time()));
+
+        if rule.by_day != 0 && rule.frequency != Frequency::Yearly {
+            assert!(actual
+                .iter()
+                .all(|x| rule.by_day & RecurrenceRule::weekday_bit(x.weekday()) != 0));
+        }
+
+        if rule.frequency == Frequency::Daily {
+            assert!(actual
+                .iter()
+                .all(|x| (*x - start).whole_days() % rule.interval as i64 == 0));
+        }
+
+        if let Some(count) = rule.count {
+            assert!(actual.len() <= count as usize);
+        }
+    }
+}
+
+#[test]
+fn should_resume_occurrences_from_any_instant() {
+    let mut random = XorShift(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..PROPERTY_RUNS {
+        let (start, rule) = random_rule(&mut random);
+
+        let from = start + Duration::hours(random.below(24 * HORIZON_DAYS as u64 / 2) as i64);
+        let horizon = start + Duration::days(HORIZON_DAYS);
+
+        let expected: Vec<_> = brute_force(&rule, start, horizon)
+            .into_iter()
+            .filter(|x| *x >= from)
+            .collect();
+
+        let actual: Vec<_> = rule
+            .occurrences_from(start, from)
+            .skip_while(|x| *x < from)
+            .take_while(|x| *x <= horizon)
+            .collect();
+
+        assert_eq!(actual, expected, "{:?} from {} at {}", rule, start, from);
+    }
+}
+
+fn brute_force(
+    rule: &RecurrenceRule,
+    start: OffsetDateTime,
+    horizon: OffsetDateTime,
+) -> Vec<OffsetDateTime> {
+    let mut result = vec![];
+    let mut generated = 0;
+
+    let mut date = start.date();
+
+    while date <= horizon.date() {
+        let candidate = date.with_time(start.time()).assume_offset(start.offset());
+        date = date.next_day().unwrap();
+
+        if !matches_rule(rule, start.date(), candidate.date()) {
+            continue;
+        }
+
+        if rule.count.is_some_and(|x| generated >= x)
+            || rule
+                .until
+                .as_ref()
+                .is_some_and(|x| candidate.unix_timestamp() > x.unix_epoch_seconds)
+        {
+            break;
+        }
+
+        generated += 1;
+
+        let excluded = rule
+            .exdates
+            .iter()
+            .any(|x| x.unix_epoch_seconds == candidate.unix_timestamp());
+
+        if !excluded && candidate <= horizon {
+            result.push(candidate);
+        }
+    }
+
+    result
+}
+
+fn matches_rule(rule: &RecurrenceRule, start: Date, date: Date) -> bool {
+    let interval = rule.interval.max(1) as i64;
+    let has_weekday = rule.by_day & RecurrenceRule::weekday_bit(date.weekday()) != 0;
+
+    match rule.frequency {
+        Frequency::Daily => {
+            (date - start).whole_days() % interval == 0 && (rule.by_day == 0 || has_weekday)
+        }
+        Frequency::Weekly => {
+            let weeks = (monday(date) - monday(start)).whole_weeks();
+
+            match rule.by_day {
+                0 => weeks % interval == 0 && date.weekday() == start.weekday(),
+                _ => weeks % interval == 0 && has_weekday,
+            }
+        }
+        Frequency::Monthly => {
+            let months = (date.year() - start.year()) as i64 * 12 + date.month() as i64
+                - start.month() as i64;
+
+            match rule.by_day {
+                0 => months % interval == 0 && date.day() == start.day(),
+                _ => months % interval == 0 && has_weekday,
+            }
+        }
+        Frequency::Yearly => {
+            (date.year() - start.year()) as i64 % interval == 0
+                && date.month() == start.month()
+                && date.day() == start.day()
+        }
+    }
+}
+
+fn monday(date: Date) -> Date {
+    date - Duration::days(date.weekday().number_days_from_monday() as i64)
+}
+
+fn random_rule(random: &mut XorShift) -> (OffsetDateTime, RecurrenceRule) {
+    let day = Date::from_calendar_date(2020, time::Month::January, 1)
+        .unwrap()
+        .to_julian_day()
+        + random.below(365 * 10) as i32;
+
+    let start = Date::from_julian_day(day)
+        .unwrap()
+        .with_time(Time::from_hms(random.below(24) as u8, 15, 0).unwrap())
+        .assume_utc();
+
+    let frequency = match random.below(4) {
+        0 => Frequency::Daily,
+        1 => Frequency::Weekly,
+        2 => Frequency::Monthly,
+        _ => Frequency::Yearly,
+    };
+
+    let mut rule = RecurrenceRule::new(frequency, 1 + random.below(3) as u16);
+
+    if random.below(2) == 0 {
+        rule.by_day = random.below(128) as u8;
+    }
+
+    if random.below(3) == 0 {
+        rule.count = Some(1 + random.below(40) as u16);
+    }
+
+    if random.below(3) == 0 {
+        let until = start + Duration::days(random.below(HORIZON_DAYS as u64) as i64);
+        rule.until = Some(until.into());
+    }
+
+    for _ in 0..random.below(4) {
+        let exdate = start + Duration::days(random.below(60) as i64);
+        rule.exdates.push(exdate.into());
+    }
+
+    (start, rule)
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        self.0 % bound
+    }
+}
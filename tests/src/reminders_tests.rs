@@ -0,0 +1,188 @@
+use blinky_shared::{
+    calendar::CalendarKind,
+    persistence::{PersistenceUnit, PersistenceUnitKind},
+    reminders::{Reminder, ReminderKind, ReminderSet, ReminderSettings},
+};
+use time::{macros::datetime, OffsetDateTime};
+
+#[test]
+fn should_fire_due_reminders_once() {
+    let mut reminders = ReminderSet::new();
+
+    reminders.set(vec![
+        some_reminder(2, datetime!(2024-01-01 10:00 UTC)),
+        some_reminder(1, datetime!(2024-01-01 09:50 UTC)),
+    ]);
+
+    assert_eq!(reminders.next().unwrap().event_id, 1);
+    assert!(reminders.due(datetime!(2024-01-01 09:49 UTC)).is_empty());
+
+    let due = reminders.due(datetime!(2024-01-01 10:00 UTC));
+
+    assert_eq!(
+        due.iter().map(|x| x.event_id).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert!(reminders.is_empty());
+
+    // the calendar resends the same reminders on every sync
+    reminders.set(vec![
+        some_reminder(1, datetime!(2024-01-01 09:50 UTC)),
+        some_reminder(3, datetime!(2024-01-01 11:00 UTC)),
+    ]);
+
+    assert_eq!(reminders.next().unwrap().event_id, 3);
+}
+
+#[test]
+fn should_snooze_fired_reminder() {
+    let mut reminders = ReminderSet::new();
+    let reminder = some_reminder(1, datetime!(2024-01-01 09:50 UTC));
+
+    reminders.set(vec![reminder.clone()]);
+
+    let fired = reminders.due(datetime!(2024-01-01 09:50 UTC)).remove(0);
+
+    reminders.snooze(fired.clone(), datetime!(2024-01-01 09:55 UTC));
+    reminders.snooze(fired, datetime!(2024-01-01 09:57 UTC));
+
+    reminders.set(vec![reminder]);
+
+    assert_eq!(
+        reminders.next().unwrap().remind_at,
+        datetime!(2024-01-01 09:57 UTC)
+    );
+    assert!(reminders.due(datetime!(2024-01-01 09:56 UTC)).is_empty());
+    assert_eq!(reminders.due(datetime!(2024-01-01 09:57 UTC)).len(), 1);
+    assert!(reminders.is_empty());
+}
+
+#[test]
+fn should_not_rearm_dismissed_reminders() {
+    let mut reminders = ReminderSet::new();
+    let upcoming = some_reminder(1, datetime!(2024-01-01 09:50 UTC));
+
+    reminders.set(vec![upcoming.clone()]);
+    reminders.dismiss(&upcoming);
+    reminders.set(vec![upcoming.clone()]);
+
+    assert!(reminders.next().is_none());
+
+    let snoozed = some_reminder(2, datetime!(2024-01-01 09:00 UTC));
+
+    reminders.snooze(snoozed.clone(), datetime!(2024-01-01 09:10 UTC));
+    reminders.dismiss(&snoozed);
+
+    assert!(reminders.due(datetime!(2024-01-01 12:00 UTC)).is_empty());
+}
+
+#[test]
+fn should_tell_apart_occurrences_and_offsets_of_one_event() {
+    let mut reminders = ReminderSet::new();
+
+    let ten_before = some_reminder(1, datetime!(2024-01-01 09:50 UTC));
+    let five_before = some_reminder(1, datetime!(2024-01-01 09:55 UTC));
+    let tomorrow = some_reminder(1, datetime!(2024-01-02 09:50 UTC));
+
+    reminders.set(vec![
+        ten_before.clone(),
+        five_before.clone(),
+        tomorrow.clone(),
+    ]);
+
+    let fired = reminders.due(datetime!(2024-01-01 09:50 UTC)).remove(0);
+    reminders.snooze(fired, datetime!(2024-01-01 10:00 UTC));
+
+    let fired = reminders.due(datetime!(2024-01-01 09:55 UTC)).remove(0);
+    reminders.snooze(fired, datetime!(2024-01-01 10:05 UTC));
+
+    // both snoozes survive, one doesn't replace the other
+    let due = reminders.due(datetime!(2024-01-01 10:05 UTC));
+
+    assert_eq!(
+        due.iter().map(|x| x.scheduled_at).collect::<Vec<_>>(),
+        vec![ten_before.remind_at, five_before.remind_at]
+    );
+
+    reminders.dismiss(&tomorrow);
+    reminders.set(vec![
+        tomorrow.clone(),
+        some_reminder(1, datetime!(2024-01-03 09:50 UTC)),
+    ]);
+
+    assert_eq!(
+        reminders.next().unwrap().remind_at,
+        datetime!(2024-01-03 09:50 UTC)
+    );
+}
+
+#[test]
+fn should_not_rearm_reminder_dismissed_while_snoozed() {
+    let mut reminders = ReminderSet::new();
+    let reminder = some_reminder(1, datetime!(2024-01-01 09:50 UTC));
+
+    reminders.set(vec![reminder.clone()]);
+
+    let fired = reminders.due(datetime!(2024-01-01 09:50 UTC)).remove(0);
+    reminders.snooze(fired, datetime!(2024-01-01 10:00 UTC));
+
+    let snoozed = reminders.next().unwrap().clone();
+    reminders.dismiss(&snoozed);
+
+    reminders.set(vec![reminder]);
+
+    assert!(reminders.next().is_none());
+}
+
+#[tokio::test]
+async fn should_keep_snoozed_reminders_across_restore() {
+    let mut before_sleep = ReminderSet::new();
+    let reminder = some_reminder(1, datetime!(2024-01-01 09:50 UTC));
+
+    before_sleep.set(vec![reminder.clone()]);
+    before_sleep.due(datetime!(2024-01-01 09:50 UTC));
+    before_sleep.snooze(reminder.clone(), datetime!(2024-01-01 10:05 UTC));
+
+    let unit = PersistenceUnit::new(PersistenceUnitKind::Reminders, &before_sleep);
+    let restored: ReminderSet = unit.deserialize().await.unwrap();
+
+    // the calendar may schedule its reminders before the persister replies
+    let mut after_wakeup = ReminderSet::new();
+    after_wakeup.set(vec![
+        reminder,
+        some_reminder(2, datetime!(2024-01-01 11:00 UTC)),
+    ]);
+    after_wakeup.restore(restored);
+
+    assert_eq!(
+        after_wakeup.next().unwrap().remind_at,
+        datetime!(2024-01-01 10:05 UTC)
+    );
+
+    let due = after_wakeup.due(datetime!(2024-01-01 10:06 UTC));
+
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].event_id, 1);
+    assert_eq!(after_wakeup.next().unwrap().event_id, 2);
+}
+
+#[test]
+fn should_use_kind_default_offsets() {
+    let settings = ReminderSettings::default();
+
+    assert_eq!(
+        settings.default_offsets(CalendarKind::Phone, false),
+        &[10, 0]
+    );
+    assert_eq!(settings.default_offsets(CalendarKind::Phone, true), &[-540]);
+    assert!(settings
+        .default_offsets(CalendarKind::Weather, false)
+        .is_empty());
+
+    assert_eq!(Reminder::kind_for_offset(0), ReminderKind::Event);
+    assert_eq!(Reminder::kind_for_offset(15), ReminderKind::Notification);
+}
+
+fn some_reminder(event_id: i32, remind_at: OffsetDateTime) -> Reminder {
+    Reminder::new(event_id, ReminderKind::Notification, remind_at)
+}
@@ -0,0 +1,210 @@
+use blinky_shared::rtc_drift::{
+    DriftEstimate, SyncHistory, SyncSample, BASE_SYNC_INTERVAL, MAX_SYNC_HISTORY, MAX_SYNC_INTERVAL,
+};
+use time::{macros::datetime, Duration};
+
+const START: i64 = 946_684_800;
+
+const HISTORIES: usize = 200;
+
+// a single estimate is only as good as the whole second readings allow, but it must not be biased
+#[test]
+fn should_estimate_synthetic_drift_without_bias() {
+    let mut random = XorShift(0x5851_f42d_4c95_7f2d);
+
+    for ppm in [-80.0, -23.5, 0.0, 4.0, 17.0, 61.0] {
+        let estimates: Vec<f64> = (0..HISTORIES)
+            .map(|_| synthetic_history(&mut random, ppm, MAX_SYNC_HISTORY, &[]))
+            .map(|x| x.estimate().unwrap().ppm)
+            .collect();
+
+        let mean = estimates.iter().sum::<f64>() / HISTORIES as f64;
+
+        assert!((mean - ppm).abs() < 3.0, "{} estimated as {}", ppm, mean);
+        assert!(estimates.iter().all(|x| (x - ppm).abs() < 60.0));
+    }
+}
+
+#[test]
+fn should_ignore_power_loss_and_manual_time_changes() {
+    let mut random = XorShift(0x1405_7b7e_f767_814f);
+
+    let estimates: Vec<f64> = (0..HISTORIES)
+        .map(|_| synthetic_history(&mut random, 35.0, MAX_SYNC_HISTORY, &[3, 9, 10]))
+        .map(|x| x.estimate().unwrap().ppm)
+        .collect();
+
+    let mean = estimates.iter().sum::<f64>() / HISTORIES as f64;
+
+    assert!((mean - 35.0).abs() < 3.0, "{}", mean);
+    assert!(estimates.iter().all(|x| (x - 35.0).abs() < 60.0));
+}
+
+#[test]
+fn should_anchor_estimate_at_last_sync() {
+    let mut random = XorShift(0x9e37_79b9_7f4a_7c15);
+
+    let history = synthetic_history(&mut random, 20.0, MAX_SYNC_HISTORY, &[]);
+
+    assert_eq!(
+        history.estimate().unwrap().anchor,
+        history.samples().last().unwrap().reference
+    );
+}
+
+#[test]
+fn should_not_estimate_from_short_history() {
+    let mut random = XorShift(0x2545_f491_4f6c_dd1d);
+
+    assert_eq!(SyncHistory::new().estimate(), None);
+    assert_eq!(
+        synthetic_history(&mut random, 20.0, 2, &[]).estimate(),
+        None
+    );
+
+    let mut history = SyncHistory::new();
+
+    for index in 0..4 {
+        let reference = START + index * 600;
+        history.push(SyncSample {
+            rtc: reference,
+            reference,
+        });
+    }
+
+    assert_eq!(history.estimate(), None);
+}
+
+#[test]
+fn should_keep_bounded_history() {
+    let mut history = SyncHistory::new();
+
+    for index in 0..(MAX_SYNC_HISTORY as i64 * 2) {
+        history.push(SyncSample {
+            rtc: START + index,
+            reference: START + index,
+        });
+    }
+
+    assert_eq!(history.samples().len(), MAX_SYNC_HISTORY);
+    assert_eq!(
+        history.samples()[0].reference,
+        START + MAX_SYNC_HISTORY as i64
+    );
+}
+
+#[test]
+fn should_correct_rtc_time_since_anchor() {
+    let anchor = datetime!(2024-05-01 12:00 +02:00);
+
+    let estimate = DriftEstimate {
+        ppm: 50.0,
+        anchor: anchor.unix_timestamp(),
+    };
+
+    // a million seconds last 50 seconds longer on a 50 ppm fast rtc
+    let rtc = anchor + Duration::seconds(1_000_050);
+
+    assert_eq!(estimate.correct(rtc), anchor + Duration::seconds(1_000_000));
+    assert_eq!(estimate.correct(anchor), anchor);
+    assert_eq!(
+        estimate.correct(anchor - Duration::hours(1)),
+        anchor - Duration::hours(1)
+    );
+
+    let reanchored = estimate.anchored_at((anchor + Duration::days(1)).unix_timestamp());
+
+    assert_eq!(
+        reanchored.correct(anchor + Duration::days(1)),
+        anchor + Duration::days(1)
+    );
+}
+
+#[test]
+fn should_map_reference_time_back_to_rtc_time() {
+    let anchor = datetime!(2024-05-01 12:00 +02:00);
+
+    let estimate = DriftEstimate {
+        ppm: 50.0,
+        anchor: anchor.unix_timestamp(),
+    };
+
+    let reference = anchor + Duration::seconds(1_000_000);
+
+    assert_eq!(
+        estimate.to_rtc(reference),
+        anchor + Duration::seconds(1_000_050)
+    );
+    assert_eq!(estimate.to_rtc(anchor), anchor);
+
+    let slow = DriftEstimate {
+        ppm: -120.0,
+        anchor: anchor.unix_timestamp(),
+    };
+
+    for seconds in [1, 3_600, 86_400, 30 * 86_400] {
+        let reference = anchor + Duration::seconds(seconds);
+
+        for estimate in [estimate, slow] {
+            let error = estimate.correct(estimate.to_rtc(reference)) - reference;
+            assert!(error.abs() <= Duration::seconds(1), "{}", error);
+        }
+    }
+}
+
+#[test]
+fn should_stretch_sync_interval_when_drift_is_low() {
+    let interval = |ppm: f64| DriftEstimate::sync_interval(Some(&DriftEstimate { ppm, anchor: 0 }));
+
+    assert_eq!(DriftEstimate::sync_interval(None), BASE_SYNC_INTERVAL);
+    assert_eq!(interval(0.0), MAX_SYNC_INTERVAL);
+    assert_eq!(interval(10000.0), BASE_SYNC_INTERVAL);
+
+    assert!(interval(2.0) > interval(40.0));
+    assert!(interval(-40.0) == interval(40.0));
+    assert!(interval(40.0) > BASE_SYNC_INTERVAL);
+}
+
+// the rtc is overwritten with the whole seconds of the reference on every sync and read back
+// in whole seconds, outliers are syncs after the rtc lost power or was set by hand
+fn synthetic_history(
+    random: &mut XorShift,
+    ppm: f64,
+    samples: usize,
+    outliers: &[usize],
+) -> SyncHistory {
+    let mut history = SyncHistory::new();
+
+    let mut reference = START as f64;
+
+    for index in 0..samples {
+        let set_at = reference;
+        reference += (600 + random.below(12 * 3600)) as f64 + random.below(1000) as f64 / 1000.0;
+
+        let rtc = set_at.floor() + (reference - set_at) * (1.0 + ppm / 1e6);
+
+        let rtc = match outliers.contains(&index) {
+            true => rtc - 3600.0 * (1 + random.below(24)) as f64,
+            false => rtc,
+        };
+
+        history.push(SyncSample {
+            rtc: rtc.floor() as i64,
+            reference: reference.floor() as i64,
+        });
+    }
+
+    history
+}
+
+struct XorShift(u64);
+
+impl XorShift {
+    fn below(&mut self, bound: u64) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        self.0 % bound
+    }
+}
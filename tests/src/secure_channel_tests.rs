@@ -0,0 +1,434 @@
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+use blinky_shared::{
+    commands::Commands,
+    contract::{
+        packets::{
+            HandshakePacket, PairingConfirmPacket, PairingKeyPacket, PairingRequestPacket,
+            ReferenceDataPacket, ReferenceDataPacketType, ReferenceTimePacket, SecurePacket,
+        },
+        secure_channel::{open, seal, PairingSession, Role, SecureChannel, SecureLink, KEY_SIZE},
+        versioning::{ProtocolNegotiation, PROTOCOL_VERSION},
+    },
+    error::Error,
+    events::Events,
+    message_bus::{BusHandler, BusSender, MessageBus},
+    modules::reference_time::ReferenceTime,
+    persistence::{PersistenceUnit, PersistenceUnitKind},
+    reference_data::ReferenceTimeOffset,
+};
+use time::OffsetDateTime;
+
+const WATCH_SECRET: &str = "77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a";
+const COMPANION_SECRET: &str = "5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb";
+const SESSION_ID: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+const SOME_PIN: u32 = 123456;
+
+#[test]
+fn should_derive_same_channel_with_same_pin() {
+    let (mut watch, mut companion) = some_channels();
+
+    let sealed = companion.seal(some_reference_time_packet());
+    let secure: SecurePacket = rmp_serde::from_slice(&sealed.packet_payload).unwrap();
+
+    assert_eq!(watch.open(&secure).unwrap(), some_reference_time_packet());
+
+    let sealed = watch.seal(some_reference_time_packet());
+    let secure: SecurePacket = rmp_serde::from_slice(&sealed.packet_payload).unwrap();
+
+    assert_eq!(
+        companion.open(&secure).unwrap(),
+        some_reference_time_packet()
+    );
+}
+
+#[test]
+fn should_hide_pin_in_public_keys() {
+    let session = |pin| PairingSession::new(Role::Watch, key(WATCH_SECRET), key(SESSION_ID), pin);
+
+    assert_ne!(
+        session(SOME_PIN).public_key(),
+        session(SOME_PIN + 1).public_key()
+    );
+
+    let other_session = PairingSession::new(
+        Role::Watch,
+        key(WATCH_SECRET),
+        key(COMPANION_SECRET),
+        SOME_PIN,
+    );
+
+    assert_ne!(session(SOME_PIN).public_key(), other_session.public_key());
+}
+
+#[test]
+fn should_reject_degenerate_public_keys() {
+    let watch = PairingSession::new(Role::Watch, key(WATCH_SECRET), key(SESSION_ID), SOME_PIN);
+
+    // the identity encodes as zeros, all ones is not a canonical encoding
+    assert!(watch.confirmation(&[0u8; KEY_SIZE]).is_err());
+    assert!(watch.confirmation(&[0xffu8; KEY_SIZE]).is_err());
+}
+
+#[test]
+fn should_match_chacha20_poly1305_vector() {
+    // RFC 8439, section 2.8.2
+    let key = key("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+    let nonce: [u8; 12] = hex("070000004041424344454647").try_into().unwrap();
+    let aad = hex("50515253c0c1c2c3c4c5c6c7");
+    let plaintext = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+    let expected = hex(concat!(
+        "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6",
+        "3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36",
+        "92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc",
+        "3ff4def08e4b7a9de576d26586cec64b6116",
+        "1ae10b594f09e26a7e902ecbd0600691"
+    ));
+
+    let sealed = seal(&key, &nonce, &aad, plaintext);
+
+    assert_eq!(sealed, expected);
+    assert_eq!(open(&key, &nonce, &aad, &sealed).unwrap(), plaintext);
+}
+
+#[test]
+fn should_reject_wrong_pin() {
+    let mut link = SecureLink::new(None);
+
+    link.start_pairing(
+        &PairingRequestPacket {
+            session_id: key(SESSION_ID),
+        },
+        key(WATCH_SECRET),
+        SOME_PIN,
+    );
+
+    let watch = PairingSession::new(Role::Watch, key(WATCH_SECRET), key(SESSION_ID), SOME_PIN);
+    let confirm = |pin| {
+        let companion =
+            PairingSession::new(Role::Companion, key(COMPANION_SECRET), key(SESSION_ID), pin);
+
+        PairingConfirmPacket {
+            public_key: companion.public_key(),
+            confirmation: companion.confirmation(&watch.public_key()).unwrap(),
+        }
+    };
+
+    assert!(link.confirm_pairing(&confirm(SOME_PIN + 1)).is_err());
+    assert!(!link.is_paired());
+
+    // the pin is gone after a failed attempt
+    assert!(link.confirm_pairing(&confirm(SOME_PIN)).is_err());
+    assert!(!link.is_paired());
+}
+
+#[test]
+fn should_confirm_pairing_to_companion() {
+    let mut link = SecureLink::new(None);
+
+    let response = link.start_pairing(
+        &PairingRequestPacket {
+            session_id: key(SESSION_ID),
+        },
+        key(WATCH_SECRET),
+        SOME_PIN,
+    );
+
+    let companion = PairingSession::new(
+        Role::Companion,
+        key(COMPANION_SECRET),
+        key(SESSION_ID),
+        SOME_PIN,
+    );
+
+    let reply = link
+        .confirm_pairing(&PairingConfirmPacket {
+            public_key: companion.public_key(),
+            confirmation: companion.confirmation(&response.public_key).unwrap(),
+        })
+        .unwrap();
+
+    assert_eq!(reply.public_key, response.public_key);
+    assert!(companion
+        .complete(&reply.public_key, &reply.confirmation)
+        .is_ok());
+    assert!(link.is_paired());
+}
+
+#[test]
+fn should_reject_replayed_and_stale_packets() {
+    let (mut watch, mut companion) = some_channels();
+    let negotiation = ProtocolNegotiation::new();
+
+    let sealed: Vec<SecurePacket> = (0..70)
+        .map(|_| {
+            let packet = companion.seal(some_reference_time_packet());
+            negotiation.decode::<SecurePacket>(&packet).unwrap()
+        })
+        .collect();
+
+    assert!(watch.open(&sealed[1]).is_ok());
+    assert!(watch.open(&sealed[1]).is_err());
+
+    // reordered within the window
+    assert!(watch.open(&sealed[0]).is_ok());
+    assert!(watch.open(&sealed[0]).is_err());
+
+    assert!(watch.open(&sealed[69]).is_ok());
+    assert!(watch.open(&sealed[2]).is_err());
+    assert!(watch.open(&sealed[10]).is_ok());
+
+    assert_eq!(watch.rx_counter(), 70);
+}
+
+#[test]
+fn should_reject_tampered_packets() {
+    let (mut watch, mut companion) = some_channels();
+    let negotiation = ProtocolNegotiation::new();
+
+    let packet = companion.seal(some_reference_time_packet());
+    let mut secure = negotiation.decode::<SecurePacket>(&packet).unwrap();
+
+    secure.ciphertext[3] ^= 0x01;
+    assert!(watch.open(&secure).is_err());
+
+    secure.ciphertext[3] ^= 0x01;
+    secure.counter += 1;
+    assert!(watch.open(&secure).is_err());
+
+    secure.counter -= 1;
+    assert!(watch.open(&secure).is_ok());
+}
+
+#[test]
+fn should_reject_plain_packets_once_paired() {
+    let negotiation = ProtocolNegotiation::new();
+
+    let mut unpaired = SecureLink::new(None);
+
+    assert!(unpaired
+        .open(&negotiation, some_reference_time_packet())
+        .is_ok());
+
+    let (watch, mut companion) = some_channels();
+    let mut paired = SecureLink::new(Some(watch));
+
+    assert!(paired
+        .open(&negotiation, some_reference_time_packet())
+        .is_err());
+
+    let opened = paired
+        .open(&negotiation, companion.seal(some_reference_time_packet()))
+        .unwrap();
+
+    assert_eq!(opened, some_reference_time_packet());
+}
+
+struct CompanionStub {}
+
+struct CompanionContext {
+    session: Option<PairingSession>,
+    watch_public_key: Option<[u8; KEY_SIZE]>,
+    pin: Option<u32>,
+    paired: Option<bool>,
+    persisted: Vec<SecureChannel>,
+    reference_time: Option<OffsetDateTime>,
+}
+
+impl BusHandler<CompanionContext> for CompanionStub {
+    async fn event_handler(bus: &BusSender, context: &mut CompanionContext, event: Events) {
+        match event {
+            Events::PairingPin(pin) => {
+                context.pin = Some(pin);
+                try_confirm(bus, context);
+            }
+            Events::Paired(paired) => {
+                context.paired = Some(paired);
+            }
+            Events::ReferenceTime(now) => {
+                context.reference_time = Some(now);
+
+                bus.send_event(Events::BleClientDisconnected);
+            }
+            _ => {}
+        }
+    }
+
+    async fn command_handler(bus: &BusSender, context: &mut CompanionContext, command: Commands) {
+        match command {
+            Commands::Restore(PersistenceUnitKind::PairingKey) => {
+                bus.reply(Events::Restored(PersistenceUnit {
+                    kind: PersistenceUnitKind::PairingKey,
+                    data: Err(Error::from("not found")),
+                }));
+            }
+            Commands::Persist(unit) if matches!(unit.kind, PersistenceUnitKind::PairingKey) => {
+                let channel: SecureChannel = unit.deserialize().await.unwrap();
+
+                if channel.rx_counter() == 1 {
+                    bus.send_cmd(Commands::StartDeepSleep);
+                }
+
+                context.persisted.push(channel);
+            }
+            Commands::SendData(data) => {
+                let packet: ReferenceDataPacket = rmp_serde::from_slice(&data).unwrap();
+
+                match packet.packet_type {
+                    ReferenceDataPacketType::PairingResponse => {
+                        let response: PairingKeyPacket =
+                            rmp_serde::from_slice(&packet.packet_payload).unwrap();
+
+                        context.watch_public_key = Some(response.public_key);
+                        try_confirm(bus, context);
+                    }
+                    ReferenceDataPacketType::PairingConfirm => {
+                        let confirm: PairingConfirmPacket =
+                            rmp_serde::from_slice(&packet.packet_payload).unwrap();
+
+                        assert_eq!(Some(confirm.public_key), context.watch_public_key);
+
+                        let mut channel = context
+                            .session
+                            .as_ref()
+                            .unwrap()
+                            .complete(&confirm.public_key, &confirm.confirmation)
+                            .unwrap();
+
+                        let sealed = channel.seal(some_reference_time_packet());
+
+                        bus.send_event(Events::IncomingData(Arc::new(sealed.serialize())));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_pair_with_reference_time_module() {
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_secs(1));
+    let message_bus_clone = message_bus.clone();
+
+    let request = ReferenceDataPacket::wrap_with_version(
+        PROTOCOL_VERSION,
+        ReferenceDataPacketType::PairingRequest,
+        PairingRequestPacket {
+            session_id: key(SESSION_ID),
+        },
+    );
+
+    let reference_time_task = ReferenceTime::start(message_bus.clone(), some_handshake());
+
+    let companion_task = async move {
+        let context = CompanionContext {
+            session: None,
+            watch_public_key: None,
+            pin: None,
+            paired: None,
+            persisted: vec![],
+            reference_time: None,
+        };
+
+        let context =
+            MessageBus::handle::<CompanionContext, CompanionStub>(message_bus, context).await;
+
+        assert_eq!(context.paired, Some(true));
+        let rx_counters: Vec<_> = context.persisted.iter().map(|x| x.rx_counter()).collect();
+
+        // once paired and again on disconnect, not with every accepted packet
+        assert_eq!(rx_counters, vec![0, 1]);
+        assert!(context.persisted.iter().all(|x| x.role() == Role::Watch));
+        assert_eq!(
+            context.reference_time.unwrap().unix_timestamp(),
+            946_720_800
+        );
+    };
+
+    let startup_sequence = async move {
+        message_bus_clone.send_event(Events::IncomingData(Arc::new(request.serialize())));
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(reference_time_task),
+        Box::pin(companion_task),
+        Box::pin(startup_sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+}
+
+// the watch shows the pin and answers with its key in no particular order,
+// the user types the pin into the companion which only then makes its key
+fn try_confirm(bus: &BusSender, context: &mut CompanionContext) {
+    let (Some(watch_public_key), Some(pin)) = (context.watch_public_key, context.pin) else {
+        return;
+    };
+
+    let session = PairingSession::new(Role::Companion, key(COMPANION_SECRET), key(SESSION_ID), pin);
+
+    let packet = ReferenceDataPacket::wrap_with_version(
+        PROTOCOL_VERSION,
+        ReferenceDataPacketType::PairingConfirm,
+        PairingConfirmPacket {
+            public_key: session.public_key(),
+            confirmation: session.confirmation(&watch_public_key).unwrap(),
+        },
+    );
+
+    context.session = Some(session);
+
+    bus.send_event(Events::IncomingData(Arc::new(packet.serialize())));
+}
+
+fn some_channels() -> (SecureChannel, SecureChannel) {
+    let watch = PairingSession::new(Role::Watch, key(WATCH_SECRET), key(SESSION_ID), SOME_PIN);
+    let companion = PairingSession::new(
+        Role::Companion,
+        key(COMPANION_SECRET),
+        key(SESSION_ID),
+        SOME_PIN,
+    );
+
+    let companion_confirmation = companion.confirmation(&watch.public_key()).unwrap();
+    let watch_confirmation = watch.confirmation(&companion.public_key()).unwrap();
+
+    (
+        watch
+            .complete(&companion.public_key(), &companion_confirmation)
+            .unwrap(),
+        companion
+            .complete(&watch.public_key(), &watch_confirmation)
+            .unwrap(),
+    )
+}
+
+fn some_handshake() -> HandshakePacket {
+    HandshakePacket::watch(240, vec![])
+}
+
+fn some_reference_time_packet() -> ReferenceDataPacket {
+    let packet = ReferenceTimePacket {
+        time: ReferenceTimeOffset {
+            now: 946_720_800,
+            offset_seconds: 0,
+        },
+    };
+
+    ReferenceDataPacket::wrap_with_version(PROTOCOL_VERSION, ReferenceDataPacketType::Time, packet)
+}
+
+fn key(text: &str) -> [u8; KEY_SIZE] {
+    hex(text).try_into().unwrap()
+}
+
+fn hex(text: &str) -> Vec<u8> {
+    (0..text.len())
+        .step_by(2)
+        .map(|x| u8::from_str_radix(&text[x..x + 2], 16).unwrap())
+        .collect()
+}
@@ -0,0 +1,227 @@
+use std::{sync::Arc, time::Duration};
+
+use blinky_shared::{
+    calendar::CalendarKind,
+    contract::packets::{
+        HandshakePacket, ReferenceDataPacket, ReferenceDataPacketType, ReferenceLocationPacket,
+    },
+    events::Events,
+    message_bus::MessageBus,
+    modules::{
+        persister_module::PersisterModule, reference_time::ReferenceTime, solar_module::SolarModule,
+    },
+    reference_data::GpsCoordinates,
+    solar::{below_horizon, solar_days, Horizon, SolarDay, SunCrossing},
+    storage::MemoryStorage,
+};
+use time::{macros::date, macros::datetime, OffsetDateTime};
+
+use crate::spy_module::SpyModule;
+
+const LONDON: GpsCoordinates = GpsCoordinates {
+    lat: 51.5074,
+    lon: -0.1278,
+};
+
+const TROMSO: GpsCoordinates = GpsCoordinates {
+    lat: 69.6492,
+    lon: 18.9553,
+};
+
+// almanac times are rounded to the minute, the calculation itself is good to about a minute
+fn assert_close(actual: Option<OffsetDateTime>, expected: OffsetDateTime, minutes: i64) {
+    let actual = actual.unwrap();
+
+    assert!(
+        (actual - expected).abs() <= time::Duration::minutes(minutes),
+        "{} is not within {} minutes of {}",
+        actual,
+        minutes,
+        expected
+    );
+}
+
+#[test]
+fn should_match_almanac_sunrise_and_sunset() {
+    let new_york = GpsCoordinates {
+        lat: 40.7128,
+        lon: -74.006,
+    };
+    let sydney = GpsCoordinates {
+        lat: -33.8688,
+        lon: 151.2093,
+    };
+
+    let cases = [
+        (
+            SolarDay::calculate(date!(2024 - 06 - 20), time::macros::offset!(-4), &new_york),
+            datetime!(2024-06-20 05:25 -4),
+            datetime!(2024-06-20 20:31 -4),
+        ),
+        (
+            SolarDay::calculate(date!(2024 - 12 - 21), time::macros::offset!(UTC), &LONDON),
+            datetime!(2024-12-21 08:04 UTC),
+            datetime!(2024-12-21 15:53 UTC),
+        ),
+        (
+            SolarDay::calculate(date!(2024 - 06 - 21), time::macros::offset!(+1), &LONDON),
+            datetime!(2024-06-21 04:43 +1),
+            datetime!(2024-06-21 21:21 +1),
+        ),
+        (
+            SolarDay::calculate(date!(2024 - 03 - 20), time::macros::offset!(+11), &sydney),
+            datetime!(2024-03-20 06:58 +11),
+            datetime!(2024-03-20 19:07 +11),
+        ),
+    ];
+
+    for (day, sunrise, sunset) in cases {
+        assert_close(day.sunrise(), sunrise, 2);
+        assert_close(day.sunset(), sunset, 2);
+        assert_eq!(day.noon.offset(), sunrise.offset());
+    }
+}
+
+#[test]
+fn should_surround_daylight_with_civil_twilight() {
+    let day = SolarDay::calculate(date!(2024 - 06 - 21), time::macros::offset!(+1), &LONDON);
+
+    let SunCrossing::Crosses { rise, set } = day.civil_twilight else {
+        panic!(
+            "expected civil twilight crossing, got {:?}",
+            day.civil_twilight
+        );
+    };
+
+    assert_close(Some(rise), datetime!(2024-06-21 03:57 +1), 3);
+    assert_close(Some(set), datetime!(2024-06-21 22:07 +1), 3);
+
+    assert!(rise < day.sunrise().unwrap());
+    assert!(set > day.sunset().unwrap());
+}
+
+#[test]
+fn should_detect_polar_day_and_night() {
+    let summer = SolarDay::calculate(date!(2024 - 06 - 21), time::macros::offset!(+2), &TROMSO);
+    let winter = SolarDay::calculate(date!(2024 - 12 - 21), time::macros::offset!(+1), &TROMSO);
+
+    assert_eq!(summer.daylight, SunCrossing::AlwaysAbove);
+    assert_eq!(winter.daylight, SunCrossing::AlwaysBelow);
+    assert!(winter.sunrise().is_none());
+
+    // the polar night still gets a few hours of twilight around noon
+    assert!(matches!(winter.civil_twilight, SunCrossing::Crosses { .. }));
+}
+
+#[test]
+fn should_find_night_within_dial_range() {
+    let now = datetime!(2024-12-21 14:00 UTC);
+    let days = solar_days(now, &LONDON, 2);
+
+    assert_eq!(days.len(), 2);
+    assert_eq!(days[1].date, date!(2024 - 12 - 22));
+
+    let night = below_horizon(
+        &days,
+        Horizon::Sunrise,
+        now,
+        now + time::Duration::hours(12),
+    );
+
+    assert_eq!(night.len(), 1);
+    assert_close(Some(night[0].0), datetime!(2024-12-21 15:53 UTC), 2);
+    assert_eq!(night[0].1, now + time::Duration::hours(12));
+
+    let polar_night_days = solar_days(datetime!(2024-12-21 00:00 +1), &TROMSO, 2);
+    let polar_day_days = solar_days(datetime!(2024-06-21 00:00 +2), &TROMSO, 2);
+
+    let from = datetime!(2024-06-21 06:00 +2);
+    let to = from + time::Duration::hours(12);
+
+    assert!(below_horizon(&polar_day_days, Horizon::Sunrise, from, to).is_empty());
+
+    let from = datetime!(2024-12-21 06:00 +1);
+    let to = from + time::Duration::hours(12);
+
+    assert_eq!(
+        below_horizon(&polar_night_days, Horizon::Sunrise, from, to),
+        vec![(from, to)]
+    );
+}
+
+#[tokio::test]
+async fn should_send_solar_days_from_persisted_location() {
+    let now = datetime!(2024-12-21 14:00 UTC);
+
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(100));
+
+    let persister_task = PersisterModule::start(message_bus.clone(), MemoryStorage::new());
+    let reference_time_task = ReferenceTime::start(
+        message_bus.clone(),
+        HandshakePacket::watch(240, vec![CalendarKind::Phone]),
+    );
+    let solar_task = SolarModule::start(message_bus.clone());
+
+    let packet = ReferenceDataPacket::wrap(
+        ReferenceDataPacketType::Location,
+        ReferenceLocationPacket {
+            coordinates: LONDON,
+        },
+    );
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(message_bus.clone(), Events::Key2Press);
+
+    let first_run = async move {
+        message_bus.send_event(Events::IncomingData(Arc::new(packet.serialize())));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        for seconds in 0..3 {
+            message_bus.send_event(Events::TimeNow(now + time::Duration::seconds(seconds)));
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        message_bus.send_event(Events::Key2Press);
+    };
+
+    let (storage, _, _, _, _) = futures::join!(
+        persister_task,
+        reference_time_task,
+        solar_task,
+        spy_task,
+        first_run
+    );
+
+    // calculated once per date
+    assert_eq!(sunsets(&spy).len(), 1);
+
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(100));
+
+    let persister_task = PersisterModule::start(message_bus.clone(), storage);
+    let solar_task = SolarModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(message_bus.clone(), Events::SolarDays(vec![]));
+
+    let second_run = async move {
+        message_bus.send_event(Events::TimeNow(now));
+    };
+
+    futures::join!(persister_task, solar_task, spy_task, second_run);
+
+    let sunsets = sunsets(&spy);
+
+    assert_eq!(sunsets.len(), 1);
+    assert_close(sunsets[0], datetime!(2024-12-21 15:53 UTC), 2);
+}
+
+fn sunsets(spy: &SpyModule) -> Vec<Option<OffsetDateTime>> {
+    spy.get_result()
+        .filter_map(|x| match x {
+            Events::SolarDays(days) => Some(days[0].sunset()),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,224 @@
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use blinky_shared::{
+    commands::Commands,
+    events::Events,
+    message_bus::{BusHandler, BusSender, MessageBus},
+    persistence::{PersistenceUnit, PersistenceUnitKind},
+    supervisor::{ModuleHealth, ModuleSpec, RestartPolicy, Supervisor},
+};
+use tokio::time::sleep;
+
+use crate::spy_module::SpyModule;
+
+type Journal = Arc<Mutex<Vec<String>>>;
+
+struct JournalContext {
+    name: &'static str,
+    journal: Journal,
+}
+
+struct CalendarStub {}
+
+struct PersisterStub {}
+
+impl BusHandler<JournalContext> for CalendarStub {
+    async fn event_handler(_bus: &BusSender, _context: &mut JournalContext, _event: Events) {}
+
+    async fn command_handler(bus: &BusSender, context: &mut JournalContext, command: Commands) {
+        if let Commands::StartDeepSleep = command {
+            bus.send_cmd(Commands::Persist(PersistenceUnit::new(
+                PersistenceUnitKind::CalendarSyncInfo,
+                &42,
+            )));
+
+            write_journal(context, "stopped");
+        }
+    }
+}
+
+impl BusHandler<JournalContext> for PersisterStub {
+    async fn event_handler(_bus: &BusSender, _context: &mut JournalContext, _event: Events) {}
+
+    async fn command_handler(_bus: &BusSender, context: &mut JournalContext, command: Commands) {
+        match command {
+            Commands::Persist(unit) => write_journal(context, unit.kind.as_ref()),
+            Commands::StartDeepSleep => write_journal(context, "stopped"),
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_start_in_dependency_order_and_shutdown_in_reverse() {
+    let message_bus = MessageBus::new();
+    let message_bus_clone = message_bus.clone();
+
+    let journal: Journal = Arc::new(Mutex::new(vec![]));
+    let started: Journal = Arc::new(Mutex::new(vec![]));
+
+    let mut spy = SpyModule::new();
+    let mut spy_ref = Some(&mut spy);
+
+    let mut supervisor = Supervisor::new(message_bus);
+
+    supervisor.register(
+        ModuleSpec::new("spy", |bus| {
+            started.lock().unwrap().push("spy".to_string());
+            spy_ref.take().unwrap().start(bus, Events::Key1Press)
+        })
+        .depends_on("calendar"),
+    );
+
+    supervisor.register(
+        ModuleSpec::new("calendar", |bus| {
+            started.lock().unwrap().push("calendar".to_string());
+            start_journaled::<CalendarStub>(bus, "calendar", journal.clone())
+        })
+        .depends_on("persister"),
+    );
+
+    supervisor.register(ModuleSpec::new("persister", |bus| {
+        started.lock().unwrap().push("persister".to_string());
+        start_journaled::<PersisterStub>(bus, "persister", journal.clone())
+    }));
+
+    let health = supervisor.health();
+
+    let sequence = async move {
+        sleep(Duration::from_millis(20)).await;
+
+        assert!(health
+            .all()
+            .iter()
+            .all(|x| x.health == ModuleHealth::Running));
+
+        // the spy stops the world on the first key press
+        message_bus_clone.send_event(Events::Key1Press);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()> + '_>>> = vec![
+        Box::pin(async {
+            supervisor.run().await.unwrap();
+        }),
+        Box::pin(sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+
+    assert_eq!(
+        *started.lock().unwrap(),
+        vec!["persister", "calendar", "spy"]
+    );
+
+    assert_eq!(
+        *journal.lock().unwrap(),
+        vec![
+            "calendar: stopped",
+            "persister: CalendarSyncInfo",
+            "persister: stopped"
+        ]
+    );
+
+    assert!(matches!(spy.get_result().next(), Some(Events::Key1Press)));
+}
+
+#[tokio::test]
+async fn should_restart_panicked_module() {
+    let message_bus = MessageBus::new();
+    let message_bus_clone = message_bus.clone();
+
+    let journal: Journal = Arc::new(Mutex::new(vec![]));
+    let mut attempts = 0;
+
+    let mut supervisor = Supervisor::new(message_bus);
+
+    supervisor.register(
+        ModuleSpec::new("persister", |bus| {
+            attempts += 1;
+            let should_panic = attempts == 1;
+            let journal = journal.clone();
+
+            async move {
+                if should_panic {
+                    panic!("flash is busy");
+                }
+
+                start_journaled::<PersisterStub>(bus, "persister", journal).await;
+            }
+        })
+        .restart(RestartPolicy::OnPanic { max_restarts: 3 }),
+    );
+
+    let health = supervisor.health();
+    let health_clone = health.clone();
+
+    let sequence = async move {
+        sleep(Duration::from_millis(20)).await;
+
+        let status = health_clone.get("persister").unwrap();
+
+        assert_eq!(status.health, ModuleHealth::Running);
+        assert_eq!(status.restarts, 1);
+
+        message_bus_clone.send_cmd(Commands::StartDeepSleep);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()> + '_>>> = vec![
+        Box::pin(async {
+            supervisor.run().await.unwrap();
+        }),
+        Box::pin(sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+
+    assert_eq!(
+        health.get("persister").unwrap().health,
+        ModuleHealth::Stopped
+    );
+    assert_eq!(*journal.lock().unwrap(), vec!["persister: stopped"]);
+}
+
+#[tokio::test]
+async fn should_reject_circular_dependencies() {
+    let mut supervisor = Supervisor::new(MessageBus::new());
+
+    supervisor.register(ModuleSpec::new("calendar", |_| async {}).depends_on("persister"));
+    supervisor.register(ModuleSpec::new("persister", |_| async {}).depends_on("calendar"));
+
+    assert!(supervisor.run().await.is_err());
+}
+
+#[test]
+fn should_not_keep_receivers_of_handed_over_bus() {
+    let supervisor = Supervisor::new(MessageBus::new());
+    let bus = supervisor.bus();
+
+    for _ in 0..10 {
+        bus.send_event(Events::TouchReleased);
+    }
+
+    assert!(bus.lag_metrics().is_empty());
+}
+
+async fn start_journaled<THandler>(bus: MessageBus, name: &'static str, journal: Journal)
+where
+    THandler: BusHandler<JournalContext>,
+{
+    let context = JournalContext { name, journal };
+
+    MessageBus::handle::<JournalContext, THandler>(bus, context).await;
+}
+
+fn write_journal(context: &JournalContext, record: &str) {
+    context
+        .journal
+        .lock()
+        .unwrap()
+        .push(format!("{}: {}", context.name, record));
+}
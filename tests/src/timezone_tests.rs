@@ -0,0 +1,218 @@
+use std::{sync::Arc, time::Duration};
+
+use blinky_shared::{
+    calendar::{
+        recurrence::{Frequency, RecurrenceRule},
+        CalendarKind,
+    },
+    contract::{
+        packets::{
+            HandshakePacket, ReferenceDataPacket, ReferenceDataPacketType, ReferenceTimezonePacket,
+        },
+        versioning::{ProtocolNegotiation, SECURE_PROTOCOL_VERSION, TIMEZONE_PROTOCOL_VERSION},
+    },
+    events::Events,
+    message_bus::MessageBus,
+    modules::reference_time::ReferenceTime,
+    reference_data::{TimezoneRules, TimezoneTransition},
+};
+use time::{macros::datetime, PrimitiveDateTime, UtcOffset};
+
+use crate::spy_module::SpyModule;
+
+#[test]
+fn should_resolve_offset_per_instant() {
+    let timezone = berlin_2024();
+
+    let before = datetime!(2024-03-31 00:59:59 UTC);
+    let after = datetime!(2024-03-31 01:00 UTC);
+
+    assert_eq!(timezone.offset_at(before).whole_hours(), 1);
+    assert_eq!(timezone.offset_at(after).whole_hours(), 2);
+    assert_eq!(
+        timezone
+            .offset_at(datetime!(2025-06-01 00:00 UTC))
+            .whole_hours(),
+        1
+    );
+
+    assert_eq!(timezone.to_local(after), datetime!(2024-03-31 03:00 +02:00));
+    assert_eq!(
+        timezone.next_transition_after(after),
+        Some(datetime!(2024-10-27 01:00 UTC))
+    );
+    assert_eq!(
+        timezone.next_transition_after(datetime!(2024-10-27 01:00 UTC)),
+        None
+    );
+}
+
+#[test]
+fn should_resolve_skipped_and_repeated_wall_clock_times() {
+    let timezone = berlin_2024();
+
+    let skipped = timezone.resolve_local(datetime!(2024-03-31 02:30));
+    let repeated = timezone.resolve_local(datetime!(2024-10-27 02:30));
+    let regular = timezone.resolve_local(datetime!(2024-07-01 09:00));
+
+    assert_eq!(skipped, datetime!(2024-03-31 03:30 +02:00));
+    assert_eq!(repeated, datetime!(2024-10-27 02:30 +02:00));
+    assert_eq!(regular, datetime!(2024-07-01 09:00 +02:00));
+    assert_eq!(regular.offset(), UtcOffset::from_hms(2, 0, 0).unwrap());
+}
+
+#[test]
+fn should_shift_wall_clock_when_transition_passes() {
+    let timezone = berlin_2024();
+    let winter = UtcOffset::from_hms(1, 0, 0).unwrap();
+    let summer = UtcOffset::from_hms(2, 0, 0).unwrap();
+
+    // the rtc keeps local wall time
+    assert_eq!(
+        timezone.shift_wall_clock(datetime!(2024-03-31 01:59:59), winter),
+        None
+    );
+
+    let spring = timezone
+        .shift_wall_clock(datetime!(2024-03-31 02:00), winter)
+        .unwrap();
+
+    assert_eq!(spring, datetime!(2024-03-31 03:00 +02:00));
+    assert_eq!(
+        timezone.shift_wall_clock(
+            PrimitiveDateTime::new(spring.date(), spring.time()),
+            spring.offset()
+        ),
+        None
+    );
+
+    assert_eq!(
+        timezone.shift_wall_clock(datetime!(2024-10-27 02:59:59), summer),
+        None
+    );
+
+    let autumn = timezone
+        .shift_wall_clock(datetime!(2024-10-27 03:00), summer)
+        .unwrap();
+
+    assert_eq!(autumn, datetime!(2024-10-27 02:00 +01:00));
+    assert_eq!(
+        timezone.shift_wall_clock(datetime!(2024-10-27 02:00), winter),
+        None
+    );
+}
+
+#[test]
+fn should_keep_recurring_wall_clock_time_across_transition() {
+    let timezone = berlin_2024();
+
+    let mut rule = RecurrenceRule::new(Frequency::Daily, 1);
+    // the exdate is the real instant of the occurrence in summer time
+    rule.exdates = vec![datetime!(2024-04-01 07:00 UTC).into()];
+
+    let start = datetime!(2024-03-29 09:00 +01:00);
+
+    let occurrences: Vec<_> = rule
+        .occurrences(start)
+        .in_timezone(Some(&timezone))
+        .take(4)
+        .collect();
+
+    assert_eq!(
+        occurrences,
+        vec![
+            datetime!(2024-03-29 09:00 +01:00),
+            datetime!(2024-03-30 09:00 +01:00),
+            datetime!(2024-03-31 09:00 +02:00),
+            datetime!(2024-04-02 09:00 +02:00),
+        ]
+    );
+}
+
+#[test]
+fn should_decode_timezone_packet_from_v5() {
+    let mut negotiation = ProtocolNegotiation::new();
+
+    let mut companion = HandshakePacket::watch(240, vec![CalendarKind::Phone]);
+    companion.protocol_version = SECURE_PROTOCOL_VERSION;
+    negotiation.accept(&companion).unwrap();
+
+    let packet = ReferenceDataPacket::wrap_with_version(
+        TIMEZONE_PROTOCOL_VERSION,
+        ReferenceDataPacketType::Timezone,
+        ReferenceTimezonePacket {
+            timezone: berlin_2024(),
+        },
+    );
+
+    assert!(negotiation
+        .decode::<ReferenceTimezonePacket>(&packet)
+        .is_err());
+
+    companion.protocol_version = TIMEZONE_PROTOCOL_VERSION;
+    negotiation.accept(&companion).unwrap();
+
+    let decoded = negotiation
+        .decode::<ReferenceTimezonePacket>(&packet)
+        .unwrap();
+
+    assert_eq!(decoded.timezone, berlin_2024());
+}
+
+#[tokio::test]
+async fn should_emit_sorted_timezone_rules() {
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(50));
+
+    let reference_time_task = ReferenceTime::start(
+        message_bus.clone(),
+        HandshakePacket::watch(240, vec![CalendarKind::Phone]),
+    );
+
+    let mut unsorted = berlin_2024();
+    unsorted.transitions.reverse();
+
+    let packet = ReferenceDataPacket::wrap_with_version(
+        TIMEZONE_PROTOCOL_VERSION,
+        ReferenceDataPacketType::Timezone,
+        ReferenceTimezonePacket { timezone: unsorted },
+    );
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::ReferenceTimezone(berlin_2024()),
+    );
+
+    let startup_sequence = async move {
+        message_bus.send_event(Events::IncomingData(Arc::new(packet.serialize())));
+    };
+
+    futures::join!(reference_time_task, spy_task, startup_sequence);
+
+    let timezone = spy
+        .get_result()
+        .find_map(|x| match x {
+            Events::ReferenceTimezone(timezone) => Some(timezone.clone()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(timezone, berlin_2024());
+}
+
+pub fn berlin_2024() -> TimezoneRules {
+    TimezoneRules {
+        name: "Europe/Berlin".to_string(),
+        initial_offset_seconds: 3600,
+        transitions: vec![
+            TimezoneTransition {
+                at: datetime!(2024-03-31 01:00 UTC).into(),
+                offset_seconds: 7200,
+            },
+            TimezoneTransition {
+                at: datetime!(2024-10-27 01:00 UTC).into(),
+                offset_seconds: 3600,
+            },
+        ],
+    }
+}
@@ -0,0 +1,378 @@
+use std::{collections::VecDeque, pin::Pin, sync::Arc, time::Duration};
+
+use blinky_shared::{
+    calendar::CalendarKind,
+    commands::Commands,
+    contract::{
+        packets::{
+            HandshakePacket, ReferenceDataPacket, ReferenceDataPacketType, ReferenceTimePacket,
+        },
+        transport::{
+            FrameKind, TransportFrame, TransportReceiver, TransportSender, MAX_FRAGMENTS,
+            MAX_INCOMPLETE_MESSAGES,
+        },
+    },
+    crc::crc32,
+    events::Events,
+    message_bus::{BusHandler, BusSender, MessageBus},
+    modules::reference_time::ReferenceTime,
+    reference_data::ReferenceTimeOffset,
+};
+
+const MTU: usize = 64;
+
+#[test]
+fn should_compute_standard_crc32() {
+    assert_eq!(crc32(b"123456789"), 0xCBF43926);
+}
+
+#[test]
+fn should_reassemble_out_of_order_fragments() {
+    let packet = some_payload(1000);
+
+    let mut sender = TransportSender::new(MTU);
+    let mut receiver = TransportReceiver::new();
+
+    let frames = sender.send(&packet);
+
+    assert!(frames.len() > 1);
+    assert!(frames.iter().all(|x| x.len() <= MTU));
+
+    let mut packets = vec![];
+    let mut replies = vec![];
+
+    for frame in frames.iter().rev() {
+        let mut received = receiver.receive(frame);
+        packets.append(&mut received.packets);
+        replies.append(&mut received.replies);
+    }
+
+    assert_eq!(packets, vec![packet]);
+
+    let ack = TransportFrame::decode(replies.last().unwrap()).unwrap();
+    assert_eq!(ack.kind, FrameKind::Ack);
+
+    sender.handle_reply(replies.last().unwrap());
+
+    assert!(sender.is_idle());
+}
+
+#[test]
+fn should_drop_corrupted_frame_and_request_it_again() {
+    let packet = some_payload(300);
+
+    let mut sender = TransportSender::new(MTU);
+    let mut receiver = TransportReceiver::new();
+
+    let mut frames = sender.send(&packet);
+
+    let middle = frames.len() / 2;
+    frames[middle][10] ^= 0xFF;
+
+    let mut replies = vec![];
+
+    for frame in frames.iter() {
+        let mut received = receiver.receive(frame);
+        assert!(received.packets.is_empty());
+        replies.append(&mut received.replies);
+    }
+
+    assert_eq!(receiver.corrupted_frames(), 1);
+
+    let nack = TransportFrame::decode(&replies[0]).unwrap();
+    assert_eq!(nack.kind, FrameKind::Nack);
+
+    let retransmitted = sender.handle_reply(&replies[0]);
+    assert_eq!(retransmitted.len(), 1);
+
+    let received = receiver.receive(&retransmitted[0]);
+
+    assert_eq!(received.packets, vec![packet]);
+}
+
+#[test]
+fn should_pass_unframed_packets_through() {
+    let packet = some_reference_time_packet().serialize();
+
+    let mut receiver = TransportReceiver::new();
+
+    let received = receiver.receive(&packet);
+
+    assert_eq!(received.packets, vec![packet]);
+    assert!(received.replies.is_empty());
+}
+
+#[test]
+fn should_reject_frames_over_fragments_cap() {
+    let mut receiver = TransportReceiver::new();
+
+    let frame = TransportFrame {
+        kind: FrameKind::Data,
+        message_id: 1,
+        seq: 0,
+        fragments_count: MAX_FRAGMENTS + 1,
+        payload: some_payload(10),
+    };
+
+    let received = receiver.receive(&frame.encode());
+
+    assert!(received.packets.is_empty());
+    assert!(received.replies.is_empty());
+    assert_eq!(receiver.incomplete_messages(), 0);
+}
+
+#[test]
+fn should_evict_oldest_incomplete_messages() {
+    let mut sender = TransportSender::new(MTU);
+    let mut receiver = TransportReceiver::new();
+
+    let messages: Vec<Vec<Vec<u8>>> = (0..MAX_INCOMPLETE_MESSAGES * 4)
+        .map(|_| sender.send(&some_payload(300)))
+        .collect();
+
+    // every message stalls after its first fragment
+    for frames in messages.iter() {
+        receiver.receive(&frames[0]);
+        assert!(receiver.incomplete_messages() <= MAX_INCOMPLETE_MESSAGES);
+    }
+
+    let (evicted, kept) = messages.split_at(messages.len() - MAX_INCOMPLETE_MESSAGES);
+
+    let packets: Vec<Vec<u8>> = kept
+        .iter()
+        .flat_map(|frames| frames[1..].to_vec())
+        .flat_map(|frame| receiver.receive(&frame).packets)
+        .collect();
+
+    assert_eq!(packets.len(), MAX_INCOMPLETE_MESSAGES);
+
+    // an evicted message starts over from a retransmission
+    let packets: Vec<Vec<u8>> = evicted[0]
+        .iter()
+        .flat_map(|frame| receiver.receive(frame).packets)
+        .collect();
+
+    assert_eq!(packets, vec![some_payload(300)]);
+}
+
+#[test]
+fn should_deliver_all_packets_over_lossy_loopback() {
+    let packets: Vec<Vec<u8>> = (0..20).map(|x| some_payload(50 + x * 97)).collect();
+
+    let mut sender = TransportSender::new(MTU);
+    let mut receiver = TransportReceiver::new();
+    let mut channel = LossyChannel::new(0x2545F491);
+
+    for packet in packets.iter() {
+        for frame in sender.send(packet) {
+            channel.push(frame);
+        }
+    }
+
+    let mut delivered = vec![];
+    let mut rounds = 0;
+
+    while !sender.is_idle() {
+        rounds += 1;
+        assert!(rounds < 100, "transport didn't converge");
+
+        let mut replies = LossyChannel::new(rounds);
+
+        while let Some(frame) = channel.pop() {
+            let mut received = receiver.receive(&frame);
+            delivered.append(&mut received.packets);
+
+            for reply in received.replies {
+                replies.push(reply);
+            }
+        }
+
+        // the link went quiet, ask for whatever is still missing
+        for reply in receiver.nack_missing() {
+            replies.push(reply);
+        }
+
+        while let Some(reply) = replies.pop() {
+            for frame in sender.handle_reply(&reply) {
+                channel.push(frame);
+            }
+        }
+
+        if channel.is_empty() {
+            for frame in sender.retransmit_unacked() {
+                channel.push(frame);
+            }
+        }
+    }
+
+    delivered.sort_by_key(|x| x.len());
+
+    assert_eq!(delivered, packets);
+}
+
+struct FramesStub {}
+
+impl BusHandler<Vec<Vec<u8>>> for FramesStub {
+    async fn event_handler(bus: &BusSender, _context: &mut Vec<Vec<u8>>, event: Events) {
+        if let Events::ReferenceTime(_) = event {
+            bus.send_cmd(Commands::StartDeepSleep);
+        }
+    }
+
+    async fn command_handler(_bus: &BusSender, context: &mut Vec<Vec<u8>>, command: Commands) {
+        if let Commands::SendData(data) = command {
+            context.push(data.to_vec());
+        }
+    }
+}
+
+#[tokio::test]
+async fn should_decode_fragmented_reference_time() {
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(50));
+    let message_bus_clone = message_bus.clone();
+
+    let reference_time_task = ReferenceTime::start(message_bus.clone(), some_handshake());
+
+    let frames_task = async move {
+        let replies = MessageBus::handle::<Vec<Vec<u8>>, FramesStub>(message_bus, vec![]).await;
+
+        let ack = TransportFrame::decode(&replies[0]).unwrap();
+        assert_eq!(ack.kind, FrameKind::Ack);
+    };
+
+    let startup_sequence = async move {
+        let mut sender = TransportSender::new(30);
+
+        for frame in sender.send(&some_reference_time_packet().serialize()) {
+            message_bus_clone.send_event(Events::IncomingData(Arc::new(frame)));
+        }
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(reference_time_task),
+        Box::pin(frames_task),
+        Box::pin(startup_sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+}
+
+struct ReconnectStub {}
+
+impl BusHandler<Vec<i64>> for ReconnectStub {
+    async fn event_handler(bus: &BusSender, context: &mut Vec<i64>, event: Events) {
+        if let Events::ReferenceTime(now) = event {
+            context.push(now.unix_timestamp());
+
+            if context.len() == 2 {
+                bus.send_cmd(Commands::StartDeepSleep);
+            }
+        }
+    }
+
+    async fn command_handler(_bus: &BusSender, _context: &mut Vec<i64>, _command: Commands) {}
+}
+
+#[tokio::test]
+async fn should_accept_reused_message_ids_after_reconnect() {
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(50));
+    let message_bus_clone = message_bus.clone();
+
+    let reference_time_task = ReferenceTime::start(message_bus.clone(), some_handshake());
+
+    let times_task = async move {
+        let times = MessageBus::handle::<Vec<i64>, ReconnectStub>(message_bus, vec![]).await;
+
+        assert_eq!(times, vec![946_720_800, 946_720_860]);
+    };
+
+    let startup_sequence = async move {
+        let send = |now| {
+            // each connection starts a new sender, message ids begin at zero again
+            for frame in
+                TransportSender::new(30).send(&some_reference_time_packet_at(now).serialize())
+            {
+                message_bus_clone.send_event(Events::IncomingData(Arc::new(frame)));
+            }
+        };
+
+        message_bus_clone.send_event(Events::BleClientConnected);
+        send(946_720_800);
+        message_bus_clone.send_event(Events::BleClientDisconnected);
+        message_bus_clone.send_event(Events::BleClientConnected);
+        send(946_720_860);
+    };
+
+    let tasks: Vec<Pin<Box<dyn futures::Future<Output = ()>>>> = vec![
+        Box::pin(reference_time_task),
+        Box::pin(times_task),
+        Box::pin(startup_sequence),
+    ];
+
+    futures::future::join_all(tasks).await;
+}
+
+// drops, duplicates and reorders frames deterministically
+struct LossyChannel {
+    state: u32,
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl LossyChannel {
+    fn new(seed: u32) -> Self {
+        Self {
+            state: seed.max(1),
+            frames: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, frame: Vec<u8>) {
+        match self.next() % 10 {
+            0 | 1 => {}
+            2 => {
+                self.frames.push_back(frame.clone());
+                self.frames.push_back(frame);
+            }
+            3 => self.frames.push_front(frame),
+            _ => self.frames.push_back(frame),
+        }
+    }
+
+    fn pop(&mut self) -> Option<Vec<u8>> {
+        self.frames.pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    fn next(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+}
+
+fn some_payload(size: usize) -> Vec<u8> {
+    (0..size).map(|x| (x * 31 % 251) as u8).collect()
+}
+
+fn some_reference_time_packet() -> ReferenceDataPacket {
+    some_reference_time_packet_at(946_720_800)
+}
+
+fn some_reference_time_packet_at(now: i64) -> ReferenceDataPacket {
+    let packet = ReferenceTimePacket {
+        time: ReferenceTimeOffset {
+            now,
+            offset_seconds: 60 * 60 * 2,
+        },
+    };
+
+    ReferenceDataPacket::wrap(ReferenceDataPacketType::Time, packet)
+}
+
+fn some_handshake() -> HandshakePacket {
+    HandshakePacket::watch(240, vec![CalendarKind::Phone])
+}
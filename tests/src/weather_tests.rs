@@ -0,0 +1,215 @@
+use std::{sync::Arc, time::Duration};
+
+use blinky_shared::{
+    calendar::CalendarKind,
+    contract::{
+        packets::{
+            HandshakePacket, ReferenceDataPacket, ReferenceDataPacketType, ReferenceWeatherPacket,
+        },
+        versioning::{ProtocolNegotiation, TIMEZONE_PROTOCOL_VERSION, WEATHER_PROTOCOL_VERSION},
+    },
+    events::Events,
+    message_bus::MessageBus,
+    modules::{
+        persister_module::PersisterModule, reference_time::ReferenceTime,
+        weather_module::WeatherModule,
+    },
+    storage::MemoryStorage,
+    weather::{CurrentWeather, Daylight, HourlyForecast, WeatherCondition, WeatherForecast},
+};
+use time::macros::datetime;
+
+use crate::spy_module::SpyModule;
+
+#[test]
+fn should_resolve_current_weather_per_hour() {
+    let forecast = some_forecast();
+
+    let morning = forecast
+        .current(datetime!(2024-05-01 05:30 +02:00))
+        .unwrap();
+    let noon = forecast
+        .current(datetime!(2024-05-01 12:30 +02:00))
+        .unwrap();
+
+    assert_eq!(morning.temperature, 8.0);
+    assert!(!morning.is_daylight);
+
+    assert_eq!(
+        noon,
+        CurrentWeather {
+            condition: WeatherCondition::Rain,
+            temperature: 15.0,
+            precipitation_probability: 80,
+            wind_speed: 6.5,
+            wind_direction: 270,
+            is_daylight: true,
+        }
+    );
+
+    assert!(forecast
+        .current(datetime!(2024-05-01 03:59 +02:00))
+        .is_none());
+    assert!(forecast
+        .current(datetime!(2024-05-02 04:00 +02:00))
+        .is_none());
+}
+
+#[test]
+fn should_decode_weather_packet_from_v6() {
+    let mut negotiation = ProtocolNegotiation::new();
+
+    let mut companion = HandshakePacket::watch(240, vec![CalendarKind::Phone]);
+    companion.protocol_version = TIMEZONE_PROTOCOL_VERSION;
+    negotiation.accept(&companion).unwrap();
+
+    let packet = ReferenceDataPacket::wrap_with_version(
+        WEATHER_PROTOCOL_VERSION,
+        ReferenceDataPacketType::Weather,
+        ReferenceWeatherPacket {
+            forecast: some_forecast(),
+        },
+    );
+
+    assert!(negotiation
+        .decode::<ReferenceWeatherPacket>(&packet)
+        .is_err());
+
+    companion.protocol_version = WEATHER_PROTOCOL_VERSION;
+    negotiation.accept(&companion).unwrap();
+
+    let decoded = negotiation
+        .decode::<ReferenceWeatherPacket>(&packet)
+        .unwrap();
+
+    assert_eq!(decoded.forecast, some_forecast());
+    assert!(HandshakePacket::watch(240, vec![])
+        .supported_packet_types
+        .contains(&ReferenceDataPacketType::Weather));
+}
+
+#[tokio::test]
+async fn should_emit_sorted_forecast_from_packet() {
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(50));
+
+    let reference_time_task = ReferenceTime::start(
+        message_bus.clone(),
+        HandshakePacket::watch(240, vec![CalendarKind::Weather]),
+    );
+
+    let mut unsorted = some_forecast();
+    unsorted.hours.reverse();
+
+    let packet = ReferenceDataPacket::wrap_with_version(
+        WEATHER_PROTOCOL_VERSION,
+        ReferenceDataPacketType::Weather,
+        ReferenceWeatherPacket { forecast: unsorted },
+    );
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::ReferenceWeather(some_forecast()),
+    );
+
+    let startup_sequence = async move {
+        message_bus.send_event(Events::IncomingData(Arc::new(packet.serialize())));
+    };
+
+    futures::join!(reference_time_task, spy_task, startup_sequence);
+
+    let forecast = spy
+        .get_result()
+        .find_map(|x| match x {
+            Events::ReferenceWeather(forecast) => Some(forecast.clone()),
+            _ => None,
+        })
+        .unwrap();
+
+    assert_eq!(forecast, some_forecast());
+}
+
+#[tokio::test]
+async fn should_send_current_weather_and_restore_forecast_after_restart() {
+    let now = datetime!(2024-05-01 11:59:58 +02:00);
+
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(100));
+
+    let persister_task = PersisterModule::start(message_bus.clone(), MemoryStorage::new());
+    let weather_task = WeatherModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(message_bus.clone(), Events::Key2Press);
+
+    let first_run = async move {
+        message_bus.send_event(Events::ReferenceWeather(some_forecast()));
+
+        for seconds in 0..4 {
+            message_bus.send_event(Events::TimeNow(now + time::Duration::seconds(seconds)));
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        message_bus.send_event(Events::Key2Press);
+    };
+
+    let (storage, _, _, _) = futures::join!(persister_task, weather_task, spy_task, first_run);
+
+    assert_eq!(temperatures(&spy), vec![14.0, 15.0]);
+
+    let message_bus = MessageBus::new().with_request_timeout(Duration::from_millis(100));
+
+    let persister_task = PersisterModule::start(message_bus.clone(), storage);
+    let weather_task = WeatherModule::start(message_bus.clone());
+
+    let mut spy = SpyModule::new();
+    let spy_task = spy.start(
+        message_bus.clone(),
+        Events::WeatherNow(some_forecast().current(now).unwrap()),
+    );
+
+    let second_run = async move {
+        message_bus.send_event(Events::TimeNow(now));
+    };
+
+    futures::join!(persister_task, weather_task, spy_task, second_run);
+
+    assert_eq!(temperatures(&spy), vec![14.0]);
+}
+
+fn temperatures(spy: &SpyModule) -> Vec<f32> {
+    spy.get_result()
+        .filter_map(|x| match x {
+            Events::WeatherNow(current) => Some(current.temperature),
+            _ => None,
+        })
+        .collect()
+}
+
+fn some_forecast() -> WeatherForecast {
+    let start = datetime!(2024-05-01 04:00 +02:00);
+
+    let hours = (0..24)
+        .map(|hour| HourlyForecast {
+            at: (start + time::Duration::hours(hour)).into(),
+            condition: if hour == 8 {
+                WeatherCondition::Rain
+            } else {
+                WeatherCondition::Cloudy
+            },
+            temperature: 7.0 + hour as f32,
+            precipitation_probability: if hour == 8 { 80 } else { 10 },
+            wind_speed: 6.5,
+            wind_direction: 270,
+        })
+        .collect();
+
+    WeatherForecast {
+        issued_at: start.into(),
+        hours,
+        daylight: vec![Daylight {
+            sunrise: datetime!(2024-05-01 05:45 +02:00).into(),
+            sunset: datetime!(2024-05-01 20:52 +02:00).into(),
+        }],
+    }
+}